@@ -0,0 +1,50 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fo4builder::{
+    build::Build,
+    special::{catalog, Catalog, PerkId, SpecialStat},
+};
+
+fn catalog_load(c: &mut Criterion) {
+    c.bench_function("load built-in catalog", |b| {
+        b.iter(|| Catalog::load(None).unwrap())
+    });
+    c.bench_function("parse perks.yaml", |b| {
+        b.iter(|| Catalog::parse(include_str!("../src/perks.yaml")).unwrap())
+    });
+}
+
+fn perk_matching(c: &mut Criterion) {
+    let catalog = catalog();
+    c.bench_function("match exact perk name", |b| {
+        b.iter(|| catalog.search(black_box("rifleman")))
+    });
+    c.bench_function("match misspelled perk name", |b| {
+        b.iter(|| catalog.search(black_box("gunslnger")))
+    });
+    c.bench_function("match partial perk name", |b| {
+        b.iter(|| catalog.search(black_box("lone")))
+    });
+}
+
+fn display(c: &mut Criterion) {
+    colored::control::set_override(false);
+    let mut build = Build::default();
+    for (stat, value) in [
+        (SpecialStat::Strength, 3),
+        (SpecialStat::Perception, 4),
+        (SpecialStat::Agility, 9),
+        (SpecialStat::Luck, 6),
+    ] {
+        build.set(stat, value).unwrap();
+    }
+    for (name, rank) in [("rifleman", 5), ("sneak", 3), ("mister sandman", 2)] {
+        let id: PerkId = name.parse().unwrap();
+        build.add_perk(id, rank).unwrap();
+    }
+    c.bench_function("display build", |b| {
+        b.iter(|| black_box(&build).to_string())
+    });
+}
+
+criterion_group!(benches, catalog_load, perk_matching, display);
+criterion_main!(benches);
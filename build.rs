@@ -0,0 +1,91 @@
+use std::{env, fs, path::Path};
+
+#[allow(dead_code)]
+#[path = "src/perk_data.rs"]
+mod perk_data;
+
+use perk_data::{normalize, AllPerksRep, LocationsRep};
+
+/// Must match the hash the catalog gives perk data it parses at runtime
+fn fnv(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn read(name: &str) -> String {
+    let path = format!("src/{}.yaml", name);
+    println!("cargo:rerun-if-changed={}", path);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("Unable to read {}: {}", path, e))
+}
+
+fn write(out_dir: &Path, file: &str, bytes: Vec<u8>) {
+    fs::write(out_dir.join(file), bytes)
+        .unwrap_or_else(|e| panic!("Unable to write {}: {}", file, e));
+}
+
+/// Convert built-in data from YAML to MessagePack, which is much faster to
+/// deserialize at startup, and fail the build if the YAML is malformed
+fn convert(name: &str, out_dir: &Path) -> String {
+    let yaml = read(name);
+    let value: serde_json::Value =
+        serde_yaml::from_str(&yaml).unwrap_or_else(|e| panic!("Error in src/{}.yaml: {}", name, e));
+    let bytes = rmp_serde::to_vec_named(&value)
+        .unwrap_or_else(|e| panic!("Unable to serialize {}: {}", name, e));
+    write(out_dir, &format!("{}.msgpack", name), bytes);
+    yaml
+}
+
+/// Serialize the perk data the way the catalog loads it, failing the build if it
+/// would not load, the same way the catalog checks it at runtime
+fn convert_perks(out_dir: &Path) -> AllPerksRep {
+    let yaml = read("perks");
+    let perks: AllPerksRep =
+        serde_yaml::from_str(&yaml).unwrap_or_else(|e| panic!("Error in src/perks.yaml: {}", e));
+    perks
+        .check()
+        .unwrap_or_else(|e| panic!("Error in src/perks.yaml: {}", e));
+    let blob = perks
+        .to_blob(fnv(&yaml))
+        .unwrap_or_else(|e| panic!("Unable to serialize the perk data: {}", e));
+    write(out_dir, "perks.bin", blob);
+    perks
+}
+
+/// Fail the build if a location is for a perk the perk data doesn't have
+fn check_locations(perks: &AllPerksRep, locations: &str) {
+    let locations: LocationsRep = serde_yaml::from_str(locations)
+        .unwrap_or_else(|e| panic!("Error in src/locations.yaml: {}", e));
+    let perk_names = perks.perk_names();
+    for (section, names) in [
+        (
+            "bobbleheads",
+            locations.bobbleheads.keys().collect::<Vec<_>>(),
+        ),
+        ("magazines", locations.magazines.keys().collect()),
+        ("companions", locations.companions.keys().collect()),
+    ] {
+        if let Some(name) = names
+            .into_iter()
+            .find(|name| !perk_names.contains(&normalize(name)))
+        {
+            panic!(
+                "Error in src/locations.yaml: Unknown perk in {}: {}",
+                section, name
+            );
+        }
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/perk_data.rs");
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let out_dir = Path::new(&out_dir);
+    let perks = convert_perks(out_dir);
+    let locations = convert("locations", out_dir);
+    check_locations(&perks, &locations);
+    for name in ["fo76", "classic"] {
+        convert(name, out_dir);
+    }
+}
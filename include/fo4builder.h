@@ -0,0 +1,66 @@
+/* C interface to fo4builder, built with `cargo build --release --lib --features ffi` */
+#ifndef FO4BUILDER_H
+#define FO4BUILDER_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An opaque build handle */
+typedef struct Fo4Build Fo4Build;
+
+typedef struct Fo4Stats {
+    uint32_t required_level;
+    float health;
+    float action_points;
+    uint32_t carry_weight;
+    uint32_t hits_per_crit;
+    double experience_mul;
+    float melee_damage_mul;
+    float buying_price_mul;
+    float selling_price_mul;
+} Fo4Stats;
+
+/* S.P.E.C.I.A.L. indices */
+enum {
+    FO4_STRENGTH,
+    FO4_PERCEPTION,
+    FO4_ENDURANCE,
+    FO4_CHARISMA,
+    FO4_INTELLIGENCE,
+    FO4_AGILITY,
+    FO4_LUCK,
+};
+
+/* Functions returning int32_t give 0 on success and -1 on failure */
+const char *fo4_last_error(void);
+
+/* Load the perk data, call before anything else */
+int32_t fo4_init(void);
+
+/* Null if the perk data can't be loaded */
+Fo4Build *fo4_build_new(void);
+void fo4_build_free(Fo4Build *build);
+
+int32_t fo4_build_set_stat(Fo4Build *build, uint32_t stat, uint8_t value);
+uint8_t fo4_build_get_stat(const Fo4Build *build, uint32_t stat);
+int32_t fo4_build_set_bobblehead(Fo4Build *build, uint32_t stat, uint8_t on);
+
+/* Perks are named by language-independent keys like "strength-1", rank 0 removes */
+int32_t fo4_build_add_perk(Fo4Build *build, const char *key, uint8_t rank);
+uint8_t fo4_build_perk_rank(const Fo4Build *build, const char *key);
+
+int32_t fo4_build_stats(const Fo4Build *build, Fo4Stats *stats);
+
+uint32_t fo4_perk_count(void);
+/* Free the result with fo4_string_free */
+char *fo4_perk_key(uint32_t index);
+void fo4_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,261 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    build::Build,
+    config::{config, reload_config, Config},
+};
+
+const BUILDS: &str = "builds/";
+const CONFIG: &str = "config.yaml";
+
+pub struct ArchiveReport {
+    pub files: usize,
+    pub config: bool,
+    /// Builds that differ from the archived ones and were not overwritten
+    pub kept: Vec<String>,
+    /// Archived files that are not builds, which are never unpacked
+    pub skipped: Vec<String>,
+}
+
+/// The build a file in the builds folder holds, if its name is one a build could be saved as
+fn build_name(file_name: &str) -> Option<&str> {
+    let name = file_name.strip_suffix(".yaml")?;
+    Build::check_name(name).is_ok().then_some(name)
+}
+
+/// The build files in a folder, leaving out the perk data, scripts, and anything
+/// else the builds folder shares with the data folder
+fn build_files(dir: &Path, skip: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_file()
+            && build_name(&entry.file_name().to_string_lossy()).is_some()
+            && path.canonicalize()? != skip
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Bundle every build in the builds folder, along with the config, into a zip file
+pub fn export(path: &Path) -> anyhow::Result<ArchiveReport> {
+    let dir = Build::dir();
+    let mut zip = ZipWriter::new(File::create(path)?);
+    // The archive may be inside the builds folder
+    let skip = path.canonicalize()?;
+    let paths = build_files(&dir, &skip)?;
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for file in &paths {
+        let name = file
+            .strip_prefix(&dir)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        zip.start_file(format!("{}{}", BUILDS, name), options)?;
+        zip.write_all(&fs::read(file)?)?;
+    }
+    let config_path = Config::path();
+    let config = config_path.exists();
+    if config {
+        // The backup token is a password, so it stays out of the archive
+        let mut config = self::config();
+        config.backup_token = None;
+        zip.start_file(CONFIG, options)?;
+        zip.write_all(&serde_yaml::to_vec(&config)?)?;
+    }
+    zip.finish()?;
+    Ok(ArchiveReport {
+        files: paths.len(),
+        config,
+        kept: Vec::new(),
+        skipped: Vec::new(),
+    })
+}
+
+/// Unpack an archive made by [`export`] into the builds folder and config
+///
+/// The folders in the archived config belong to the machine it was made on,
+/// so this machine's builds and sync folders are kept. So are the settings that
+/// decide what this machine trusts or runs: the backup server and token, the
+/// perk data source and key, and the stat formulas. Only files named like builds
+/// are unpacked, directly into the builds folder, so an archive can't replace the
+/// perk data, scripts, or hidden files like the share signing key. Builds that
+/// differ from the archived ones are only replaced with `overwrite`.
+pub fn import(path: &Path, overwrite: bool) -> anyhow::Result<ArchiveReport> {
+    import_into(path, &Build::dir(), overwrite)
+}
+
+fn import_into(path: &Path, dir: &Path, overwrite: bool) -> anyhow::Result<ArchiveReport> {
+    let mut zip = ZipArchive::new(File::open(path)?)?;
+    let mut report = ArchiveReport {
+        files: 0,
+        config: false,
+        kept: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+        if name == CONFIG {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            let mut imported: Config = serde_yaml::from_slice(&bytes)?;
+            let current = config();
+            imported.builds_dir = current.builds_dir;
+            imported.sync_dir = current.sync_dir;
+            imported.backup_url = current.backup_url;
+            imported.backup_token = current.backup_token;
+            imported.data_url = current.data_url;
+            imported.data_key = current.data_key;
+            imported.stats = current.stats;
+            let config_path = Config::path();
+            if let Some(parent) = config_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(config_path, serde_yaml::to_vec(&imported)?)?;
+            reload_config();
+            report.config = true;
+        } else if let Some(file_name) = name
+            .strip_prefix(BUILDS)
+            .filter(|n| build_name(n).is_some())
+        {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            let target = dir.join(file_name);
+            if !overwrite && target.exists() && fs::read(&target)? != bytes {
+                report.kept.push(file_name.to_string());
+                continue;
+            }
+            fs::create_dir_all(dir)?;
+            fs::write(target, bytes)?;
+            report.files += 1;
+        } else {
+            report.skipped.push(name);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root(test: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("fo4-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("builds")).unwrap();
+        root
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        for (name, bytes) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn only_build_files_are_archived() {
+        let root = temp_root("archive-list");
+        let dir = root.join("builds");
+        for name in [
+            "sniper.yaml",
+            "perks.yaml",
+            "config.yaml",
+            ".hidden.yaml",
+            "notes.txt",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        fs::create_dir_all(dir.join("sub.yaml")).unwrap();
+        let archive = dir.join("backup.yaml");
+        fs::write(&archive, "").unwrap();
+        let files = build_files(&dir, &archive.canonicalize().unwrap()).unwrap();
+        assert_eq!(files, [dir.join("sniper.yaml")]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn changed_builds_are_kept_unless_overwriting() {
+        let root = temp_root("archive-keep");
+        let dir = root.join("builds");
+        fs::write(dir.join("sniper.yaml"), "name: mine\n").unwrap();
+        fs::write(dir.join("tank.yaml"), "name: tank\n").unwrap();
+        let path = root.join("archive.zip");
+        write_zip(
+            &path,
+            &[
+                ("builds/sniper.yaml", b"name: theirs\n"),
+                ("builds/tank.yaml", b"name: tank\n"),
+            ],
+        );
+        let report = import_into(&path, &dir, false).unwrap();
+        assert_eq!(report.files, 1);
+        assert_eq!(report.kept, ["sniper.yaml"]);
+        assert_eq!(
+            fs::read_to_string(dir.join("sniper.yaml")).unwrap(),
+            "name: mine\n"
+        );
+        let report = import_into(&path, &dir, true).unwrap();
+        assert_eq!(report.files, 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("sniper.yaml")).unwrap(),
+            "name: theirs\n"
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn only_builds_are_unpacked_into_the_builds_folder() {
+        let root = temp_root("archive");
+        let dir = root.join("builds");
+        let path = root.join("archive.zip");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        for name in [
+            "builds/sniper.yaml",
+            "builds/../escaped.yaml",
+            "builds/..\\escaped.yaml",
+            "builds/sub/nested.yaml",
+            "builds/perks.yaml",
+            "builds/perks.cache",
+            "builds/lang/en.yaml",
+            "builds/scripts/hook.rhai",
+            "builds/.share-key",
+            "../outside.yaml",
+            "/tmp/absolute.yaml",
+        ] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(b"name: evil\n").unwrap();
+        }
+        zip.finish().unwrap();
+
+        let report = import_into(&path, &dir, true).unwrap();
+        assert_eq!(report.files, 1);
+        assert_eq!(report.skipped.len(), 10, "{:?}", report.skipped);
+        let mut unpacked: Vec<String> = fs::read_dir(&root)
+            .unwrap()
+            .chain(fs::read_dir(&dir).unwrap())
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        unpacked.sort();
+        assert_eq!(unpacked, ["archive.zip", "builds", "sniper.yaml"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+use std::{fs, io::Read};
+
+use anyhow::bail;
+
+use crate::{build::Build, config::config};
+
+const INDEX: &str = "index.txt";
+
+fn endpoint() -> anyhow::Result<(String, Option<String>)> {
+    let config = config();
+    match config.backup_url {
+        Some(url) => Ok((url.trim_end_matches('/').into(), config.backup_token)),
+        None => bail!("No backup server set. Try \"config backup-url <URL>\"."),
+    }
+}
+
+fn request(method: &str, url: &str, token: &Option<String>) -> ureq::Request {
+    let request = ureq::request(method, url);
+    match token {
+        Some(token) => request.set("Authorization", &format!("Bearer {}", token)),
+        None => request,
+    }
+}
+
+fn get(url: &str, token: &Option<String>) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    request("GET", url, token)
+        .call()?
+        .into_reader()
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn push() -> anyhow::Result<usize> {
+    let (url, token) = endpoint()?;
+    let mut names = Vec::new();
+    for entry in fs::read_dir(Build::dir())? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.file_type()?.is_file() || name.starts_with('.') {
+            continue;
+        }
+        request("PUT", &format!("{}/{}", url, name), &token)
+            .send_bytes(&fs::read(entry.path())?)?;
+        names.push(name);
+    }
+    request("PUT", &format!("{}/{}", url, INDEX), &token).send_string(&names.join("\n"))?;
+    Ok(names.len())
+}
+
+pub fn pull() -> anyhow::Result<usize> {
+    let (url, token) = endpoint()?;
+    let index = String::from_utf8(get(&format!("{}/{}", url, INDEX), &token)?)?;
+    let dir = Build::dir();
+    fs::create_dir_all(&dir)?;
+    let mut count = 0;
+    for name in index.lines().map(str::trim).filter(|name| !name.is_empty()) {
+        if name.contains(['/', '\\']) || name.starts_with('.') {
+            bail!("Backup index contains an invalid file name: {}", name)
+        }
+        fs::write(dir.join(name), get(&format!("{}/{}", url, name), &token)?)?;
+        count += 1;
+    }
+    Ok(count)
+}
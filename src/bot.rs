@@ -0,0 +1,216 @@
+use std::{io, iter::once};
+
+use clap::Parser;
+use fo4builder::{
+    build::Build,
+    special::{catalog, PerkId, SpecialStat},
+};
+use serenity::{
+    all::{
+        Context, CreateEmbed, CreateEmbedFooter, CreateMessage, EventHandler, GatewayIntents,
+        Message,
+    },
+    async_trait, Client,
+};
+
+use crate::{Command, Outcome, Session};
+
+const PREFIX: char = '!';
+const DEFAULT_LEVEL: u8 = 50;
+const MAX_SEARCH_TIME: f32 = 10.0;
+const MAX_THREADS: usize = 4;
+const COLOR: u32 = 0xf5c542;
+
+pub fn run(token: &str) -> anyhow::Result<()> {
+    colored::control::set_override(false);
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let intents = GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::DIRECT_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT;
+        let mut client = Client::builder(token, intents)
+            .event_handler(Handler)
+            .await?;
+        println!("Connected to Discord, listening for {}commands", PREFIX);
+        client.start().await?;
+        Ok(())
+    })
+}
+
+struct Handler;
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn message(&self, ctx: Context, msg: Message) {
+        if msg.author.bot {
+            return;
+        }
+        let line = match msg.content.strip_prefix(PREFIX) {
+            Some(line) => line.to_owned(),
+            None => return,
+        };
+        // The optimizer can take a while, so keep it off the async workers
+        let reply = match tokio::task::spawn_blocking(move || respond(&line)).await {
+            Ok(Some(reply)) => reply,
+            Ok(None) => return,
+            Err(e) => error_embed(e.to_string()),
+        };
+        let message = CreateMessage::new().embed(reply);
+        if let Err(e) = msg.channel_id.send_message(&ctx.http, message).await {
+            eprintln!("Unable to reply: {}", e);
+        }
+    }
+}
+
+fn respond(line: &str) -> Option<CreateEmbed> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let res = match words.as_slice() {
+        ["perk", name @ ..] if !name.is_empty() => perk(&name.join(" ")),
+        ["build", "show", code] => {
+            Build::from_share_code(code).and_then(|build| build_embed(&build))
+        }
+        ["optimize" | "opt", ..] => optimize(&words),
+        ["help"] => Ok(help()),
+        _ => return None,
+    };
+    Some(res.unwrap_or_else(|e| error_embed(e.to_string())))
+}
+
+fn help() -> CreateEmbed {
+    CreateEmbed::new().title("Commands").colour(COLOR).description(format!(
+        "`{p}perk <name>` Describe a perk\n\
+         `{p}build show <code>` Show a build from its share code\n\
+         `{p}optimize <stat=weight>... [--level N] [--search deep]` Find a build for weighted stats",
+        p = PREFIX
+    ))
+}
+
+fn error_embed(message: String) -> CreateEmbed {
+    CreateEmbed::new()
+        .colour(0xe04040)
+        .description(truncate(&message, 4096))
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        s.into()
+    } else {
+        s.chars().take(max - 1).chain(once('…')).collect()
+    }
+}
+
+fn perk(name: &str) -> anyhow::Result<CreateEmbed> {
+    let id: PerkId = name.parse()?;
+    let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+    let info = def.info(id, Default::default(), Default::default());
+    let mut embed = CreateEmbed::new()
+        .title(&info.name)
+        .colour(COLOR)
+        .footer(CreateEmbedFooter::new(&info.kind));
+    if let [rank] = info.ranks.as_slice() {
+        embed = embed.description(truncate(&rank.description, 4096));
+    } else {
+        for (i, rank) in info.ranks.iter().enumerate().take(25) {
+            let title = if rank.required_level > 1 {
+                format!("Rank {} (level {})", i + 1, rank.required_level)
+            } else {
+                format!("Rank {}", i + 1)
+            };
+            embed = embed.field(title, truncate(&rank.description, 1024), false);
+        }
+    }
+    Ok(embed)
+}
+
+fn build_embed(build: &Build) -> anyhow::Result<CreateEmbed> {
+    let profile = build.profile();
+    let special = SpecialStat::ALL
+        .iter()
+        .map(|stat| format!("{} {}", stat, build.total_points(*stat)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let perks = build
+        .perks
+        .iter()
+        .map(|(id, rank)| format!("{} {}", build.perk_name(id), rank))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(CreateEmbed::new()
+        .title(build.name.as_deref().unwrap_or("Build"))
+        .colour(COLOR)
+        .description(format!(
+            "Required level {}\n{}",
+            profile.required_level(build),
+            profile
+                .derived_stats(build)
+                .iter()
+                .map(|(name, value)| format!("{} {}", name, value))
+                .collect::<Vec<_>>()
+                .join(" · ")
+        ))
+        .field("S.P.E.C.I.A.L.", special, true)
+        .field(
+            "Perks",
+            if perks.is_empty() {
+                "None".into()
+            } else {
+                truncate(&perks, 1024)
+            },
+            true,
+        )
+        .footer(CreateEmbedFooter::new(build.share_code()?)))
+}
+
+fn optimize(words: &[&str]) -> anyhow::Result<CreateEmbed> {
+    let args = once("fo4")
+        .chain(once("optimize"))
+        .chain(words[1..].iter().copied());
+    let command = Command::try_parse_from(args).map_err(|e| {
+        anyhow::anyhow!(
+            "{}",
+            e.to_string()
+                .trim_end_matches("\n\nFor more information try --help\n")
+        )
+    })?;
+    let command = match command {
+        Command::Optimize {
+            weights,
+            search,
+            time,
+            steps: None,
+            seed,
+            level,
+            pareto: None,
+            threads,
+        } => Command::Optimize {
+            weights,
+            search,
+            time: time.min(MAX_SEARCH_TIME),
+            steps: None,
+            seed,
+            level: Some(level.unwrap_or(DEFAULT_LEVEL)),
+            pareto: None,
+            threads: Some(threads.unwrap_or(MAX_THREADS).min(MAX_THREADS)),
+        },
+        Command::Optimize { steps: Some(_), .. } => anyhow::bail!("--steps is not available here"),
+        Command::Optimize { .. } => anyhow::bail!("--pareto is not available here"),
+        _ => unreachable!(),
+    };
+    let mut session = Session {
+        build: Build::default(),
+        pareto_builds: Vec::new(),
+        progress: Box::new(io::sink()),
+        tabs: Default::default(),
+        merge: None,
+        scripts: Default::default(),
+        snapshots: Default::default(),
+        history: Default::default(),
+        listing: Vec::new(),
+    };
+    match session.apply(command) {
+        Outcome::Message(message) => {
+            let message = message?;
+            Ok(build_embed(&session.build)?.title(message))
+        }
+        _ => unreachable!(),
+    }
+}
@@ -1,20 +1,46 @@
 use std::{
-    collections::BTreeMap,
-    fmt, fs,
-    ops::{Add, Mul},
-    path::{Path, PathBuf},
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
 };
+#[cfg(feature = "native")]
+use std::{fs, path::PathBuf};
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use colored::{Color, Colorize};
 use serde::{Deserialize, Serialize};
 
-use crate::special::{
-    BobbleheadId, Difficulty, FullyVariable, Gender, PerkDef, PerkId, PerkKind, Ranks, SpecialStat,
-    PERKS,
+use crate::{
+    changelog::ChangeEntry,
+    classic::{ClassicBuild, ClassicGame},
+    fo76::Fo76Build,
+    gear::{GearItem, Pounds},
+    load,
+    plan::PlanStep,
+    special::{
+        catalog, BobbleheadId, Catalog, CatalogStamp, Combine, Difficulty, EffectValue,
+        FullyVariable, Gender, Location, PerkDef, PerkId, PerkKind, Ranks, SpecialStat,
+    },
+    storage::Storage,
+    watch::Watch,
+    wishlist::Wish,
 };
+#[cfg(feature = "native")]
+use crate::{
+    config::{config, data_dir},
+    expr::format_value,
+    storage::Folder,
+};
+
+/// The version of the program, recorded in saved builds
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Names whose files the builds folder shares with other data, since it defaults to the data folder
+const RESERVED_NAMES: &[&str] = &["perks", "config"];
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Build {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
@@ -23,14 +49,97 @@ pub struct Build {
     pub special: BTreeMap<SpecialStat, u8>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub special_book: Option<SpecialStat>,
+    /// Whether the bobblehead for the book's stat is picked up before the book is read
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bobblehead_before_book: bool,
+    /// The levels stat bobbleheads are collected at, if not from the start
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bobblehead_levels: BTreeMap<SpecialStat, u8>,
+    /// The level the book is read at, if not from the start
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub book_level: Option<u8>,
+    /// Bonuses from gear and chems, which raise effective stats past 10
+    /// but never count toward perk requirements
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub buffs: BTreeMap<SpecialStat, u8>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub difficulty: Option<Difficulty>,
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub perks: BTreeMap<PerkId, u8>,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub show_sheet: bool,
+    /// Derived stats shown after every command
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watches: Vec<Watch>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub level_limit: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_level: Option<u8>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub completed: BTreeMap<PerkId, u8>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub collected: BTreeMap<PerkId, BTreeSet<u8>>,
+    /// Carried items, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub gear: BTreeMap<String, GearItem>,
+    /// Perks the build should take if they fit, highest priority first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wishlist: Vec<Wish>,
+    /// What each level-up is spent on, by level
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub plan: BTreeMap<u8, PlanStep>,
+    /// Reminders for the plan, like where to go before a pick, by the level they are due
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub milestones: BTreeMap<u8, Vec<String>>,
+    /// Each save that changed the build, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changelog: Vec<ChangeEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog: Option<CatalogStamp>,
+    /// The version of the program that last saved the build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fo76: Option<Fo76Build>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classic: Option<ClassicBuild>,
+    #[serde(skip)]
+    pub(crate) stats_cache: StatsCache,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Game {
+    #[default]
+    Fallout4,
+    Fallout76,
+    Fallout3,
+    NewVegas,
+}
+
+impl FromStr for Game {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(
+            match s.to_lowercase().replace([' ', '-', '_'], "").as_str() {
+                "4" | "fo4" | "fallout4" => Game::Fallout4,
+                "76" | "fo76" | "fallout76" => Game::Fallout76,
+                "3" | "fo3" | "fallout3" => Game::Fallout3,
+                "nv" | "fnv" | "newvegas" | "falloutnewvegas" | "falloutnv" => Game::NewVegas,
+                _ => bail!("Unknown game: {}", s),
+            },
+        )
+    }
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Game::Fallout4 => write!(f, "Fallout 4"),
+            Game::Fallout76 => write!(f, "Fallout 76"),
+            Game::Fallout3 => write!(f, "Fallout 3"),
+            Game::NewVegas => write!(f, "Fallout: New Vegas"),
+        }
+    }
 }
 
 impl Default for Build {
@@ -38,8 +147,9 @@ impl Default for Build {
         Build {
             name: None,
             gender: None,
-            special: PERKS
-                .left_values()
+            special: catalog()
+                .perks
+                .keys()
                 .filter_map(|id| {
                     if let PerkId::Special { stat, .. } = id {
                         Some((*stat, 1))
@@ -50,15 +160,201 @@ impl Default for Build {
                 .collect(),
             difficulty: None,
             special_book: None,
+            bobblehead_before_book: false,
+            bobblehead_levels: BTreeMap::new(),
+            book_level: None,
+            buffs: BTreeMap::new(),
             perks: BTreeMap::new(),
             show_sheet: false,
+            watches: Vec::new(),
             level_limit: None,
+            current_level: None,
+            completed: BTreeMap::new(),
+            collected: BTreeMap::new(),
+            gear: BTreeMap::new(),
+            wishlist: Vec::new(),
+            plan: BTreeMap::new(),
+            milestones: BTreeMap::new(),
+            changelog: Vec::new(),
+            catalog: None,
+            written_by: None,
+            fo76: None,
+            classic: None,
+            stats_cache: StatsCache::default(),
         }
     }
 }
 
+#[cfg(feature = "native")]
+fn terminal_width() -> usize {
+    terminal_size::terminal_size().map_or(80, |(width, _)| width.0 as usize)
+}
+
+#[cfg(not(feature = "native"))]
+fn terminal_width() -> usize {
+    80
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub required_level: u8,
+    pub remaining_initial_points: u8,
+    pub special: BTreeMap<SpecialStat, u8>,
+    pub health: f32,
+    pub base_health: f32,
+    pub health_per_level: f32,
+    pub base_ap: f32,
+    pub experience_mul: f64,
+    pub melee_damage_mul: f32,
+    pub hits_per_crit: u8,
+    pub carry_weight: u16,
+    pub buying_price_mul: f32,
+    pub selling_price_mul: f32,
+    pub sprint_time: f32,
+    /// Extra damage dealt to characters of a gender
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub damage_against: BTreeMap<Gender, f32>,
+    /// The totals of effects the perk data labels for display, by label
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub effects: BTreeMap<String, f32>,
+}
+
+/// Check that the book can be read for a stat with some allocated points. The book
+/// can't be read for a stat at 10, which its bobblehead brings it to if picked up first.
+fn check_book(
+    stat: SpecialStat,
+    allocated: u8,
+    bobblehead: bool,
+    bobblehead_first: bool,
+) -> anyhow::Result<()> {
+    if allocated >= 10 {
+        bail!("The S.P.E.C.I.A.L. book cannot be used on a maxed-out stat")
+    } else if bobblehead && bobblehead_first && allocated >= 9 {
+        bail!(
+            "The {} bobblehead brings it to 10 before the S.P.E.C.I.A.L. book is read, \
+            so the book must be read first",
+            stat
+        )
+    }
+    Ok(())
+}
+
+/// A single change that brings a stat's base points, bobblehead, and book back
+/// within what the game allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapFix {
+    /// Read the book for a different stat
+    MoveBook,
+    /// Read the book before picking up the bobblehead
+    BookFirst,
+    /// Go without the stat's bobblehead
+    DropBobblehead,
+    /// Allocate fewer points to the stat
+    Lower(u8),
+}
+
+impl CapFix {
+    pub fn describe(self, stat: SpecialStat) -> String {
+        let name = stat.to_string().to_lowercase();
+        match self {
+            CapFix::MoveBook => "read the book for another stat with \"book <stat>\"".into(),
+            CapFix::BookFirst => {
+                format!("read the book before the bobblehead with \"book {}\"", name)
+            }
+            CapFix::DropBobblehead => {
+                format!("go without the bobblehead with \"bobblehead {} off\"", name)
+            }
+            CapFix::Lower(points) => format!(
+                "allocate {} points with \"set {} {}\"",
+                points, name, points
+            ),
+        }
+    }
+}
+
+/// Every single change that would let the book be read for a stat with some
+/// allocated points, its bobblehead, and the order they are picked up in
+pub fn cap_fixes(
+    stat: SpecialStat,
+    allocated: u8,
+    bobblehead: bool,
+    bobblehead_first: bool,
+) -> Vec<CapFix> {
+    let legal =
+        |allocated, bobblehead, first| check_book(stat, allocated, bobblehead, first).is_ok();
+    let mut fixes = vec![CapFix::MoveBook];
+    if bobblehead && bobblehead_first && legal(allocated, true, false) {
+        fixes.push(CapFix::BookFirst);
+    }
+    if bobblehead && legal(allocated, false, false) {
+        fixes.push(CapFix::DropBobblehead);
+    }
+    if let Some(points) = (1..allocated)
+        .rev()
+        .find(|&points| legal(points, bobblehead, bobblehead_first))
+    {
+        fixes.push(CapFix::Lower(points));
+    }
+    fixes
+}
+
+/// Like [`check_book`], but list the ways to resolve the conflict when there is one
+fn check_cap(
+    stat: SpecialStat,
+    allocated: u8,
+    bobblehead: bool,
+    bobblehead_first: bool,
+) -> anyhow::Result<()> {
+    check_book(stat, allocated, bobblehead, bobblehead_first).map_err(|e| {
+        let fixes: Vec<String> = cap_fixes(stat, allocated, bobblehead, bobblehead_first)
+            .into_iter()
+            .map(|fix| fix.describe(stat))
+            .collect();
+        let fixes = match fixes.split_last() {
+            Some((last, rest)) if !rest.is_empty() => format!("{} or {}", rest.join(", "), last),
+            _ => fixes.concat(),
+        };
+        anyhow!("{}. Instead, {}.", e, fixes)
+    })
+}
+
+/// The last [`Stats`] computed for a build, with the hash of the catalog they were computed from
+///
+/// Methods that change what the stats depend on clear it with [`Build::invalidate_stats`].
+/// A clone starts out empty, since clones are usually made to be changed.
+#[derive(Default)]
+pub(crate) struct StatsCache(Mutex<Option<(u64, Stats)>>);
+
+impl Clone for StatsCache {
+    fn clone(&self) -> Self {
+        StatsCache::default()
+    }
+}
+
+impl fmt::Debug for StatsCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StatsCache").finish_non_exhaustive()
+    }
+}
+
 impl fmt::Display for Build {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.profile().fmt_build(self, f)
+    }
+}
+
+impl Build {
+    pub fn fmt_fo4(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_summary(f)?;
+        if !self.perks.is_empty() {
+            writeln!(f)?;
+            self.write_perk_list(f)?;
+        }
+        Ok(())
+    }
+    /// Write everything shown about the build above its perks: its derived stats,
+    /// S.P.E.C.I.A.L. points, and the perk chart if shown
+    pub fn write_summary(&self, f: &mut impl fmt::Write) -> fmt::Result {
         if let Some(name) = &self.name {
             let bars: String = "─".repeat(name.len());
             writeln!(f, "{}", bars)?;
@@ -66,26 +362,32 @@ impl fmt::Display for Build {
             writeln!(f, "{}", bars)?;
         }
         if let Some(difficuly) = self.difficulty {
-            writeln!(f, "{:?}", difficuly)?;
+            writeln!(
+                f,
+                "{:?} {}",
+                difficuly,
+                format!("({})", difficuly.effects()).bright_black()
+            )?;
         }
         if let Some(gender) = self.gender {
             writeln!(f, "Gender: {:?}", gender)?;
         }
+        let stats = self.stats();
         if let Some(limit) = self.level_limit {
             writeln!(
                 f,
                 "Required Level: {} {}",
-                self.required_level(),
+                stats.required_level,
                 format!("(limit {})", limit).bright_black()
             )?;
         } else {
-            writeln!(f, "Required Level: {}", self.required_level())?;
+            writeln!(f, "Required Level: {}", stats.required_level)?;
         }
-        if self.remaining_initial_points() > 0 {
+        if stats.remaining_initial_points > 0 {
             writeln!(
                 f,
                 "Remaining Initial Points: {}",
-                self.remaining_initial_points()
+                stats.remaining_initial_points
             )?;
         } else if let Some(limit) = self.level_limit {
             let points_left = limit - self.level_up_assigned_points();
@@ -96,37 +398,64 @@ impl fmt::Display for Build {
         writeln!(
             f,
             "{} {}",
-            format!("Base Health: {}", self.health()).bright_red(),
-            format!("({} + {}/lvl)", self.base_health(), self.health_per_level()).bright_black(),
-        )?;
-        writeln!(
-            f,
-            "{}",
-            format!("Base AP: {}", self.base_ap()).bright_blue()
+            format!("Base Health: {}", stats.health).bright_red(),
+            format!("({} + {}/lvl)", stats.base_health, stats.health_per_level).bright_black(),
         )?;
+        writeln!(f, "{}", format!("Base AP: {}", stats.base_ap).bright_blue())?;
         writeln!(
             f,
             "{}",
-            format!("{:.0}% XP", self.experience_mul() * 100.0).bright_green()
+            format!("{:.0}% XP", stats.experience_mul * 100.0).bright_green()
         )?;
         writeln!(
             f,
             "{}",
-            format!("Melee Damage: {:.0}%", self.melee_damage_mul() * 100.0).bright_magenta()
+            format!("Melee Damage: {:.0}%", stats.melee_damage_mul * 100.0).bright_magenta()
         )?;
         writeln!(
             f,
             "{}",
-            format!("Hits per Crit: {}", self.hits_per_crit()).bright_yellow()
+            format!("Hits per Crit: {}", stats.hits_per_crit).bright_yellow()
         )?;
-        writeln!(f, "Carry Weight: {}", self.carry_weight())?;
+        if self.gear.is_empty() {
+            writeln!(f, "Carry Weight: {}", stats.carry_weight)?;
+        } else {
+            let free = self.free_carry_weight();
+            let carried = format!("{} carried", Pounds(self.carried_weight()));
+            let left = if free < 0.0 {
+                format!("{} over", Pounds(-free)).bright_red()
+            } else {
+                format!("{} free", Pounds(free)).normal()
+            };
+            writeln!(
+                f,
+                "Carry Weight: {} ({}, {})",
+                stats.carry_weight, carried, left
+            )?;
+        }
         writeln!(
             f,
             "Buy Prices: {} / Sell Prices: {}",
-            format!("{:.0}%", self.buying_price_mul() * 100.0,).bright_white(),
-            format!("{:.0}%", self.selling_price_mul() * 100.0).bright_white(),
+            format!("{:.0}%", stats.buying_price_mul * 100.0,).bright_white(),
+            format!("{:.0}%", stats.selling_price_mul * 100.0).bright_white(),
         )?;
-        writeln!(f, "Sprint Time: {:.1} s", self.sprint_time())?;
+        writeln!(f, "Sprint Time: {:.1} s", stats.sprint_time)?;
+        for (gender, bonus) in &stats.damage_against {
+            writeln!(f, "Damage vs {}: +{:.0}%", gender.plural(), bonus * 100.0)?;
+        }
+        for (label, total) in &stats.effects {
+            writeln!(f, "{}: {}", label, total)?;
+        }
+        #[cfg(feature = "native")]
+        for (name, formula) in &config().stats {
+            match self.evaluate(formula) {
+                Ok(value) => writeln!(f, "{}: {}", name, format_value(value).bright_white())?,
+                Err(e) => writeln!(f, "{}: {}", name, e.to_string().bright_red())?,
+            }
+        }
+        if let Some(progress) = self.progress_summary() {
+            writeln!(f, "{}", progress.bright_cyan())?;
+        }
         writeln!(f)?;
         for &stat in self.special.keys() {
             let total_points = self.total_base_points(stat);
@@ -144,6 +473,21 @@ impl fmt::Display for Build {
                 stat.to_string(),
                 self.points_string(stat).color(color),
             )?;
+            if self.book_order_matters() == Some(stat) {
+                write!(
+                    f,
+                    " {}",
+                    "(read the book before the bobblehead)".bright_yellow()
+                )?;
+            }
+            let effective = stats.special[&stat];
+            if effective != total_points {
+                write!(
+                    f,
+                    " {}",
+                    format!("(effective {})", effective).bright_white()
+                )?;
+            }
             writeln!(f)?;
         }
         if self.show_sheet {
@@ -169,32 +513,50 @@ impl fmt::Display for Build {
                 writeln!(f)?;
             }
         }
-        if !self.perks.is_empty() {
-            writeln!(f)?;
-            let mut last_kind = None;
-            for (id, rank) in &self.perks {
-                if self.show_sheet && matches!(id, PerkId::Special { .. })
-                    || matches!(id, PerkId::Bobblehead(_))
-                {
-                    continue;
-                }
-                let kind = id.kind();
-                if Some(kind) != last_kind {
-                    writeln!(f, "{}", kind.to_string().bright_yellow())?;
-                    last_kind = Some(kind);
-                }
-                let def = PERKS.get_by_left(id).expect("Unknown perk");
-                writeln!(
-                    f,
-                    "  {}{}",
-                    def.name[self.gender.unwrap_or_default()],
-                    if def.max_rank() > 1 {
-                        format!(" {}", rank)
-                    } else {
-                        String::new()
-                    }
-                )?;
+        Ok(())
+    }
+    /// Write the build's perks by category, leaving out the ones the perk chart shows
+    pub fn write_perk_list(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        let mut last_kind = None;
+        for (id, rank) in &self.perks {
+            if self.show_sheet && matches!(id, PerkId::Special { .. })
+                || matches!(id, PerkId::Bobblehead(_))
+            {
+                continue;
+            }
+            let kind = id.kind();
+            if Some(kind) != last_kind {
+                writeln!(f, "{}", kind.to_string().bright_yellow())?;
+                last_kind = Some(kind);
             }
+            let def = catalog().perks.get(id).cloned().expect("Unknown perk");
+            let reached = self.reached_rank(id);
+            let rank = if self.current_level.is_some()
+                && matches!(id, PerkId::Special { .. })
+                && reached < *rank
+            {
+                format!("{}/{}", reached, rank)
+            } else {
+                rank.to_string()
+            };
+            let capped = self.rank_cap(id).is_some_and(|cap| cap == self.perks[id]);
+            writeln!(
+                f,
+                "  {}{}{}",
+                def.name[self.gender.unwrap_or_default()],
+                if def.max_rank() > 1 {
+                    format!(" {}", rank)
+                } else {
+                    String::new()
+                },
+                if capped {
+                    " (highest rank under the level limit)"
+                        .bright_black()
+                        .to_string()
+                } else {
+                    String::new()
+                }
+            )?;
         }
         Ok(())
     }
@@ -208,17 +570,20 @@ impl Build {
     pub fn base_health(&self) -> f32 {
         let endurance = self.total_points(SpecialStat::Endurance) as f32;
         let base = 80.0 + endurance * 5.0;
-        let from_perks = self.fold_effect(PerkDef::hp_add, 0.0, Add::add);
+        let from_perks = self.effect_total("hp_add");
         base + from_perks
     }
     pub fn health(&self) -> f32 {
-        let level = self.required_level() as f32;
-        self.base_health() + self.health_per_level() * (level - 1.0)
+        self.health_at(self.required_level())
+    }
+    /// The build's health at a level, which may be past its required level
+    pub fn health_at(&self, level: u8) -> f32 {
+        self.base_health() + self.health_per_level() * (level as f32 - 1.0)
     }
     pub fn base_ap(&self) -> f32 {
         let agility = self.total_points(SpecialStat::Agility) as f32;
         let base = 60.0 + agility * 10.0;
-        let from_perks = self.fold_effect(PerkDef::ap_add, 0.0, Add::add);
+        let from_perks = self.effect_total("ap_add");
         base + from_perks
     }
     pub fn hits_per_crit(&self) -> u8 {
@@ -239,7 +604,7 @@ impl Build {
     }
     pub fn buying_price_mul(&self) -> f32 {
         ((3.5 - self.total_points(SpecialStat::Charisma) as f32 * 0.15)
-            / (1.0 + self.fold_effect(PerkDef::buy_price_sub, 0.0, Add::add)))
+            / (1.0 + self.effect_total("buy_price_sub")))
         .max(1.2)
     }
     pub fn selling_price_mul(&self) -> f32 {
@@ -256,19 +621,113 @@ impl Build {
             200
         };
         let from_strength = self.total_points(SpecialStat::Strength) as u16 * 10;
-        let from_perks = self.fold_effect(PerkDef::carry_weight_add, 0, Add::add);
+        let from_perks = self.effect_total("carry_weight_add") as u16;
         base + from_strength + from_perks
     }
     pub fn melee_damage_mul(&self) -> f32 {
         1.0 + self.total_points(SpecialStat::Strength) as f32 * 0.1
-            + self.fold_effect(PerkDef::melee_damage_add, 0.0, Add::add)
+            + self.effect_total("melee_damage_add")
+    }
+    /// Extra damage dealt to characters of each gender by perks that act against one
+    pub fn damage_against(&self) -> BTreeMap<Gender, f32> {
+        let gender = self.gender.unwrap_or_default();
+        let mut damage = BTreeMap::new();
+        for (id, rank) in &self.perks {
+            let def = catalog().perks.get(id).cloned().expect("Unknown perk");
+            let Some(against) = def.gender.against else {
+                continue;
+            };
+            for bonus in def
+                .effect("damage_against_add", *rank)
+                .filter_map(EffectValue::number)
+            {
+                *damage.entry(against.resolve(gender)).or_default() += bonus;
+            }
+        }
+        damage
+    }
+    /// Whether the build's character can take a perk. Any perk can be taken
+    /// until the build has a gender.
+    pub fn can_take(&self, def: &PerkDef) -> bool {
+        match (def.gender.only, self.gender) {
+            (Some(only), Some(gender)) => only == gender,
+            _ => true,
+        }
+    }
+    /// Set the character's gender, returning the perks and ranks only the other gender can take,
+    /// which were removed
+    pub fn set_gender(&mut self, gender: Gender) -> Vec<(PerkId, u8)> {
+        self.invalidate_stats();
+        self.gender = Some(gender);
+        let removed: Vec<(PerkId, u8)> = self
+            .perks
+            .iter()
+            .filter(|(id, _)| !self.can_take(catalog().perks.get(id).expect("Unknown perk")))
+            .map(|(id, rank)| (*id, *rank))
+            .collect();
+        for (id, _) in &removed {
+            self.perks.remove(id);
+        }
+        let removed = [removed, self.remove_invalid_perks()].concat();
+        self.debug_check();
+        removed
     }
     pub fn sprint_time(&self) -> f32 {
         let ap_per_sec = (1.05 - 0.05 * self.total_points(SpecialStat::Endurance) as f32)
             * 12.0
-            * self.fold_effect(PerkDef::sprint_drain_mul, 1.0, Mul::mul);
+            * self.effect_total("sprint_drain_mul");
         self.base_ap() / ap_per_sec
     }
+    /// The build's derived stats, only recomputed after the build has changed
+    pub fn stats(&self) -> Stats {
+        let hash = catalog().hash;
+        let mut cached = self.stats_cache.0.lock().unwrap();
+        match &*cached {
+            Some((cached_hash, stats)) if *cached_hash == hash => stats.clone(),
+            _ => {
+                let stats = self.compute_stats();
+                *cached = Some((hash, stats.clone()));
+                stats
+            }
+        }
+    }
+    /// Mark the cached derived stats as stale. Anything that changes the build's
+    /// SPECIAL, perks, buffs, difficulty, gender, or gear outside of the build's own
+    /// methods must call this.
+    pub fn invalidate_stats(&mut self) {
+        *self.stats_cache.0.get_mut().unwrap() = None;
+    }
+    fn compute_stats(&self) -> Stats {
+        Stats {
+            required_level: self.required_level(),
+            remaining_initial_points: self.remaining_initial_points(),
+            special: SpecialStat::ALL
+                .iter()
+                .map(|&stat| (stat, self.total_points(stat)))
+                .collect(),
+            health: self.health(),
+            base_health: self.base_health(),
+            health_per_level: self.health_per_level(),
+            base_ap: self.base_ap(),
+            experience_mul: self.experience_mul(),
+            melee_damage_mul: self.melee_damage_mul(),
+            hits_per_crit: self.hits_per_crit(),
+            carry_weight: self.carry_weight(),
+            buying_price_mul: self.buying_price_mul(),
+            selling_price_mul: self.selling_price_mul(),
+            sprint_time: self.sprint_time(),
+            damage_against: self.damage_against(),
+            effects: catalog()
+                .effects
+                .iter()
+                .filter_map(|(name, def)| {
+                    let label = def.label.clone()?;
+                    let total = self.effect_total(name);
+                    (total != def.combine.identity()).then_some((label, total))
+                })
+                .collect(),
+        }
+    }
     pub fn total_base_points(&self, stat: SpecialStat) -> u8 {
         self.special[&stat]
             + self.bobblehead_for(stat) as u8
@@ -291,6 +750,7 @@ impl Build {
                 _ => 0,
             }
             + self.stat_increase_for(stat)
+            + self.buffs.get(&stat).copied().unwrap_or(0)
             - self.bobblehead_for(stat) as u8
     }
     pub fn bobblehead_for(&self, stat: SpecialStat) -> bool {
@@ -298,9 +758,11 @@ impl Build {
             .contains_key(&PerkId::Bobblehead(BobbleheadId::Special(stat)))
     }
     pub fn stat_increase_for(&self, stat: SpecialStat) -> u8 {
-        self.fold_effect(PerkDef::stat_increase, 0, |acc, si| {
-            acc + if si.stat == stat { si.increase } else { 0 }
-        })
+        self.effect_values("stat_increase")
+            .filter_map(EffectValue::stat)
+            .filter(|si| si.stat == stat)
+            .map(|si| si.increase)
+            .sum()
     }
     pub fn points_string(&self, stat: SpecialStat) -> String {
         format!(
@@ -318,16 +780,30 @@ impl Build {
             }
         )
     }
-    pub fn fold_effect<'a, F, T, G, A, I>(&'a self, get: F, init: A, fold: G) -> A
-    where
-        F: Fn(&'a PerkDef, u8) -> I + 'a,
-        G: Fn(A, T) -> A + Clone,
-        I: Iterator<Item = T>,
-    {
+    fn effect_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = EffectValue> + 'a {
         self.perks
             .iter()
-            .flat_map(|(id, rank)| get(PERKS.get_by_left(id).expect("Unknown perk"), *rank))
-            .fold(init, fold)
+            .flat_map(move |(id, rank)| {
+                catalog()
+                    .perks
+                    .get(id)
+                    .expect("Unknown perk")
+                    .effect(name, *rank)
+            })
+            .chain(
+                self.worn_legendaries()
+                    .filter_map(move |def| def.effects.get(name)),
+            )
+    }
+    /// The combined value of a numeric effect across the build's perks
+    pub fn effect_total(&self, name: &str) -> f32 {
+        let combine = catalog()
+            .effects
+            .get(name)
+            .map_or(Combine::Add, |def| def.combine);
+        self.effect_values(name)
+            .filter_map(EffectValue::number)
+            .fold(combine.identity(), |acc, val| combine.apply(acc, val))
     }
     pub fn remaining_initial_points(&self) -> u8 {
         Self::INITIAL_ASSIGNABLE_POINTS.saturating_sub(self.assigned_special_points())
@@ -349,42 +825,115 @@ impl Build {
     pub fn level_up_assigned_points(&self) -> u8 {
         self.level_up_assigned_special_points() + self.assigned_perk_points()
     }
+    /// The highest rank of a perk that can be taken under the level limit,
+    /// if the limit keeps any of its ranks out of reach
+    pub fn rank_cap(&self, id: &PerkId) -> Option<u8> {
+        let limit = self.level_limit?;
+        let def = catalog().perks.get(id).cloned()?;
+        let cap = def.ranks.highest_rank_within_level(limit);
+        (cap < def.max_rank()).then_some(cap)
+    }
     pub fn required_level(&self) -> u8 {
         let for_rank_reqs = self
             .perks
             .iter()
-            .map(|(id, rank)| {
-                PERKS
-                    .get_by_left(id)
-                    .expect("Unknown perk")
-                    .ranks
-                    .required_level(*rank)
-            })
+            .map(|(id, rank)| self.pick_level(*id, *rank))
             .max()
             .unwrap_or(1);
         let for_spent_points = self.level_up_assigned_points() + 1;
         for_rank_reqs.max(for_spent_points)
     }
-    pub fn set(&mut self, stat: SpecialStat, mut allocated: u8) -> anyhow::Result<()> {
-        let mut add_bobble = false;
-        if allocated == 11 {
-            allocated = 10;
-            add_bobble = true;
-        }
+    /// Set a stat's allocated points, returning the perks and ranks that no
+    /// longer have enough points and were removed
+    pub fn set(&mut self, stat: SpecialStat, allocated: u8) -> anyhow::Result<Vec<(PerkId, u8)>> {
+        self.invalidate_stats();
         if allocated > 10 {
-            bail!("Cannot allocate more than 10 points to any S.P.E.C.I.A.L. stat");
+            bail!(
+                "Cannot allocate more than 10 points to any S.P.E.C.I.A.L. stat. \
+                Use \"bobblehead {} on\" to add its bobblehead.",
+                stat.to_string().to_lowercase()
+            );
         } else if allocated == 0 {
             bail!("S.P.E.C.I.A.L. stats cannot be less the 1")
         }
+        if self.special_book == Some(stat) {
+            check_cap(
+                stat,
+                allocated,
+                self.bobblehead_for(stat),
+                self.bobblehead_before_book,
+            )?;
+        }
         self.special.insert(stat, allocated);
-        if add_bobble {
-            self.perks
-                .insert(PerkId::Bobblehead(BobbleheadId::Special(stat)), 1);
+        let removed = self.remove_invalid_perks();
+        self.debug_check();
+        Ok(removed)
+    }
+    /// Pick up or drop a stat's bobblehead, returning the perks and ranks that
+    /// no longer have enough points without it
+    pub fn set_bobblehead(
+        &mut self,
+        stat: SpecialStat,
+        on: bool,
+    ) -> anyhow::Result<Vec<(PerkId, u8)>> {
+        if on == self.bobblehead_for(stat) {
+            bail!(
+                "The {} bobblehead is already {}",
+                stat,
+                if on { "on" } else { "off" }
+            );
+        }
+        let id = PerkId::Bobblehead(BobbleheadId::Special(stat));
+        if on {
+            self.add_perk(id, 1)?;
+            Ok(Vec::new())
+        } else {
+            self.remove_perk(id)
+        }
+    }
+    /// Read the book for a stat, picking up that stat's bobblehead before or after it
+    pub fn set_book(
+        &mut self,
+        stat: Option<SpecialStat>,
+        bobblehead_first: bool,
+    ) -> anyhow::Result<()> {
+        if let Some(stat) = stat {
+            check_cap(
+                stat,
+                self.special[&stat],
+                self.bobblehead_for(stat),
+                bobblehead_first,
+            )?;
+        }
+        self.special_book = stat;
+        self.bobblehead_before_book = stat.is_some() && bobblehead_first;
+        if stat.is_none() {
+            self.book_level = None;
         }
         self.remove_invalid_perks();
+        self.debug_check();
         Ok(())
     }
-    fn add_perk_impl(&mut self, id: PerkId, rank: u8) {
+    /// The stat whose book and bobblehead only both count if the book is read first
+    pub fn book_order_matters(&self) -> Option<SpecialStat> {
+        self.special_book.filter(|stat| {
+            !self.bobblehead_before_book && self.bobblehead_for(*stat) && self.special[stat] == 9
+        })
+    }
+    pub fn set_buff(&mut self, stat: SpecialStat, amount: u8) -> anyhow::Result<()> {
+        self.invalidate_stats();
+        if amount > 100 {
+            bail!("A buff cannot add more than 100 points");
+        }
+        if amount == 0 {
+            self.buffs.remove(&stat);
+        } else {
+            self.buffs.insert(stat, amount);
+        }
+        Ok(())
+    }
+    pub fn add_perk_impl(&mut self, id: PerkId, rank: u8) {
+        self.invalidate_stats();
         self.perks.insert(id, rank);
         if let PerkId::Special { stat, points } = id {
             while self.total_base_points(stat) < points {
@@ -392,14 +941,26 @@ impl Build {
             }
         }
     }
-    pub fn add_perk(&mut self, def: &PerkDef, rank: u8) -> anyhow::Result<()> {
-        let id = if let Some(id) = PERKS.get_by_right(def) {
-            *id
-        } else {
-            bail!("Unknown perk")
-        };
+    pub fn add_perk(&mut self, id: PerkId, rank: u8) -> anyhow::Result<()> {
+        let def = catalog()
+            .perks
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown perk"))?;
+        if let PerkId::Bobblehead(BobbleheadId::Special(stat)) = id {
+            if self.special_book == Some(stat) {
+                check_cap(stat, self.special[&stat], true, self.bobblehead_before_book)?;
+            }
+        }
+        if rank > 0 && !self.can_take(&def) {
+            bail!(
+                "{} can only be taken by a {:?} character",
+                self.perk_name(&id),
+                def.gender.only.unwrap_or_default()
+            );
+        }
         if rank == 0 {
-            self.remove_perk(def)?;
+            self.remove_perk(id)?;
         } else {
             match &def.ranks {
                 Ranks::Single { .. } => {
@@ -429,38 +990,261 @@ impl Build {
                 }
             }
         }
+        self.debug_check();
         Ok(())
     }
-    pub fn remove_perk(&mut self, def: &PerkDef) -> anyhow::Result<()> {
-        if let Some(id) = PERKS.get_by_right(def) {
-            self.perks.remove(id);
-            self.remove_invalid_perks();
-            Ok(())
+    /// Remove a perk, returning the other perks and ranks that no longer have
+    /// enough points without it and were removed too
+    pub fn remove_perk(&mut self, id: PerkId) -> anyhow::Result<Vec<(PerkId, u8)>> {
+        self.invalidate_stats();
+        if catalog().perks.contains_key(&id) {
+            self.perks.remove(&id);
+            if let PerkId::Bobblehead(BobbleheadId::Special(stat)) = id {
+                self.bobblehead_levels.remove(&stat);
+            }
+            let removed = self.remove_invalid_perks();
+            self.debug_check();
+            Ok(removed)
         } else {
             bail!("Unknown perk")
         }
     }
     pub fn reset(&mut self) {
+        self.invalidate_stats();
         for i in self.special.values_mut() {
             *i = 1;
         }
         self.special_book = None;
+        self.bobblehead_before_book = false;
+        self.bobblehead_levels.clear();
+        self.book_level = None;
+        self.buffs.clear();
         self.perks.clear();
-        self.gender = None
+        self.completed.clear();
+        self.collected.clear();
+        self.current_level = None;
+        self.gender = None;
+        if let Some(fo76) = &mut self.fo76 {
+            *fo76 = Fo76Build::default();
+        }
+        if let Some(classic) = &mut self.classic {
+            *classic = ClassicBuild::new(classic.game);
+        }
+        self.debug_check();
+    }
+    pub fn game(&self) -> Game {
+        match &self.classic {
+            Some(classic) if classic.game == ClassicGame::Fallout3 => Game::Fallout3,
+            Some(_) => Game::NewVegas,
+            None if self.fo76.is_some() => Game::Fallout76,
+            None => Game::Fallout4,
+        }
     }
-    fn remove_invalid_perks(&mut self) {
+    /// Reset the build and switch it to another game's rules
+    pub fn reset_for(&mut self, game: Game) {
+        self.reset();
+        self.fo76 = (game == Game::Fallout76).then(Fo76Build::default);
+        self.classic = match game {
+            Game::Fallout3 => Some(ClassicBuild::new(ClassicGame::Fallout3)),
+            Game::NewVegas => Some(ClassicBuild::new(ClassicGame::NewVegas)),
+            _ => None,
+        };
+        self.debug_check();
+    }
+    /// Fit the build to perk data that was just reloaded, describing each perk removed or
+    /// lowered. Names come from the data the build was made with.
+    pub fn revalidate_perks(&mut self, previous: &Catalog) -> Vec<String> {
+        self.invalidate_stats();
+        let gender = self.gender.unwrap_or_default();
+        let name = |id: &PerkId| match previous.perks.get(id) {
+            Some(def) => def.name[gender].to_string(),
+            None => format!("{:?}", id),
+        };
+        let catalog = catalog();
+        let unknown: BTreeSet<PerkId> = self
+            .perks
+            .keys()
+            .chain(self.completed.keys())
+            .chain(self.collected.keys())
+            .filter(|id| !catalog.perks.contains_key(id))
+            .copied()
+            .collect();
+        let mut changes = Vec::new();
+        for id in &unknown {
+            self.perks.remove(id);
+            self.completed.remove(id);
+            self.collected.remove(id);
+            changes.push(format!(
+                "Removed {}, which is no longer in the data",
+                name(id)
+            ));
+        }
+        for (id, rank) in &mut self.perks {
+            let max = catalog.perks[id].max_rank();
+            if *rank > max {
+                *rank = max;
+                changes.push(format!(
+                    "Lowered {} to its new max rank of {}",
+                    name(id),
+                    max
+                ));
+            }
+        }
+        for (id, rank) in self.remove_invalid_perks() {
+            changes.push(format!(
+                "Removed {} {}, which now needs more points",
+                name(&id),
+                rank
+            ));
+        }
+        if let Err(e) = self.check_invariants() {
+            changes.push(format!("The build still has a problem: {}", e));
+        }
+        changes
+    }
+    /// Check that the build is internally consistent, describing the first problem found
+    pub fn check_invariants(&self) -> anyhow::Result<()> {
+        let max = self.profile().max_stat();
+        for &stat in SpecialStat::ALL {
+            match self.special.get(&stat) {
+                None => bail!("{} has no value", stat),
+                Some(&points) if !(1..=max).contains(&points) => {
+                    bail!("{} is {}, outside of 1 to {}", stat, points, max)
+                }
+                _ => {}
+            }
+        }
+        for (stat, &amount) in &self.buffs {
+            if amount > 100 {
+                bail!("{} has a buff of {}, above 100", stat, amount)
+            }
+        }
+        for (id, &rank) in &self.perks {
+            let def = catalog()
+                .perks
+                .get(id)
+                .cloned()
+                .ok_or_else(|| anyhow!("Unknown perk: {:?}", id))?;
+            if rank == 0 || rank > def.max_rank() {
+                bail!(
+                    "{} is at rank {} of {}",
+                    self.perk_name(id),
+                    rank,
+                    def.max_rank()
+                )
+            }
+            if !self.can_take(&def) {
+                bail!(
+                    "{} cannot be taken by a {:?} character",
+                    self.perk_name(id),
+                    self.gender.unwrap_or_default()
+                )
+            }
+            if let PerkId::Special { stat, points } = *id {
+                let total = self.total_base_points(stat);
+                if total < points {
+                    bail!(
+                        "{} needs {} {}, but the build has {}",
+                        self.perk_name(id),
+                        stat,
+                        points,
+                        total
+                    )
+                }
+            }
+        }
+        for wish in &self.wishlist {
+            let def = catalog()
+                .perks
+                .get(&wish.perk)
+                .cloned()
+                .ok_or_else(|| anyhow!("Unknown perk on the wishlist: {:?}", wish.perk))?;
+            if wish.rank == 0 || wish.rank > def.max_rank() {
+                bail!(
+                    "{} is wished for at rank {} of {}",
+                    self.perk_name(&wish.perk),
+                    wish.rank,
+                    def.max_rank()
+                )
+            }
+        }
+        for step in self.plan.values() {
+            if let PlanStep::Perk { perk, .. } = step {
+                if !catalog().perks.contains_key(perk) {
+                    bail!("Unknown perk in the plan: {:?}", perk)
+                }
+            }
+        }
+        self.check_book_placement()
+    }
+    /// Check that the book can still be read for its stat after the stat or its bobblehead changed
+    pub fn check_book_placement(&self) -> anyhow::Result<()> {
+        match self.special_book {
+            Some(stat) => check_book(
+                stat,
+                self.special[&stat],
+                self.bobblehead_for(stat),
+                self.bobblehead_before_book,
+            ),
+            None => Ok(()),
+        }
+    }
+    /// Clear the book if it can no longer be read for its stat, describing why
+    pub fn revalidate_book(&mut self) -> Option<String> {
+        self.invalidate_stats();
+        let stat = self.special_book?;
+        let e = self.check_book_placement().err()?;
+        self.special_book = None;
+        self.bobblehead_before_book = false;
+        self.book_level = None;
+        let removed = self.remove_invalid_perks();
+        let mut message = format!("Removed the S.P.E.C.I.A.L. book from {}: {}", stat, e);
+        if !removed.is_empty() {
+            message.push_str(&format!(
+                ", which also removed {}",
+                self.describe_perks(&removed)
+            ));
+        }
+        self.debug_check();
+        Some(message)
+    }
+    /// Panic in debug builds if a mutation left the build inconsistent
+    pub(crate) fn debug_check(&self) {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check_invariants() {
+                panic!("Build invariant violated: {}", e);
+            }
+        }
+    }
+    /// Remove the perks whose stats no longer have enough points, returning them with their ranks
+    fn remove_invalid_perks(&mut self) -> Vec<(PerkId, u8)> {
+        self.invalidate_stats();
         let special: BTreeMap<SpecialStat, u8> = self
             .special
             .keys()
             .map(|&stat| (stat, self.total_base_points(stat)))
             .collect();
-        self.perks.retain(|id, _| match id {
-            PerkId::Special { stat, points } => special[stat] >= *points,
+        let mut removed = Vec::new();
+        self.perks.retain(|id, rank| match id {
+            PerkId::Special { stat, points } if special[stat] < *points => {
+                removed.push((*id, *rank));
+                false
+            }
             _ => true,
         });
+        removed
+    }
+    /// List perks and ranks like "Armorer 2, Blacksmith 1"
+    pub fn describe_perks(&self, perks: &[(PerkId, u8)]) -> String {
+        perks
+            .iter()
+            .map(|(id, rank)| format!("{} {}", self.perk_name(id), rank))
+            .collect::<Vec<_>>()
+            .join(", ")
     }
     fn column_width(&self, stat: SpecialStat) -> usize {
-        PERKS
+        catalog()
+            .perks
             .iter()
             .filter(|(id, _)| id.kind() == PerkKind::Special(stat))
             .map(|(id, def)| {
@@ -470,12 +1254,14 @@ impl Build {
             .max()
             .unwrap_or(0)
     }
-    fn fmt_point(&self, point: u8, f: &mut fmt::Formatter) -> fmt::Result {
-        for (perk, def) in PERKS.iter() {
+    fn fmt_point(&self, point: u8, f: &mut impl fmt::Write) -> fmt::Result {
+        for (perk, def) in catalog().perks.iter() {
             if let PerkId::Special { stat, points } = perk {
                 if *points == point {
                     let color = if self.perks.contains_key(perk) {
                         Color::Cyan
+                    } else if self.rank_cap(perk) == Some(0) {
+                        Color::BrightBlack
                     } else if self.total_points(*stat) >= *points {
                         Color::White
                     } else {
@@ -501,28 +1287,113 @@ impl Build {
         }
         Ok(())
     }
+    /// The folder builds are saved in. If there is no data directory, which the
+    /// program checks for on startup, this falls back to the current directory.
+    #[cfg(feature = "native")]
     pub fn dir() -> PathBuf {
-        dirs::data_dir()
-            .expect("No data directory")
-            .join("Fallout4Builds")
+        config()
+            .builds_dir
+            .unwrap_or_else(|| data_dir().unwrap_or_else(|_| "Fallout4Builds".into()))
     }
-    pub fn path(&self) -> PathBuf {
-        Self::dir()
-            .join(self.name.as_deref().unwrap_or("last"))
+    /// Check that a build name makes a file of its own directly inside the builds folder
+    pub fn check_name(name: &str) -> anyhow::Result<()> {
+        if name.trim().is_empty()
+            || name.starts_with('.')
+            || name.contains(['/', '\\', ':', '\0'])
+            || RESERVED_NAMES
+                .iter()
+                .any(|reserved| reserved.eq_ignore_ascii_case(name))
+        {
+            bail!("Invalid build name: {:?}", name)
+        }
+        Ok(())
+    }
+    pub fn file_name(&self) -> String {
+        Path::new(self.name.as_deref().unwrap_or("last"))
             .with_extension("yaml")
+            .to_string_lossy()
+            .into_owned()
     }
-    pub fn save(&self) -> anyhow::Result<()> {
-        if self.name.is_none() {
-            bail!(
+    #[cfg(feature = "native")]
+    pub fn path(&self) -> PathBuf {
+        Self::dir().join(self.file_name())
+    }
+    #[cfg(feature = "native")]
+    pub fn save(&mut self, message: Option<&str>) -> anyhow::Result<()> {
+        self.save_to(&Folder(Build::dir()), message)
+    }
+    /// Save the build, logging what changed since it was last saved
+    pub fn save_to(&mut self, storage: &dyn Storage, message: Option<&str>) -> anyhow::Result<()> {
+        match &self.name {
+            Some(name) => Build::check_name(name)?,
+            None => bail!(
                 "A name for the build must be specified. Try \"name <NAME>\" or \"save <NAME>\"."
-            );
-        };
-        fs::create_dir_all(Build::dir())?;
-        fs::write(self.path(), serde_yaml::to_vec(&self)?)?;
+            ),
+        }
+        let previous = storage
+            .read(&self.file_name())?
+            .and_then(|bytes| load::parse(&bytes, &self.file_name()).ok());
+        let mut build = self.clone();
+        build.record_change(previous.as_ref(), message);
+        build.catalog = Some(catalog().stamp(self.perks.keys().chain(self.completed.keys())));
+        build.written_by = Some(VERSION.into());
+        storage.write(&self.file_name(), &serde_yaml::to_vec(&build)?)?;
+        self.changelog = build.changelog;
         Ok(())
     }
+    /// A warning if the build was saved by a different version of the program
+    pub fn version_skew(&self) -> Option<String> {
+        let written_by = self.written_by.as_deref()?;
+        let parse = |version: &str| -> Vec<u32> {
+            version.split('.').filter_map(|n| n.parse().ok()).collect()
+        };
+        match parse(written_by).cmp(&parse(VERSION)) {
+            Ordering::Equal => None,
+            Ordering::Less => Some(format!(
+                "This build was saved by version {}, this is version {}",
+                written_by, VERSION
+            )),
+            Ordering::Greater => Some(format!(
+                "This build was saved by version {}, which is newer than this version {}. \
+                Anything it added was ignored and will be lost if the build is saved.",
+                written_by, VERSION
+            )),
+        }
+    }
+    pub fn catalog_changes(&self) -> Vec<String> {
+        let stamp = match &self.catalog {
+            Some(stamp) => stamp,
+            None => return Vec::new(),
+        };
+        let catalog = catalog();
+        if stamp.hash == format!("{:016x}", catalog.hash) {
+            return Vec::new();
+        }
+        let mut changes = vec![format!(
+            "This build was saved with perk data version {}, current data is version {}",
+            stamp.version, catalog.version
+        )];
+        for (id, hash) in &stamp.perks {
+            match catalog.perk_hash(id) {
+                Some(current) if current == *hash => {}
+                Some(_) => changes.push(format!("  Changed: {}", self.perk_name(id))),
+                None => changes.push(format!("  Missing: {:?}", id)),
+            }
+        }
+        changes
+    }
+    #[cfg(feature = "native")]
     pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        let original_path = path.as_ref();
+        let path = Self::find(path.as_ref())?;
+        load::parse(&fs::read(&path)?, &path.to_string_lossy())
+    }
+    /// Load the valid parts of a build file, along with a description of each part that was dropped
+    #[cfg(feature = "native")]
+    pub fn load_partial(path: impl AsRef<Path>) -> anyhow::Result<(Self, Vec<String>)> {
+        load::parse_partial(&fs::read(Self::find(path.as_ref())?)?)
+    }
+    #[cfg(feature = "native")]
+    fn find(original_path: &Path) -> anyhow::Result<PathBuf> {
         let mut path = original_path.to_path_buf();
         if !path.exists() {
             path = path.with_extension("yaml")
@@ -536,21 +1407,35 @@ impl Build {
                 original_path.to_string_lossy()
             );
         }
-        let bytes = fs::read(path)?;
-        let build: Build = serde_yaml::from_slice(&bytes)?;
-        Ok(build)
+        Ok(path)
+    }
+    pub fn load_from(storage: &dyn Storage, name: &str) -> anyhow::Result<Self> {
+        let yaml = Path::new(name).with_extension("yaml");
+        let bytes = match storage.read(name)? {
+            Some(bytes) => bytes,
+            None => match storage.read(&yaml.to_string_lossy())? {
+                Some(bytes) => bytes,
+                None => bail!("Unable to find build file for \"{}\"", name),
+            },
+        };
+        load::parse(&bytes, name)
     }
-    pub fn print_special(&self, stat: SpecialStat) {
+    pub fn write_special(&self, f: &mut impl fmt::Write, stat: SpecialStat) -> fmt::Result {
         let gender = self.gender.unwrap_or_default();
         let total_points = self.total_base_points(stat);
-        println!(
+        writeln!(
+            f,
             "{} ({})",
             stat.to_string().bright_yellow(),
             self.points_string(stat)
-        );
+        )?;
         for points in 1..=10 {
             let perk_id = PerkId::Special { stat, points };
-            let perk = PERKS.get_by_left(&perk_id).expect("Unknown perk");
+            let perk = catalog()
+                .perks
+                .get(&perk_id)
+                .cloned()
+                .expect("Unknown perk");
             let this_perk_points = self.perks.get(&perk_id);
             let color = if points <= total_points {
                 if this_perk_points.is_some() {
@@ -561,53 +1446,362 @@ impl Build {
             } else {
                 Color::BrightBlack
             };
-            println!(
-                "{:2}: {} {}",
+            let past_limit = match self.rank_cap(&perk_id) {
+                Some(0) => "past the level limit".into(),
+                Some(cap) if cap + 1 == perk.max_rank() => {
+                    format!("rank {} past the level limit", perk.max_rank())
+                }
+                Some(cap) => format!("ranks {}-{} past the level limit", cap + 1, perk.max_rank()),
+                None => String::new(),
+            };
+            writeln!(
+                f,
+                "{:2}: {} {}{}",
                 points,
                 perk.name[gender].color(color),
                 if let Some(points) = this_perk_points {
-                    format!("({})", points)
+                    format!("({}) ", points)
                 } else {
                     String::new()
-                }
-            );
+                },
+                past_limit.bright_black()
+            )?;
         }
+        Ok(())
     }
-    pub fn print_perk_names(&self, kind: PerkKind) {
-        println!("{}", kind.to_string().bright_yellow());
+    pub fn write_perk_names(
+        &self,
+        f: &mut impl fmt::Write,
+        kind: PerkKind,
+        locations: bool,
+    ) -> fmt::Result {
+        writeln!(f, "{}", kind.to_string().bright_yellow())?;
         let gender = self.gender.unwrap_or_default();
-        for (id, def) in PERKS.iter().filter(|(id, _)| id.kind() == kind) {
-            let color = if self.perks.contains_key(id) {
+        let mut remaining = 0;
+        let label = |id: &PerkId, def: &PerkDef| {
+            if kind == PerkKind::Magazine && def.max_rank() > 1 {
+                let issues = self.collected.get(id).map_or(0, BTreeSet::len);
+                format!("{} ({}/{})", def.name[gender], issues, def.max_rank())
+            } else {
+                def.name[gender].to_string()
+            }
+        };
+        let width = catalog()
+            .perks
+            .iter()
+            .filter(|(id, _)| id.kind() == kind)
+            .map(|(id, def)| label(id, def).chars().count())
+            .max()
+            .unwrap_or(0);
+        for (id, def) in catalog().perks.iter().filter(|(id, _)| id.kind() == kind) {
+            let collected = self.completed.contains_key(id);
+            let color = if collected {
+                Color::BrightCyan
+            } else if self.perks.contains_key(id) {
+                remaining += 1;
                 Color::White
             } else {
                 Color::BrightBlack
             };
-            println!("  {}", def.name[gender].color(color));
+            let name = label(id, def);
+            if let Some(location) = catalog().locations.get(id).filter(|_| locations) {
+                let name = format!("{:width$}", name);
+                let level = location
+                    .level
+                    .map(|level| format!(" (level {}+)", level))
+                    .unwrap_or_default();
+                writeln!(
+                    f,
+                    "  {} {}",
+                    name.color(color),
+                    format!("{}{}", location.describe(), level).bright_black()
+                )?;
+            } else {
+                writeln!(f, "  {}", name.color(color))?;
+            }
+            if let Some(issues) = catalog().issue_locations.get(id).filter(|_| locations) {
+                self.write_issue_locations(f, id, issues)?;
+            }
+        }
+        if matches!(kind, PerkKind::Bobblehead | PerkKind::Magazine) && remaining > 0 {
+            writeln!(
+                f,
+                "{}",
+                format!("{} in the build still to collect", remaining).bright_green()
+            )?;
         }
+        Ok(())
+    }
+    fn forced_points(&self, stat: SpecialStat) -> Option<u8> {
+        let highest = self
+            .perks
+            .keys()
+            .filter_map(|id| match id {
+                PerkId::Special { stat: s, points } if *s == stat => Some(*points),
+                _ => None,
+            })
+            .max()?;
+        (highest > 1 && self.total_base_points(stat) == highest).then_some(highest)
     }
-    pub fn print_perk(&self, perk: &PerkDef) {
+    pub fn write_tree(&self, f: &mut impl fmt::Write, stat: SpecialStat) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        let total_points = self.total_base_points(stat);
+        let forced = self.forced_points(stat);
+        writeln!(
+            f,
+            "{} ({})",
+            stat.to_string().bright_yellow(),
+            self.points_string(stat)
+        )?;
+        for points in 1..=10 {
+            let perk_id = PerkId::Special { stat, points };
+            let perk = catalog()
+                .perks
+                .get(&perk_id)
+                .cloned()
+                .expect("Unknown perk");
+            let branch = if points == 10 { "└─" } else { "├─" };
+            let width = self.column_width(stat);
+            let name = if let Some(rank) = self.perks.get(&perk_id) {
+                format!("{} {}", perk.name[gender], rank)
+            } else {
+                perk.name[gender].to_string()
+            };
+            let name = format!("{:width$}", name);
+            let name = if self.perks.contains_key(&perk_id) {
+                name.bright_cyan()
+            } else if points <= total_points {
+                name.white()
+            } else {
+                name.bright_black()
+            };
+            let note = if forced == Some(points) {
+                format!("raised {} to {}", stat, points)
+            } else if points > total_points {
+                format!("needs +{}", points - total_points)
+            } else {
+                String::new()
+            };
+            writeln!(
+                f,
+                "{} {:2} {} {}",
+                branch,
+                points,
+                name,
+                note.bright_black()
+            )?;
+        }
+        Ok(())
+    }
+    pub fn write_tree_dot(&self, f: &mut impl fmt::Write, stats: &[SpecialStat]) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        writeln!(f, "digraph perks {{")?;
+        writeln!(f, "  rankdir=LR;")?;
+        for &stat in stats {
+            let total_points = self.total_base_points(stat);
+            let forced = self.forced_points(stat);
+            writeln!(f, "  \"{}\" [shape=box];", stat)?;
+            for points in 1..=10 {
+                let perk_id = PerkId::Special { stat, points };
+                let perk = catalog()
+                    .perks
+                    .get(&perk_id)
+                    .cloned()
+                    .expect("Unknown perk");
+                let threshold = format!("{} {}", stat, points);
+                let from = if points == 1 {
+                    stat.to_string()
+                } else {
+                    format!("{} {}", stat, points - 1)
+                };
+                let style = if points <= total_points {
+                    ""
+                } else {
+                    ", style=dashed"
+                };
+                writeln!(
+                    f,
+                    "  \"{}\" [shape=circle, label=\"{}\"{}];",
+                    threshold, points, style
+                )?;
+                writeln!(f, "  \"{}\" -> \"{}\";", from, threshold)?;
+                let name = &perk.name[gender];
+                let perk_style = if self.perks.contains_key(&perk_id) {
+                    "style=filled"
+                } else if points <= total_points {
+                    "style=solid"
+                } else {
+                    "style=dashed"
+                };
+                writeln!(f, "  \"{}\" [{}];", name, perk_style)?;
+                if forced == Some(points) {
+                    writeln!(f, "  \"{}\" -> \"{}\" [label=\"forced\"];", name, threshold)?;
+                } else {
+                    writeln!(f, "  \"{}\" -> \"{}\";", threshold, name)?;
+                }
+            }
+        }
+        writeln!(f, "}}")?;
+        Ok(())
+    }
+    pub fn write_path(&self, f: &mut impl fmt::Write, id: PerkId, rank: u8) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        let perk = catalog().perks.get(&id).cloned().expect("Unknown perk");
+        let rank = rank.clamp(1, perk.max_rank());
+        let current_rank = self.perks.get(&id).copied().unwrap_or(0);
+        write!(f, "{}", perk.name[gender].bright_yellow())?;
+        if perk.max_rank() > 1 {
+            write!(f, " {}", format!("rank {}", rank).bright_black())?;
+        }
+        writeln!(f)?;
+        if current_rank >= rank {
+            writeln!(f, "  Already taken")?;
+            return Ok(());
+        }
+        let mut next = self.clone();
+        next.add_perk_impl(id, rank);
+        match id {
+            PerkId::Special { stat, .. } => {
+                let from = self.total_base_points(stat);
+                let to = next.total_base_points(stat);
+                if to > from {
+                    let initial = (to - from).min(self.remaining_initial_points());
+                    write!(f, "  Raise {} from {} to {}", stat, from, to)?;
+                    if initial > 0 {
+                        write!(f, " ({} from initial points)", initial)?;
+                    }
+                    writeln!(f)?;
+                }
+                writeln!(
+                    f,
+                    "  Spend {} perk point{} on ranks {}-{}",
+                    rank - current_rank,
+                    if rank - current_rank == 1 { "" } else { "s" },
+                    current_rank + 1,
+                    rank
+                )?;
+            }
+            PerkId::Bobblehead(_) => writeln!(f, "  Collect the bobblehead")?,
+            PerkId::Magazine(_) => writeln!(f, "  Find and read the magazine")?,
+            PerkId::Companion(_) => {
+                match catalog().locations.get(&id) {
+                    Some(Location {
+                        location,
+                        quest: Some(quest),
+                        ..
+                    }) => writeln!(f, "  Recruit at {} during {}", location, quest)?,
+                    Some(location) => writeln!(f, "  Recruit at {}", location.location)?,
+                    None => {}
+                }
+                writeln!(f, "  Reach maximum affinity with the companion")?
+            }
+            PerkId::Faction(_) => writeln!(f, "  Earned through faction quests")?,
+            PerkId::Other(_) => {}
+        }
+        let rank_level = perk.ranks.required_level(rank);
+        if rank_level > 1 {
+            writeln!(f, "  Rank {} requires level {}", rank, rank_level)?;
+        }
+        let (from, to) = (self.required_level(), next.required_level());
+        if to > from {
+            writeln!(
+                f,
+                "  The build would require level {} (currently {})",
+                to, from
+            )?;
+        } else {
+            writeln!(f, "  The build's required level would stay at {}", from)?;
+        }
+        if let Some(limit) = self.level_limit {
+            if to > limit {
+                writeln!(
+                    f,
+                    "  {}",
+                    format!("This exceeds the level limit of {}", limit).bright_red()
+                )?;
+            }
+        }
+        Ok(())
+    }
+    pub fn write_synergies(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(f, "{}", "Synergies".bright_yellow())?;
+        let gender = self.gender.unwrap_or_default();
+        let mut any = false;
+        for syn in &catalog().synergies {
+            for id in self.perks.keys() {
+                let other = match syn.other(id) {
+                    Some(other) if !self.perks.contains_key(&other) => other,
+                    _ => continue,
+                };
+                any = true;
+                let name = |id| catalog().perks[&id].name[gender].clone();
+                writeln!(
+                    f,
+                    "  {} {} {}",
+                    name(other),
+                    format!("(with {})", name(*id)).bright_black(),
+                    syn.note
+                )?;
+            }
+        }
+        if !any {
+            writeln!(
+                f,
+                "  {}",
+                "No synergies with the current perks".bright_black()
+            )?;
+        }
+        Ok(())
+    }
+    pub fn write_perk(&self, f: &mut impl fmt::Write, perk_id: PerkId) -> fmt::Result {
         let gender = self.gender.unwrap_or_default();
         let difficulty = self.difficulty.unwrap_or_default();
-        print!("{}", perk.name[gender].bright_yellow());
-        let perk_id = PERKS.get_by_right(perk).expect("Unknown perk");
-        let my_rank = self.perks.get(perk_id).copied().unwrap_or(0);
-        let print_rank = |i: Option<usize>,
+        let perk = catalog()
+            .perks
+            .get(&perk_id)
+            .cloned()
+            .expect("Unknown perk");
+        write!(f, "{}", perk.name[gender].bright_yellow())?;
+        let my_rank = self.perks.get(&perk_id).copied().unwrap_or(0);
+        let cap = self.rank_cap(&perk_id);
+        #[allow(clippy::unnecessary_map_or)]
+        let write_rank = |f: &mut dyn fmt::Write,
+                          i: Option<usize>,
                           required_level: u8,
-                          description: &FullyVariable<String>| {
+                          description: &FullyVariable<String>|
+         -> fmt::Result {
+            let out_of_reach = i.is_some_and(|i| cap.is_some_and(|cap| i as u8 >= cap));
             let (rank_color, desc_color) = if i.map_or(false, |i| my_rank > i as u8) {
                 (Color::BrightCyan, Color::BrightWhite)
+            } else if out_of_reach {
+                (Color::BrightBlack, Color::BrightBlack)
             } else {
                 (Color::Cyan, Color::White)
             };
             if let Some(i) = i {
-                print!("{}", format!("Rank {}", i + 1).color(rank_color),);
-                if required_level > 1 {
-                    println!("{}", format!(" (Level {})", required_level).bright_black())
+                write!(f, "{}", format!("Rank {}", i + 1).color(rank_color))?;
+                if out_of_reach {
+                    writeln!(
+                        f,
+                        "{}",
+                        format!(
+                            " (Level {}, past the level limit of {})",
+                            required_level,
+                            self.level_limit.unwrap_or_default()
+                        )
+                        .bright_black()
+                    )?
+                } else if required_level > 1 {
+                    writeln!(
+                        f,
+                        "{}",
+                        format!(" (Level {})", required_level).bright_black()
+                    )?
                 } else {
-                    println!();
+                    writeln!(f)?;
                 }
             }
-            let width = terminal_size::terminal_size().map_or(80, |(width, _)| width.0 as usize);
+            let width = terminal_width();
             let mut words: Vec<&str> = Vec::new();
             for word in description[difficulty][gender]
                 .split_inclusive('\n')
@@ -622,44 +1816,88 @@ impl Build {
                 if newline
                     || words.iter().map(|s| s.len() + 1).sum::<usize>() + word.len() >= width - 2
                 {
-                    print!("  ");
+                    write!(f, "  ")?;
                     for word in words.drain(..) {
-                        print!("{} ", word.color(desc_color));
+                        write!(f, "{} ", word.color(desc_color))?;
                     }
-                    println!();
+                    writeln!(f)?;
                 }
                 if !newline {
                     words.push(word);
                 }
             }
             if !words.is_empty() {
-                print!("  ");
+                write!(f, "  ")?;
                 for word in words {
-                    print!("{} ", word.color(desc_color));
+                    write!(f, "{} ", word.color(desc_color))?;
                 }
-                println!();
+                writeln!(f)?;
             }
+            Ok(())
         };
         match &perk.ranks {
             Ranks::Single { description, .. } => {
-                println!();
-                print_rank(None, 1, description);
+                writeln!(f)?;
+                write_rank(f, None, 1, description)?;
             }
             Ranks::UniformCumulative {
                 count, description, ..
             } => {
-                println!(" {}", format!("({}/{})", my_rank, count).bright_black());
-                print_rank(None, 1, description);
+                writeln!(f, " {}", format!("({}/{})", my_rank, count).bright_black())?;
+                write_rank(f, None, 1, description)?;
             }
             Ranks::VaryingCumulative(ranks) => {
-                println!(
+                writeln!(
+                    f,
                     " {}",
                     format!("({}/{})", my_rank, ranks.len()).bright_black()
-                );
+                )?;
                 for (i, rank) in ranks.iter().enumerate() {
-                    print_rank(Some(i), rank.required_level, &rank.description);
+                    write_rank(f, Some(i), rank.required_level, &rank.description)?;
                 }
             }
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_cached(build: &Build) -> bool {
+        build.stats_cache.0.lock().unwrap().is_some()
+    }
+
+    #[test]
+    fn stats_are_reused_until_the_build_changes() {
+        let mut build = Build::default();
+        assert!(!is_cached(&build));
+        let before = build.stats();
+        assert!(is_cached(&build));
+        assert_eq!(build.stats().carry_weight, before.carry_weight);
+
+        build.set(SpecialStat::Strength, 5).unwrap();
+        assert!(!is_cached(&build));
+        let after = build.stats();
+        assert!(after.carry_weight > before.carry_weight);
+        assert!(is_cached(&build));
+
+        build.set_buff(SpecialStat::Strength, 2).unwrap();
+        assert!(!is_cached(&build));
+        assert!(build.stats().carry_weight > after.carry_weight);
+        assert!(!is_cached(&build.clone()));
+    }
+
+    #[test]
+    fn build_names_stay_in_the_builds_folder() {
+        for name in ["sniper", "Power Armor v2", "café"] {
+            assert!(Build::check_name(name).is_ok(), "{}", name);
+        }
+        for name in [
+            "", " ", ".hidden", "..", "../x", "a/b", "a\\b", "c:x", "a\0b", "perks", "Config",
+        ] {
+            assert!(Build::check_name(name).is_err(), "{:?}", name);
+        }
     }
 }
@@ -0,0 +1,174 @@
+#[cfg(not(all(target_arch = "wasm32", feature = "web")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::{build::Build, special::SpecialStat};
+
+/// One save of a build that changed something
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeEntry {
+    /// When the build was saved, in seconds since the Unix epoch
+    pub time: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "web"))]
+fn now() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+#[cfg(not(all(target_arch = "wasm32", feature = "web")))]
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+fn differs<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_yaml::to_value(a).ok() != serde_yaml::to_value(b).ok()
+}
+
+/// Format seconds since the Unix epoch as a UTC date and time, like "2024-03-09 14:05 UTC"
+pub fn format_time(time: u64) -> String {
+    let minutes = time % 86400 / 60;
+    // Days to a civil date, from Howard Hinnant's date algorithms
+    let z = (time / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+impl Build {
+    /// Describe what is different about the build from an earlier version of it
+    pub fn changes_since(&self, old: &Build) -> Vec<String> {
+        let mut changes = Vec::new();
+        if self.name != old.name {
+            if let Some(name) = &self.name {
+                changes.push(format!("Renamed to {}", name));
+            }
+        }
+        if self.gender != old.gender {
+            changes.push(format!(
+                "Gender {:?} -> {:?}",
+                old.gender.unwrap_or_default(),
+                self.gender.unwrap_or_default()
+            ));
+        }
+        if self.difficulty != old.difficulty {
+            changes.push(format!(
+                "Difficulty {:?} -> {:?}",
+                old.difficulty.unwrap_or_default(),
+                self.difficulty.unwrap_or_default()
+            ));
+        }
+        for stat in SpecialStat::ALL {
+            let (before, after) = (old.special.get(stat), self.special.get(stat));
+            if let (Some(before), Some(after)) = (before, after) {
+                if before != after {
+                    changes.push(format!("{} {} -> {}", stat, before, after));
+                }
+            }
+        }
+        if self.special_book != old.special_book {
+            changes.push(match self.special_book {
+                Some(stat) => format!("Special book moved to {}", stat),
+                None => "Special book removed".into(),
+            });
+        }
+        for (id, rank) in &old.perks {
+            match self.perks.get(id) {
+                None => changes.push(format!("Removed {}", self.perk_name(id))),
+                Some(new) if new != rank => {
+                    changes.push(format!("{} {} -> {}", self.perk_name(id), rank, new))
+                }
+                Some(_) => {}
+            }
+        }
+        for (id, rank) in &self.perks {
+            if !old.perks.contains_key(id) {
+                changes.push(format!("Added {} {}", self.perk_name(id), rank));
+            }
+        }
+        for name in old.gear.keys() {
+            if !self.gear.contains_key(name) {
+                changes.push(format!("Dropped {}", name));
+            }
+        }
+        for (name, item) in &self.gear {
+            match old.gear.get(name) {
+                None => changes.push(format!("Packed {}", name)),
+                Some(before) if before != item => changes.push(format!("Changed {}", name)),
+                Some(_) => {}
+            }
+        }
+        if self.level_limit != old.level_limit {
+            changes.push(match self.level_limit {
+                Some(level) => format!("Level limit set to {}", level),
+                None => "Level limit removed".into(),
+            });
+        }
+        if differs(&self.fo76, &old.fo76) {
+            changes.push("Changed the Fallout 76 cards".into());
+        }
+        if differs(&self.classic, &old.classic) {
+            changes.push("Changed skills, traits, or perks".into());
+        }
+        changes
+    }
+    /// Log a save of the build, given the version it replaces if there is one.
+    /// Nothing is logged if nothing changed and there is no message.
+    pub fn record_change(&mut self, previous: Option<&Build>, message: Option<&str>) {
+        let changes = match previous {
+            Some(previous) => self.changes_since(previous),
+            None if self.changelog.is_empty() => vec!["Created".into()],
+            None => vec![format!(
+                "Saved as {}",
+                self.name.as_deref().unwrap_or_default()
+            )],
+        };
+        if changes.is_empty() && message.is_none() {
+            return;
+        }
+        self.changelog.push(ChangeEntry {
+            time: now(),
+            changes,
+            message: message.map(String::from),
+        });
+    }
+    /// Describe each logged save, newest first
+    pub fn describe_changelog(&self) -> String {
+        if self.changelog.is_empty() {
+            return "Nothing logged yet, each save that changes the build adds to the log".into();
+        }
+        let mut lines = Vec::new();
+        for entry in self.changelog.iter().rev() {
+            let time = format_time(entry.time).bright_yellow();
+            lines.push(match &entry.message {
+                Some(message) => format!("{}  {}", time, message.bright_white()),
+                None => time.to_string(),
+            });
+            for change in &entry.changes {
+                lines.push(format!("  {}", change));
+            }
+        }
+        lines.join("\n")
+    }
+}
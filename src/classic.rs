@@ -0,0 +1,468 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use anyhow::bail;
+use colored::Colorize;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build::Build,
+    profile::{Classic, GameProfile},
+    special::{similarity, Gender, MaybeGendered, SpecialStat},
+};
+
+/// Every S.P.E.C.I.A.L. stat starts at 1, which leaves 33 points to assign
+pub const SPECIAL_POINTS: u8 = 40;
+pub const MAX_SKILL: u8 = 100;
+pub const TAG_BONUS: u8 = 15;
+pub const TAG_COUNT: usize = 3;
+pub const TRAIT_COUNT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ClassicGame {
+    Fallout3,
+    NewVegas,
+}
+
+impl fmt::Display for ClassicGame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClassicGame::Fallout3 => write!(f, "Fallout 3"),
+            ClassicGame::NewVegas => write!(f, "Fallout: New Vegas"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Ruleset {
+    pub level_cap: u8,
+    /// Levels between perks, starting from level 2
+    pub perk_every: u8,
+    pub skill_points: u8,
+    pub skill_points_per_int: f32,
+    pub skills: BTreeMap<String, SpecialStat>,
+    pub perks: Vec<ClassicPerkDef>,
+    #[serde(default)]
+    pub traits: Vec<TraitDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClassicPerkDef {
+    pub name: MaybeGendered<String>,
+    #[serde(default = "one")]
+    pub level: u8,
+    #[serde(default = "one")]
+    pub ranks: u8,
+    #[serde(default)]
+    pub special: BTreeMap<SpecialStat, u8>,
+    #[serde(default)]
+    pub skills: BTreeMap<String, u8>,
+    #[serde(alias = "desc")]
+    pub description: String,
+}
+
+fn one() -> u8 {
+    1
+}
+
+impl ClassicPerkDef {
+    /// The name builds store the perk under
+    pub fn key(&self) -> &str {
+        &self.name[Gender::Male]
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TraitDef {
+    pub name: String,
+    #[serde(alias = "desc")]
+    pub description: String,
+}
+
+static RULES: Lazy<BTreeMap<ClassicGame, Ruleset>> = Lazy::new(|| {
+    rmp_serde::from_slice(include_bytes!(concat!(env!("OUT_DIR"), "/classic.msgpack")))
+        .expect("Invalid Fallout 3/New Vegas data")
+});
+
+impl ClassicGame {
+    pub fn rules(self) -> &'static Ruleset {
+        &RULES[&self]
+    }
+}
+
+fn best_match<'a, T>(
+    name: &str,
+    items: impl Iterator<Item = (&'a str, T)>,
+    what: &str,
+) -> anyhow::Result<T> {
+    let lower = name.to_lowercase();
+    match items
+        .map(|(item, value)| (similarity(&lower, item.to_lowercase()), value))
+        .max_by_key(|(sim, _)| (*sim * 1000000.0) as u32)
+    {
+        Some((sim, value)) if sim >= 0.6 => Ok(value),
+        _ => bail!("Unknown {}: {}", what, name),
+    }
+}
+
+impl Ruleset {
+    pub fn find_perk(&self, name: &str) -> anyhow::Result<&ClassicPerkDef> {
+        best_match(
+            name,
+            self.perks
+                .iter()
+                .flat_map(|def| def.name.iter().map(move |name| (name.as_str(), def))),
+            "perk",
+        )
+    }
+    pub fn find_skill(&self, name: &str) -> anyhow::Result<&str> {
+        let lower = name.to_lowercase();
+        if let Some(skill) = self
+            .skills
+            .keys()
+            .find(|skill| skill.to_lowercase().starts_with(&lower))
+        {
+            return Ok(skill);
+        }
+        best_match(
+            name,
+            self.skills
+                .keys()
+                .map(|skill| (skill.as_str(), skill.as_str())),
+            "skill",
+        )
+    }
+    pub fn find_trait(&self, name: &str) -> anyhow::Result<&TraitDef> {
+        if self.traits.is_empty() {
+            bail!("There are no traits in this game")
+        }
+        best_match(
+            name,
+            self.traits.iter().map(|def| (def.name.as_str(), def)),
+            "trait",
+        )
+    }
+}
+
+/// The Fallout 3 or New Vegas half of a build. Skills hold the points invested at level ups.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassicBuild {
+    pub game: ClassicGame,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tagged: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub skills: BTreeMap<String, u8>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub perks: BTreeMap<String, u8>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub traits: BTreeSet<String>,
+}
+
+impl ClassicBuild {
+    pub fn new(game: ClassicGame) -> Self {
+        ClassicBuild {
+            game,
+            tagged: BTreeSet::new(),
+            skills: BTreeMap::new(),
+            perks: BTreeMap::new(),
+            traits: BTreeSet::new(),
+        }
+    }
+}
+
+impl Build {
+    fn classic(&self) -> anyhow::Result<&ClassicBuild> {
+        match &self.classic {
+            Some(classic) => Ok(classic),
+            None => bail!("This is not a Fallout 3 or New Vegas build"),
+        }
+    }
+    fn classic_mut(&mut self) -> anyhow::Result<&mut ClassicBuild> {
+        match &mut self.classic {
+            Some(classic) => Ok(classic),
+            None => bail!("This is not a Fallout 3 or New Vegas build"),
+        }
+    }
+    fn classic_special_total(&self) -> u8 {
+        self.special.values().sum()
+    }
+    /// A skill's starting value, before tagging and level-up points
+    pub fn initial_skill(&self, skill: &str) -> u8 {
+        let rules = match &self.classic {
+            Some(classic) => classic.game.rules(),
+            None => return 0,
+        };
+        let governing = rules.skills.get(skill).map_or(0, |stat| self.special[stat]);
+        2 + 2 * governing + self.special[&SpecialStat::Luck].div_ceil(2)
+    }
+    pub fn skill(&self, skill: &str) -> u8 {
+        let classic = match &self.classic {
+            Some(classic) => classic,
+            None => return 0,
+        };
+        let tag = if classic.tagged.contains(skill) {
+            TAG_BONUS
+        } else {
+            0
+        };
+        let invested = classic.skills.get(skill).copied().unwrap_or(0);
+        (self.initial_skill(skill) + tag)
+            .saturating_add(invested)
+            .min(MAX_SKILL)
+    }
+    /// Skill points gained by the given level
+    pub fn skill_points_by(&self, level: u8) -> u16 {
+        let rules = match &self.classic {
+            Some(classic) => classic.game.rules(),
+            None => return 0,
+        };
+        let per_level = rules.skill_points as f32
+            + rules.skill_points_per_int * self.special[&SpecialStat::Intelligence] as f32;
+        (per_level * level.saturating_sub(1) as f32) as u16
+    }
+    pub fn classic_required_level(&self) -> u8 {
+        let classic = match &self.classic {
+            Some(classic) => classic,
+            None => return 1,
+        };
+        let rules = classic.game.rules();
+        let perk_count = classic.perks.values().map(|&rank| rank as u16).sum::<u16>();
+        let for_perks = if perk_count == 0 {
+            1
+        } else {
+            1 + rules.perk_every as u16 * perk_count - (rules.perk_every as u16 - 1)
+        };
+        let for_levels = classic
+            .perks
+            .keys()
+            .filter_map(|key| rules.perks.iter().find(|def| def.key() == key))
+            .map(|def| def.level as u16)
+            .max()
+            .unwrap_or(1);
+        let invested: u16 = classic.skills.values().map(|&points| points as u16).sum();
+        let mut for_skills = 1;
+        while self.skill_points_by(for_skills) < invested && for_skills < u8::MAX {
+            for_skills += 1;
+        }
+        for_perks
+            .max(for_levels)
+            .max(for_skills as u16)
+            .min(u8::MAX as u16) as u8
+    }
+    pub fn set_classic(&mut self, stat: SpecialStat, value: u8) -> anyhow::Result<()> {
+        self.invalidate_stats();
+        if value > 10 {
+            bail!("Cannot allocate more than 10 points to any S.P.E.C.I.A.L. stat");
+        } else if value == 0 {
+            bail!("S.P.E.C.I.A.L. stats cannot be less the 1")
+        }
+        let total = self.classic_special_total() + value - self.special[&stat];
+        if total > SPECIAL_POINTS {
+            bail!(
+                "Only {} S.P.E.C.I.A.L. points can be assigned",
+                SPECIAL_POINTS - SpecialStat::ALL.len() as u8
+            )
+        }
+        self.special.insert(stat, value);
+        self.debug_check();
+        Ok(())
+    }
+    /// Add a perk at the given rank, or its highest one. Returns the perk's name and rank.
+    pub fn add_classic_perk(
+        &mut self,
+        name: &str,
+        rank: Option<u8>,
+    ) -> anyhow::Result<(String, u8)> {
+        let rules = self.classic()?.game.rules();
+        let def = rules.find_perk(name)?;
+        let name = def.name[self.gender.unwrap_or_default()].clone();
+        let rank = rank.unwrap_or(def.ranks);
+        if rank == 0 {
+            self.classic_mut()?.perks.remove(def.key());
+            return Ok((name, 0));
+        } else if rank > def.ranks {
+            bail!("{} only has {} ranks", name, def.ranks)
+        }
+        for (stat, needed) in &def.special {
+            if self.special[stat] < *needed {
+                bail!("{} requires {} {}", name, stat, needed)
+            }
+        }
+        for (skill, needed) in &def.skills {
+            if self.skill(skill) < *needed {
+                bail!("{} requires {} {}", name, skill, needed)
+            }
+        }
+        self.classic_mut()?.perks.insert(def.key().into(), rank);
+        Ok((name, rank))
+    }
+    pub fn remove_classic_perk(&mut self, name: &str) -> anyhow::Result<String> {
+        let def = self.classic()?.game.rules().find_perk(name)?;
+        self.classic_mut()?.perks.remove(def.key());
+        Ok(def.name[self.gender.unwrap_or_default()].clone())
+    }
+    /// Tag or untag a skill. Returns the skill's name and whether it is now tagged.
+    pub fn toggle_tag(&mut self, name: &str) -> anyhow::Result<(String, bool)> {
+        let classic = self.classic_mut()?;
+        let skill = classic.game.rules().find_skill(name)?.to_string();
+        if classic.tagged.remove(&skill) {
+            return Ok((skill, false));
+        }
+        if classic.tagged.len() == TAG_COUNT {
+            bail!("Only {} skills can be tagged", TAG_COUNT)
+        }
+        classic.tagged.insert(skill.clone());
+        Ok((skill, true))
+    }
+    /// Set how many level-up points go into a skill. Returns the skill's name.
+    pub fn invest_skill(&mut self, name: &str, points: u8) -> anyhow::Result<String> {
+        let skill = self.classic()?.game.rules().find_skill(name)?.to_string();
+        let base = self.skill(&skill) - self.classic()?.skills.get(&skill).copied().unwrap_or(0);
+        if base as u16 + points as u16 > MAX_SKILL as u16 {
+            bail!(
+                "{} can only take {} more points",
+                skill,
+                MAX_SKILL.saturating_sub(base)
+            )
+        }
+        let classic = self.classic_mut()?;
+        if points == 0 {
+            classic.skills.remove(&skill);
+        } else {
+            classic.skills.insert(skill.clone(), points);
+        }
+        Ok(skill)
+    }
+    /// Take or drop a trait. Returns the trait's name and whether it is now taken.
+    pub fn toggle_trait(&mut self, name: &str) -> anyhow::Result<(String, bool)> {
+        let classic = self.classic_mut()?;
+        let name = classic.game.rules().find_trait(name)?.name.clone();
+        if classic.traits.remove(&name) {
+            return Ok((name, false));
+        }
+        if classic.traits.len() == TRAIT_COUNT {
+            bail!("Only {} traits can be taken", TRAIT_COUNT)
+        }
+        classic.traits.insert(name.clone());
+        Ok((name, true))
+    }
+    pub fn fmt_classic(&self, classic: &ClassicBuild, f: &mut fmt::Formatter) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        let rules = classic.game.rules();
+        if let Some(name) = &self.name {
+            let bars: String = "─".repeat(name.len());
+            writeln!(f, "{}", bars)?;
+            writeln!(f, "{}", name)?;
+            writeln!(f, "{}", bars)?;
+        }
+        writeln!(f, "{}", classic.game.to_string().bright_black())?;
+        let level = self.classic_required_level();
+        if level > rules.level_cap {
+            writeln!(
+                f,
+                "{}",
+                format!(
+                    "Required Level: {} (over the level cap of {})",
+                    level, rules.level_cap
+                )
+                .bright_red()
+            )?;
+        } else {
+            writeln!(f, "Required Level: {}", level)?;
+        }
+        let remaining = SPECIAL_POINTS - self.classic_special_total();
+        if remaining > 0 {
+            writeln!(f, "Remaining Initial Points: {}", remaining)?;
+        }
+        let invested: u16 = classic.skills.values().map(|&points| points as u16).sum();
+        for (name, value) in Classic(classic.game).derived_stats(self) {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        writeln!(
+            f,
+            "Skill Points: {} {}",
+            invested,
+            format!("({} by level {})", self.skill_points_by(level), level).bright_black()
+        )?;
+        writeln!(f)?;
+        for &stat in SpecialStat::ALL {
+            writeln!(f, "{:>12} {}", stat.to_string(), self.special[&stat])?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{}", "Skills".bright_yellow())?;
+        for skill in rules.skills.keys() {
+            let line = format!("  {:16} {:3}", skill, self.skill(skill));
+            if classic.tagged.contains(skill) {
+                writeln!(f, "{} {}", line.bright_white(), "(tagged)".bright_black())?;
+            } else {
+                writeln!(f, "{}", line)?;
+            }
+        }
+        if !classic.traits.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", "Traits".bright_yellow())?;
+            for name in &classic.traits {
+                writeln!(f, "  {}", name)?;
+            }
+        }
+        if !classic.perks.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", "Perks".bright_yellow())?;
+            for (key, rank) in &classic.perks {
+                let def = rules.perks.iter().find(|def| def.key() == key);
+                let name = def.map_or(key.as_str(), |def| &def.name[gender]);
+                if def.is_some_and(|def| def.ranks > 1) {
+                    writeln!(f, "  {} {}", name, rank)?;
+                } else {
+                    writeln!(f, "  {}", name)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    pub fn write_classic_perk(&self, f: &mut impl fmt::Write, def: &ClassicPerkDef) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        writeln!(
+            f,
+            "{} {}",
+            def.name[gender].bright_yellow(),
+            format!("(level {})", def.level).bright_black()
+        )?;
+        let requirements: Vec<String> = def
+            .special
+            .iter()
+            .map(|(stat, value)| format!("{} {}", stat, value))
+            .chain(
+                def.skills
+                    .iter()
+                    .map(|(skill, value)| format!("{} {}", skill, value)),
+            )
+            .collect();
+        if !requirements.is_empty() {
+            writeln!(f, "Requires {}", requirements.join(", "))?;
+        }
+        if def.ranks > 1 {
+            writeln!(f, "{} ranks", def.ranks)?;
+        }
+        writeln!(f, "{}", def.description)?;
+        Ok(())
+    }
+    pub fn write_traits(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        let classic = match &self.classic {
+            Some(classic) => classic,
+            None => return Ok(()),
+        };
+        for def in &classic.game.rules().traits {
+            let line = format!("{}: {}", def.name, def.description);
+            if classic.traits.contains(&def.name) {
+                writeln!(f, "{}", line.bright_white())?;
+            } else {
+                writeln!(f, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
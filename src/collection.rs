@@ -0,0 +1,72 @@
+use std::{collections::BTreeSet, fmt};
+
+use anyhow::bail;
+use colored::Colorize;
+
+use crate::{
+    build::Build,
+    special::{catalog, Location, PerkId, PerkKind},
+};
+
+impl Build {
+    pub fn collect(&mut self, id: PerkId, issue: Option<u8>) -> anyhow::Result<(u8, bool)> {
+        self.invalidate_stats();
+        let def = catalog()
+            .perks
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown perk"))?;
+        let name = &def.name[self.gender.unwrap_or_default()];
+        if id.kind() != PerkKind::Magazine {
+            bail!("{} is not a magazine", name)
+        }
+        let count = def.max_rank();
+        let issues = self.collected.entry(id).or_default();
+        let issue = match issue {
+            Some(issue) if issue == 0 || issue > count => {
+                bail!("{} only has {} issue(s)", name, count)
+            }
+            Some(issue) => issue,
+            None => match (1..=count).find(|i| !issues.contains(i)) {
+                Some(issue) => issue,
+                None => bail!("All issues of {} are already collected", name),
+            },
+        };
+        let collected = issues.insert(issue);
+        if !collected {
+            issues.remove(&issue);
+        }
+        let rank = issues.len() as u8;
+        if rank == 0 {
+            self.collected.remove(&id);
+            self.completed.remove(&id);
+            self.perks.remove(&id);
+        } else {
+            self.completed.insert(id, rank);
+            self.add_perk_impl(id, rank);
+        }
+        Ok((issue, collected))
+    }
+    pub fn write_issue_locations(
+        &self,
+        f: &mut impl fmt::Write,
+        id: &PerkId,
+        issues: &[Location],
+    ) -> fmt::Result {
+        let collected = self
+            .collected
+            .get(id)
+            .cloned()
+            .unwrap_or_else(BTreeSet::new);
+        for (i, location) in issues.iter().enumerate() {
+            let issue = i as u8 + 1;
+            let line = format!("    Issue {:<2} {}", issue, location.location);
+            if collected.contains(&issue) {
+                writeln!(f, "{}", line.bright_cyan())?;
+            } else {
+                writeln!(f, "{}", line.bright_black())?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+
+use anyhow::bail;
+use colored::Colorize;
+
+use crate::{
+    build::Build,
+    expr::format_value,
+    special::{catalog, Combine, EffectValue, PerkDef, PerkId, Ranks},
+};
+
+/// The description of a perk at a rank, as words
+fn rank_words<'a>(def: &'a PerkDef, rank: u8, build: &Build) -> Vec<&'a str> {
+    let description = match &def.ranks {
+        Ranks::Single { description, .. } | Ranks::UniformCumulative { description, .. } => {
+            description
+        }
+        Ranks::VaryingCumulative(ranks) => &ranks[rank as usize - 1].description,
+    };
+    description[build.difficulty.unwrap_or_default()][build.gender.unwrap_or_default()]
+        .split_whitespace()
+        .collect()
+}
+
+/// The combined value of a numeric effect of a perk taken to a rank
+fn effect_at(def: &PerkDef, name: &str, rank: u8) -> Option<f32> {
+    let combine = catalog()
+        .effects
+        .get(name)
+        .map_or(Combine::Add, |def| def.combine);
+    let value = match &def.ranks {
+        Ranks::UniformCumulative { effects, .. } => {
+            let value = effects.get(name).and_then(EffectValue::number)?;
+            return Some((0..rank).fold(combine.identity(), |acc, _| combine.apply(acc, value)));
+        }
+        _ => def
+            .effect(name, rank)
+            .filter_map(EffectValue::number)
+            .next(),
+    };
+    value.or_else(|| def.has_effect(name).then(|| combine.identity()))
+}
+
+/// Split two lists of words into the runs that differ, as pairs of what was
+/// removed and what was added, by their longest common subsequence
+fn word_changes<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<(Vec<&'a str>, Vec<&'a str>)> {
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut changes = Vec::new();
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            if !removed.is_empty() || !added.is_empty() {
+                changes.push((removed.split_off(0), added.split_off(0)));
+            }
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            added.push(b[j]);
+            j += 1;
+        } else {
+            removed.push(a[i]);
+            i += 1;
+        }
+    }
+    if !removed.is_empty() || !added.is_empty() {
+        changes.push((removed, added));
+    }
+    changes
+}
+
+impl Build {
+    /// Check that two ranks of a perk can be compared
+    pub fn check_rank_comparison(&self, id: PerkId, from: u8, to: u8) -> anyhow::Result<()> {
+        let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+        let max = def.max_rank();
+        for rank in [from, to] {
+            if rank == 0 || rank > max {
+                bail!(
+                    "{} has ranks 1 to {}, not {}",
+                    self.perk_name(&id),
+                    max,
+                    rank
+                )
+            }
+        }
+        if from == to {
+            bail!("Give two different ranks to compare")
+        }
+        Ok(())
+    }
+    /// Write only what changes between two ranks of a perk: the level needed,
+    /// the words of the description, and the totals of its numeric effects
+    pub fn write_rank_comparison(
+        &self,
+        f: &mut impl fmt::Write,
+        id: PerkId,
+        from: u8,
+        to: u8,
+    ) -> fmt::Result {
+        let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+        writeln!(
+            f,
+            "{} {}",
+            self.perk_name(&id).bright_yellow(),
+            format!("rank {} → {}", from, to).bright_cyan()
+        )?;
+        let (level_from, level_to) = (def.ranks.required_level(from), def.ranks.required_level(to));
+        if level_from != level_to {
+            writeln!(f, "Level {} → {}", level_from, level_to)?;
+        }
+        let changes = word_changes(&rank_words(&def, from, self), &rank_words(&def, to, self));
+        if changes.is_empty() {
+            writeln!(f, "{}", "The description is the same".bright_black())?;
+        }
+        for (removed, added) in changes {
+            if !removed.is_empty() {
+                writeln!(f, "  {}", format!("- {}", removed.join(" ")).bright_red())?;
+            }
+            if !added.is_empty() {
+                writeln!(f, "  {}", format!("+ {}", added.join(" ")).bright_green())?;
+            }
+        }
+        let mut names: Vec<&str> = def
+            .ranks
+            .effects()
+            .into_iter()
+            .flat_map(|e| e.names())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        let mut first = true;
+        for name in names {
+            let (Some(before), Some(after)) =
+                (effect_at(&def, name, from), effect_at(&def, name, to))
+            else {
+                continue;
+            };
+            if (after - before).abs() < 1e-4 {
+                continue;
+            }
+            if first {
+                writeln!(f, "Effects:")?;
+                first = false;
+            }
+            let label = catalog()
+                .effects
+                .get(name)
+                .and_then(|def| def.label.clone())
+                .unwrap_or_else(|| name.into());
+            let delta = after - before;
+            let sign = if delta > 0.0 { "+" } else { "" };
+            writeln!(
+                f,
+                "  {}: {} → {} {}",
+                label,
+                format_value(before),
+                format_value(after),
+                format!("({}{})", sign, format_value(delta)).bright_white()
+            )?;
+        }
+        Ok(())
+    }
+}
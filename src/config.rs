@@ -0,0 +1,265 @@
+use std::{collections::BTreeMap, fs, path::PathBuf, str::FromStr, sync::RwLock};
+
+use anyhow::{anyhow, bail};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{expr::Expr, matcher::MatchOptions};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builds_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_threshold: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_initials: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fold_diacritics: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fold_case: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exact_match: Option<bool>,
+    /// Stats shown with every build, by name, as formulas like "agility*2 + sneak_rank*3"
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stats: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigKey {
+    BuildsDir,
+    SyncDir,
+    BackupUrl,
+    BackupToken,
+    Language,
+    DataUrl,
+    DataKey,
+    MatchThreshold,
+    MatchInitials,
+    FoldDiacritics,
+    FoldCase,
+    ExactMatch,
+}
+
+impl FromStr for ConfigKey {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().replace('_', "-").as_str() {
+            "builds-dir" | "builds" => ConfigKey::BuildsDir,
+            "sync-dir" | "sync" => ConfigKey::SyncDir,
+            "backup-url" => ConfigKey::BackupUrl,
+            "backup-token" => ConfigKey::BackupToken,
+            "language" | "lang" => ConfigKey::Language,
+            "data-url" => ConfigKey::DataUrl,
+            "data-key" => ConfigKey::DataKey,
+            "match-threshold" | "threshold" => ConfigKey::MatchThreshold,
+            "match-initials" | "initials" => ConfigKey::MatchInitials,
+            "fold-diacritics" => ConfigKey::FoldDiacritics,
+            "fold-case" => ConfigKey::FoldCase,
+            "exact-match" | "exact" => ConfigKey::ExactMatch,
+            _ => bail!("Unknown config key: {}", s),
+        })
+    }
+}
+
+fn parse_flag(value: Option<String>) -> anyhow::Result<Option<bool>> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    Ok(Some(match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" => true,
+        "false" | "no" | "off" => false,
+        _ => bail!("Expected on or off, not {}", value),
+    }))
+}
+
+/// Why the config file could not be read, if it exists but does not parse
+static CONFIG_ERROR: RwLock<Option<String>> = RwLock::new(None);
+
+fn read_config() -> Config {
+    let path = Config::path();
+    let (config, error) = match fs::read(&path) {
+        Ok(bytes) => match serde_yaml::from_slice(&bytes) {
+            Ok(config) => (config, None),
+            Err(e) => (
+                Config::default(),
+                Some(format!(
+                    "{} could not be read, so the default settings are used: {}",
+                    path.to_string_lossy(),
+                    e
+                )),
+            ),
+        },
+        Err(_) => (Config::default(), None),
+    };
+    *CONFIG_ERROR.write().unwrap() = error;
+    config
+}
+
+/// Why the config file could not be read, if it exists but does not parse
+pub fn config_error() -> Option<String> {
+    CONFIG_ERROR.read().unwrap().clone()
+}
+
+/// Refuse to change settings while the config file does not parse, so it is not
+/// written over
+fn check_writable() -> anyhow::Result<()> {
+    if let Some(error) = config_error() {
+        bail!("{}\nFix or remove it before changing settings", error)
+    }
+    Ok(())
+}
+
+fn write_config(config: &Config) -> anyhow::Result<()> {
+    let path = Config::path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_yaml::to_vec(config)?)?;
+    Ok(())
+}
+
+static CONFIG: Lazy<RwLock<Config>> = Lazy::new(|| RwLock::new(read_config()));
+
+pub fn config() -> Config {
+    CONFIG.read().unwrap().clone()
+}
+
+/// Read the config file again after it was replaced
+pub fn reload_config() {
+    *CONFIG.write().unwrap() = read_config();
+}
+
+/// The folder chosen for builds and the config file, overriding the platform's
+static DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Keep builds and the config file in a folder instead of the platform's data and config folders
+pub fn set_data_dir(dir: PathBuf) {
+    *DATA_DIR.write().unwrap() = Some(dir);
+    reload_config();
+}
+
+/// The folder builds and other data are kept in
+pub fn data_dir() -> anyhow::Result<PathBuf> {
+    DATA_DIR
+        .read()
+        .unwrap()
+        .clone()
+        .or_else(|| dirs::data_dir().map(|dir| dir.join("Fallout4Builds")))
+        .ok_or_else(|| {
+            anyhow!(
+                "This system has no data directory to keep builds in. \
+                Choose one with --data-dir or the FO4_DATA_DIR environment variable."
+            )
+        })
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        match &*DATA_DIR.read().unwrap() {
+            Some(dir) => dir.join("config.yaml"),
+            None => dirs::config_dir()
+                .map(|dir| dir.join("Fallout4Builds"))
+                .or_else(|| data_dir().ok())
+                .unwrap_or_default()
+                .join("config.yaml"),
+        }
+    }
+    pub fn set(key: ConfigKey, value: Option<String>) -> anyhow::Result<()> {
+        check_writable()?;
+        let mut config = CONFIG.write().unwrap();
+        match key {
+            ConfigKey::BuildsDir => config.builds_dir = value.map(PathBuf::from),
+            ConfigKey::SyncDir => config.sync_dir = value.map(PathBuf::from),
+            ConfigKey::BackupUrl => config.backup_url = value,
+            ConfigKey::BackupToken => config.backup_token = value,
+            ConfigKey::Language => config.language = value,
+            ConfigKey::DataUrl => config.data_url = value,
+            ConfigKey::DataKey => config.data_key = value,
+            ConfigKey::MatchThreshold => {
+                config.match_threshold = match value {
+                    Some(value) => match value.parse::<f64>() {
+                        Ok(threshold) if (0.0..=1.0).contains(&threshold) => Some(threshold),
+                        _ => bail!("The match threshold must be from 0 to 1, not {}", value),
+                    },
+                    None => None,
+                }
+            }
+            ConfigKey::MatchInitials => config.match_initials = parse_flag(value)?,
+            ConfigKey::FoldDiacritics => config.fold_diacritics = parse_flag(value)?,
+            ConfigKey::FoldCase => config.fold_case = parse_flag(value)?,
+            ConfigKey::ExactMatch => config.exact_match = parse_flag(value)?,
+        }
+        write_config(&config)
+    }
+    /// Define a stat shown with every build by a formula, or remove it without one
+    pub fn set_stat(name: &str, formula: Option<String>) -> anyhow::Result<()> {
+        check_writable()?;
+        let mut config = CONFIG.write().unwrap();
+        match formula {
+            Some(formula) => {
+                if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    bail!("A stat's name can only have letters, digits, and underscores")
+                }
+                formula.parse::<Expr>()?;
+                config.stats.insert(name.into(), formula);
+            }
+            None => {
+                if config.stats.remove(name).is_none() {
+                    bail!("There is no stat named {}", name)
+                }
+            }
+        }
+        write_config(&config)
+    }
+    /// How perk names are matched, with anything not configured left at its default
+    pub fn match_options(&self) -> MatchOptions {
+        let default = MatchOptions::default();
+        MatchOptions {
+            threshold: self.match_threshold.unwrap_or(default.threshold),
+            initials: self.match_initials.unwrap_or(default.initials),
+            fold_diacritics: self.fold_diacritics.unwrap_or(default.fold_diacritics),
+            fold_case: self.fold_case.unwrap_or(default.fold_case),
+            exact: self.exact_match.unwrap_or(default.exact),
+        }
+    }
+    pub fn describe(&self) -> String {
+        let path = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map_or("not set".into(), |path| path.to_string_lossy().into_owned())
+        };
+        let flag = |flag: bool| if flag { "on" } else { "off" };
+        let matching = self.match_options();
+        format!(
+            "sync-dir: {}\nbackup-url: {}\nbackup-token: {}\nlanguage: {}\ndata-url: {}\ndata-key: {}\n\
+            match-threshold: {}\nmatch-initials: {}\nfold-diacritics: {}\nfold-case: {}\nexact-match: {}",
+            path(&self.sync_dir),
+            self.backup_url.as_deref().unwrap_or("not set"),
+            if self.backup_token.is_some() {
+                "set"
+            } else {
+                "not set"
+            },
+            self.language.as_deref().unwrap_or("not set"),
+            self.data_url.as_deref().unwrap_or("not set"),
+            self.data_key.as_deref().unwrap_or("not set"),
+            matching.threshold,
+            flag(matching.initials),
+            flag(matching.fold_diacritics),
+            flag(matching.fold_case),
+            flag(matching.exact)
+        )
+    }
+}
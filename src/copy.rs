@@ -0,0 +1,66 @@
+use anyhow::bail;
+
+use crate::{
+    build::{Build, Game},
+    special::{PerkId, PerkKind, SpecialStat},
+};
+
+impl Build {
+    /// Take on another build's S.P.E.C.I.A.L. allocation, returning the perks and ranks
+    /// that no longer have enough points and were removed
+    pub fn copy_special(&mut self, other: &Build) -> anyhow::Result<Vec<(PerkId, u8)>> {
+        check_same_game(other)?;
+        self.apply_preset(&other.special)
+    }
+    /// Merge another build's perks into this one, optionally only those of one kind,
+    /// describing each conflict and what had to change to take the perks
+    pub fn copy_perks(
+        &mut self,
+        other: &Build,
+        kind: Option<PerkKind>,
+    ) -> anyhow::Result<(usize, Vec<String>)> {
+        check_same_game(other)?;
+        let before = self.special.clone();
+        let mut copied = 0;
+        let mut conflicts = Vec::new();
+        for (&id, &rank) in &other.perks {
+            if kind.is_some_and(|kind| id.kind() != kind) {
+                continue;
+            }
+            let name = self.perk_name(&id);
+            match self.perks.get(&id) {
+                Some(&own) if own > rank => {
+                    conflicts.push(format!("Kept {} at rank {} instead of {}", name, own, rank));
+                    continue;
+                }
+                Some(&own) if own == rank => continue,
+                _ => {}
+            }
+            match self.add_perk(id, rank) {
+                Ok(()) => copied += 1,
+                Err(e) => conflicts.push(format!("Skipped {}: {}", name, e)),
+            }
+        }
+        for &stat in SpecialStat::ALL {
+            if self.special[&stat] > before[&stat] {
+                conflicts.push(format!(
+                    "Raised {} from {} to {} to take the copied perks",
+                    stat, before[&stat], self.special[&stat]
+                ));
+            }
+        }
+        Ok((copied, conflicts))
+    }
+}
+
+pub(crate) fn check_same_game(other: &Build) -> anyhow::Result<()> {
+    let game = other.game();
+    if game != Game::Fallout4 {
+        bail!(
+            "{} is a {} build",
+            other.name.as_deref().unwrap_or("That"),
+            game
+        )
+    }
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use std::{collections::BTreeMap, fmt};
+
+use colored::Colorize;
+
+use crate::special::{Catalog, Gender, PerkId, PerkKind};
+
+/// How many perks and ranks of a category there are, and which perks have no effects
+#[derive(Default)]
+struct Category {
+    perks: usize,
+    ranks: usize,
+    description_only: Vec<PerkId>,
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("{} {}", count, if count == 1 { one } else { many })
+}
+
+impl Catalog {
+    /// The DLC a perk comes from, if it is not in the base game
+    pub fn dlc_of(&self, id: &PerkId) -> Option<&str> {
+        self.dlc
+            .iter()
+            .find_map(|(name, ids)| ids.contains(id).then_some(name.as_str()))
+    }
+    /// Write a summary of the loaded data: the perks in each category, how many perks
+    /// have each kind of effect, which perks are only described, and what each DLC adds
+    pub fn write_stats(&self, f: &mut impl fmt::Write, list: bool) -> fmt::Result {
+        let mut categories: BTreeMap<PerkKind, Category> = BTreeMap::new();
+        for (id, def) in &self.perks {
+            let category = categories.entry(id.kind()).or_default();
+            category.perks += 1;
+            category.ranks += def.max_rank() as usize;
+            if !def.has_effects() {
+                category.description_only.push(*id);
+            }
+        }
+        let ranks: usize = categories.values().map(|c| c.ranks).sum();
+        let title = match &self.source {
+            Some(source) if self.version.is_empty() => {
+                format!("Perk data in {}:", source.to_string_lossy())
+            }
+            _ => format!("Perk data {}:", self.version),
+        };
+        writeln!(
+            f,
+            "{} {}, {}",
+            title.bright_yellow(),
+            plural(self.perks.len(), "perk", "perks"),
+            plural(ranks, "rank", "ranks")
+        )?;
+        let width = categories
+            .keys()
+            .map(|kind| kind.to_string().len())
+            .max()
+            .unwrap_or(0);
+        writeln!(
+            f,
+            "{}",
+            format!(
+                "  {:width$}  {:>5}  {:>5}  {:>7}  {:>16}",
+                "",
+                "perks",
+                "ranks",
+                "effects",
+                "description only",
+                width = width
+            )
+            .bright_cyan()
+        )?;
+        for (kind, category) in &categories {
+            writeln!(
+                f,
+                "  {:width$}  {:>5}  {:>5}  {:>7}  {:>16}",
+                kind.to_string(),
+                category.perks,
+                category.ranks,
+                category.perks - category.description_only.len(),
+                category.description_only.len(),
+                width = width
+            )?;
+        }
+        writeln!(f, "{}", "Effects".bright_yellow())?;
+        for (name, def) in &self.effects {
+            let perks = self
+                .perks
+                .values()
+                .filter(|def| def.has_effect(name))
+                .count();
+            let legendaries = self
+                .legendaries
+                .values()
+                .filter(|def| def.effects.get(name).is_some())
+                .count();
+            let label = def.label.as_deref().unwrap_or(name);
+            let mut sources = vec![plural(perks, "perk", "perks")];
+            if legendaries > 0 {
+                sources.push(plural(legendaries, "legendary", "legendaries"));
+            }
+            let line = format!("  {}: {}", label, sources.join(", "));
+            if perks + legendaries == 0 {
+                writeln!(f, "{}", line.bright_black())?;
+            } else {
+                writeln!(f, "{}", line)?;
+            }
+        }
+        writeln!(f, "{}", "DLC".bright_yellow())?;
+        let mut base: BTreeMap<PerkKind, usize> = BTreeMap::new();
+        for id in self.perks.keys().filter(|id| self.dlc_of(id).is_none()) {
+            *base.entry(id.kind()).or_default() += 1;
+        }
+        let composition = |kinds: &BTreeMap<PerkKind, usize>| {
+            let total: usize = kinds.values().sum();
+            let mut specials = 0;
+            let mut parts = Vec::new();
+            for (kind, count) in kinds {
+                match kind {
+                    PerkKind::Special(_) => specials += count,
+                    kind => {
+                        let many = kind.to_string().to_lowercase();
+                        parts.push(plural(*count, many.trim_end_matches('s'), &many));
+                    }
+                }
+            }
+            if specials > 0 {
+                parts.insert(0, format!("{} S.P.E.C.I.A.L.", specials));
+            }
+            format!("{} ({})", plural(total, "perk", "perks"), parts.join(", "))
+        };
+        writeln!(f, "  Base game: {}", composition(&base))?;
+        for (name, ids) in &self.dlc {
+            let mut kinds: BTreeMap<PerkKind, usize> = BTreeMap::new();
+            for id in ids {
+                *kinds.entry(id.kind()).or_default() += 1;
+            }
+            writeln!(f, "  {}: {}", name, composition(&kinds))?;
+        }
+        if list {
+            writeln!(f, "{}", "Description only".bright_yellow())?;
+            for (kind, category) in &categories {
+                if category.description_only.is_empty() {
+                    continue;
+                }
+                let names: Vec<&str> = category
+                    .description_only
+                    .iter()
+                    .map(|id| &*self.perks[id].name[Gender::Male])
+                    .collect();
+                writeln!(
+                    f,
+                    "  {}: {}",
+                    kind.to_string().bright_cyan(),
+                    names.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
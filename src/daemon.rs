@@ -0,0 +1,92 @@
+#[cfg(not(unix))]
+use std::net::{TcpListener as Listener, TcpStream as Stream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener as Listener, UnixStream as Stream};
+use std::{
+    io::{BufRead, BufReader, Write},
+    thread,
+};
+
+use anyhow::{anyhow, bail};
+use serde_json::{json, Value};
+
+use fo4builder::special::catalog;
+
+use crate::rpc;
+
+/// Where the daemon listens unless `--socket` is given
+#[cfg(unix)]
+pub fn default_socket() -> String {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("fo4builder.sock")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Where the daemon listens unless `--socket` is given
+#[cfg(not(unix))]
+pub fn default_socket() -> String {
+    "127.0.0.1:7879".into()
+}
+
+#[cfg(unix)]
+fn bind(socket: &str) -> anyhow::Result<Listener> {
+    let path = std::path::Path::new(socket);
+    if path.exists() {
+        // A socket file is left behind if the last daemon was killed
+        if Stream::connect(path).is_ok() {
+            bail!("A daemon is already listening on {}", socket)
+        }
+        std::fs::remove_file(path)?;
+    }
+    Ok(Listener::bind(path)?)
+}
+
+#[cfg(not(unix))]
+fn bind(socket: &str) -> anyhow::Result<Listener> {
+    Ok(Listener::bind(socket)?)
+}
+
+/// Keep the perk data loaded and answer JSON-RPC on a local socket
+///
+/// Each connection gets its own build, which lasts as long as the connection.
+pub fn run(socket: &str) -> anyhow::Result<()> {
+    let listener = bind(socket)?;
+    catalog();
+    println!("Listening on {}", socket);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        thread::spawn(move || {
+            let res = stream
+                .try_clone()
+                .map_err(Into::into)
+                .and_then(|reader| rpc::serve(BufReader::new(reader), stream));
+            if let Err(e) = res {
+                eprintln!("{}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Send a single request to a running daemon and return its result
+pub fn call(socket: &str, method: &str, params: Vec<String>) -> anyhow::Result<Value> {
+    let stream = Stream::connect(socket)
+        .map_err(|e| anyhow!("No daemon is listening on {}: {}", socket, e))?;
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(&stream, "{}", request)?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let mut response: Value = serde_json::from_str(&line)?;
+    if let Some(error) = response.get("error") {
+        bail!("{}", error["message"].as_str().unwrap_or_default())
+    }
+    Ok(response["result"].take())
+}
@@ -0,0 +1,119 @@
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::bail;
+
+use crate::{
+    build::Build,
+    special::{catalog, PerkId, SpecialStat},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Checklist,
+    Archive,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Checklist => "md",
+            ExportFormat::Archive => "zip",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "checklist" | "markdown" | "md" => ExportFormat::Checklist,
+            "archive" | "zip" => ExportFormat::Archive,
+            _ => bail!("Unknown export format: {}", s),
+        })
+    }
+}
+
+impl Build {
+    pub fn export(&self, format: ExportFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            ExportFormat::Checklist => self.checklist(),
+            ExportFormat::Archive => bail!("An archive holds every build rather than one"),
+        })
+    }
+    pub fn perk_name(&self, id: &PerkId) -> String {
+        catalog().perks.get(id).expect("Unknown perk").name[self.gender.unwrap_or_default()]
+            .to_string()
+    }
+    pub fn checklist(&self) -> String {
+        let mut s = String::new();
+        let title = self.name.as_deref().unwrap_or("Build");
+        let _ = writeln!(s, "# {}\n", title.trim());
+        let _ = writeln!(s, "## Character Creation\n");
+        for &stat in SpecialStat::ALL {
+            let _ = writeln!(s, "- [ ] {} {}", stat, self.special[&stat]);
+        }
+        let level_up_points = self.level_up_assigned_special_points();
+        if level_up_points > 0 {
+            let _ = writeln!(
+                s,
+                "\nThe allocation above includes {} S.P.E.C.I.A.L. point{} taken at level up.",
+                level_up_points,
+                if level_up_points == 1 { "" } else { "s" }
+            );
+        }
+        let picks = self.planned_picks();
+        if !picks.is_empty() {
+            let _ = writeln!(s, "\n## Perks\n");
+            for (level, id, rank) in picks {
+                let _ = write!(s, "- [ ] Level {}: {}", level, self.perk_name(&id));
+                if catalog().perks.get(&id).expect("Unknown perk").max_rank() > 1 {
+                    let _ = write!(s, " rank {}", rank);
+                }
+                let _ = writeln!(s);
+            }
+        }
+        if !self.milestones.is_empty() {
+            let _ = writeln!(s, "\n## Milestones\n");
+            for (level, text) in self.milestones_between(0, u8::MAX) {
+                let _ = writeln!(s, "- [ ] Level {}: {}", level, text);
+            }
+        }
+        let bobbleheads: Vec<&PerkId> = self
+            .perks
+            .keys()
+            .filter(|id| matches!(id, PerkId::Bobblehead(_)))
+            .collect();
+        if !bobbleheads.is_empty() || self.special_book.is_some() {
+            let _ = writeln!(s, "\n## Bobbleheads\n");
+            for id in bobbleheads {
+                let _ = writeln!(s, "- [ ] {}", self.perk_name(id));
+            }
+            if let Some(stat) = self.special_book {
+                let _ = write!(s, "- [ ] You're S.P.E.C.I.A.L.! book ({})", stat);
+                if self.book_order_matters().is_some() {
+                    let _ = write!(s, ", before the {} bobblehead", stat);
+                }
+                let _ = writeln!(s);
+            }
+        }
+        let magazines: Vec<(&PerkId, &u8)> = self
+            .perks
+            .iter()
+            .filter(|(id, _)| matches!(id, PerkId::Magazine(_)))
+            .collect();
+        if !magazines.is_empty() {
+            let _ = writeln!(s, "\n## Magazines\n");
+            for (id, rank) in magazines {
+                let name = self.perk_name(id);
+                if catalog().perks.get(id).expect("Unknown perk").max_rank() > 1 {
+                    for issue in 1..=*rank {
+                        let _ = writeln!(s, "- [ ] {} issue {}", name, issue);
+                    }
+                } else {
+                    let _ = writeln!(s, "- [ ] {}", name);
+                }
+            }
+        }
+        s
+    }
+}
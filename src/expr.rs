@@ -0,0 +1,270 @@
+use anyhow::{anyhow, bail};
+
+use crate::{
+    build::{Build, Game},
+    special::{catalog, Gender, SpecialStat},
+};
+
+/// A formula over a build's stats, like "agility*2 + sneak_rank*3"
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f32),
+    Variable(String),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    /// `min` or `max` of any number of arguments
+    Call(String, Vec<Expr>),
+}
+
+/// How deeply a formula, or a script's expressions and calls, may nest
+pub const MAX_DEPTH: usize = 64;
+/// How many numbers, names, and symbols a formula may have, which bounds the work to
+/// evaluate it
+pub const MAX_TOKENS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Symbol(char),
+}
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut text = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                text.push(c);
+                chars.next();
+            }
+            let number = text
+                .parse()
+                .map_err(|_| anyhow!("Invalid number: {}", text))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut text = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                text.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Ident(text.to_lowercase()));
+        } else if "+-*/(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            bail!("Unexpected {:?} in formula", c)
+        }
+        if tokens.len() > MAX_TOKENS {
+            bail!("The formula is too long")
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, symbol: char) -> anyhow::Result<()> {
+        if !self.eat(symbol) {
+            bail!("Expected {:?} in formula", symbol)
+        }
+        Ok(())
+    }
+    /// Terms joined by `+` and `-`
+    fn sum(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+    }
+    /// Factors joined by `*` and `/`
+    fn product(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.factor()?;
+        loop {
+            let op = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else {
+                return Ok(expr);
+            };
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.factor()?));
+        }
+    }
+    fn factor(&mut self) -> anyhow::Result<Expr> {
+        self.depth += 1;
+        let expr = self.nested();
+        self.depth -= 1;
+        expr
+    }
+    /// A factor, counting toward how deeply the formula nests
+    fn nested(&mut self) -> anyhow::Result<Expr> {
+        if self.depth > MAX_DEPTH {
+            bail!("The formula nests too deeply")
+        }
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.factor()?)));
+        }
+        if self.eat('(') {
+            let expr = self.sum()?;
+            self.expect(')')?;
+            return Ok(expr);
+        }
+        let token = self.peek().cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::Ident(name)) if self.eat('(') => {
+                if name != "min" && name != "max" {
+                    bail!("Unknown function {}, expected min or max", name)
+                }
+                let mut args = vec![self.sum()?];
+                while self.eat(',') {
+                    args.push(self.sum()?);
+                }
+                self.expect(')')?;
+                Ok(Expr::Call(name, args))
+            }
+            Some(Token::Ident(name)) => Ok(Expr::Variable(name)),
+            Some(Token::Symbol(c)) => bail!("Unexpected {:?} in formula", c),
+            None => bail!("The formula ends too soon"),
+        }
+    }
+}
+
+impl std::str::FromStr for Expr {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser.sum()?;
+        if let Some(token) = parser.peek() {
+            bail!("Unexpected {:?} in formula", token)
+        }
+        Ok(expr)
+    }
+}
+
+impl Expr {
+    /// Evaluate the formula, looking up each variable by name
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<f32>) -> anyhow::Result<f32> {
+        Ok(match self {
+            Expr::Number(number) => *number,
+            Expr::Variable(name) => {
+                lookup(name).ok_or_else(|| anyhow!("Unknown variable {}", name))?
+            }
+            Expr::Neg(expr) => -expr.eval(lookup)?,
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(lookup)?, b.eval(lookup)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    _ if b == 0.0 => bail!("Division by zero"),
+                    _ => a / b,
+                }
+            }
+            Expr::Call(name, args) => {
+                let values = args
+                    .iter()
+                    .map(|arg| arg.eval(lookup))
+                    .collect::<anyhow::Result<Vec<f32>>>()?;
+                let fold = if name == "min" { f32::min } else { f32::max };
+                values.into_iter().reduce(fold).unwrap_or(0.0)
+            }
+        })
+    }
+}
+
+/// A perk name as a formula variable, like "grim_reapers_sprint"
+fn ident(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, '\'' | '’' | '.' | '!'))
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+impl Build {
+    /// The value of a formula variable: a S.P.E.C.I.A.L. stat's total points, a perk's rank
+    /// like "sneak_rank", the total of an effect like "hp_add", or a derived stat
+    pub fn variable(&self, name: &str) -> Option<f32> {
+        if self.game() != Game::Fallout4 {
+            return None;
+        }
+        let stats = self.stats();
+        if let Some(stat) = SpecialStat::ALL
+            .iter()
+            .find(|stat| stat.to_string().to_lowercase() == name)
+        {
+            return stats.special.get(stat).map(|points| *points as f32);
+        }
+        if let Some(perk) = name.strip_suffix("_rank") {
+            let id = catalog()
+                .perks
+                .iter()
+                .find(|(_, def)| ident(&def.name[Gender::Male]) == perk)
+                .map(|(id, _)| *id)?;
+            return Some(self.perks.get(&id).copied().unwrap_or(0) as f32);
+        }
+        if catalog().effects.contains_key(name) {
+            return Some(self.effect_total(name));
+        }
+        Some(match name {
+            "level" => stats.required_level as f32,
+            "health" | "base_health" | "basehealth" => stats.health,
+            "ap" => stats.base_ap,
+            "xp" => stats.experience_mul as f32,
+            "melee_damage" => stats.melee_damage_mul,
+            "hits_per_crit" => stats.hits_per_crit as f32,
+            "carry_weight" => stats.carry_weight as f32,
+            "buy_prices" => stats.buying_price_mul,
+            "sell_prices" => stats.selling_price_mul,
+            "sprint_time" => stats.sprint_time,
+            _ => return None,
+        })
+    }
+    /// Evaluate a formula against the build
+    pub fn evaluate(&self, formula: &str) -> anyhow::Result<f32> {
+        formula.parse::<Expr>()?.eval(&|name| self.variable(name))
+    }
+}
+
+/// Format a formula's value without needless decimals
+pub fn format_value(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
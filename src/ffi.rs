@@ -0,0 +1,310 @@
+//! A C interface to the planner, declared in `include/fo4builder.h`
+//!
+//! Call [`fo4_init`] first to load the perk data. Builds are passed around as opaque handles
+//! from [`fo4_build_new`] that must be freed with [`fo4_build_free`]. Functions that can fail
+//! return 0 on success and -1 on failure, after which [`fo4_last_error`] describes what went
+//! wrong. Panics are caught and reported the same way, so none unwind into the caller.
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    build::Build,
+    special::{try_catalog, SpecialStat},
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn fail(e: impl ToString) -> i32 {
+    let message = CString::new(e.to_string().replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    -1
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    let message = (payload.downcast_ref::<&str>().copied())
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error");
+    format!("Panicked: {}", message)
+}
+
+/// Run the body of an exported function, returning `on_error` if it fails or panics
+fn guard<T>(on_error: T, f: impl FnOnce() -> anyhow::Result<T>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            fail(e);
+            on_error
+        }
+        Err(payload) => {
+            fail(panic_message(payload));
+            on_error
+        }
+    }
+}
+
+/// [`guard`] for functions that only report success or failure
+fn result(f: impl FnOnce() -> anyhow::Result<()>) -> i32 {
+    guard(-1, || f().map(|()| 0))
+}
+
+fn stat(index: u32) -> anyhow::Result<SpecialStat> {
+    SpecialStat::ALL
+        .get(index as usize)
+        .copied()
+        .ok_or_else(|| anyhow::anyhow!("Invalid S.P.E.C.I.A.L. index: {}", index))
+}
+
+/// Derived stats of a build, as filled in by [`fo4_build_stats`]
+#[repr(C)]
+pub struct Fo4Stats {
+    pub required_level: u32,
+    pub health: f32,
+    pub action_points: f32,
+    pub carry_weight: u32,
+    pub hits_per_crit: u32,
+    pub experience_mul: f64,
+    pub melee_damage_mul: f32,
+    pub buying_price_mul: f32,
+    pub selling_price_mul: f32,
+}
+
+/// A message describing the last failure on this thread, valid until the next failure
+#[no_mangle]
+pub extern "C" fn fo4_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Load the perk data, which every other function needs
+///
+/// Returns -1 if it can't be loaded, in which case the other functions fail the same way.
+#[no_mangle]
+pub extern "C" fn fo4_init() -> i32 {
+    result(|| try_catalog().map(drop))
+}
+
+/// A new build, or null if the perk data can't be loaded
+#[no_mangle]
+pub extern "C" fn fo4_build_new() -> *mut Build {
+    guard(ptr::null_mut(), || {
+        try_catalog()?;
+        Ok(Box::into_raw(Box::default()))
+    })
+}
+
+/// # Safety
+/// `build` must be null or a handle from [`fo4_build_new`] that has not been freed
+#[no_mangle]
+pub unsafe extern "C" fn fo4_build_free(build: *mut Build) {
+    if !build.is_null() {
+        guard((), || {
+            drop(Box::from_raw(build));
+            Ok(())
+        })
+    }
+}
+
+/// Set a S.P.E.C.I.A.L. stat, indexed from 0 (Strength) to 6 (Luck)
+///
+/// # Safety
+/// `build` must be a live handle from [`fo4_build_new`]
+#[no_mangle]
+pub unsafe extern "C" fn fo4_build_set_stat(build: *mut Build, stat: u32, value: u8) -> i32 {
+    let build = &mut *build;
+    result(|| {
+        try_catalog()?;
+        build.set(self::stat(stat)?, value).map(drop)
+    })
+}
+
+/// Pick up (nonzero `on`) or drop a S.P.E.C.I.A.L. stat's bobblehead
+///
+/// # Safety
+/// `build` must be a live handle from [`fo4_build_new`]
+#[no_mangle]
+pub unsafe extern "C" fn fo4_build_set_bobblehead(build: *mut Build, stat: u32, on: u8) -> i32 {
+    let build = &mut *build;
+    result(|| {
+        try_catalog()?;
+        build.set_bobblehead(self::stat(stat)?, on != 0).map(drop)
+    })
+}
+
+/// Get a S.P.E.C.I.A.L. stat's assigned points, or 0 for an invalid index
+///
+/// # Safety
+/// `build` must be a live handle from [`fo4_build_new`]
+#[no_mangle]
+pub unsafe extern "C" fn fo4_build_get_stat(build: *const Build, stat: u32) -> u8 {
+    let build = &*build;
+    guard(0, || Ok(build.special[&self::stat(stat)?]))
+}
+
+/// Add a perk by its language-independent key, like `strength-1`, at a rank
+///
+/// A rank of 0 removes the perk.
+///
+/// # Safety
+/// `build` must be a live handle from [`fo4_build_new`] and `key` a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn fo4_build_add_perk(
+    build: *mut Build,
+    key: *const c_char,
+    rank: u8,
+) -> i32 {
+    let build = &mut *build;
+    let key = CStr::from_ptr(key).to_string_lossy();
+    result(|| {
+        let id = (try_catalog()?.id_by_key(&key))
+            .ok_or_else(|| anyhow::anyhow!("Unknown perk key: {}", key))?;
+        build.add_perk(id, rank)
+    })
+}
+
+/// Get the rank of a perk in the build by its key, or 0 if it is not taken
+///
+/// # Safety
+/// `build` must be a live handle from [`fo4_build_new`] and `key` a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn fo4_build_perk_rank(build: *const Build, key: *const c_char) -> u8 {
+    let build = &*build;
+    let key = CStr::from_ptr(key).to_string_lossy();
+    guard(0, || {
+        Ok((try_catalog()?.id_by_key(&key))
+            .and_then(|id| build.perks.get(&id).copied())
+            .unwrap_or(0))
+    })
+}
+
+/// # Safety
+/// `build` must be a live handle from [`fo4_build_new`] and `stats` must point to writable memory
+#[no_mangle]
+pub unsafe extern "C" fn fo4_build_stats(build: *const Build, stats: *mut Fo4Stats) -> i32 {
+    if stats.is_null() {
+        return fail("Stats pointer is null");
+    }
+    result(|| {
+        try_catalog()?;
+        let computed = (*build).stats();
+        ptr::write(
+            stats,
+            Fo4Stats {
+                required_level: computed.required_level as u32,
+                health: computed.health,
+                action_points: computed.base_ap,
+                carry_weight: computed.carry_weight as u32,
+                hits_per_crit: computed.hits_per_crit as u32,
+                experience_mul: computed.experience_mul,
+                melee_damage_mul: computed.melee_damage_mul,
+                buying_price_mul: computed.buying_price_mul,
+                selling_price_mul: computed.selling_price_mul,
+            },
+        );
+        Ok(())
+    })
+}
+
+/// The number of perks in the catalog, for iterating with [`fo4_perk_key`], or 0 if the
+/// perk data can't be loaded
+#[no_mangle]
+pub extern "C" fn fo4_perk_count() -> u32 {
+    guard(0, || Ok(try_catalog()?.perks.len() as u32))
+}
+
+/// The key of the perk at an index, or null if the index is out of range
+///
+/// The string is owned by the caller and must be freed with [`fo4_string_free`].
+#[no_mangle]
+pub extern "C" fn fo4_perk_key(index: u32) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let catalog = try_catalog()?;
+        Ok((catalog.perks.keys().nth(index as usize))
+            .and_then(|id| catalog.key(id))
+            .and_then(|key| CString::new(key).ok())
+            .map_or(ptr::null_mut(), CString::into_raw))
+    })
+}
+
+/// # Safety
+/// `s` must be null or a string returned by this library that has not been freed
+#[no_mangle]
+pub unsafe extern "C" fn fo4_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(fo4_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn builds_are_edited_through_handles() {
+        assert_eq!(fo4_init(), 0);
+        let build = fo4_build_new();
+        assert!(!build.is_null());
+        unsafe {
+            assert_eq!(fo4_build_set_stat(build, 1, 6), 0);
+            assert_eq!(fo4_build_get_stat(build, 1), 6);
+            assert_eq!(fo4_build_add_perk(build, c"perception-1".as_ptr(), 2), 0);
+            assert_eq!(fo4_build_perk_rank(build, c"perception-1".as_ptr()), 2);
+            let mut stats = std::mem::MaybeUninit::<Fo4Stats>::uninit();
+            assert_eq!(fo4_build_stats(build, stats.as_mut_ptr()), 0);
+            assert_eq!(
+                stats.assume_init().required_level,
+                (*build).stats().required_level as u32
+            );
+            fo4_build_free(build);
+        }
+    }
+
+    #[test]
+    fn failures_are_reported_through_the_last_error() {
+        let build = fo4_build_new();
+        unsafe {
+            assert_eq!(fo4_build_set_stat(build, 7, 3), -1);
+            assert_eq!(last_error(), "Invalid S.P.E.C.I.A.L. index: 7");
+            assert_eq!(fo4_build_get_stat(build, 7), 0);
+            assert_eq!(fo4_build_set_stat(build, 0, 11), -1);
+            assert!(!last_error().is_empty());
+            assert_eq!(fo4_build_add_perk(build, c"no-such-perk".as_ptr(), 1), -1);
+            assert_eq!(last_error(), "Unknown perk key: no-such-perk");
+            assert_eq!(fo4_build_stats(build, ptr::null_mut()), -1);
+            fo4_build_free(build);
+        }
+    }
+
+    #[test]
+    fn panics_are_caught() {
+        let result = guard(-1, || -> anyhow::Result<i32> { panic!("boom") });
+        assert_eq!(result, -1);
+        assert_eq!(last_error(), "Panicked: boom");
+    }
+
+    #[test]
+    fn perk_keys_are_listed_by_index() {
+        let count = fo4_perk_count();
+        assert!(count > 0);
+        let key = fo4_perk_key(0);
+        assert!(!key.is_null());
+        assert!(unsafe { CStr::from_ptr(key) }
+            .to_str()
+            .unwrap()
+            .contains('-'));
+        unsafe { fo4_string_free(key) };
+        assert!(fo4_perk_key(count).is_null());
+    }
+}
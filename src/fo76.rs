@@ -0,0 +1,493 @@
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::bail;
+use colored::{Color, Colorize};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build::Build,
+    profile::{Fallout76, GameProfile},
+    special::{similarity, Gender, MaybeGendered, SpecialStat},
+};
+
+pub const MAX_STAT: u8 = 15;
+/// Every level up to this one grants a S.P.E.C.I.A.L. point
+pub const SPECIAL_LEVEL_CAP: u16 = 50;
+pub const LEGENDARY_SLOT_LEVELS: [u16; 6] = [50, 75, 100, 150, 200, 300];
+/// How much each rank of a legendary S.P.E.C.I.A.L. perk raises its stat
+const LEGENDARY_STAT_BONUS: [u8; 4] = [1, 2, 3, 5];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CardDef {
+    pub name: MaybeGendered<String>,
+    pub ranks: Vec<CardRank>,
+    /// For legendary perks, the stat they raise
+    #[serde(default)]
+    pub stat: Option<SpecialStat>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CardRank {
+    #[serde(alias = "desc")]
+    pub description: String,
+    /// The card points the rank takes, which is its rank number unless given
+    #[serde(default)]
+    pub cost: Option<u8>,
+    #[serde(default = "default_required_level", alias = "level")]
+    pub required_level: u16,
+}
+
+fn default_required_level() -> u16 {
+    1
+}
+
+impl CardDef {
+    pub fn max_rank(&self) -> u8 {
+        self.ranks.len() as u8
+    }
+    /// The card points the card takes at a rank, 0 if it isn't equipped
+    pub fn cost(&self, rank: u8) -> u8 {
+        match rank.checked_sub(1) {
+            Some(i) => self.ranks[i as usize].cost.unwrap_or(rank),
+            None => 0,
+        }
+    }
+    pub fn required_level(&self, rank: u8) -> u16 {
+        match rank.checked_sub(1) {
+            Some(i) => self.ranks[i as usize].required_level,
+            None => 1,
+        }
+    }
+    /// The name builds store the card under
+    pub fn key(&self) -> &str {
+        &self.name[Gender::Male]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardKind {
+    Special(SpecialStat),
+    Legendary,
+}
+
+impl fmt::Display for CardKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CardKind::Special(stat) => write!(f, "{}", stat),
+            CardKind::Legendary => write!(f, "Legendary"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CardsRep {
+    cards: BTreeMap<SpecialStat, Vec<CardDef>>,
+    legendary: Vec<CardDef>,
+}
+
+pub struct Fo76Catalog {
+    pub cards: BTreeMap<SpecialStat, Vec<CardDef>>,
+    pub legendary: Vec<CardDef>,
+}
+
+impl Fo76Catalog {
+    pub fn iter(&self) -> impl Iterator<Item = (CardKind, &CardDef)> {
+        self.cards
+            .iter()
+            .flat_map(|(stat, defs)| defs.iter().map(|def| (CardKind::Special(*stat), def)))
+            .chain(self.legendary.iter().map(|def| (CardKind::Legendary, def)))
+    }
+    pub fn find(&self, name: &str) -> anyhow::Result<(CardKind, &CardDef)> {
+        let lower = &name.to_lowercase();
+        let (kind, def, sim) = self
+            .iter()
+            .flat_map(|(kind, def)| {
+                def.name
+                    .iter()
+                    .map(move |name| (kind, def, similarity(lower, name.to_lowercase())))
+            })
+            .max_by_key(|(.., sim)| (*sim * 1000000.0) as u32)
+            .expect("No perk cards");
+        if sim >= 0.6 {
+            Ok((kind, def))
+        } else {
+            bail!("Unknown perk card: {}", name)
+        }
+    }
+    pub fn get(&self, key: &str) -> Option<(CardKind, &CardDef)> {
+        self.iter().find(|(_, def)| def.key() == key)
+    }
+}
+
+pub static FO76: Lazy<Fo76Catalog> = Lazy::new(|| {
+    let rep: CardsRep =
+        rmp_serde::from_slice(include_bytes!(concat!(env!("OUT_DIR"), "/fo76.msgpack")))
+            .expect("Invalid Fallout 76 card data");
+    Fo76Catalog {
+        cards: rep.cards,
+        legendary: rep.legendary,
+    }
+});
+
+/// The Fallout 76 half of a build. Cards are keyed by name and cost what their rank costs in the card data.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Fo76Build {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cards: BTreeMap<String, u8>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub legendary: BTreeMap<String, u8>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub loadouts: BTreeMap<String, BTreeMap<String, u8>>,
+}
+
+impl Fo76Build {
+    fn cards_for(&self, stat: SpecialStat) -> impl Iterator<Item = (&CardDef, u8)> {
+        self.cards
+            .iter()
+            .filter_map(move |(key, rank)| match FO76.get(key) {
+                Some((CardKind::Special(s), def)) if s == stat => Some((def, *rank)),
+                _ => None,
+            })
+    }
+    pub fn used_points(&self, stat: SpecialStat) -> u8 {
+        self.cards_for(stat).map(|(def, rank)| def.cost(rank)).sum()
+    }
+    pub fn legendary_bonus(&self, stat: SpecialStat) -> u8 {
+        self.legendary
+            .iter()
+            .filter_map(|(key, rank)| match FO76.get(key) {
+                Some((_, def)) if def.stat == Some(stat) => {
+                    Some(LEGENDARY_STAT_BONUS[*rank as usize - 1])
+                }
+                _ => None,
+            })
+            .sum()
+    }
+    /// Card ranks the character must own to equip every loadout
+    pub fn owned_ranks(&self) -> u16 {
+        let mut owned: BTreeMap<&str, u8> = BTreeMap::new();
+        for cards in self.loadouts.values().chain([&self.cards]) {
+            for (key, rank) in cards {
+                let entry = owned.entry(key).or_default();
+                *entry = (*entry).max(*rank);
+            }
+        }
+        owned.values().map(|&rank| rank as u16).sum()
+    }
+    /// The highest level any equipped or saved card rank requires
+    pub fn card_level(&self) -> u16 {
+        self.loadouts
+            .values()
+            .chain([&self.cards, &self.legendary])
+            .flatten()
+            .filter_map(|(key, rank)| Some(FO76.get(key)?.1.required_level(*rank)))
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+impl Build {
+    fn fo76(&self) -> anyhow::Result<&Fo76Build> {
+        match &self.fo76 {
+            Some(fo76) => Ok(fo76),
+            None => bail!("This is not a Fallout 76 build"),
+        }
+    }
+    fn fo76_mut(&mut self) -> anyhow::Result<&mut Fo76Build> {
+        match &mut self.fo76 {
+            Some(fo76) => Ok(fo76),
+            None => bail!("This is not a Fallout 76 build"),
+        }
+    }
+    fn fo76_spent_points(&self) -> u8 {
+        self.special.values().sum::<u8>() - SpecialStat::ALL.len() as u8
+    }
+    pub fn fo76_card_points(&self, stat: SpecialStat) -> u8 {
+        self.special[&stat]
+            + self
+                .fo76
+                .as_ref()
+                .map_or(0, |fo76| fo76.legendary_bonus(stat))
+    }
+    pub fn fo76_required_level(&self) -> u16 {
+        let fo76 = match &self.fo76 {
+            Some(fo76) => fo76,
+            None => return 1,
+        };
+        let for_special = self.fo76_spent_points() as u16 + 1;
+        let for_cards = fo76.owned_ranks() + 1;
+        let for_legendary = fo76
+            .legendary
+            .len()
+            .checked_sub(1)
+            .map_or(1, |i| LEGENDARY_SLOT_LEVELS[i]);
+        for_special
+            .max(for_cards)
+            .max(for_legendary)
+            .max(fo76.card_level())
+    }
+    pub fn set_fo76(&mut self, stat: SpecialStat, value: u8) -> anyhow::Result<()> {
+        self.invalidate_stats();
+        if value > MAX_STAT {
+            bail!(
+                "Cannot allocate more than {} points to any S.P.E.C.I.A.L. stat",
+                MAX_STAT
+            )
+        } else if value == 0 {
+            bail!("S.P.E.C.I.A.L. stats cannot be less the 1")
+        }
+        let spent = self.fo76_spent_points() + value - self.special[&stat];
+        if spent as u16 >= SPECIAL_LEVEL_CAP {
+            bail!(
+                "Only {} S.P.E.C.I.A.L. points can be assigned",
+                SPECIAL_LEVEL_CAP - 1
+            )
+        }
+        let fo76 = self.fo76()?;
+        let used = fo76.used_points(stat);
+        if used > value + fo76.legendary_bonus(stat) {
+            bail!("{} cards use {} points, unequip some first", stat, used)
+        }
+        self.special.insert(stat, value);
+        self.debug_check();
+        Ok(())
+    }
+    /// Equip a card or legendary perk at the given rank, or its highest one.
+    /// Returns the card's name and rank.
+    pub fn equip_card(&mut self, name: &str, rank: Option<u8>) -> anyhow::Result<(String, u8)> {
+        let (kind, def) = FO76.find(name)?;
+        let name = def.name[self.gender.unwrap_or_default()].clone();
+        let rank = rank.unwrap_or_else(|| def.max_rank());
+        if rank == 0 {
+            self.unequip_card(&name)?;
+            return Ok((name, 0));
+        } else if rank > def.max_rank() {
+            bail!("{} only has {} ranks", name, def.max_rank())
+        }
+        let fo76 = self.fo76()?;
+        match kind {
+            CardKind::Special(stat) => {
+                let current = fo76.cards.get(def.key()).copied().unwrap_or(0);
+                let needed = fo76.used_points(stat) - def.cost(current) + def.cost(rank);
+                let bonus = fo76.legendary_bonus(stat);
+                if needed > self.special[&stat] + bonus {
+                    let value = needed - bonus;
+                    if value > MAX_STAT {
+                        bail!(
+                            "{} rank {} does not fit in {}'s {} points",
+                            name,
+                            rank,
+                            stat,
+                            MAX_STAT + bonus
+                        )
+                    }
+                    self.set_fo76(stat, value)?;
+                }
+                self.fo76_mut()?.cards.insert(def.key().into(), rank);
+            }
+            CardKind::Legendary => {
+                if !fo76.legendary.contains_key(def.key())
+                    && fo76.legendary.len() == LEGENDARY_SLOT_LEVELS.len()
+                {
+                    bail!(
+                        "All {} legendary perk slots are taken",
+                        LEGENDARY_SLOT_LEVELS.len()
+                    )
+                }
+                self.check_legendary_fit(def, rank)?;
+                self.fo76_mut()?.legendary.insert(def.key().into(), rank);
+            }
+        }
+        Ok((name, rank))
+    }
+    pub fn unequip_card(&mut self, name: &str) -> anyhow::Result<()> {
+        let (kind, def) = FO76.find(name)?;
+        if kind == CardKind::Legendary {
+            self.check_legendary_fit(def, 0)?;
+        }
+        let fo76 = self.fo76_mut()?;
+        match kind {
+            CardKind::Special(_) => fo76.cards.remove(def.key()),
+            CardKind::Legendary => fo76.legendary.remove(def.key()),
+        };
+        Ok(())
+    }
+    /// Make sure equipped cards still fit if a legendary perk changes to the given rank
+    fn check_legendary_fit(&self, def: &CardDef, rank: u8) -> anyhow::Result<()> {
+        let (fo76, stat) = match (self.fo76()?, def.stat) {
+            (fo76, Some(stat)) => (fo76, stat),
+            _ => return Ok(()),
+        };
+        let bonus = |rank: u8| {
+            rank.checked_sub(1)
+                .map_or(0, |i| LEGENDARY_STAT_BONUS[i as usize])
+        };
+        let current = fo76.legendary.get(def.key()).copied().unwrap_or(0);
+        let points =
+            self.special[&stat] + fo76.legendary_bonus(stat) - bonus(current) + bonus(rank);
+        if fo76.used_points(stat) > points {
+            bail!("{} cards would no longer fit, unequip some first", stat)
+        }
+        Ok(())
+    }
+    pub fn save_loadout(&mut self, name: &str) -> anyhow::Result<()> {
+        let fo76 = self.fo76_mut()?;
+        let cards = fo76.cards.clone();
+        fo76.loadouts.insert(name.into(), cards);
+        Ok(())
+    }
+    /// Swap in a saved loadout, raising S.P.E.C.I.A.L. stats it needs if there are points left
+    pub fn load_loadout(&mut self, name: &str) -> anyhow::Result<()> {
+        let fo76 = self.fo76()?;
+        let cards = match fo76.loadouts.get(name) {
+            Some(cards) => cards.clone(),
+            None => bail!("There is no loadout called {:?}", name),
+        };
+        let previous = self.clone();
+        self.fo76_mut()?.cards.clear();
+        for (key, rank) in cards {
+            if let Err(e) = self.equip_card(&key, Some(rank)) {
+                *self = previous;
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+    pub fn delete_loadout(&mut self, name: &str) -> anyhow::Result<()> {
+        if self.fo76_mut()?.loadouts.remove(name).is_none() {
+            bail!("There is no loadout called {:?}", name)
+        }
+        Ok(())
+    }
+    pub fn fmt_fo76(&self, fo76: &Fo76Build, f: &mut fmt::Formatter) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        if let Some(name) = &self.name {
+            let bars: String = "─".repeat(name.len());
+            writeln!(f, "{}", bars)?;
+            writeln!(f, "{}", name)?;
+            writeln!(f, "{}", bars)?;
+        }
+        writeln!(f, "{}", "Fallout 76".bright_black())?;
+        writeln!(f, "Required Level: {}", self.fo76_required_level())?;
+        let remaining = SPECIAL_LEVEL_CAP as u8 - 1 - self.fo76_spent_points();
+        if remaining > 0 {
+            writeln!(f, "Unassigned S.P.E.C.I.A.L. Points: {}", remaining)?;
+        }
+        for (name, value) in Fallout76.derived_stats(self) {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        writeln!(f)?;
+        for &stat in SpecialStat::ALL {
+            let points = self.fo76_card_points(stat);
+            let used = fo76.used_points(stat);
+            let color = if used == points {
+                Color::BrightGreen
+            } else {
+                Color::BrightYellow
+            };
+            writeln!(
+                f,
+                "{:>12} {} {}",
+                stat.to_string(),
+                points,
+                format!("({}/{} card points)", used, points).color(color)
+            )?;
+            for (def, rank) in fo76.cards_for(stat) {
+                writeln!(f, "{:>12}   {} {}", "", def.name[gender], rank)?;
+            }
+        }
+        if !fo76.legendary.is_empty() {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{}",
+                format!(
+                    "Legendary ({}/{})",
+                    fo76.legendary.len(),
+                    LEGENDARY_SLOT_LEVELS.len()
+                )
+                .bright_yellow()
+            )?;
+            for (key, rank) in &fo76.legendary {
+                let name = FO76
+                    .get(key)
+                    .map_or(key.as_str(), |(_, def)| &def.name[gender]);
+                writeln!(f, "  {} {}", name, rank)?;
+            }
+        }
+        if !fo76.loadouts.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", "Loadouts".bright_yellow())?;
+            for name in fo76.loadouts.keys() {
+                writeln!(f, "  {}", name)?;
+            }
+        }
+        Ok(())
+    }
+    pub fn write_card(
+        &self,
+        f: &mut impl fmt::Write,
+        kind: CardKind,
+        def: &CardDef,
+    ) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        let equipped = self.fo76.as_ref().and_then(|fo76| match kind {
+            CardKind::Special(_) => fo76.cards.get(def.key()),
+            CardKind::Legendary => fo76.legendary.get(def.key()),
+        });
+        writeln!(
+            f,
+            "{} {}",
+            def.name[gender].bright_yellow(),
+            format!("({})", kind).bright_black()
+        )?;
+        for (i, rank) in def.ranks.iter().enumerate() {
+            let rank_number = i as u8 + 1;
+            let mut text = format!("Rank {}", rank_number);
+            if let CardKind::Special(_) = kind {
+                let cost = def.cost(rank_number);
+                text.push_str(&format!(
+                    " ({} point{}",
+                    cost,
+                    if cost == 1 { "" } else { "s" }
+                ));
+                if rank.required_level > 1 {
+                    text.push_str(&format!(", level {}", rank.required_level));
+                }
+                text.push(')');
+            }
+            text.push_str(&format!(": {}", rank.description));
+            if equipped.is_some_and(|&r| r >= rank_number) {
+                writeln!(f, "{}", text.bright_white())?;
+            } else {
+                writeln!(f, "{}", text.white())?;
+            }
+        }
+        Ok(())
+    }
+    pub fn write_cards(&self, f: &mut impl fmt::Write, stat: SpecialStat) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        let fo76 = match &self.fo76 {
+            Some(fo76) => fo76,
+            None => return Ok(()),
+        };
+        writeln!(
+            f,
+            "{} ({}/{} card points)",
+            stat.to_string().bright_yellow(),
+            fo76.used_points(stat),
+            self.fo76_card_points(stat)
+        )?;
+        for def in FO76.cards.get(&stat).into_iter().flatten() {
+            let rank = fo76.cards.get(def.key()).copied().unwrap_or(0);
+            let line = format!("  {} {}/{}", def.name[gender], rank, def.max_rank());
+            if rank > 0 {
+                writeln!(f, "{}", line.bright_white())?;
+            } else {
+                writeln!(f, "{}", line.bright_black())?;
+            }
+        }
+        Ok(())
+    }
+}
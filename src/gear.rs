@@ -0,0 +1,248 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build::Build,
+    special::{catalog, Difficulty, EffectValue, LegendaryDef},
+};
+
+/// Where a piece of armor or clothing is worn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum GearSlot {
+    Head,
+    Outfit,
+    Chest,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+}
+
+impl GearSlot {
+    pub const ALL: &'static [Self] = &[
+        GearSlot::Head,
+        GearSlot::Outfit,
+        GearSlot::Chest,
+        GearSlot::LeftArm,
+        GearSlot::RightArm,
+        GearSlot::LeftLeg,
+        GearSlot::RightLeg,
+    ];
+}
+
+impl FromStr for GearSlot {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let compact = s.to_lowercase().replace([' ', '-', '_'], "");
+        GearSlot::ALL
+            .iter()
+            .find(|slot| format!("{:?}", slot).to_lowercase() == compact)
+            .copied()
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown slot {:?}, expected one of {}",
+                    s,
+                    GearSlot::ALL
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for GearSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GearSlot::LeftArm => write!(f, "left arm"),
+            GearSlot::RightArm => write!(f, "right arm"),
+            GearSlot::LeftLeg => write!(f, "left leg"),
+            GearSlot::RightLeg => write!(f, "right leg"),
+            slot => write!(f, "{}", format!("{:?}", slot).to_lowercase()),
+        }
+    }
+}
+
+/// Something carried as part of a build's loadout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GearItem {
+    /// The weight of one of the item
+    pub weight: f32,
+    pub count: u32,
+    /// Whether the item only weighs anything on Survival, like ammo and most aid items
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub survival_only: bool,
+    /// Where the item is worn, if it is armor or clothing that is equipped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<GearSlot>,
+    /// The name of the item's legendary effect, which only applies while it is worn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legendary: Option<String>,
+}
+
+impl GearItem {
+    /// The weight of every one of the item on a difficulty
+    pub fn total_weight(&self, difficulty: Difficulty) -> f32 {
+        if self.survival_only && difficulty != Difficulty::Survival {
+            return 0.0;
+        }
+        let mul = self
+            .legendary_def()
+            .and_then(|def| def.effects.get("weight_mul"))
+            .and_then(EffectValue::number)
+            .unwrap_or(1.0);
+        self.weight * mul * self.count as f32
+    }
+    pub fn legendary_def(&self) -> Option<LegendaryDef> {
+        catalog()
+            .legendaries
+            .get(self.legendary.as_deref()?)
+            .cloned()
+    }
+}
+
+/// A weight in pounds without trailing zeros
+pub struct Pounds(pub f32);
+
+impl fmt::Display for Pounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = format!("{:.3}", self.0);
+        write!(f, "{}", text.trim_end_matches('0').trim_end_matches('.'))
+    }
+}
+
+impl Build {
+    /// Add an item to the loadout, replacing one with the same name
+    pub fn add_gear(&mut self, name: &str, mut item: GearItem) -> anyhow::Result<()> {
+        self.invalidate_stats();
+        if !item.weight.is_finite() || item.weight < 0.0 {
+            bail!("An item's weight cannot be negative")
+        }
+        if item.count == 0 {
+            bail!("An item's count must be at least 1")
+        }
+        let name = self.gear_name(name).unwrap_or(name).to_string();
+        if let Some(slot) = item.slot {
+            if item.count > 1 {
+                bail!("Only one item can be worn in a slot")
+            }
+            if let Some((other, _)) = self
+                .gear
+                .iter()
+                .find(|(other, worn)| **other != name && worn.slot == Some(slot))
+            {
+                bail!("{} is already worn in the {} slot", other, slot)
+            }
+        }
+        if let Some(legendary) = &item.legendary {
+            if item.slot.is_none() {
+                bail!("A legendary effect only applies to armor worn in a slot")
+            }
+            let Some(known) = catalog()
+                .legendaries
+                .keys()
+                .find(|known| known.eq_ignore_ascii_case(legendary))
+                .cloned()
+            else {
+                bail!(
+                    "Unknown legendary effect {:?}, try \"gear legendaries\"",
+                    legendary
+                )
+            };
+            item.legendary = Some(known);
+        }
+        self.gear.insert(name, item);
+        Ok(())
+    }
+    /// The legendary effects of the armor the build wears
+    pub fn worn_legendaries(&self) -> impl Iterator<Item = LegendaryDef> + '_ {
+        self.gear
+            .values()
+            .filter(|item| item.slot.is_some())
+            .filter_map(GearItem::legendary_def)
+    }
+    /// Remove an item from the loadout, returning its name as it was added
+    pub fn remove_gear(&mut self, name: &str) -> anyhow::Result<String> {
+        self.invalidate_stats();
+        let Some(name) = self.gear_name(name).map(String::from) else {
+            bail!("There is no {:?} in the loadout", name)
+        };
+        self.gear.remove(&name);
+        Ok(name)
+    }
+    fn gear_name(&self, name: &str) -> Option<&str> {
+        self.gear
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(name))
+            .map(String::as_str)
+    }
+    /// The weight of everything in the loadout
+    pub fn carried_weight(&self) -> f32 {
+        let difficulty = self.difficulty.unwrap_or_default();
+        self.gear
+            .values()
+            .map(|item| item.total_weight(difficulty))
+            .sum()
+    }
+    /// The carry weight left over after the loadout, which is negative when over-encumbered
+    pub fn free_carry_weight(&self) -> f32 {
+        self.carry_weight() as f32 - self.carried_weight()
+    }
+    /// Describe each item in the loadout and what is left of the carry weight
+    pub fn describe_gear(&self) -> String {
+        if self.gear.is_empty() {
+            return "No gear in the loadout, try \"gear add <WEIGHT> <NAME>\"".into();
+        }
+        let difficulty = self.difficulty.unwrap_or_default();
+        let width = self.gear.keys().map(|name| name.len()).max().unwrap_or(0);
+        let mut lines: Vec<String> = self
+            .gear
+            .iter()
+            .map(|(name, item)| {
+                let mut line = format!("  {:width$}  ", name, width = width);
+                if item.count == 1 {
+                    line.push_str(&format!("{} lb", Pounds(item.weight)));
+                } else {
+                    line.push_str(&format!(
+                        "{} x {} lb = {} lb",
+                        item.count,
+                        Pounds(item.weight),
+                        Pounds(item.weight * item.count as f32)
+                    ));
+                }
+                if let Some(slot) = item.slot {
+                    line.push_str(&format!(", worn on {}", slot));
+                }
+                if let Some(legendary) = &item.legendary {
+                    line.push_str(&format!(", {}", legendary));
+                }
+                let weight = item.total_weight(difficulty);
+                if item.survival_only && difficulty != Difficulty::Survival {
+                    line.push_str(" (weightless off Survival)");
+                } else if weight != item.weight * item.count as f32 {
+                    line.push_str(&format!(" (weighs {} lb)", Pounds(weight)));
+                }
+                line
+            })
+            .collect();
+        lines.push(self.carry_summary());
+        lines.join("\n")
+    }
+    /// "Carrying 40 of 230 lb, 190 free"
+    pub fn carry_summary(&self) -> String {
+        let free = self.free_carry_weight();
+        format!(
+            "Carrying {} of {} lb, {}",
+            Pounds(self.carried_weight()),
+            self.carry_weight(),
+            if free < 0.0 {
+                format!("{} over", Pounds(-free))
+            } else {
+                format!("{} free", Pounds(free))
+            }
+        )
+    }
+}
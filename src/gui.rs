@@ -0,0 +1,214 @@
+use std::{io, iter::once};
+
+use anyhow::anyhow;
+use clap::Parser;
+use eframe::egui::{self, Color32, RichText};
+use fo4builder::{
+    build::Build,
+    special::{catalog, PerkDef, PerkId, PerkKind, SpecialStat},
+};
+
+use crate::{reload_perks, Command, Outcome, Session, View};
+
+pub fn run(build: Build) -> anyhow::Result<()> {
+    colored::control::set_override(false);
+    let gui = Gui {
+        session: Session {
+            build,
+            pareto_builds: Vec::new(),
+            progress: Box::new(io::sink()),
+            tabs: Default::default(),
+            merge: None,
+            scripts: Default::default(),
+            snapshots: Default::default(),
+            history: Default::default(),
+            listing: Vec::new(),
+        },
+        tab: PerkKind::Special(SpecialStat::Strength),
+        command: String::new(),
+        status: Ok(String::new()),
+    };
+    eframe::run_native(
+        "Fallout 4 Builds",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::new(gui))),
+    )
+    .map_err(|e| anyhow!("{}", e))
+}
+
+struct Gui {
+    session: Session,
+    tab: PerkKind,
+    command: String,
+    status: anyhow::Result<String>,
+}
+
+impl Gui {
+    fn run_command(&mut self, ctx: &egui::Context) {
+        let line = std::mem::take(&mut self.command);
+        if catalog().changed() {
+            if let Err(e) = reload_perks(&mut self.session.build) {
+                self.status = Err(anyhow!("Perk data not reloaded: {}", e));
+                return;
+            }
+        }
+        let args = once("fo4").chain(line.split_whitespace());
+        let command = match Command::try_parse_from(args) {
+            Ok(command) => command,
+            Err(e) => {
+                let message = e.to_string();
+                let message = message.trim_end_matches("\n\nFor more information try --help\n");
+                self.status = Err(anyhow!("{}", message));
+                return;
+            }
+        };
+        self.status = match self.session.apply(command) {
+            Outcome::Message(message) => message,
+            Outcome::View(View::Special(stat)) => {
+                self.tab = PerkKind::Special(stat.unwrap_or(SpecialStat::Strength));
+                Ok(String::new())
+            }
+            Outcome::View(View::PerkNames(kind, _)) => {
+                self.tab = kind;
+                Ok(String::new())
+            }
+            Outcome::View(View::Perk(id)) => {
+                self.tab = id.kind();
+                Ok(String::new())
+            }
+            Outcome::View(_) => Ok("That command is only shown in the terminal".into()),
+            Outcome::Exit => {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                Ok(String::new())
+            }
+        };
+    }
+    fn build_panel(&mut self, ui: &mut egui::Ui) {
+        let build = &mut self.session.build;
+        let profile = build.profile();
+        ui.label(RichText::new(build.to_string()).monospace());
+        ui.separator();
+        for &stat in SpecialStat::ALL {
+            let mut value = build.special[&stat];
+            ui.horizontal(|ui| {
+                ui.label(format!("{:12}", stat.to_string()));
+                if ui
+                    .add(egui::DragValue::new(&mut value).range(1..=profile.max_stat()))
+                    .changed()
+                {
+                    self.status = profile.set_stat(build, stat, value, false);
+                }
+            });
+        }
+    }
+    fn perk_row(&mut self, ui: &mut egui::Ui, id: PerkId, def: &PerkDef, available: bool) {
+        let build = &mut self.session.build;
+        let gender = build.gender.unwrap_or_default();
+        let difficulty = build.difficulty.unwrap_or_default();
+        let rank = build.perks.get(&id).copied().unwrap_or(0);
+        let limit = def
+            .ranks
+            .highest_rank_within_level(build.level_limit.unwrap_or(u8::MAX));
+        let mut name = RichText::new(&*def.name[gender]);
+        if rank > 0 {
+            name = name.strong().color(Color32::LIGHT_YELLOW);
+        } else if !available {
+            name = name.weak();
+        }
+        let mut change = None;
+        ui.horizontal(|ui| {
+            if let PerkId::Special { points, .. } = id {
+                ui.label(RichText::new(format!("{:2}", points)).monospace());
+            }
+            let info = def.info(id, gender, difficulty);
+            let description = info
+                .ranks
+                .iter()
+                .enumerate()
+                .map(|(i, rank)| format!("Rank {}: {}", i + 1, rank.description))
+                .collect::<Vec<_>>()
+                .join("\n");
+            ui.label(name).on_hover_text(description);
+            ui.label(format!("{}/{}", rank, def.max_rank()));
+            if ui.small_button("-").clicked() && rank > 0 {
+                change = Some(rank - 1);
+            }
+            if ui.small_button("+").clicked() && rank < limit {
+                change = Some(rank + 1);
+            }
+            if let Some(location) = catalog().locations.get(&id) {
+                ui.label(RichText::new(location.describe()).weak());
+            }
+        });
+        if let Some(rank) = change {
+            self.status = build.add_perk(id, rank).map(|_| String::new());
+        }
+    }
+    fn perks_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            for &stat in SpecialStat::ALL {
+                ui.selectable_value(&mut self.tab, PerkKind::Special(stat), stat.to_string());
+            }
+            for kind in [
+                PerkKind::Bobblehead,
+                PerkKind::Magazine,
+                PerkKind::Companion,
+                PerkKind::Faction,
+                PerkKind::Other,
+            ] {
+                ui.selectable_value(&mut self.tab, kind, kind.to_string());
+            }
+        });
+        ui.separator();
+        if let PerkKind::Special(stat) = self.tab {
+            ui.label(format!(
+                "{} ({})",
+                stat,
+                self.session.build.points_string(stat)
+            ));
+        }
+        let tab = self.tab;
+        let total_points = match tab {
+            PerkKind::Special(stat) => self.session.build.total_base_points(stat),
+            _ => u8::MAX,
+        };
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (id, def) in catalog().perks.iter().filter(|(id, _)| id.kind() == tab) {
+                let available = match id {
+                    PerkId::Special { points, .. } => *points <= total_points,
+                    _ => true,
+                };
+                self.perk_row(ui, *id, def, available);
+            }
+        });
+    }
+}
+
+impl eframe::App for Gui {
+    fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
+        egui::TopBottomPanel::bottom("command").show(ctx, |ui| {
+            match &self.status {
+                Ok(message) if message.is_empty() => {}
+                Ok(message) => {
+                    ui.colored_label(Color32::LIGHT_GREEN, message);
+                }
+                Err(e) => {
+                    ui.colored_label(Color32::LIGHT_RED, e.to_string());
+                }
+            }
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.command)
+                    .hint_text("Type a command, e.g. \"add rifleman 3\" or \"save My Build\"")
+                    .desired_width(f32::INFINITY),
+            );
+            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                self.run_command(ctx);
+                response.request_focus();
+            }
+        });
+        egui::SidePanel::left("build").show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| self.build_panel(ui));
+        });
+        egui::CentralPanel::default().show(ctx, |ui| self.perks_panel(ui));
+    }
+}
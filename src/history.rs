@@ -0,0 +1,87 @@
+use anyhow::bail;
+use fo4builder::build::Build;
+
+use crate::Session;
+
+/// How many changes to a build can be undone
+const LIMIT: usize = 100;
+
+/// Earlier versions of a build to undo back to, and undone ones to redo
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Build>,
+    redo: Vec<Build>,
+}
+
+/// Describe the change from one version of a build to another
+fn describe_change(old: &Build, new: &Build) -> String {
+    let changes = new.changes_since(old);
+    if changes.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", changes.join(", "))
+    }
+}
+
+/// What undoing a change puts back, which leaves out what only records saving
+fn state(build: &Build) -> Option<serde_yaml::Value> {
+    let mut build = build.clone();
+    build.changelog.clear();
+    build.written_by = None;
+    build.catalog = None;
+    serde_yaml::to_value(&build).ok()
+}
+
+impl Session {
+    /// Remember the build from before a command if the command changed it
+    pub fn record_history(&mut self, before: Build) {
+        if state(&before) == state(&self.build) {
+            return;
+        }
+        self.history.undo.push(before);
+        if self.history.undo.len() > LIMIT {
+            self.history.undo.remove(0);
+        }
+        self.history.redo.clear();
+    }
+    /// Move the build between versions, keeping what records its saves
+    fn restore(&mut self, mut build: Build) -> Build {
+        build.changelog = self.build.changelog.clone();
+        build.written_by = self.build.written_by.clone();
+        build.catalog = self.build.catalog.clone();
+        self.merge = None;
+        std::mem::replace(&mut self.build, build)
+    }
+    /// Go back some number of changes to the build
+    pub fn undo(&mut self, steps: usize) -> anyhow::Result<String> {
+        if self.history.undo.is_empty() {
+            bail!("There is nothing to undo")
+        }
+        let mut lines = Vec::new();
+        for _ in 0..steps.max(1) {
+            let Some(build) = self.history.undo.pop() else {
+                break;
+            };
+            let undone = self.restore(build);
+            lines.push(format!("Undid{}", describe_change(&self.build, &undone)));
+            self.history.redo.push(undone);
+        }
+        Ok(lines.join("\n"))
+    }
+    /// Make some number of undone changes again
+    pub fn redo(&mut self, steps: usize) -> anyhow::Result<String> {
+        if self.history.redo.is_empty() {
+            bail!("There is nothing to redo")
+        }
+        let mut lines = Vec::new();
+        for _ in 0..steps.max(1) {
+            let Some(build) = self.history.redo.pop() else {
+                break;
+            };
+            let redone = self.restore(build);
+            lines.push(format!("Redid{}", describe_change(&redone, &self.build)));
+            self.history.undo.push(redone);
+        }
+        Ok(lines.join("\n"))
+    }
+}
@@ -0,0 +1,770 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::{
+    build::Build,
+    special::{catalog, join_perk_id_and_rank, Gender, PerkId, SpecialStat},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Text,
+    Json,
+    Code,
+    Schedule,
+    FalloutBuilds,
+}
+
+impl FromStr for ImportFormat {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "text" | "txt" => ImportFormat::Text,
+            "json" => ImportFormat::Json,
+            "code" | "share" => ImportFormat::Code,
+            "schedule" | "csv" => ImportFormat::Schedule,
+            "falloutbuilds" | "fb" => ImportFormat::FalloutBuilds,
+            _ => bail!("Unknown import format: {}", s),
+        })
+    }
+}
+
+pub struct Imported {
+    pub build: Build,
+    pub notes: Vec<String>,
+}
+
+pub trait Importer {
+    fn format(&self) -> ImportFormat;
+    fn detect(&self, source: &str, bytes: &[u8]) -> bool;
+    fn import(&self, source: &str, bytes: &[u8]) -> anyhow::Result<Imported>;
+}
+
+struct CodeImporter;
+struct FalloutBuildsImporter;
+struct JsonImporter;
+struct ScheduleImporter;
+struct TextImporter;
+
+const IMPORTERS: &[&dyn Importer] = &[
+    &CodeImporter,
+    &FalloutBuildsImporter,
+    &JsonImporter,
+    &ScheduleImporter,
+    &TextImporter,
+];
+
+pub fn import(format: Option<ImportFormat>, source: &str) -> anyhow::Result<Imported> {
+    let path = Path::new(source);
+    let bytes = if path.exists() {
+        fs::read(path)?
+    } else {
+        Vec::new()
+    };
+    let importer = match format {
+        Some(format) => IMPORTERS
+            .iter()
+            .find(|importer| importer.format() == format),
+        None => IMPORTERS
+            .iter()
+            .find(|importer| importer.detect(source, &bytes)),
+    };
+    match importer {
+        Some(importer) => {
+            if bytes.is_empty()
+                && !matches!(
+                    importer.format(),
+                    ImportFormat::Code | ImportFormat::FalloutBuilds
+                )
+            {
+                bail!("Unable to read \"{}\"", source)
+            }
+            let mut imported = importer.import(source, &bytes)?;
+            imported.notes.extend(imported.build.revalidate_book());
+            Ok(imported)
+        }
+        None => bail!("Unable to tell what kind of build \"{}\" is", source),
+    }
+}
+
+fn source_name(source: &str) -> Option<String> {
+    Path::new(source)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+}
+
+impl Importer for CodeImporter {
+    fn format(&self) -> ImportFormat {
+        ImportFormat::Code
+    }
+    fn detect(&self, source: &str, bytes: &[u8]) -> bool {
+        bytes.is_empty() && source.trim().starts_with("FO4-")
+    }
+    fn import(&self, source: &str, _: &[u8]) -> anyhow::Result<Imported> {
+        let build = Build::from_share_code(source)?;
+        #[cfg(feature = "native")]
+        let notes = vec![crate::share::verify_share_code(source)?.describe()];
+        #[cfg(not(feature = "native"))]
+        let notes = Vec::new();
+        Ok(Imported { build, notes })
+    }
+}
+
+impl Importer for FalloutBuildsImporter {
+    fn format(&self) -> ImportFormat {
+        ImportFormat::FalloutBuilds
+    }
+    fn detect(&self, source: &str, bytes: &[u8]) -> bool {
+        let html = String::from_utf8_lossy(bytes).to_lowercase();
+        source.to_lowercase().contains("falloutbuilds.com")
+            || (html.contains("<html") && html.contains("falloutbuilds.com"))
+    }
+    fn import(&self, source: &str, bytes: &[u8]) -> anyhow::Result<Imported> {
+        let html = if bytes.is_empty() {
+            fetch_page(source)?
+        } else {
+            String::from_utf8_lossy(bytes).into_owned()
+        };
+        import_falloutbuilds(&html)
+    }
+}
+
+impl Importer for JsonImporter {
+    fn format(&self) -> ImportFormat {
+        ImportFormat::Json
+    }
+    fn detect(&self, source: &str, bytes: &[u8]) -> bool {
+        source.to_lowercase().ends_with(".json")
+            || bytes.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{')
+    }
+    fn import(&self, source: &str, bytes: &[u8]) -> anyhow::Result<Imported> {
+        let mut build: Build = serde_yaml::from_slice(bytes)?;
+        if build.name.is_none() {
+            build.name = source_name(source);
+        }
+        Ok(Imported {
+            build,
+            notes: Vec::new(),
+        })
+    }
+}
+
+impl Importer for ScheduleImporter {
+    fn format(&self) -> ImportFormat {
+        ImportFormat::Schedule
+    }
+    fn detect(&self, source: &str, bytes: &[u8]) -> bool {
+        let header = bytes.split(|b| *b == b'\n').next().unwrap_or_default();
+        let header = String::from_utf8_lossy(header).to_lowercase();
+        source.to_lowercase().ends_with(".csv")
+            && header.contains("level")
+            && header.contains("action")
+    }
+    fn import(&self, source: &str, bytes: &[u8]) -> anyhow::Result<Imported> {
+        import_schedule(source, std::str::from_utf8(bytes)?)
+    }
+}
+
+impl Importer for TextImporter {
+    fn format(&self) -> ImportFormat {
+        ImportFormat::Text
+    }
+    fn detect(&self, _: &str, bytes: &[u8]) -> bool {
+        std::str::from_utf8(bytes).is_ok()
+    }
+    fn import(&self, source: &str, bytes: &[u8]) -> anyhow::Result<Imported> {
+        import_text(source, std::str::from_utf8(bytes)?)
+    }
+}
+
+enum ScheduleAction {
+    Perk(PerkId, Option<u8>),
+    Stat(SpecialStat, u8),
+    Book(SpecialStat),
+}
+
+fn schedule_action(action: &str, target: &str) -> anyhow::Result<ScheduleAction> {
+    let words: Vec<String> = target.split_whitespace().map(Into::into).collect();
+    Ok(match action.trim().to_lowercase().as_str() {
+        "perk" | "take" | "rank" => {
+            let (id, rank) = join_perk_id_and_rank(&words)?;
+            ScheduleAction::Perk(id, rank)
+        }
+        "stat" | "special" | "point" => match words.as_slice() {
+            [stat] => ScheduleAction::Stat(stat.parse().map_err(|e: String| anyhow!(e))?, 1),
+            [stat, points] => ScheduleAction::Stat(
+                stat.parse().map_err(|e: String| anyhow!(e))?,
+                points
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid number of points: {}", points))?,
+            ),
+            _ => bail!("Expected a S.P.E.C.I.A.L. stat"),
+        },
+        "book" => ScheduleAction::Book(target.trim().parse().map_err(|e: String| anyhow!(e))?),
+        _ => bail!(
+            "Unknown action: {}, expected perk, stat, or book",
+            action.trim()
+        ),
+    })
+}
+
+/// Import a spreadsheet of picks with level, action, and target columns
+///
+/// Each level after the first pays for one perk rank or S.P.E.C.I.A.L. point.
+/// Level 1 rows assign the initial S.P.E.C.I.A.L. points.
+fn import_schedule(source: &str, text: &str) -> anyhow::Result<Imported> {
+    let mut rows = crate::csv::rows(text).into_iter().enumerate();
+    let header: Vec<String> = match rows.next() {
+        Some((_, header)) => header.iter().map(|h| h.trim().to_lowercase()).collect(),
+        None => bail!("Schedule is empty"),
+    };
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h == name)
+            .ok_or_else(|| anyhow!("Schedule has no {} column", name))
+    };
+    let (level_col, action_col, target_col) =
+        (column("level")?, column("action")?, column("target")?);
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for (i, row) in rows {
+        let field = |col: usize| row.get(col).map(String::as_str).unwrap_or_default();
+        let parsed = field(level_col)
+            .trim()
+            .parse::<u8>()
+            .map_err(|_| anyhow!("Invalid level: {}", field(level_col)))
+            .and_then(|level| {
+                Ok((
+                    level,
+                    schedule_action(field(action_col), field(target_col))?,
+                ))
+            });
+        match parsed {
+            Ok(entry) => entries.push((i + 1, entry)),
+            Err(e) => errors.push((i + 1, e)),
+        }
+    }
+    entries.sort_by_key(|(_, (level, _))| *level);
+    let mut build = Build {
+        name: source_name(source),
+        ..Default::default()
+    };
+    let mut paid_levels = BTreeSet::new();
+    for (row, (level, action)) in entries {
+        let mut pay = |level: u8| -> anyhow::Result<()> {
+            if level > 1 && !paid_levels.insert(level) {
+                bail!("Level {} already has a pick", level)
+            }
+            Ok(())
+        };
+        let result = match action {
+            ScheduleAction::Stat(stat, points) => (|| {
+                if level > 1 && points != 1 {
+                    bail!("Only 1 point is gained per level")
+                }
+                pay(level)?;
+                let assigned = build.assigned_special_points().checked_add(points);
+                if level == 1
+                    && !matches!(assigned, Some(assigned) if assigned <= Build::INITIAL_ASSIGNABLE_POINTS)
+                {
+                    bail!(
+                        "More than {} initial points assigned",
+                        Build::INITIAL_ASSIGNABLE_POINTS
+                    )
+                }
+                let value = match build.special[&stat].checked_add(points) {
+                    Some(value) if value <= 10 => value,
+                    _ => bail!("{} would be above 10", stat),
+                };
+                build.special.insert(stat, value);
+                Ok(())
+            })(),
+            ScheduleAction::Book(stat) => {
+                build.special_book = Some(stat);
+                Ok(())
+            }
+            ScheduleAction::Perk(id, rank) => (|| {
+                let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+                let name = &def.name[Gender::Male];
+                let next = build.perks.get(&id).copied().unwrap_or(0) + 1;
+                let rank = rank.unwrap_or(next);
+                if rank != next {
+                    bail!("{} rank {} is taken before rank {}", name, rank, next)
+                }
+                if rank > def.max_rank() {
+                    bail!("{} only has {} ranks", name, def.max_rank())
+                }
+                if let PerkId::Special { stat, points } = id {
+                    if level < 2 {
+                        bail!("Perks are taken from level 2")
+                    }
+                    pay(level)?;
+                    let required = def.ranks.required_level(rank);
+                    if required > level {
+                        bail!("{} rank {} requires level {}", name, rank, required)
+                    }
+                    if build.total_base_points(stat) < points {
+                        bail!("{} requires {} {} by level {}", name, stat, points, level)
+                    }
+                }
+                build.perks.insert(id, rank);
+                Ok(())
+            })(),
+        };
+        if let Err(e) = result {
+            errors.push((row, e));
+        }
+    }
+    if !errors.is_empty() {
+        errors.sort_by_key(|(row, _)| *row);
+        bail!(
+            "{}",
+            errors
+                .iter()
+                .map(|(row, e)| format!("Row {}: {}", row, e))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    }
+    let notes = vec![format!(
+        "Scheduled {} pick(s) up to level {}",
+        paid_levels.len(),
+        build.required_level()
+    )];
+    Ok(Imported { build, notes })
+}
+
+#[cfg(feature = "native")]
+fn fetch_page(url: &str) -> anyhow::Result<String> {
+    let url = if url.starts_with("http") {
+        url.to_string()
+    } else {
+        format!("https://{}", url)
+    };
+    ureq::get(&url)
+        .call()
+        .map_err(|e| anyhow!("Unable to download {}: {}", url, e))?
+        .into_string()
+        .map_err(Into::into)
+}
+
+#[cfg(not(feature = "native"))]
+fn fetch_page(_: &str) -> anyhow::Result<String> {
+    bail!("Pages can't be downloaded here, save the page and import the file instead")
+}
+
+/// The text of an HTML page, one line per element
+fn html_lines(html: &str) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let mut text = String::new();
+    let mut i = 0;
+    while let Some(start) = lower[i..].find('<').map(|start| i + start) {
+        text.push_str(&html[i..start]);
+        text.push('\n');
+        let end = lower[start..]
+            .find('>')
+            .map_or(html.len(), |end| start + end + 1);
+        i = end;
+        for skipped in ["script", "style"] {
+            if lower[start + 1..].starts_with(skipped) {
+                i = lower[end..]
+                    .find(&format!("</{}", skipped))
+                    .map_or(html.len(), |close| end + close);
+            }
+        }
+    }
+    text.push_str(&html[i..]);
+    text.lines()
+        .map(|line| {
+            decode_entities(line)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn decode_entities(s: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" | "rsquo" | "lsquo" => Some('\''),
+            "nbsp" => Some(' '),
+            "ndash" | "mdash" => Some('-'),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#')?.parse().ok())
+                .and_then(char::from_u32),
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn name_key(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn page_words(line: &str) -> Vec<&str> {
+    line.split(|c: char| c.is_whitespace() || "()[]/:,|-".contains(c))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// A S.P.E.C.I.A.L. stat spelled out in full, so stray letters on the page don't match
+fn page_stat(word: &str) -> Option<SpecialStat> {
+    SpecialStat::ALL
+        .iter()
+        .copied()
+        .find(|stat| stat.to_string().eq_ignore_ascii_case(word))
+}
+
+fn page_number(words: &[&str]) -> Option<u8> {
+    match words {
+        [n] => n.parse().ok(),
+        _ => None,
+    }
+}
+
+struct PagePick {
+    level: Option<u8>,
+    id: PerkId,
+    rank: Option<u8>,
+}
+
+/// Read a line like `Level 4: Gunslinger (Rank 2)` or `Gunslinger 2/5`
+fn page_pick(words: &[&str], names: &BTreeMap<String, PerkId>) -> Option<PagePick> {
+    let (level, words) = match words {
+        [prefix, level, rest @ ..]
+            if ["level", "lvl", "lv"].contains(&prefix.to_lowercase().as_str()) =>
+        {
+            (Some(level.parse().ok()?), rest)
+        }
+        _ => (None, words),
+    };
+    let words: Vec<&str> = words
+        .iter()
+        .copied()
+        .filter(|word| !word.eq_ignore_ascii_case("rank"))
+        .collect();
+    let name_len = words
+        .iter()
+        .position(|word| word.parse::<u8>().is_ok())
+        .unwrap_or(words.len());
+    let id = *names.get(&name_key(&words[..name_len].concat()))?;
+    let rank = words.get(name_len).and_then(|rank| rank.parse().ok());
+    Some(PagePick { level, id, rank })
+}
+
+/// Read the S.P.E.C.I.A.L. spread and perks from a FalloutBuilds.com build page
+///
+/// When the page lists perks by level, ranks are counted in that order and
+/// checked against each rank's required level.
+fn import_falloutbuilds(html: &str) -> anyhow::Result<Imported> {
+    let lines = html_lines(html);
+    let names: BTreeMap<String, PerkId> = catalog()
+        .perks
+        .iter()
+        .flat_map(|(id, def)| {
+            def.name
+                .iter()
+                .chain(&def.aliases)
+                .map(move |name| (name_key(name), *id))
+        })
+        .collect();
+    let mut stats = BTreeMap::new();
+    let mut picks = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let words = page_words(line);
+        let next = lines
+            .get(i + 1)
+            .map(|line| page_words(line))
+            .unwrap_or_default();
+        // Stats and ranks are often in their own element after the name
+        let stat = match words.as_slice() {
+            [stat, value] => page_stat(stat).zip(value.parse().ok()),
+            [stat] => page_stat(stat).zip(page_number(&next)),
+            _ => None,
+        };
+        if let Some((stat, value)) = stat {
+            if (1..=10).contains(&value) {
+                stats.entry(stat).or_insert(value);
+            }
+            continue;
+        }
+        if let Some(mut pick) = page_pick(&words, &names) {
+            if pick.rank.is_none() {
+                pick.rank = page_number(&next);
+            }
+            picks.push(pick);
+        }
+    }
+    if stats.is_empty() && picks.is_empty() {
+        bail!("No S.P.E.C.I.A.L. stats or perks found on the page")
+    }
+    let lower = html.to_ascii_lowercase();
+    let title = lower.find("<title>").and_then(|start| {
+        let start = start + "<title>".len();
+        let end = start + lower[start..].find("</title>")?;
+        // Titles look like "Build Name - Fallout 4 Build - FalloutBuilds.com"
+        let title = decode_entities(&html[start..end]);
+        let name = title.split(" - ").next().unwrap_or_default().trim();
+        (!name.is_empty()).then(|| name.to_string())
+    });
+    let mut build = Build {
+        name: title,
+        ..Default::default()
+    };
+    for (stat, value) in &stats {
+        build.special.insert(*stat, *value);
+    }
+    let mut notes = Vec::new();
+    let ordered = picks.iter().any(|pick| pick.level.is_some());
+    if ordered {
+        picks.retain(|pick| pick.level.is_some());
+        picks.sort_by_key(|pick| pick.level);
+    }
+    for pick in picks {
+        let def = catalog()
+            .perks
+            .get(&pick.id)
+            .cloned()
+            .expect("Unknown perk");
+        let name = &def.name[Gender::Male];
+        let current = build.perks.get(&pick.id).copied().unwrap_or(0);
+        let rank = match (ordered, pick.rank) {
+            (true, rank) => rank.unwrap_or(current + 1).max(current + 1),
+            (false, rank) => rank.unwrap_or(1).max(current),
+        };
+        if rank > def.max_rank() {
+            notes.push(format!("{} only has {} ranks", name, def.max_rank()));
+            continue;
+        }
+        if let Some(level) = pick.level {
+            let required = def.ranks.required_level(rank);
+            if required > level {
+                notes.push(format!(
+                    "Level {}: {} rank {} requires level {}",
+                    level, name, rank, required
+                ));
+            }
+        }
+        let before = build.special.clone();
+        build.add_perk_impl(pick.id, rank);
+        for stat in SpecialStat::ALL {
+            if build.special[stat] != before[stat] {
+                notes.push(format!(
+                    "Raised {} to {} for {}",
+                    stat, build.special[stat], name
+                ));
+            }
+        }
+    }
+    notes.insert(
+        0,
+        format!(
+            "Read {} stat(s) and {} perk(s){}",
+            stats.len(),
+            build.perks.len(),
+            if ordered { " in level order" } else { "" }
+        ),
+    );
+    Ok(Imported { build, notes })
+}
+
+fn text_words(line: &str) -> Vec<String> {
+    let line = line
+        .trim()
+        .trim_start_matches(|c: char| "-*+•".contains(c))
+        .trim_start()
+        .trim_start_matches("[ ]")
+        .trim_start_matches("[x]")
+        .trim();
+    let line = match line.split_once(':') {
+        Some((prefix, rest)) if prefix.to_lowercase().starts_with("level") => rest,
+        _ => line,
+    };
+    let words: Vec<String> = line
+        .split(|c: char| c.is_whitespace() || "(),:/".contains(c))
+        .filter(|word| !word.is_empty() && !word.eq_ignore_ascii_case("rank"))
+        .map(|word| {
+            word.trim_start_matches(['x', 'X'])
+                .parse::<u8>()
+                .map_or_else(|_| word.into(), |n| n.to_string())
+        })
+        .collect();
+    match words.first() {
+        Some(first) if first.ends_with('.') && first[..first.len() - 1].parse::<u8>().is_ok() => {
+            words[1..].to_vec()
+        }
+        _ => words,
+    }
+}
+
+fn import_text(source: &str, text: &str) -> anyhow::Result<Imported> {
+    let mut build = Build {
+        name: source_name(source),
+        ..Default::default()
+    };
+    let mut stats = Vec::new();
+    let mut perks = Vec::new();
+    let mut unmatched = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let words = text_words(line);
+        if let [stat, value] = words.as_slice() {
+            if let (Ok(stat), Ok(value)) = (stat.parse::<SpecialStat>(), value.parse::<u8>()) {
+                stats.push((stat, value));
+                continue;
+            }
+        }
+        match join_perk_id_and_rank(&words) {
+            Ok((id, rank)) => perks.push((id, rank)),
+            Err(_) => unmatched.push(line.trim().to_string()),
+        }
+    }
+    for (stat, value) in stats {
+        build.special.insert(stat, value.clamp(1, 10));
+    }
+    for (id, rank) in &perks {
+        let max_rank = catalog().perks.get(id).expect("Unknown perk").max_rank();
+        build.add_perk(*id, rank.unwrap_or(max_rank).min(max_rank))?;
+    }
+    let mut notes = vec![format!("Matched {} perk(s)", perks.len())];
+    if !unmatched.is_empty() {
+        notes.push("Unmatched lines:".into());
+        notes.extend(unmatched.into_iter().map(|line| format!("  {}", line)));
+    }
+    Ok(Imported { build, notes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule_error(text: &str) -> String {
+        match import_schedule("plan.csv", text) {
+            Ok(_) => panic!("schedule imported"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    fn special(stat: SpecialStat, points: u8) -> PerkId {
+        PerkId::Special { stat, points }
+    }
+
+    #[test]
+    fn text_lists_read_stats_and_perks() {
+        let imported = import_text(
+            "notes/sniper.txt",
+            "# My sniper\nStrength 6\n- Agility: 4\n[x] Gunslinger (Rank 2)\n3. Commando x1\nsomething else\n",
+        )
+        .unwrap();
+        let build = imported.build;
+        assert_eq!(build.name.as_deref(), Some("sniper"));
+        assert_eq!(build.special[&SpecialStat::Strength], 6);
+        assert_eq!(build.special[&SpecialStat::Agility], 4);
+        assert_eq!(build.perks.get(&special(SpecialStat::Agility, 1)), Some(&2));
+        assert_eq!(build.perks.get(&special(SpecialStat::Agility, 2)), Some(&1));
+        assert_eq!(imported.notes[0], "Matched 2 perk(s)");
+        assert!(imported.notes.contains(&"  something else".to_string()));
+    }
+
+    #[test]
+    fn html_entities_are_decoded() {
+        assert_eq!(
+            decode_entities("Guns &amp; Ammo &#x41;&#66; &bogus; & &#xffffffff;"),
+            "Guns & Ammo AB &bogus; & &#xffffffff;"
+        );
+    }
+
+    #[test]
+    fn falloutbuilds_pages_are_read_in_level_order() {
+        let html = "<html><head><title>Gun &amp; Run - Fallout 4 Build - FalloutBuilds.com</title>\
+            <script>var stats = '<div>Strength</div><div>9</div>';</script></head><body>\
+            <div>Strength</div><div>3</div><div>Agility 5</div><div>Luck 11</div>\
+            <li>Level 3: Gunslinger (Rank 2)</li><li>Level 2: Gunslinger (Rank 1)</li>\
+            <li>Level 4: Commando</li></body></html>";
+        let imported = import_falloutbuilds(html).unwrap();
+        let build = imported.build;
+        assert_eq!(build.name.as_deref(), Some("Gun & Run"));
+        assert_eq!(build.special[&SpecialStat::Strength], 3);
+        assert_eq!(build.special[&SpecialStat::Agility], 5);
+        assert_eq!(build.special[&SpecialStat::Luck], 1);
+        assert_eq!(build.perks.get(&special(SpecialStat::Agility, 1)), Some(&2));
+        assert_eq!(build.perks.get(&special(SpecialStat::Agility, 2)), Some(&1));
+        assert!(imported
+            .notes
+            .contains(&"Level 3: Gunslinger rank 2 requires level 7".to_string()));
+        assert!(imported.notes[0].ends_with("in level order"));
+    }
+
+    #[test]
+    fn pages_without_a_build_are_rejected() {
+        assert!(import_falloutbuilds("<html><p>Nothing to see</p></html>").is_err());
+    }
+
+    #[test]
+    fn formats_are_told_apart() {
+        assert!(CodeImporter.detect(" FO4-AAAA", b""));
+        assert!(JsonImporter.detect("build", b"  {\"name\": \"x\"}"));
+        assert!(!JsonImporter.detect("build.txt", b"Strength 3"));
+        assert!(ScheduleImporter.detect("plan.CSV", b"Level,Action,Target\n"));
+        assert!(!ScheduleImporter.detect("plan.csv", b"a,b,c\n"));
+        assert!(FalloutBuildsImporter.detect("https://falloutbuilds.com/fo4/sniper/", b""));
+        assert!("FB".parse::<ImportFormat>().unwrap() == ImportFormat::FalloutBuilds);
+        assert!("yaml".parse::<ImportFormat>().is_err());
+    }
+
+    #[test]
+    fn schedules_assign_initial_points() {
+        let imported = import_schedule(
+            "plan.csv",
+            "level,action,target\n1,stat,strength 3\n1,stat,agility 2\n2,stat,agility\n",
+        )
+        .unwrap();
+        assert_eq!(imported.build.name.as_deref(), Some("plan"));
+        assert_eq!(imported.build.special[&SpecialStat::Strength], 4);
+        assert_eq!(imported.build.special[&SpecialStat::Agility], 4);
+    }
+
+    #[test]
+    fn schedule_points_that_overflow_are_rejected() {
+        let e = schedule_error("level,action,target\n1,stat,strength 3\n1,stat,agility 254\n");
+        assert!(e.contains("Row 3: More than"), "{}", e);
+        let e = schedule_error("level,action,target\n1,stat,luck 255\n");
+        assert!(e.contains("Row 2:"), "{}", e);
+    }
+}
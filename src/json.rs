@@ -0,0 +1,63 @@
+use std::{collections::BTreeMap, mem};
+
+use anyhow::bail;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    build::Build,
+    special::{PerkId, SpecialStat},
+};
+
+impl Build {
+    /// Builds key their perks by [`PerkId`], which JSON can't use as an object key,
+    /// so the API keys them by perk name instead
+    pub fn to_json(&self) -> anyhow::Result<Value> {
+        fn named<T: Serialize>(build: &Build, map: BTreeMap<PerkId, T>) -> anyhow::Result<Value> {
+            let mut named = Map::new();
+            for (id, value) in map {
+                named.insert(build.perk_name(&id), serde_json::to_value(value)?);
+            }
+            Ok(Value::Object(named))
+        }
+        let mut rest = self.clone();
+        let perks = mem::take(&mut rest.perks);
+        let completed = mem::take(&mut rest.completed);
+        let collected = mem::take(&mut rest.collected);
+        rest.catalog = None;
+        let mut value = serde_json::to_value(&rest)?;
+        value["perks"] = named(self, perks)?;
+        value["completed"] = named(self, completed)?;
+        value["collected"] = named(self, collected)?;
+        Ok(value)
+    }
+    pub fn from_json(mut value: Value) -> anyhow::Result<Build> {
+        fn ids<T: DeserializeOwned>(value: Option<Value>) -> anyhow::Result<BTreeMap<PerkId, T>> {
+            let mut ids = BTreeMap::new();
+            let named: BTreeMap<String, T> = match value {
+                Some(value) => serde_json::from_value(value)?,
+                None => return Ok(ids),
+            };
+            for (name, value) in named {
+                ids.insert(name.parse::<PerkId>()?, value);
+            }
+            Ok(ids)
+        }
+        let object = match value.as_object_mut() {
+            Some(object) => object,
+            None => bail!("Expected a build object"),
+        };
+        let perks = ids(object.remove("perks"))?;
+        let completed = ids(object.remove("completed"))?;
+        let collected = ids(object.remove("collected"))?;
+        object.remove("catalog");
+        let mut build: Build = serde_json::from_value(value)?;
+        for &stat in SpecialStat::ALL {
+            build.special.entry(stat).or_insert(1);
+        }
+        build.perks = perks;
+        build.completed = completed;
+        build.collected = collected;
+        Ok(build)
+    }
+}
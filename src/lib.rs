@@ -0,0 +1,64 @@
+//! The Fallout 4 build planner as a library, for tools that want to plan builds
+//! without going through the `fo4builder` REPL
+//!
+//! A [`build::Build`] holds a S.P.E.C.I.A.L. allocation and perks, changed with methods
+//! like `set` and `add_perk`, and [`build::Build::stats`] works out its derived stats.
+//! Perk data comes from [`special::catalog`], keyed by [`special::PerkId`], with each perk's
+//! ranks and effects in a [`special::PerkDef`]. Builds load and save through a
+//! [`storage::Storage`]. The terminal, web, C, and Python front ends are behind the
+//! `native`, `web`, `ffi`, and `python` features.
+
+#[cfg(feature = "native")]
+pub mod archive;
+#[cfg(feature = "native")]
+pub mod backup;
+pub mod build;
+pub mod changelog;
+pub mod classic;
+pub mod collection;
+pub mod compare;
+#[cfg(feature = "native")]
+pub mod config;
+pub mod copy;
+pub mod coverage;
+pub mod csv;
+pub mod export;
+pub mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fo76;
+pub mod gear;
+pub mod import;
+pub mod json;
+pub mod lint;
+pub mod load;
+pub mod matcher;
+pub mod matrix;
+pub mod merge;
+pub mod optimize;
+mod perk_data;
+pub mod plan;
+pub mod plugin;
+pub mod preset;
+pub mod profile;
+pub mod progress;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "native")]
+pub mod script;
+pub mod search;
+pub mod share;
+pub mod special;
+pub mod storage;
+#[cfg(feature = "native")]
+pub mod sync;
+#[cfg(feature = "native")]
+pub mod update;
+pub mod usage;
+pub mod value;
+pub mod watch;
+#[cfg(feature = "web")]
+pub mod web;
+pub mod why;
+pub mod wishlist;
+pub mod xedit;
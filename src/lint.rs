@@ -0,0 +1,141 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::special::*;
+
+fn descriptions(ranks: &Ranks) -> Vec<&FullyVariable<String>> {
+    match ranks {
+        Ranks::Single { description, .. } | Ranks::UniformCumulative { description, .. } => {
+            vec![description]
+        }
+        Ranks::VaryingCumulative(ranks) => ranks.iter().map(|rank| &rank.description).collect(),
+    }
+}
+
+fn check_range<T>(problems: &mut Vec<String>, name: &str, field: &str, val: T, min: T, max: T)
+where
+    T: PartialOrd + std::fmt::Display,
+{
+    if val < min || val > max {
+        problems.push(format!(
+            "{}: {} is {}, expected {} to {}",
+            name, field, val, min, max
+        ));
+    }
+}
+
+pub fn lint(catalog: &Catalog) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut names: BTreeMap<String, usize> = BTreeMap::new();
+    for def in catalog.perks.values() {
+        let name = &def.name[Gender::Male];
+        let own: BTreeSet<String> = def.name.iter().map(|n| n.trim().to_lowercase()).collect();
+        for n in own {
+            *names.entry(n).or_default() += 1;
+        }
+        if let MaybeVaried::Multi(gendered) = &def.name {
+            if gendered.male == gendered.female {
+                problems.push(format!("{}: male and female names are identical", name));
+            }
+            if gendered.male.trim().is_empty() || gendered.female.trim().is_empty() {
+                problems.push(format!("{}: gendered name has an empty variant", name));
+            }
+        }
+        if def.max_rank() == 0 {
+            problems.push(format!("{}: has no ranks", name));
+            continue;
+        }
+        if let Ranks::VaryingCumulative(ranks) = &def.ranks {
+            let mut previous = 0;
+            for (i, rank) in ranks.iter().enumerate() {
+                if rank.required_level == 0 || rank.required_level < previous {
+                    problems.push(format!(
+                        "{}: rank {} requires level {}, below rank {} at level {}",
+                        name,
+                        i + 1,
+                        rank.required_level,
+                        i,
+                        previous
+                    ));
+                }
+                previous = rank.required_level;
+            }
+        }
+        for desc in descriptions(&def.ranks) {
+            match desc {
+                MaybeVaried::One(desc) => {
+                    if desc.iter().any(|d| d.to_lowercase().contains("survival")) {
+                        problems.push(format!(
+                            "{}: description mentions survival but has no survival variant",
+                            name
+                        ));
+                    }
+                }
+                MaybeVaried::Multi(desc) => {
+                    for (difficulty, variant) in desc.overrides() {
+                        if *variant == desc.normal {
+                            problems.push(format!(
+                                "{}: {:?} and normal descriptions are identical",
+                                name, difficulty
+                            ));
+                        }
+                    }
+                }
+            }
+            for desc in desc.iter().flat_map(|desc| desc.iter()) {
+                if desc.trim().is_empty() {
+                    problems.push(format!("{}: has an empty description", name));
+                }
+            }
+        }
+        if def.gender.against.is_none() && def.has_effect("damage_against_add") {
+            problems.push(format!(
+                "{}: has damage_against_add but no gender it acts against",
+                name
+            ));
+        }
+        let max = def.max_rank();
+        for rank in 1..=max {
+            for (effect, effect_def) in &catalog.effects {
+                let Some([low, high]) = effect_def.range else {
+                    continue;
+                };
+                for val in def.effect(effect, rank) {
+                    let val = match val {
+                        EffectValue::Number(val) => val,
+                        EffectValue::Stat(si) => si.increase as f32,
+                    };
+                    check_range(&mut problems, name, effect, val, low, high);
+                }
+            }
+        }
+    }
+    for (name, def) in &catalog.legendaries {
+        for (effect, effect_def) in &catalog.effects {
+            let (Some([low, high]), Some(val)) = (effect_def.range, def.effects.get(effect)) else {
+                continue;
+            };
+            let val = match val {
+                EffectValue::Number(val) => val,
+                EffectValue::Stat(si) => si.increase as f32,
+            };
+            check_range(&mut problems, name, effect, val, low, high);
+        }
+    }
+    for (name, count) in names {
+        if count > 1 {
+            problems.push(format!("{:?} is used by {} perks", name, count));
+        }
+    }
+    // Saved builds refer to perks by key, so two perks with the same key can't both be saved
+    let mut keys: BTreeMap<&str, usize> = BTreeMap::new();
+    for key in catalog.perks.keys().filter_map(|id| catalog.key(id)) {
+        *keys.entry(key).or_default() += 1;
+    }
+    for (key, count) in keys {
+        if count > 1 {
+            problems.push(format!("The key `{}` is used by {} perks", key, count));
+        }
+    }
+    problems.dedup();
+    problems
+}
@@ -0,0 +1,462 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+use serde_yaml::Value;
+
+use crate::{
+    build::Build,
+    gear::GearItem,
+    plan::PlanStep,
+    special::{catalog, similarity, BobbleheadId, Gender, PerkId, SpecialStat},
+    wishlist::Wish,
+};
+
+const FIELDS: &[&str] = &[
+    "name",
+    "gender",
+    "special",
+    "special_book",
+    "bobblehead_before_book",
+    "bobblehead_levels",
+    "book_level",
+    "buffs",
+    "difficulty",
+    "perks",
+    "show_sheet",
+    "watches",
+    "level_limit",
+    "current_level",
+    "completed",
+    "collected",
+    "gear",
+    "wishlist",
+    "plan",
+    "milestones",
+    "changelog",
+    "catalog",
+    "written_by",
+    "fo76",
+    "classic",
+];
+
+/// Parse a build file, describing where and why it is invalid if it is
+pub fn parse(bytes: &[u8], source: &str) -> anyhow::Result<Build> {
+    let build: Build = serde_yaml::from_slice(bytes).map_err(|e| {
+        let mut message = format!("Invalid build file \"{}\": {}", source, e);
+        if let Some(location) = e.location() {
+            let text = String::from_utf8_lossy(bytes);
+            if let Some(line) = text.lines().nth(location.line().saturating_sub(1)) {
+                message.push_str(&format!("\n{:>5} | {}", location.line(), line));
+                message.push_str(&format!("\n{:>5} | {:>2$}", "", "^", location.column()));
+            }
+        }
+        if let Some(suggestion) = suggestion(&e.to_string()) {
+            message.push_str(&format!("\nDid you mean `{}`?", suggestion));
+        }
+        anyhow!("{}", message)
+    })?;
+    build.check_invariants().map_err(|e| {
+        let hint = match unknown_perk(&build) {
+            Some(id) => describe_unknown(id),
+            None => e.to_string(),
+        };
+        anyhow!("Invalid build file \"{}\": {}", source, hint)
+    })?;
+    Ok(build)
+}
+
+/// Parse whatever parts of a build file are valid, along with a description
+/// of each part that had to be dropped or changed
+pub fn parse_partial(bytes: &[u8]) -> anyhow::Result<(Build, Vec<String>)> {
+    let mut map = match serde_yaml::from_slice(bytes)? {
+        Value::Mapping(map) => map,
+        _ => anyhow::bail!("A build file must be a mapping of fields"),
+    };
+    let mut dropped = Vec::new();
+    let mut build = Build::default();
+    let mut take = |field: &str| map.remove(&Value::from(field));
+    let fo76 = take("fo76");
+    let classic = take("classic");
+    let name = take("name");
+    let gender = take("gender");
+    let special = take("special");
+    let special_book = take("special_book");
+    let bobblehead_before_book = take("bobblehead_before_book");
+    let bobblehead_levels = take("bobblehead_levels");
+    let book_level = take("book_level");
+    let buffs = take("buffs");
+    let difficulty = take("difficulty");
+    let perks = take("perks");
+    let show_sheet = take("show_sheet");
+    let watches = take("watches");
+    let level_limit = take("level_limit");
+    let current_level = take("current_level");
+    let completed = take("completed");
+    let collected = take("collected");
+    let gear = take("gear");
+    let wishlist = take("wishlist");
+    let plan = take("plan");
+    let milestones = take("milestones");
+    let changelog = take("changelog");
+    let stamp = take("catalog");
+    let written_by = take("written_by");
+    for (key, _) in map {
+        let key = describe(&key);
+        match best_match(&key, FIELDS) {
+            Some(field) => dropped.push(format!(
+                "Unknown field `{}`, did you mean `{}`?",
+                key, field
+            )),
+            None => dropped.push(format!("Unknown field `{}`", key)),
+        }
+    }
+    build.fo76 = field(fo76, "fo76", &mut dropped);
+    build.classic = field(classic, "classic", &mut dropped);
+    build.name = field(name, "name", &mut dropped);
+    build.gender = field(gender, "gender", &mut dropped);
+    build.difficulty = field(difficulty, "difficulty", &mut dropped);
+    build.show_sheet = field(show_sheet, "show_sheet", &mut dropped);
+    build.watches = field(watches, "watches", &mut dropped);
+    build.level_limit = field(level_limit, "level_limit", &mut dropped);
+    build.current_level = field(current_level, "current_level", &mut dropped);
+    build.catalog = field(stamp, "catalog", &mut dropped);
+    build.changelog = field(changelog, "changelog", &mut dropped);
+    for (name, item) in entries::<String, GearItem>(gear, "gear", &mut dropped) {
+        if let Err(e) = build.add_gear(&name, item) {
+            dropped.push(format!("{}: {}", name, e));
+        }
+    }
+    build.written_by = field(written_by, "written_by", &mut dropped);
+    let max = build.profile().max_stat();
+    for (stat, points) in entries::<SpecialStat, u8>(special, "special", &mut dropped) {
+        if (1..=max).contains(&points) {
+            build.special.insert(stat, points);
+        } else {
+            let clamped = points.clamp(1, max);
+            dropped.push(format!("{} of {} changed to {}", stat, points, clamped));
+            build.special.insert(stat, clamped);
+        }
+    }
+    for (stat, amount) in entries::<SpecialStat, u8>(buffs, "buffs", &mut dropped) {
+        if let Err(e) = build.set_buff(stat, amount) {
+            dropped.push(format!("{} buff: {}", stat, e));
+        }
+    }
+    for (id, rank) in entries::<PerkId, u8>(perks, "perks", &mut dropped) {
+        let Some(def) = catalog().perks.get(&id).cloned() else {
+            dropped.push(describe_unknown(id));
+            continue;
+        };
+        if rank == 0 {
+            dropped.push(format!("{} at rank 0", build.perk_name(&id)));
+        } else if rank > def.max_rank() {
+            dropped.push(format!(
+                "{} rank {} lowered to {}",
+                build.perk_name(&id),
+                rank,
+                def.max_rank()
+            ));
+            build.perks.insert(id, def.max_rank());
+        } else {
+            build.perks.insert(id, rank);
+        }
+    }
+    let special_book: Option<SpecialStat> = field(special_book, "special_book", &mut dropped);
+    let bobblehead_first = field(
+        bobblehead_before_book,
+        "bobblehead_before_book",
+        &mut dropped,
+    );
+    let perks = build.perks.clone();
+    if let Err(e) = build.set_book(special_book, bobblehead_first) {
+        dropped.push(format!("S.P.E.C.I.A.L. book: {}", e));
+        build.set_book(None, false)?;
+    }
+    for (stat, level) in
+        entries::<SpecialStat, u8>(bobblehead_levels, "bobblehead_levels", &mut dropped)
+    {
+        if let Err(e) = build.set_bobblehead_level(stat, level) {
+            dropped.push(format!("{} bobblehead level: {}", stat, e));
+        }
+    }
+    if let Some(level) = field::<Option<u8>>(book_level, "book_level", &mut dropped) {
+        if let Err(e) = build.set_book_level(level) {
+            dropped.push(format!("Book level: {}", e));
+        }
+    }
+    for id in perks.keys().filter(|id| !build.perks.contains_key(id)) {
+        dropped.push(format!("{} without enough points", build.perk_name(id)));
+    }
+    build.completed = entries(completed, "completed", &mut dropped);
+    build.collected = entries(collected, "collected", &mut dropped);
+    for id in build.completed.keys().chain(build.collected.keys()) {
+        if !catalog().perks.contains_key(id) {
+            dropped.push(describe_unknown(*id));
+        }
+    }
+    build
+        .completed
+        .retain(|id, _| catalog().perks.contains_key(id));
+    build
+        .collected
+        .retain(|id, _| catalog().perks.contains_key(id));
+    for wish in field::<Vec<Wish>>(wishlist, "wishlist", &mut dropped) {
+        if !catalog().perks.contains_key(&wish.perk) {
+            dropped.push(describe_unknown(wish.perk));
+        } else if let Err(e) = build.wish(wish.perk, Some(wish.rank), None) {
+            dropped.push(format!("Wishlist: {}", e));
+        }
+    }
+    build.milestones = entries(milestones, "milestones", &mut dropped);
+    build.plan = entries(plan, "plan", &mut dropped);
+    build.plan.retain(|_, step| match step {
+        PlanStep::Perk { perk, .. } if !catalog().perks.contains_key(perk) => {
+            dropped.push(describe_unknown(*perk));
+            false
+        }
+        _ => true,
+    });
+    Ok((build, dropped))
+}
+
+/// Deserialize a single field, recording it as dropped if it is invalid
+fn field<T: DeserializeOwned + Default>(
+    value: Option<Value>,
+    name: &str,
+    dropped: &mut Vec<String>,
+) -> T {
+    let Some(value) = value else {
+        return T::default();
+    };
+    serde_yaml::from_value(value.clone()).unwrap_or_else(|e| {
+        dropped.push(invalid(name, &e));
+        T::default()
+    })
+}
+
+/// Deserialize the entries of a mapping one at a time, recording each invalid one as dropped
+fn entries<K, V>(value: Option<Value>, name: &str, dropped: &mut Vec<String>) -> BTreeMap<K, V>
+where
+    K: DeserializeOwned + Ord,
+    V: DeserializeOwned,
+{
+    let map = match value {
+        None => return BTreeMap::new(),
+        Some(Value::Mapping(map)) => map,
+        Some(value) => {
+            dropped.push(format!(
+                "{} `{}`, which is not a mapping",
+                name,
+                describe(&value)
+            ));
+            return BTreeMap::new();
+        }
+    };
+    let mut entries = BTreeMap::new();
+    for (key, value) in map {
+        let entry = serde_yaml::from_value(key.clone())
+            .and_then(|key| serde_yaml::from_value(value.clone()).map(|value| (key, value)));
+        match entry {
+            Ok((key, value)) => {
+                entries.insert(key, value);
+            }
+            Err(e) => dropped.push(invalid(
+                &format!("{} `{}: {}`", name, describe(&key), describe(&value)),
+                &e,
+            )),
+        }
+    }
+    entries
+}
+
+fn invalid(what: &str, e: &serde_yaml::Error) -> String {
+    let mut message = format!("{}: {}", what, e);
+    if let Some(suggestion) = suggestion(&e.to_string()) {
+        message.push_str(&format!(", did you mean `{}`?", suggestion));
+    }
+    message
+}
+
+/// A compact, single-line rendering of a YAML value
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => "~".into(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Sequence(seq) => {
+            format!(
+                "[{}]",
+                seq.iter().map(describe).collect::<Vec<_>>().join(", ")
+            )
+        }
+        Value::Mapping(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{}: {}", describe(k), describe(v)))
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+    }
+}
+
+/// Suggest a replacement for the unknown name in a serde error like
+/// "unknown variant `Strenght`, expected one of `Strength`, `Perception`, ..."
+fn suggestion(message: &str) -> Option<String> {
+    if !message.contains("unknown variant") && !message.contains("unknown field") {
+        return None;
+    }
+    let mut names = message.split('`').skip(1).step_by(2);
+    let unknown = names.next()?;
+    let expected: Vec<&str> = names.collect();
+    best_match(unknown, &expected).map(Into::into)
+}
+
+fn best_match<'a>(unknown: &str, expected: &[&'a str]) -> Option<&'a str> {
+    expected
+        .iter()
+        .map(|name| {
+            (
+                *name,
+                similarity(unknown.to_lowercase(), name.to_lowercase()),
+            )
+        })
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .filter(|(_, sim)| *sim >= 0.7)
+        .map(|(name, _)| name)
+}
+
+fn unknown_perk(build: &Build) -> Option<PerkId> {
+    build
+        .perks
+        .keys()
+        .chain(build.completed.keys())
+        .chain(build.collected.keys())
+        .chain(build.wishlist.iter().map(|wish| &wish.perk))
+        .chain(build.plan.values().filter_map(|step| match step {
+            PlanStep::Perk { perk, .. } => Some(perk),
+            PlanStep::Special(_) => None,
+        }))
+        .find(|id| !catalog().perks.contains_key(id))
+        .copied()
+}
+
+/// Describe a perk id missing from the catalog along with the closest one that exists
+fn describe_unknown(id: PerkId) -> String {
+    let catalog = catalog();
+    let nearest = catalog
+        .perks
+        .keys()
+        .filter(|other| other.kind() == id.kind())
+        .min_by_key(|other| distance(id, **other));
+    match nearest {
+        Some(nearest) => format!(
+            "Unknown perk {:?}, the closest is {:?} ({})",
+            id,
+            nearest,
+            catalog.perks[nearest].name[Gender::default()]
+        ),
+        None => format!("Unknown perk {:?}", id),
+    }
+}
+
+fn distance(a: PerkId, b: PerkId) -> usize {
+    let index = |id| match id {
+        PerkId::Special { points, .. } => Some(points as usize),
+        PerkId::Bobblehead(BobbleheadId::Special(_)) => None,
+        PerkId::Bobblehead(BobbleheadId::Other(i))
+        | PerkId::Magazine(i)
+        | PerkId::Companion(i)
+        | PerkId::Faction(i)
+        | PerkId::Other(i) => Some(i),
+    };
+    match (index(a), index(b)) {
+        (Some(a), Some(b)) => a.abs_diff(b),
+        _ => usize::MAX,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A build file saved before perks were saved by key
+    const INDEXED: &str = "\
+name: legacy
+special:
+  Strength: 3
+  Perception: 1
+  Endurance: 1
+  Charisma: 1
+  Intelligence: 1
+  Agility: 1
+  Luck: 1
+perks:
+  ? Special:
+      stat: Strength
+      points: 1
+  : 2
+  ? Bobblehead:
+      Other: 10
+  : 1
+  ? Magazine: 15
+  : 2
+  ? Faction: 0
+  : 1
+completed:
+  ? Magazine: 15
+  : 2
+collected:
+  ? Magazine: 15
+  : - 1
+    - 2
+";
+
+    fn keys(perks: &BTreeMap<PerkId, u8>) -> Vec<String> {
+        let catalog = catalog();
+        perks
+            .keys()
+            .map(|id| catalog.key(id).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn indexed_perks_migrate_to_keys() {
+        let build = parse(INDEXED.as_bytes(), "legacy").unwrap();
+        assert_eq!(
+            keys(&build.perks),
+            [
+                "strength-1",
+                "bobblehead-sneak-bobblehead",
+                "magazine-grognak-the-barbarian",
+                "faction-ace-operator"
+            ]
+        );
+        assert_eq!(keys(&build.completed), ["magazine-grognak-the-barbarian"]);
+    }
+
+    #[test]
+    fn builds_round_trip_by_key() {
+        let build = parse(INDEXED.as_bytes(), "legacy").unwrap();
+        let yaml = serde_yaml::to_string(&build).unwrap();
+        assert!(yaml.contains("magazine-grognak-the-barbarian: 2"));
+        let saved = parse(yaml.as_bytes(), "saved").unwrap();
+        assert_eq!(saved.perks, build.perks);
+        assert_eq!(saved.completed, build.completed);
+        assert_eq!(saved.collected, build.collected);
+    }
+
+    #[test]
+    fn partial_loads_migrate_indexed_perks() {
+        let (build, dropped) = parse_partial(INDEXED.as_bytes()).unwrap();
+        assert!(dropped.is_empty(), "{:?}", dropped);
+        assert_eq!(keys(&build.perks).len(), 4);
+    }
+
+    #[test]
+    fn unknown_indices_are_rejected() {
+        let file = INDEXED.replace("Magazine: 15", "Magazine: 200");
+        assert!(parse(file.as_bytes(), "legacy").is_err());
+    }
+}
@@ -1,34 +1,110 @@
 #![allow(unstable_name_collisions)]
 
-mod build;
-mod special;
+#[cfg(feature = "bot")]
+mod bot;
+mod daemon;
+#[cfg(feature = "gui")]
+mod gui;
+mod history;
+mod picker;
+mod rpc;
+mod screen;
+mod serve;
+mod snapshot;
+mod tabs;
+#[cfg(feature = "tui")]
+mod tui;
 
 use std::{
-    io::{stdin, BufRead},
+    collections::BTreeMap,
+    fmt, fs,
+    io::{stdin, stdout, BufRead, IsTerminal, Write},
     iter::once,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::exit,
 };
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use clap::Parser;
 
-use build::*;
 use colored::Colorize;
+use fo4builder::{
+    archive, backup, build::*, classic::*, config::*, export::*, expr::format_value, fo76::*,
+    gear::*, import::*, lint, merge::Merge, optimize::*, plugin, preset::*, profile::with_removed,
+    script::Scripts, search, share, special::*, storage::Folder, sync, update, usage, watch::Watch,
+    xedit,
+};
 use itertools::Itertools;
-use once_cell::sync::Lazy;
-use special::*;
 
-fn main() {
-    Lazy::force(&PERKS);
+use crate::{
+    history::History,
+    picker::{Entry, Picked},
+    screen::{Frame, Screen},
+    tabs::Tabs,
+};
 
+fn main() {
     let app = App::parse();
 
+    if let Some(dir) = app.data_dir {
+        set_data_dir(dir);
+    } else if let Err(e) = data_dir() {
+        match choose_data_dir() {
+            Some(dir) => set_data_dir(dir),
+            None => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(tool) = app.tool {
+        if let Err(e) = tool.run() {
+            eprintln!("{}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    let mut data_notes = Vec::new();
+    if let Some(path) = app.data {
+        match use_data_file(path.clone()) {
+            Ok(problems) => {
+                data_notes.push(format!("Using the perk data in {}", path.to_string_lossy()));
+                if !problems.is_empty() {
+                    data_notes.push(format!(
+                        "It has {} problem{}, see \"fo4builder lint-data {}\"",
+                        problems.len(),
+                        if problems.len() == 1 { "" } else { "s" },
+                        path.to_string_lossy()
+                    ));
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if let Err(e) = try_catalog() {
+        eprintln!("{}", e);
+        exit(1);
+    }
+
+    if app.rpc {
+        if let Err(e) = rpc::run() {
+            eprintln!("{}", e);
+            exit(1);
+        }
+        return;
+    }
+
     if app.no_color || !colored::control::SHOULD_COLORIZE.should_colorize() {
         colored::control::set_override(false);
     }
 
-    let mut build = if app.path.is_empty() {
+    let build = if app.path.is_empty() {
         clear_terminal();
         Build::default()
     } else {
@@ -50,221 +126,1368 @@ fn main() {
         }
     };
 
+    let notes: Vec<String> = data_notes
+        .into_iter()
+        .chain(config_error())
+        .chain(build.game().experimental_note())
+        .chain(build.version_skew())
+        .chain(build.catalog_changes())
+        .collect();
+    #[cfg(feature = "tui")]
+    if app.tui {
+        if let Err(e) = tui::run(build, notes) {
+            eprintln!("{}", e);
+            exit(1);
+        }
+        return;
+    }
     println!("\n{}", build);
-    let type_help = || println!("{}\n", "Type \"help\" for usage information".bright_blue());
-    type_help();
+    for change in &notes {
+        println!("{}", change.bright_yellow());
+    }
+    println!("{}", type_help());
 
+    let mut session = Session {
+        build,
+        pareto_builds: Vec::new(),
+        progress: Box::new(stdout()),
+        tabs: Tabs::default(),
+        merge: None,
+        scripts: Scripts::load(),
+        snapshots: BTreeMap::new(),
+        history: History::default(),
+        listing: Vec::new(),
+    };
+    for error in &session.scripts.errors {
+        println!("{}", error.bright_yellow());
+    }
+    let mut screen = Screen::default();
     for line in stdin().lock().lines().map_while(Result::ok) {
+        let reloaded = catalog()
+            .changed()
+            .then(|| reload_perks(&mut session.build));
+        let watched = session.build.watch_values();
+        let mut frame = Frame::default();
+        let line = match line.trim_start().strip_prefix('/') {
+            Some(query) => {
+                let interactive = stdin().is_terminal() && stdout().is_terminal();
+                let picked = match session.filter_listing(query.trim(), interactive) {
+                    Ok(Picked::Run(command)) => Ok(command),
+                    Ok(Picked::Cancelled) => continue,
+                    Ok(Picked::Matches(text)) => Err(text.bright_green()),
+                    Err(e) => Err(e.to_string().bright_red()),
+                };
+                match picked {
+                    Ok(command) => command,
+                    Err(shown) => {
+                        session.write_header(&mut frame, &watched);
+                        frame.line(format!("{}\n", shown));
+                        screen.present(frame);
+                        continue;
+                    }
+                }
+            }
+            None => line,
+        };
         let args: Vec<&str> = once("fo4").chain(line.split_whitespace()).collect();
         match Command::try_parse_from(args) {
             Ok(command) => {
-                let res = match command {
-                    Command::Set { stat, value } => build
-                        .set(stat, value)
-                        .map(|_| format!("Set {:?} to {}", stat, value)),
-                    Command::Add {
-                        perk: head,
-                        tail_and_rank: mut perk_and_rank,
-                    } => catch(|| {
-                        perk_and_rank.insert(0, head);
-                        let (perk, rank) = join_perk_def_and_rank(&perk_and_rank)?;
-                        let rank = rank.unwrap_or_else(|| perk.max_rank()).min(
-                            perk.ranks
-                                .highest_rank_within_level(build.level_limit.unwrap_or(u8::MAX)),
-                        );
-                        build.add_perk(&perk, rank)?;
-                        let name = &perk.name[build.gender.unwrap_or_default()];
-                        Ok(if rank == 0 {
-                            format!("Removed {}", name)
-                        } else {
-                            format!("Added {} rank {}", name, rank)
-                        })
-                    }),
-                    Command::Remove {
-                        perk: head,
-                        tail: mut perk,
-                    } => catch(|| {
-                        perk.insert(0, head);
-                        let perk = join_perk_def(&perk)?;
-                        build.remove_perk(&perk)?;
-                        let name = &perk.name[build.gender.unwrap_or_default()];
-                        Ok(format!("Removed {}", name))
-                    }),
-                    Command::Perk {
-                        perk: head,
-                        tail: mut perk,
-                    } => {
-                        perk.insert(0, head);
-                        match join_perk_def(&perk) {
-                            Ok(perk) => {
-                                clear_terminal();
-                                println!("{}", build);
-                                build.print_perk(&perk);
-                                println!();
-                                continue;
-                            }
-                            Err(e) => Err(e),
+                let res = match session.apply(command) {
+                    Outcome::Message(res) => res,
+                    Outcome::View(view) => {
+                        session.write_header(&mut frame, &watched);
+                        let _ = view.write(&mut frame, &session.build);
+                        frame.line("");
+                        screen.present(frame);
+                        continue;
+                    }
+                    Outcome::Exit => break,
+                };
+                session.write_header(&mut frame, &watched);
+                write_reloaded(&mut frame, reloaded);
+                match res {
+                    Ok(message) => {
+                        if !message.is_empty() {
+                            frame.line(format!("{}\n", message.bright_green()))
                         }
                     }
-                    Command::Special { stat } => {
-                        clear_terminal();
-                        println!("{}", build);
-                        if let Some(stat) = stat {
-                            build.print_special(stat);
+                    Err(e) => frame.line(format!("{}\n", e.to_string().bright_red())),
+                }
+            }
+            Err(e) => {
+                session.write_header(&mut frame, &watched);
+                write_reloaded(&mut frame, reloaded);
+                frame.line(parse_error_message(&e, &session.scripts));
+            }
+        }
+        screen.present(frame);
+    }
+}
+
+fn type_help() -> String {
+    format!("{}\n", "Type \"help\" for usage information".bright_blue())
+}
+
+/// What to show for a line that could not be parsed as a command, which includes
+/// asking for help
+fn parse_error_message(e: &clap::Error, scripts: &Scripts) -> String {
+    match e.kind() {
+        clap::ErrorKind::ValueValidation => format!("{e}\n"),
+        clap::ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => type_help(),
+        clap::ErrorKind::DisplayHelp => {
+            let message = e.to_string();
+            let mut text = format!(
+                "COMMANDS:{}",
+                message
+                    .split("SUBCOMMANDS:")
+                    .nth(1)
+                    .unwrap_or(&message)
+                    .replace(" fo4", "")
+            );
+            let commands = scripts.commands();
+            if !commands.is_empty() {
+                text.push_str(&format!(
+                    "\nSCRIPT COMMANDS:\n    {}\n",
+                    commands.join(", ")
+                ));
+            }
+            text
+        }
+        clap::ErrorKind::UnknownArgument => {
+            let text = e.to_string();
+            let command = text.split('\'').nth(1).unwrap_or(&text);
+            format!(
+                "{}\n\n{}",
+                format!("Unknown command: {command}").bright_red(),
+                type_help()
+            )
+        }
+        _ => {
+            let message = e.to_string();
+            let message = message.trim_end_matches("\n\nFor more information try --help\n");
+            format!("{}\n", message)
+        }
+    }
+}
+
+struct Session {
+    build: Build,
+    pareto_builds: Vec<Build>,
+    /// Where long-running commands report how far along they are
+    progress: Box<dyn Write + Send>,
+    tabs: Tabs,
+    /// A merge into the current build that is waiting on conflicts to be resolved
+    merge: Option<Merge>,
+    scripts: Scripts,
+    /// Copies of builds kept by name during the session
+    snapshots: BTreeMap<String, Build>,
+    history: History,
+    /// The entries of the last listing shown, for filtering with `/`
+    listing: Vec<Entry>,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Outcome {
+    Message(anyhow::Result<String>),
+    View(View),
+    Exit,
+}
+
+enum View {
+    Perk(PerkId),
+    Special(Option<SpecialStat>),
+    PerkNames(PerkKind, bool),
+    Path(PerkId, u8),
+    RankComparison(PerkId, u8, u8),
+    Matrix,
+    CatalogStats(bool),
+    Tree(Vec<SpecialStat>, bool),
+    Synergies,
+    Progress,
+    Card(CardKind, CardDef),
+    Cards(Option<SpecialStat>),
+    ClassicPerk(ClassicPerkDef),
+    Traits,
+}
+
+impl View {
+    /// The entries of a view that lists perks, to filter and pick from
+    fn listing(&self, build: &Build) -> Option<Vec<Entry>> {
+        let View::PerkNames(kind, _) = self else {
+            return None;
+        };
+        let gender = build.gender.unwrap_or_default();
+        Some(
+            catalog()
+                .perks
+                .iter()
+                .filter(|(id, _)| id.kind() == *kind)
+                .map(|(_, def)| {
+                    let name = format!("{}: {}", kind.qualifier(), def.name[gender].trim());
+                    Entry {
+                        label: def.name[gender].to_string(),
+                        show: format!("perk {}", name),
+                        add: Some(format!("add {}", name)),
+                    }
+                })
+                .collect(),
+        )
+    }
+    fn write(&self, f: &mut impl fmt::Write, build: &Build) -> fmt::Result {
+        match self {
+            View::Perk(id) => build.write_perk(f, *id),
+            View::Special(Some(stat)) => build.write_special(f, *stat),
+            View::Special(None) => {
+                for stat in build.special.keys() {
+                    build.write_special(f, *stat)?;
+                    writeln!(f)?;
+                }
+                Ok(())
+            }
+            View::PerkNames(kind, locations) => build.write_perk_names(f, *kind, *locations),
+            View::Path(id, rank) => build.write_path(f, *id, *rank),
+            View::RankComparison(id, from, to) => build.write_rank_comparison(f, *id, *from, *to),
+            View::Tree(stats, true) => build.write_tree_dot(f, stats),
+            View::Tree(stats, false) => {
+                for stat in stats {
+                    build.write_tree(f, *stat)?;
+                    writeln!(f)?;
+                }
+                Ok(())
+            }
+            View::Synergies => build.write_synergies(f),
+            View::Matrix => build.write_effect_matrix(f),
+            View::CatalogStats(list) => catalog().write_stats(f, *list),
+            View::Progress => build.write_progress(f),
+            View::Card(kind, def) => build.write_card(f, *kind, def),
+            View::Cards(Some(stat)) => build.write_cards(f, *stat),
+            View::ClassicPerk(def) => build.write_classic_perk(f, def),
+            View::Traits => build.write_traits(f),
+            View::Cards(None) => {
+                for stat in SpecialStat::ALL {
+                    build.write_cards(f, *stat)?;
+                    writeln!(f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Session {
+    fn apply(&mut self, command: Command) -> Outcome {
+        match command {
+            Command::Switch { new, build } => {
+                return Outcome::Message(self.switch(&build.join(" "), new))
+            }
+            Command::Close => return Outcome::Message(self.close()),
+            Command::Snapshot { name } => return Outcome::Message(self.snapshot(&name.join(" "))),
+            Command::Snapshots => return Outcome::Message(Ok(self.describe_snapshots())),
+            Command::Undo { steps } => return Outcome::Message(self.undo(steps)),
+            Command::Redo { steps } => return Outcome::Message(self.redo(steps)),
+            _ => {}
+        }
+        let before = self.build.clone();
+        let outcome = match command {
+            Command::Rollback { name } => Outcome::Message(self.rollback(&name.join(" "))),
+            command => self.apply_to_build(command),
+        };
+        self.record_history(before);
+        if let Outcome::View(view) = &outcome {
+            if let Some(listing) = view.listing(&self.build) {
+                self.listing = listing;
+            }
+        }
+        outcome
+    }
+    /// Filter the last listing shown to pick an entry from it
+    fn filter_listing(&self, query: &str, interactive: bool) -> anyhow::Result<Picked> {
+        if self.listing.is_empty() {
+            bail!("There is nothing listed to filter, list something first like \"magazines\"")
+        }
+        Ok(picker::pick(&self.listing, query, interactive)?)
+    }
+    /// Run a command that may change the build
+    fn apply_to_build(&mut self, command: Command) -> Outcome {
+        let command = match self.build.game() {
+            Game::Fallout4 => Ok(command),
+            Game::Fallout76 => self.apply_fo76(command),
+            Game::Fallout3 | Game::NewVegas => self.apply_classic(command),
+        };
+        let command = match command {
+            Ok(command) => command,
+            Err(outcome) => return outcome,
+        };
+        let Session {
+            build,
+            pareto_builds,
+            progress,
+            merge: pending,
+            scripts,
+            listing,
+            ..
+        } = self;
+        Outcome::Message(match command {
+            Command::Set { stat, value, keep } => {
+                let change = build.profile().set_stat(build, stat, value, keep);
+                scripts.after(build, change, "after_set", (stat.to_string(), value as i64))
+            }
+            Command::Add {
+                perk: head,
+                tail_and_rank: mut perk_and_rank,
+            } => {
+                perk_and_rank.insert(0, head);
+                let change = build.profile().add_perk(build, &perk_and_rank);
+                scripts.after(build, change, "after_add", (perk_and_rank.join(" "),))
+            }
+            Command::Remove {
+                perk: head,
+                tail: mut perk,
+                keep,
+            } => {
+                perk.insert(0, head);
+                let change = build.profile().remove_perk(build, &perk, keep);
+                scripts.after(build, change, "after_remove", (perk.join(" "),))
+            }
+            Command::Perk {
+                perk: head,
+                tail: mut perk,
+                compare,
+            } => {
+                perk.insert(0, head);
+                match (join_perk_id(&perk), compare.as_slice()) {
+                    (Ok(id), []) => return Outcome::View(View::Perk(id)),
+                    (Ok(id), &[from, to]) => match build.check_rank_comparison(id, from, to) {
+                        Ok(()) => return Outcome::View(View::RankComparison(id, from, to)),
+                        Err(e) => Err(e),
+                    },
+                    (Ok(_), _) => Err(anyhow!("--compare takes two ranks")),
+                    (Err(e), _) => Err(e),
+                }
+            }
+            Command::Special { stat } => return Outcome::View(View::Special(stat)),
+            Command::Bobbleheads { locations } => {
+                return Outcome::View(View::PerkNames(PerkKind::Bobblehead, locations))
+            }
+            Command::Magazines { locations } => {
+                return Outcome::View(View::PerkNames(PerkKind::Magazine, locations))
+            }
+            Command::Companions { locations } => {
+                return Outcome::View(View::PerkNames(PerkKind::Companion, locations))
+            }
+            Command::Factions => return Outcome::View(View::PerkNames(PerkKind::Faction, false)),
+            Command::OtherPerks => return Outcome::View(View::PerkNames(PerkKind::Other, false)),
+            Command::Path {
+                perk: head,
+                tail_and_rank: mut perk_and_rank,
+            } => {
+                perk_and_rank.insert(0, head);
+                match join_perk_id_and_rank(&perk_and_rank) {
+                    Ok((id, rank)) => {
+                        let rank = rank
+                            .unwrap_or_else(|| catalog().perks.get(&id).expect("Unknown perk").max_rank());
+                        return Outcome::View(View::Path(id, rank));
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            Command::Tree { stat, dot } => {
+                let stats = stat.map_or(SpecialStat::ALL.to_vec(), |stat| vec![stat]);
+                return Outcome::View(View::Tree(stats, dot));
+            }
+            Command::Synergies => return Outcome::View(View::Synergies),
+            Command::Matrix => return Outcome::View(View::Matrix),
+            Command::Reset { game } => {
+                let game = game.unwrap_or_else(|| build.game());
+                build.reset_for(game);
+                Ok(once(format!("Build reset for {}!", game))
+                    .chain(game.experimental_note())
+                    .collect::<Vec<String>>()
+                    .join("\n"))
+            }
+            Command::Loadout { .. } => Err(anyhow::anyhow!(
+                "Loadouts are only for Fallout 76 builds, try \"reset fo76\""
+            )),
+            Command::Tag { .. } | Command::Skill { .. } | Command::Trait { .. } => {
+                Err(anyhow::anyhow!(
+                    "Skills and traits are only for Fallout 3 and New Vegas builds, try \"reset fo3\" or \"reset nv\""
+                ))
+            }
+            Command::Name { name } => catch(|| {
+                if name.is_empty() {
+                    bail!("Name cannot be empty")
+                }
+                let name = name.into_iter().intersperse(" ".into()).collect();
+                let message = format!("Build name set to {:?}", name);
+                build.name = Some(name);
+                Ok(message)
+            }),
+            Command::Gender { gender } => {
+                let removed = build.set_gender(gender);
+                Ok(if removed.is_empty() {
+                    format!("Gender set to {:?}", gender)
+                } else {
+                    format!(
+                        "Gender set to {:?}, which removed {}",
+                        gender,
+                        build.describe_perks(&removed)
+                    )
+                })
+            }
+            Command::Book {
+                stat,
+                after_bobblehead,
+            } => catch(|| {
+                build.set_book(stat, after_bobblehead)?;
+                Ok(if let Some(stat) = stat {
+                    let order = if after_bobblehead {
+                        ", read after its bobblehead"
+                    } else {
+                        ""
+                    };
+                    format!("Special book set to {:?}{}", stat, order)
+                } else {
+                    "Special book reset".into()
+                })
+            }),
+            Command::Bobblehead { stat, state, keep } => {
+                let on = matches!(state, Switch::On);
+                let (verb, state) = if on { ("Adding", "on") } else { ("Removing", "off") };
+                with_removed(
+                    build,
+                    keep,
+                    format!("{} the {} bobblehead", verb, stat),
+                    format!("Turned the {} bobblehead {}", stat, state),
+                    |build| build.set_bobblehead(stat, on),
+                )
+            }
+            Command::Buff { stat, amount } => catch(|| {
+                build.set_buff(stat, amount.unwrap_or(0))?;
+                Ok(match amount {
+                    Some(amount) if amount > 0 => format!("{:?} buffed by {}", stat, amount),
+                    _ => format!("{:?} buff removed", stat),
+                })
+            }),
+            Command::Difficulty { difficulty } => {
+                build.difficulty = Some(difficulty);
+                build.invalidate_stats();
+                Ok(format!("Difficulty set to {:?}", difficulty))
+            }
+            Command::LevelLimit { level } => {
+                build.level_limit = level;
+                Ok(if let Some(level) = level {
+                    format!("Level limit set to {}", level)
+                } else {
+                    "Removed level limit".into()
+                })
+            }
+            Command::Optimize {
+                weights,
+                search,
+                time,
+                steps,
+                seed,
+                level,
+                pareto,
+                threads,
+            } => catch(|| {
+                let level = level
+                    .or(build.level_limit)
+                    .unwrap_or_else(|| build.required_level());
+                let optimizer = Optimizer::new(build, WeightedScorer::new(weights)?, level)?;
+                let budget = match steps {
+                    Some(steps) => Budget::Steps(steps),
+                    None => Budget::seconds(time)?,
+                };
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads.unwrap_or(0))
+                    .build()?;
+                if let Some(count) = pareto {
+                    *pareto_builds =
+                        pool.install(|| optimizer.pareto(build, count, search, budget, seed));
+                    let mut message = String::from("Non-dominated builds:");
+                    for (i, b) in pareto_builds.iter().enumerate() {
+                        message.push_str(&format!("\n  {}: {}", i + 1, optimizer.summary(b)));
+                    }
+                    message.push_str("\nType \"accept <n>\" to use one of them");
+                    return Ok(message);
+                }
+                let before = optimizer.score(build);
+                let optimized = pool.install(|| match search {
+                    Search::Greedy => optimizer.greedy(build),
+                    Search::Deep => optimizer.anneal(build, budget, seed, progress),
+                });
+                let after = optimizer.score(&optimized);
+                *build = optimized;
+                Ok(format!(
+                    "Optimized for level {}: score {:.3} -> {:.3}",
+                    level, before, after
+                ))
+            }),
+            Command::Value {
+                weights,
+                level,
+                count,
+            } => {
+                let level = level.unwrap_or_else(|| build.required_level().saturating_add(1));
+                Ok(build.describe_values(&weights, level, count))
+            }
+            Command::Accept { index } => {
+                catch(
+                    || match index.checked_sub(1).and_then(|i| pareto_builds.get(i)) {
+                        Some(accepted) => {
+                            *build = accepted.clone();
+                            Ok(format!("Accepted build {}", index))
+                        }
+                        None => bail!("There is no optimizer result {}", index),
+                    },
+                )
+            }
+            Command::Sheet => {
+                build.show_sheet = !build.show_sheet;
+                Ok(String::new())
+            }
+            Command::Watch { stat } => catch(|| {
+                if stat.is_empty() {
+                    return Ok(format!(
+                        "Watching {}. Stats that can be watched are {}, and S.P.E.C.I.A.L. stats",
+                        if build.watches.is_empty() {
+                            "nothing".into()
                         } else {
-                            for stat in build.special.keys() {
-                                build.print_special(*stat);
-                                println!();
-                            }
+                            build.watches.iter().join(", ")
+                        },
+                        Watch::ALL.iter().join(", ")
+                    ));
+                }
+                let watch: Watch = stat.join(" ").parse()?;
+                if build.watches.contains(&watch) {
+                    bail!("{} is already watched", watch)
+                }
+                build.watches.push(watch);
+                Ok(format!("Watching {}", watch))
+            }),
+            Command::Unwatch { stat } => catch(|| {
+                if stat.is_empty() {
+                    build.watches.clear();
+                    return Ok("Stopped watching every stat".into());
+                }
+                let watch: Watch = stat.join(" ").parse()?;
+                let before = build.watches.len();
+                build.watches.retain(|other| *other != watch);
+                if build.watches.len() == before {
+                    bail!("{} is not watched", watch)
+                }
+                Ok(format!("Stopped watching {}", watch))
+            }),
+            Command::Why {
+                topic: WhyTopic::Level,
+            } => Ok(build.describe_level_drivers()),
+            Command::Calc { formula } => catch(|| {
+                if formula.is_empty() {
+                    bail!("Give a formula, like \"calc base_health + 20*level\"")
+                }
+                let formula = formula.join(" ");
+                let value = build.evaluate(&formula)?;
+                Ok(format!("{} = {}", formula, format_value(value)))
+            }),
+            Command::Define { name, formula } => catch(|| {
+                let name = match name {
+                    Some(name) => name.to_lowercase(),
+                    None => {
+                        let stats = &config().stats;
+                        if stats.is_empty() {
+                            return Ok("No stats defined, try \"define stealth_score agility*2 + sneak_rank*3\"".into());
                         }
-                        println!();
-                        continue;
+                        return Ok(stats
+                            .iter()
+                            .map(|(name, formula)| {
+                                let value = match build.evaluate(formula) {
+                                    Ok(value) => format_value(value).bright_white(),
+                                    Err(e) => e.to_string().bright_red(),
+                                };
+                                format!("  {} = {}  ({})", name, formula, value)
+                            })
+                            .join("\n"));
                     }
-                    Command::Bobbleheads => {
-                        clear_terminal();
-                        println!("{}", build);
-                        build.print_perk_names(PerkKind::Bobblehead);
-                        println!();
-                        continue;
+                };
+                if formula.is_empty() {
+                    Config::set_stat(&name, None)?;
+                    return Ok(format!("Removed {}", name));
+                }
+                let formula = formula.join(" ");
+                Config::set_stat(&name, Some(formula.clone()))?;
+                Ok(match build.evaluate(&formula) {
+                    Ok(value) => format!("Defined {} = {}, now {}", name, formula, format_value(value)),
+                    Err(e) => format!("Defined {} = {}, but {}", name, formula, e),
+                })
+            }),
+            Command::Gear { action } => catch(|| {
+                let name = |name: Vec<String>| {
+                    if name.is_empty() {
+                        bail!("You must name the item")
                     }
-                    Command::Magazines => {
-                        clear_terminal();
-                        println!("{}", build);
-                        build.print_perk_names(PerkKind::Magazine);
-                        println!();
-                        continue;
+                    Ok(name.join(" "))
+                };
+                Ok(match action {
+                    None => build.describe_gear(),
+                    Some(GearAction::Add {
+                        weight,
+                        name: words,
+                        count,
+                        survival,
+                        slot,
+                        legendary,
+                    }) => {
+                        let name = name(words)?;
+                        let item = GearItem {
+                            weight,
+                            count,
+                            survival_only: survival,
+                            slot,
+                            legendary,
+                        };
+                        build.add_gear(&name, item)?;
+                        format!("Added {} to the loadout. {}", name, build.carry_summary())
                     }
-                    Command::Companions => {
-                        clear_terminal();
-                        println!("{}", build);
-                        build.print_perk_names(PerkKind::Companion);
-                        println!();
-                        continue;
+                    Some(GearAction::Remove { name: words }) => {
+                        let name = build.remove_gear(&name(words)?)?;
+                        format!(
+                            "Removed {} from the loadout. {}",
+                            name,
+                            build.carry_summary()
+                        )
                     }
-                    Command::Factions => {
-                        clear_terminal();
-                        println!("{}", build);
-                        build.print_perk_names(PerkKind::Faction);
-                        println!();
-                        continue;
+                    Some(GearAction::Clear) => {
+                        build.gear.clear();
+                        build.invalidate_stats();
+                        "Cleared the loadout".into()
                     }
-                    Command::OtherPerks => {
-                        clear_terminal();
-                        println!("{}", build);
-                        build.print_perk_names(PerkKind::Other);
-                        println!();
-                        continue;
+                    Some(GearAction::Legendaries) => catalog()
+                        .legendaries
+                        .iter()
+                        .map(|(name, def)| format!("{}: {}", name.bright_yellow(), def.desc))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                })
+            }),
+            Command::Wishlist { action } => catch(|| {
+                Ok(match action {
+                    None => build.describe_wishlist(),
+                    Some(WishlistAction::Add { at, perk }) => {
+                        let (id, rank) = join_perk_id_and_rank(&perk)?;
+                        let wish = build.wish(id, rank, at)?;
+                        format!(
+                            "Wished for {} rank {}",
+                            build.perk_name(&wish.perk),
+                            wish.rank
+                        )
+                    }
+                    Some(WishlistAction::Remove { perk }) => {
+                        let id = join_perk_id(&perk)?;
+                        build.unwish(id)?;
+                        format!("Took {} off the wishlist", build.perk_name(&id))
                     }
-                    Command::Reset => {
-                        build.reset();
-                        Ok("Build reset!".into())
+                    Some(WishlistAction::Clear) => {
+                        build.wishlist.clear();
+                        "Cleared the wishlist".into()
                     }
-                    Command::Name { name } => catch(|| {
-                        if name.is_empty() {
-                            bail!("Name cannot be empty")
+                    Some(WishlistAction::Apply) => {
+                        let taken = build.apply_wishlist();
+                        format!(
+                            "Took {} {} from the wishlist, the build now needs level {}",
+                            taken,
+                            if taken == 1 { "wish" } else { "wishes" },
+                            build.required_level()
+                        )
+                    }
+                })
+            }),
+            Command::Plan { action } => catch(|| {
+                Ok(match action {
+                    None | Some(PlanAction::Show) => build.describe_plan(),
+                    Some(PlanAction::Set { level, what }) => {
+                        build.set_plan_step(level, &what)?;
+                        build.describe_plan()
+                    }
+                    Some(PlanAction::Clear { level: Some(level) }) => {
+                        if build.plan.remove(&level).is_none() {
+                            bail!("Nothing is planned for level {}", level)
                         }
-                        let name = name.into_iter().intersperse(" ".into()).collect();
-                        let message = format!("Build name set to {:?}", name);
-                        build.name = Some(name);
-                        Ok(message)
-                    }),
-                    Command::Gender { gender } => {
-                        build.gender = Some(gender);
-                        Ok(format!("Gender set to {:?}", gender))
-                    }
-                    Command::Book { stat } => catch(|| {
-                        let message = if let Some(stat) = stat {
-                            if build.special[&stat] == 10 {
-                                bail!("The S.P.E.C.I.A.L. book cannot be used on a maxed-out stat");
-                            }
-                            format!("Special book set to {:?}", stat)
+                        format!("Cleared level {} from the plan", level)
+                    }
+                    Some(PlanAction::Clear { level: None }) => {
+                        build.plan.clear();
+                        "Cleared the plan".into()
+                    }
+                    Some(PlanAction::Auto) => {
+                        build.auto_plan();
+                        build.describe_plan()
+                    }
+                    Some(PlanAction::Milestone { level, text }) => {
+                        build.set_milestone(level, &text)?
+                    }
+                })
+            }),
+            Command::Preset { action } => catch(|| {
+                let storage = Folder(Build::dir());
+                let name = |name: Vec<String>| {
+                    if name.is_empty() {
+                        bail!("You must name the preset")
+                    }
+                    Ok(name.join(" "))
+                };
+                Ok(match action {
+                    None => {
+                        let presets = presets(&storage)?;
+                        if presets.is_empty() {
+                            "No presets saved, try \"preset save <NAME>\"".into()
                         } else {
-                            "Special book reset".into()
-                        };
-                        build.special_book = stat;
-                        Ok(message)
-                    }),
-                    Command::Difficulty { difficulty } => {
-                        build.difficulty = Some(difficulty);
-                        Ok(format!("Difficulty set to {:?}", difficulty))
-                    }
-                    Command::LevelLimit { level } => {
-                        build.level_limit = level;
-                        Ok(if let Some(level) = level {
-                            format!("Level limit set to {}", level)
+                            once("Presets:".into())
+                                .chain(presets.iter().map(|(name, preset)| {
+                                    format!("  {}: {}", name, describe_preset(preset))
+                                }))
+                                .collect::<Vec<String>>()
+                                .join("\n")
+                        }
+                    }
+                    Some(PresetAction::Save { name: words }) => {
+                        let name = name(words)?;
+                        if save_preset(&storage, &name, build)? {
+                            format!("Replaced preset {:?}", name)
                         } else {
-                            "Removed level limit".into()
-                        })
+                            format!("Saved preset {:?}", name)
+                        }
                     }
-                    Command::Sheet => {
-                        build.show_sheet = !build.show_sheet;
-                        Ok(String::new())
+                    Some(PresetAction::Apply { name: words, keep }) => {
+                        let name = name(words)?;
+                        let preset = find_preset(&storage, &name)?;
+                        with_removed(
+                            build,
+                            keep,
+                            format!("Applying preset {:?}", name),
+                            format!("Applied preset {:?}", name),
+                            |build| build.apply_preset(&preset),
+                        )?
                     }
-                    Command::Save { name } => catch(|| {
-                        if !name.is_empty() {
-                            build.name = Some(name.into_iter().intersperse(" ".into()).collect());
+                    Some(PresetAction::Delete { name: words }) => {
+                        let name = name(words)?;
+                        delete_preset(&storage, &name)?;
+                        format!("Deleted preset {:?}", name)
+                    }
+                })
+            }),
+            Command::Copy { what } => catch(|| match what {
+                CopyAction::Special { build: words, keep } => {
+                    let other = load_other(&words)?;
+                    let name = other.name.clone().unwrap_or_else(|| words.join(" "));
+                    with_removed(
+                        build,
+                        keep,
+                        format!("Copying the S.P.E.C.I.A.L. allocation of {}", name),
+                        format!("Copied the S.P.E.C.I.A.L. allocation of {}", name),
+                        |build| build.copy_special(&other),
+                    )
+                }
+                CopyAction::Perks { build: words } => {
+                    let (other, kind) = match load_other(&words) {
+                        Ok(other) => (other, None),
+                        Err(e) => match words.split_last() {
+                            Some((last, rest)) if !rest.is_empty() => match last.parse() {
+                                Ok(kind) => (load_other(rest)?, Some(kind)),
+                                Err(_) => return Err(e),
+                            },
+                            _ => return Err(e),
+                        },
+                    };
+                    let name = other.name.clone().unwrap_or_else(|| words.join(" "));
+                    let (copied, conflicts) = build.copy_perks(&other, kind)?;
+                    let perks = if copied == 1 { "perk" } else { "perks" };
+                    Ok(once(format!("Copied {} {} from {}", copied, perks, name))
+                        .chain(conflicts.into_iter().map(|c| format!("  {}", c)))
+                        .collect::<Vec<String>>()
+                        .join("\n"))
+                }
+            }),
+            Command::Merge { build: words } => catch(|| {
+                let merge = build.start_merge(load_other(&words)?)?;
+                continue_merge(build, pending, merge)
+            }),
+            Command::Resolve { side } => catch(|| {
+                let Some(mut merge) = pending.take() else {
+                    bail!("There is no merge to resolve, try \"merge <BUILD>\"")
+                };
+                match side {
+                    Side::Mine => merge.resolve(false),
+                    Side::Theirs => merge.resolve(true),
+                    Side::Cancel => return Ok(format!("Cancelled merging {}", merge.from)),
+                }
+                continue_merge(build, pending, merge)
+            }),
+            Command::Verify => {
+                let problems = build.verify_points();
+                if problems.is_empty() {
+                    Ok(format!(
+                        "Perk points add up at every level through level {}",
+                        build.required_level()
+                    ))
+                } else {
+                    Err(anyhow!("{}", problems.join("\n")))
+                }
+            }
+            Command::Save { name, message } => catch(|| {
+                let mut saving = build.clone();
+                if !name.is_empty() {
+                    saving.name = Some(name.into_iter().intersperse(" ".into()).collect());
+                }
+                let message = message.join(" ");
+                let message = message.trim_matches('"');
+                let lines = scripts.before_save(&mut saving)?;
+                *build = saving;
+                build.save((!message.is_empty()).then_some(message))?;
+                Ok(once("Build saved!".into()).chain(lines).join("\n"))
+            }),
+            Command::Log => Ok(build.describe_changelog()),
+            Command::Load { partial, path } => catch(|| {
+                let path: String = path
+                    .iter()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .intersperse(" ".into())
+                    .collect();
+                let mut dropped = Vec::new();
+                if partial {
+                    (*build, dropped) = Build::load_partial(&path)?;
+                } else {
+                    *build = Build::load(&path).map_err(|e| {
+                        anyhow!(
+                            "{}\nTry \"load --partial {}\" to load the parts that are valid",
+                            e,
+                            path
+                        )
+                    })?;
+                }
+                Ok(once("Build loaded!".into())
+                    .chain(dropped.into_iter().map(|d| format!("  Dropped: {}", d)))
+                    .chain(build.game().experimental_note())
+                    .chain(build.version_skew())
+                    .chain(build.catalog_changes())
+                    .collect::<Vec<String>>()
+                    .join("\n"))
+            }),
+            Command::Check { target } => {
+                if target.is_empty() {
+                    return Outcome::View(View::Progress);
+                }
+                catch(|| {
+                    if let [level] = target.as_slice() {
+                        if let Ok(level) = level.parse::<u8>() {
+                            let checked = build.check_level(level);
+                            return Ok(format!(
+                                "Reached level {}, checked off {} pick{}",
+                                level,
+                                checked,
+                                if checked == 1 { "" } else { "s" }
+                            ));
                         }
-                        build.save()?;
-                        Ok("Build saved!".into())
-                    }),
-                    Command::Load { path } => catch(|| {
-                        let path: String = path
-                            .iter()
-                            .map(|path| path.to_string_lossy().into_owned())
-                            .intersperse(" ".into())
-                            .collect();
-                        build = Build::load(path)?;
-                        Ok("Build loaded!".into())
-                    }),
-                    Command::Builds => catch(|| {
-                        open::that(Build::dir())?;
-                        Ok(String::new())
-                    }),
-                    Command::Exit => break,
+                    }
+                    let (id, rank) = join_perk_id_and_rank(&target)?;
+                    let rank = build.check_perk(id, rank)?;
+                    let name = build.perk_name(&id);
+                    Ok(if rank == 0 {
+                        format!("Unchecked {}", name)
+                    } else {
+                        format!("Checked off {} rank {}", name, rank)
+                    })
+                })
+            }
+            Command::Level { level, apply } => catch(|| {
+                if apply {
+                    let checked = build.check_level(level);
+                    return Ok(format!(
+                        "Reached level {}, checked off {} pick{}",
+                        level,
+                        checked,
+                        if checked == 1 { "" } else { "s" }
+                    ));
+                }
+                let reached = build.current_level.unwrap_or(0);
+                build.current_level = Some(level);
+                let due = build.due_picks(level);
+                let mut message = if due.is_empty() {
+                    format!("Level {}, no picks are due", level)
+                } else {
+                    format!(
+                        "Level {}, due: {}\nUse \"level {} --apply\" to check them off",
+                        level,
+                        build.describe_perks(&due),
+                        level
+                    )
                 };
-                clear_terminal();
-                println!("{}", build);
-                match res {
-                    Ok(message) => {
-                        if !message.is_empty() {
-                            println!("{}\n", message.bright_green())
+                for (at, text) in build.milestones_between(reached, level) {
+                    message.push_str(&format!("\nMilestone at level {}: {}", at, text));
+                }
+                Ok(message)
+            }),
+            Command::Pickup { level, item } => catch(|| {
+                let item = item.join(" ").to_lowercase();
+                if item == "book" {
+                    build.set_book_level(level)?;
+                    return Ok(format!("The book is read at level {}", level.max(1)));
+                }
+                let stat: SpecialStat = item.replace("bobblehead", "").trim().parse().map_err(|e| anyhow!("{}", e))?;
+                build.set_bobblehead_level(stat, level)?;
+                Ok(format!(
+                    "The {} bobblehead is collected at level {}",
+                    stat,
+                    level.max(1)
+                ))
+            }),
+            Command::Collect {
+                magazine: head,
+                tail_and_issue: mut magazine_and_issue,
+            } => catch(|| {
+                magazine_and_issue.insert(0, head);
+                let (magazine, issue) = join_perk_id_and_rank(&magazine_and_issue)?;
+                let (issue, collected) = build.collect(magazine, issue)?;
+                let name = build.perk_name(&magazine);
+                Ok(if collected {
+                    format!("Collected {} issue {}", name, issue)
+                } else {
+                    format!("Uncollected {} issue {}", name, issue)
+                })
+            }),
+            Command::Export {
+                format: ExportFormat::Archive,
+                path,
+            } => catch(|| {
+                let path: PathBuf = if path.is_empty() {
+                    "Fallout4Builds.zip".into()
+                } else {
+                    path.iter()
+                        .map(|path| path.to_string_lossy().into_owned())
+                        .intersperse(" ".into())
+                        .collect::<String>()
+                        .into()
+                };
+                let report = archive::export(&path)?;
+                Ok(format!(
+                    "Archived {} file(s){} to {}",
+                    report.files,
+                    if report.config { " and the config" } else { "" },
+                    path.to_string_lossy()
+                ))
+            }),
+            Command::Export { format, path } => catch(|| {
+                let path = if path.is_empty() {
+                    build.path().with_extension(format.extension())
+                } else {
+                    path.iter()
+                        .map(|path| path.to_string_lossy().into_owned())
+                        .intersperse(" ".into())
+                        .collect::<String>()
+                        .into()
+                };
+                fs::create_dir_all(Build::dir())?;
+                fs::write(&path, build.export(format)?)?;
+                Ok(format!("Exported to {}", path.to_string_lossy()))
+            }),
+            Command::Share { sign: false } => {
+                catch(|| Ok(format!("Share code: {}", build.share_code()?)))
+            }
+            Command::Share { sign: true } => catch(|| {
+                Ok(format!(
+                    "Share code signed by key {}: {}",
+                    Build::share_key_fingerprint()?,
+                    build.signed_share_code()?
+                ))
+            }),
+            Command::Trust { name, code } => catch(|| match (name, code) {
+                (None, _) => {
+                    let keys = share::trusted_share_keys()?;
+                    if keys.is_empty() {
+                        return Ok("No keys are trusted, try \"trust <NAME> <SIGNED CODE>\"".into());
+                    }
+                    Ok(keys
+                        .iter()
+                        .map(|(name, fingerprint)| format!("  {} ({})", name, fingerprint))
+                        .join("\n"))
+                }
+                (Some(_), None) => bail!("Give a share code signed by the key to trust"),
+                (Some(name), Some(code)) => {
+                    let fingerprint = share::trust_share_key(&name, &code)?;
+                    Ok(format!("Trusted key {} as {}", fingerprint, name))
+                }
+            }),
+            Command::Untrust { name } => catch(|| {
+                share::untrust_share_key(&name)?;
+                Ok(format!("Stopped trusting {}", name))
+            }),
+            Command::Import {
+                format,
+                overwrite,
+                source,
+            } => catch(|| {
+                if source.first().map(String::as_str) == Some("archive") {
+                    let path: String = source[1..].iter().cloned().intersperse(" ".into()).collect();
+                    if path.is_empty() {
+                        bail!("You must specify the archive to import")
+                    }
+                    let report = archive::import(Path::new(&path), overwrite)?;
+                    let mut message = format!(
+                        "Unpacked {} file(s){} from {}",
+                        report.files,
+                        if report.config { " and the config" } else { "" },
+                        path
+                    );
+                    if !report.kept.is_empty() {
+                        message.push_str(&format!(
+                            "\nKept {} existing build(s) that differ from the archive: {}. \
+                            Use \"import --overwrite archive <FILE>\" to replace them.",
+                            report.kept.len(),
+                            report.kept.join(", ")
+                        ));
+                    }
+                    if !report.skipped.is_empty() {
+                        message.push_str(&format!(
+                            "\nSkipped {} file(s) that are not builds: {}",
+                            report.skipped.len(),
+                            report.skipped.join(", ")
+                        ));
+                    }
+                    return Ok(message);
+                }
+                // The format may also lead the source, as in "import schedule picks.csv"
+                let (format, source) = match source.split_first() {
+                    Some((first, rest)) if format.is_none() && !rest.is_empty() => {
+                        match first.parse::<ImportFormat>() {
+                            Ok(format) => (Some(format), rest.to_vec()),
+                            Err(_) => (None, source),
                         }
                     }
-                    Err(e) => println!("{}\n", e.to_string().bright_red()),
+                    _ => (format, source),
+                };
+                let source: String = source.into_iter().intersperse(" ".into()).collect();
+                if source.is_empty() {
+                    bail!("You must specify a file or link to import")
+                }
+                let imported = import(format, &source)?;
+                *build = imported.build;
+                Ok(once("Build imported!".into())
+                    .chain(imported.notes)
+                    .collect::<Vec<String>>()
+                    .join("\n"))
+            }),
+            Command::Perks { action: None } => {
+                let catalog = catalog();
+                let mut message = match &catalog.source {
+                    Some(path) => format!("Using perk data from {}", path.to_string_lossy()),
+                    None => format!(
+                        "Using built-in perk data, place a perks.yaml in {} to override it",
+                        Build::dir().to_string_lossy()
+                    ),
+                };
+                if catalog.languages.is_empty() {
+                    message.push_str(&format!(
+                        "\nPlace language files in {} to match and display perks in other languages",
+                        Catalog::language_dir().to_string_lossy()
+                    ));
+                } else {
+                    message.push_str("\nLanguages:");
+                    for (code, name) in &catalog.languages {
+                        let displayed = catalog.language.as_ref() == Some(code);
+                        message.push_str(&format!(
+                            "\n  {} ({}){}",
+                            code,
+                            name,
+                            if displayed { ", displayed" } else { "" }
+                        ));
+                    }
                 }
+                Ok(message)
             }
-            Err(e) => {
-                clear_terminal();
-                println!("{}", build);
-                match e.kind() {
-                    clap::ErrorKind::ValueValidation => println!("{e}\n"),
-                    clap::ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => type_help(),
-                    clap::ErrorKind::DisplayHelp => {
-                        let message = e.to_string();
-                        println!(
-                            "COMMANDS:{}",
-                            message
-                                .split("SUBCOMMANDS:")
-                                .nth(1)
-                                .unwrap_or(&message)
-                                .replace(" fo4", "")
-                        );
-                    }
-                    clap::ErrorKind::UnknownArgument => {
-                        let text = e.to_string();
-                        let command = text.split('\'').nth(1).unwrap_or(&text);
-                        println!("{}\n", format!("Unknown command: {command}").bright_red());
-                        type_help();
-                    }
-                    _ => {
-                        let message = e.to_string();
-                        let message =
-                            message.trim_end_matches("\n\nFor more information try --help\n");
-                        println!("{}\n", message)
+            Command::Perks {
+                action: Some(PerksAction::Reload),
+            } => reload_perks(build),
+            Command::Perks {
+                action: Some(PerksAction::Keys),
+            } => {
+                let catalog = catalog();
+                Ok(catalog
+                    .perks
+                    .iter()
+                    .filter_map(|(id, def)| {
+                        Some(format!(
+                            "{}: {}",
+                            catalog.key(id)?,
+                            def.name[build.gender.unwrap_or_default()]
+                        ))
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            Command::Perks {
+                action: Some(PerksAction::Stats { list }),
+            } => return Outcome::View(View::CatalogStats(list)),
+            Command::Config { key, value } => catch(|| {
+                let key = match key {
+                    Some(key) => key,
+                    None => {
+                        return Ok(format!(
+                            "Config file: {}\nbuilds-dir: {}\n{}",
+                            Config::path().to_string_lossy(),
+                            Build::dir().to_string_lossy(),
+                            config().describe()
+                        ))
                     }
+                };
+                let value: String = value.into_iter().intersperse(" ".into()).collect();
+                let value = (!value.is_empty()).then_some(value);
+                let mut message = match (&value, key) {
+                    (Some(_), ConfigKey::BackupToken) => format!("Set {:?}", key),
+                    (Some(value), _) => format!("Set {:?} to {}", key, value),
+                    (None, _) => format!("Reset {:?}", key),
+                };
+                if key == ConfigKey::Language {
+                    if let Some(code) = &value {
+                        let path = Catalog::language_dir().join(code).with_extension("yaml");
+                        if !path.exists() {
+                            bail!("No language file at {}", path.to_string_lossy());
+                        }
+                    }
+                    Config::set(key, value)?;
+                    message.push('\n');
+                    message.push_str(&reload_perks(build)?);
+                    return Ok(message);
+                }
+                Config::set(key, value)?;
+                Ok(message)
+            }),
+            Command::Backup { action } => catch(|| {
+                let report = match action {
+                    BackupAction::Push => backup::push()?,
+                    BackupAction::Pull => backup::pull()?,
+                };
+                Ok(format!(
+                    "{} {} file(s)",
+                    match action {
+                        BackupAction::Push => "Uploaded",
+                        BackupAction::Pull => "Downloaded",
+                    },
+                    report
+                ))
+            }),
+            Command::Sync { dir } => catch(|| {
+                let dir: String = dir
+                    .iter()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .intersperse(" ".into())
+                    .collect();
+                let dir = if dir.is_empty() {
+                    match config().sync_dir {
+                        Some(dir) => dir,
+                        None => bail!(
+                            "No sync folder given. Try \"sync <FOLDER>\" or \"config sync-dir <FOLDER>\"."
+                        ),
+                    }
+                } else {
+                    dir.into()
+                };
+                let report = sync::sync(&dir)?;
+                let mut message = format!(
+                    "Synced with {}: {} pushed, {} pulled",
+                    dir.to_string_lossy(),
+                    report.pushed.len(),
+                    report.pulled.len()
+                );
+                if !report.conflicts.is_empty() {
+                    message.push_str("\nChanged in both places, left alone:");
+                    for name in report.conflicts {
+                        message.push_str(&format!("\n  {}", name));
+                    }
+                }
+                Ok(message)
+            }),
+            Command::Stats {
+                action: StatsAction::Builds,
+            } => usage::scan(&Folder(Build::dir())).map(|usage| usage.to_string()),
+            Command::Find { query } => catch(|| {
+                let conditions = search::parse_conditions(&query)?;
+                let found = search::find(&Folder(Build::dir()), &conditions)?;
+                *listing = found
+                    .builds
+                    .iter()
+                    .map(|(file, other)| {
+                        let name = file.trim_end_matches(".yaml");
+                        Entry {
+                            label: other.name.as_deref().unwrap_or(name).into(),
+                            show: format!("switch {}", name),
+                            add: None,
+                        }
+                    })
+                    .collect();
+                let mut lines = vec![match found.builds.len() {
+                    0 => "No saved builds match".into(),
+                    1 => "1 saved build matches".into(),
+                    n => format!("{} saved builds match", n),
+                }];
+                for (file, other) in &found.builds {
+                    lines.push(format!(
+                        "  {}  {}",
+                        other
+                            .name
+                            .as_deref()
+                            .unwrap_or(file.trim_end_matches(".yaml"))
+                            .bright_white(),
+                        Build::dir().join(file).display()
+                    ));
+                }
+                for (file, e) in &found.unreadable {
+                    let reason = e.lines().next().unwrap_or_default();
+                    lines.push(format!("Skipped {}: {}", file, reason));
                 }
+                Ok(lines.join("\n"))
+            }),
+            Command::Builds => catch(|| {
+                open::that(Build::dir())?;
+                Ok(String::new())
+            }),
+            Command::Scripts => {
+                *scripts = Scripts::load();
+                Ok(scripts.describe())
             }
-        }
+            Command::Script(words) => scripts.run_command(build, &words),
+            Command::Exit => return Outcome::Exit,
+            Command::Switch { .. }
+            | Command::Close
+            | Command::Snapshot { .. }
+            | Command::Rollback { .. }
+            | Command::Snapshots
+            | Command::Undo { .. }
+            | Command::Redo { .. } => unreachable!(),
+        })
+    }
+    /// Handle the commands that work differently for Fallout 76 builds,
+    /// handing back the ones that work the same
+    #[allow(clippy::result_large_err)]
+    fn apply_fo76(&mut self, command: Command) -> Result<Command, Outcome> {
+        let build = &mut self.build;
+        Err(Outcome::Message(match command {
+            Command::Perk { compare, .. } if !compare.is_empty() => {
+                Err(anyhow!("--compare only works with Fallout 4 perks"))
+            }
+            Command::Perk {
+                perk: head,
+                tail: mut perk,
+                ..
+            } => {
+                perk.insert(0, head);
+                match FO76.find(&perk.join(" ")) {
+                    Ok((kind, def)) => return Err(Outcome::View(View::Card(kind, def.clone()))),
+                    Err(e) => Err(e),
+                }
+            }
+            Command::Special { stat } => return Err(Outcome::View(View::Cards(stat))),
+            Command::Loadout { action: None } => {
+                let fo76 = build.fo76.as_ref().expect("Not a Fallout 76 build");
+                Ok(if fo76.loadouts.is_empty() {
+                    "No loadouts saved, try \"loadout save <NAME>\"".into()
+                } else {
+                    once("Loadouts:".into())
+                        .chain(fo76.loadouts.keys().map(|name| format!("  {}", name)))
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                })
+            }
+            Command::Loadout {
+                action: Some(action),
+            } => catch(|| {
+                let name = |name: Vec<String>| {
+                    if name.is_empty() {
+                        bail!("You must name the loadout")
+                    }
+                    Ok(name.into_iter().intersperse(" ".into()).collect::<String>())
+                };
+                Ok(match action {
+                    LoadoutAction::Save { name: words } => {
+                        let name = name(words)?;
+                        build.save_loadout(&name)?;
+                        format!("Saved loadout {:?}", name)
+                    }
+                    LoadoutAction::Load { name: words } => {
+                        let name = name(words)?;
+                        build.load_loadout(&name)?;
+                        format!("Loaded loadout {:?}", name)
+                    }
+                    LoadoutAction::Delete { name: words } => {
+                        let name = name(words)?;
+                        build.delete_loadout(&name)?;
+                        format!("Deleted loadout {:?}", name)
+                    }
+                })
+            }),
+            command if command.fo4_only() => {
+                Err(anyhow::anyhow!("That command is only for Fallout 4 builds"))
+            }
+            command => return Ok(command),
+        }))
+    }
+    /// Handle the commands that work differently for Fallout 3 and New Vegas builds,
+    /// handing back the ones that work the same
+    #[allow(clippy::result_large_err)]
+    fn apply_classic(&mut self, command: Command) -> Result<Command, Outcome> {
+        let build = &mut self.build;
+        Err(Outcome::Message(match command {
+            Command::Perk { compare, .. } if !compare.is_empty() => {
+                Err(anyhow!("--compare only works with Fallout 4 perks"))
+            }
+            Command::Perk {
+                perk: head,
+                tail: mut perk,
+                ..
+            } => {
+                perk.insert(0, head);
+                let rules = build.classic.as_ref().expect("Not a classic build").game.rules();
+                match rules.find_perk(&perk.join(" ")) {
+                    Ok(def) => return Err(Outcome::View(View::ClassicPerk(def.clone()))),
+                    Err(e) => Err(e),
+                }
+            }
+            Command::Tag { skill } => catch(|| {
+                let (skill, tagged) = build.toggle_tag(&skill.join(" "))?;
+                Ok(if tagged {
+                    format!("Tagged {}", skill)
+                } else {
+                    format!("Untagged {}", skill)
+                })
+            }),
+            Command::Skill {
+                skill: head,
+                tail_and_points: mut skill_and_points,
+            } => catch(|| {
+                skill_and_points.insert(0, head);
+                let points = match skill_and_points.pop().map(|last| last.parse::<u8>()) {
+                    Some(Ok(points)) if !skill_and_points.is_empty() => points,
+                    _ => bail!("Give the skill and how many level-up points to put in it"),
+                };
+                let skill = build.invest_skill(&skill_and_points.join(" "), points)?;
+                Ok(format!("Put {} points into {}", points, skill))
+            }),
+            Command::Trait { name } if name.is_empty() => return Err(Outcome::View(View::Traits)),
+            Command::Trait { name } => catch(|| {
+                let (name, taken) = build.toggle_trait(&name.join(" "))?;
+                Ok(if taken {
+                    format!("Took {}", name)
+                } else {
+                    format!("Dropped {}", name)
+                })
+            }),
+            Command::Special { .. } => Err(anyhow::anyhow!(
+                "Fallout 3 and New Vegas perks are not grouped by S.P.E.C.I.A.L., try \"perk <NAME>\""
+            )),
+            command if command.fo4_only() => Err(anyhow::anyhow!(
+                "That command is only for Fallout 4 builds"
+            )),
+            command => return Ok(command),
+        }))
     }
 }
 
@@ -272,6 +1495,76 @@ fn clear_terminal() {
     print!("{}[2J", 27 as char);
 }
 
+fn reload_perks(build: &mut Build) -> anyhow::Result<String> {
+    let previous = catalog();
+    let catalog = reload_catalog()?;
+    let mut message = match &catalog.source {
+        Some(path) => format!("Reloaded perk data from {}", path.to_string_lossy()),
+        None => "Reloaded built-in perk data".into(),
+    };
+    for change in build.revalidate_perks(&previous) {
+        message.push_str(&format!("\n  {}", change));
+    }
+    Ok(message)
+}
+
+fn write_reloaded(frame: &mut Frame, reloaded: Option<anyhow::Result<String>>) {
+    match reloaded {
+        Some(Ok(message)) => frame.line(format!("{}\n", message.bright_yellow())),
+        Some(Err(e)) => frame.line(format!(
+            "{}\n",
+            format!("Perk data not reloaded: {}", e).bright_red()
+        )),
+        None => {}
+    }
+}
+
+/// Ask about a merge's next conflict, keeping it pending, or finish it once there are none
+fn continue_merge(
+    build: &mut Build,
+    pending: &mut Option<Merge>,
+    merge: Merge,
+) -> anyhow::Result<String> {
+    if let Some(question) = merge.describe_conflict(build) {
+        *pending = Some(merge);
+        return Ok(format!(
+            "{}\nType \"resolve mine\" or \"resolve theirs\", or \"resolve cancel\" to stop",
+            question
+        ));
+    }
+    let from = merge.from.clone();
+    let notes = build.finish_merge(merge)?;
+    Ok(once(format!(
+        "Merged {}, the build now needs level {}",
+        from,
+        build.required_level()
+    ))
+    .chain(notes.into_iter().map(|note| format!("  {}", note)))
+    .collect::<Vec<String>>()
+    .join("\n"))
+}
+
+/// Load a saved build named by the words of a command
+fn load_other(words: &[String]) -> anyhow::Result<Build> {
+    if words.is_empty() {
+        bail!("You must name the build to copy from")
+    }
+    Build::load(words.join(" "))
+}
+
+/// Ask for a folder to keep builds and the config file in when the system has no data directory
+fn choose_data_dir() -> Option<PathBuf> {
+    if !stdin().is_terminal() {
+        return None;
+    }
+    println!("This system has no data directory to keep builds in.");
+    print!("Enter a folder to use instead: ");
+    stdout().flush().ok()?;
+    let line = stdin().lock().lines().next()?.ok()?;
+    let dir = line.trim();
+    (!dir.is_empty()).then(|| PathBuf::from(dir))
+}
+
 fn catch<F, T>(f: F) -> anyhow::Result<T>
 where
     F: FnOnce() -> anyhow::Result<T>,
@@ -280,95 +1573,857 @@ where
 }
 
 #[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true)]
 struct App {
     path: Vec<PathBuf>,
     #[clap(long = "nocolor", help = "Run without terminal colors")]
     no_color: bool,
+    #[clap(
+        long,
+        env = "FO4_DATA_DIR",
+        help = "The folder to keep builds and the config file in"
+    )]
+    data_dir: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "A perk YAML file to use instead of the built-in perk data, like one for an overhaul mod"
+    )]
+    data: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Speak JSON-RPC on stdin and stdout instead of running interactively"
+    )]
+    rpc: bool,
+    #[cfg(feature = "tui")]
+    #[clap(
+        long,
+        help = "Run full-screen, with panes for the build, its perks, and output"
+    )]
+    tui: bool,
+    #[clap(subcommand)]
+    tool: Option<Tool>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Tool {
+    #[clap(about = "Extract perk definitions from a plugin file or xEdit dump as perk YAML")]
+    ExtractPerks { plugin: PathBuf },
+    #[clap(about = "Check a perk YAML file for mistakes")]
+    LintData { file: PathBuf },
+    #[clap(about = "Check perk data against an xEdit perk dump (CSV or JSON)")]
+    VerifyData {
+        dump: PathBuf,
+        #[clap(
+            long,
+            help = "The perk YAML file to check instead of the built-in data"
+        )]
+        file: Option<PathBuf>,
+    },
+    #[clap(about = "Download and install the latest signed perk data")]
+    UpdateData {
+        #[clap(
+            long,
+            help = "Where to download the release from instead of the configured data-url"
+        )]
+        url: Option<String>,
+    },
+    #[clap(about = "Serve the build planner as a JSON API over HTTP")]
+    Serve {
+        #[clap(long, short, default_value = "7878")]
+        port: u16,
+        #[clap(long, default_value = "127.0.0.1", help = "Address to listen on")]
+        host: String,
+        #[clap(
+            long,
+            env = "FO4_SERVE_TOKEN",
+            hide_env_values = true,
+            help = "The token clients must send, a random one is made if not given"
+        )]
+        token: Option<String>,
+    },
+    #[clap(about = "Keep the perk data loaded and answer JSON-RPC on a local socket")]
+    Daemon {
+        #[clap(long, help = "The socket to listen on instead of the default")]
+        socket: Option<String>,
+    },
+    #[clap(about = "Send one JSON-RPC request to a running daemon and print the result")]
+    Call {
+        method: String,
+        params: Vec<String>,
+        #[clap(long, help = "The daemon's socket instead of the default")]
+        socket: Option<String>,
+    },
+    #[cfg(feature = "gui")]
+    #[clap(about = "Open the planner in a window")]
+    Gui { path: Vec<PathBuf> },
+    #[cfg(feature = "bot")]
+    #[clap(about = "Answer commands in Discord channels")]
+    Bot {
+        #[clap(
+            long,
+            env = "DISCORD_TOKEN",
+            hide_env_values = true,
+            help = "The bot's Discord token"
+        )]
+        token: String,
+    },
+}
+
+impl Tool {
+    fn run(self) -> anyhow::Result<()> {
+        match self {
+            Tool::ExtractPerks { plugin } => {
+                let plugin = if xedit::is_dump(&plugin) {
+                    xedit::read_dump(&plugin)?
+                } else {
+                    plugin::read_plugin(&plugin)?
+                };
+                for warning in &plugin.warnings {
+                    eprintln!("{}", warning.bright_yellow());
+                }
+                print!("{}", plugin::perk_pack(&plugin)?);
+            }
+            Tool::LintData { file } => {
+                let catalog = Catalog::load(Some(&file))?;
+                let problems = lint::lint(&catalog);
+                for problem in &problems {
+                    println!("{}", problem.bright_yellow());
+                }
+                if !problems.is_empty() {
+                    bail!("{} problem(s) found", problems.len())
+                }
+                println!("{}", "No problems found".bright_green());
+            }
+            Tool::UpdateData { url } => {
+                let updated = update::update_data(url)?;
+                if updated.changed {
+                    println!(
+                        "{}",
+                        format!(
+                            "Installed perk data {} (was {}) to {}",
+                            updated.new_version,
+                            updated.old_version,
+                            Catalog::active_path().to_string_lossy()
+                        )
+                        .bright_green()
+                    );
+                } else {
+                    println!(
+                        "{}",
+                        format!("Perk data {} is already up to date", updated.new_version)
+                            .bright_green()
+                    );
+                }
+            }
+            Tool::VerifyData { dump, file } => {
+                let dump = xedit::read_dump(&dump)?;
+                for warning in &dump.warnings {
+                    eprintln!("{}", warning.bright_yellow());
+                }
+                let catalog = Catalog::load(file.as_deref())?;
+                let problems = xedit::verify(&catalog, &dump);
+                for problem in &problems {
+                    println!("{}", problem.bright_yellow());
+                }
+                if !problems.is_empty() {
+                    bail!("{} difference(s) found", problems.len())
+                }
+                println!("{}", "Perk data matches the dump".bright_green());
+            }
+            Tool::Serve { port, host, token } => serve::serve(&host, port, token)?,
+            Tool::Daemon { socket } => daemon::run(&socket.unwrap_or_else(daemon::default_socket))?,
+            Tool::Call {
+                method,
+                params,
+                socket,
+            } => {
+                let socket = socket.unwrap_or_else(daemon::default_socket);
+                let result = daemon::call(&socket, &method, params)?;
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            }
+            #[cfg(feature = "gui")]
+            Tool::Gui { path } => {
+                let build = if path.is_empty() {
+                    Build::default()
+                } else {
+                    let path: String = path
+                        .iter()
+                        .map(|path| path.to_string_lossy().into_owned())
+                        .intersperse(" ".into())
+                        .collect();
+                    Build::load(path)?
+                };
+                gui::run(build)?
+            }
+            #[cfg(feature = "bot")]
+            Tool::Bot { token } => bot::run(&token)?,
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Parser)]
 #[allow(clippy::large_enum_variant)]
 enum Command {
     #[clap(display_order = 1, about = "Set a special stat")]
-    Set { stat: SpecialStat, value: u8 },
-    #[clap(display_order = 1, about = "Add a perk by name and rank")]
+    Set {
+        stat: SpecialStat,
+        value: u8,
+        #[clap(long, help = "Refuse the change instead of removing perks")]
+        keep: bool,
+    },
+    #[clap(
+        display_order = 1,
+        about = "Add a perk by name and rank, qualifying the name like \"magazine: grognak\" if needed"
+    )]
     Add {
         perk: String,
         tail_and_rank: Vec<String>,
     },
     #[clap(display_order = 1, about = "Remove a perk")]
-    Remove { perk: String, tail: Vec<String> },
+    Remove {
+        perk: String,
+        tail: Vec<String>,
+        #[clap(long, help = "Refuse the change instead of removing other perks")]
+        keep: bool,
+    },
     #[clap(display_order = 1, about = "Display a perk")]
-    Perk { perk: String, tail: Vec<String> },
+    Perk {
+        perk: String,
+        tail: Vec<String>,
+        #[clap(
+            long,
+            number_of_values = 2,
+            value_names = &["FROM", "TO"],
+            help = "Show only what changes between two ranks"
+        )]
+        compare: Vec<u8>,
+    },
+    #[clap(
+        display_order = 1,
+        about = "Explain what is needed to take a perk rank"
+    )]
+    Path {
+        perk: String,
+        tail_and_rank: Vec<String>,
+    },
     #[clap(
         display_order = 1,
         about = "Display all perks for a S.P.E.C.I.A.L. stat(s)"
     )]
     Special { stat: Option<SpecialStat> },
+    #[clap(about = "Display which S.P.E.C.I.A.L. thresholds gate which perks")]
+    Tree {
+        stat: Option<SpecialStat>,
+        #[clap(long, help = "Output the tree in Graphviz DOT format")]
+        dot: bool,
+    },
     #[clap(about = "Display all perk bobbleheads")]
-    Bobbleheads,
+    Bobbleheads {
+        #[clap(long, short, help = "Show where each one is found")]
+        locations: bool,
+    },
     #[clap(about = "Display all perk magazines")]
-    Magazines,
+    Magazines {
+        #[clap(long, short, help = "Show where each one is found")]
+        locations: bool,
+    },
     #[clap(about = "Display all companion perks")]
-    Companions,
+    Companions {
+        #[clap(long, short, help = "Show where each companion is recruited")]
+        locations: bool,
+    },
     #[clap(about = "Display all faction perks")]
     Factions,
     #[clap(about = "Display all other perks")]
     OtherPerks,
-    #[clap(display_order = 2, about = "Reset the build")]
-    Reset,
+    #[clap(about = "Display untaken perks that synergize with the build's perks")]
+    Synergies,
+    #[clap(about = "Show a table of what each perk and worn legendary adds to each effect")]
+    Matrix,
+    #[clap(
+        display_order = 2,
+        about = "Reset the build, optionally switching games (fo4, fo76, fo3, nv)"
+    )]
+    Reset { game: Option<Game> },
+    #[clap(
+        display_order = 2,
+        about = "List, save, load, or delete Fallout 76 perk card loadouts"
+    )]
+    Loadout {
+        #[clap(subcommand)]
+        action: Option<LoadoutAction>,
+    },
+    #[clap(about = "Tag or untag a Fallout 3 or New Vegas skill")]
+    Tag { skill: Vec<String> },
+    #[clap(about = "Set how many level-up points go into a Fallout 3 or New Vegas skill")]
+    Skill {
+        skill: String,
+        tail_and_points: Vec<String>,
+    },
+    #[clap(about = "List New Vegas traits, or take or drop one")]
+    Trait { name: Vec<String> },
     #[clap(display_order = 2, about = "Set the build's name")]
     Name { name: Vec<String> },
     #[clap(about = "Set the build's gender (affects perk names)")]
     Gender { gender: Gender },
     #[clap(about = "Set which stat to allocate the special book to")]
-    Book { stat: Option<SpecialStat> },
-    #[clap(about = "Set the difficulty (affects carry weight)", alias = "diff")]
+    Book {
+        stat: Option<SpecialStat>,
+        #[clap(
+            long,
+            help = "The stat's bobblehead is picked up before the book is read"
+        )]
+        after_bobblehead: bool,
+    },
+    #[clap(about = "Pick up or drop a stat's bobblehead")]
+    Bobblehead {
+        stat: SpecialStat,
+        #[clap(arg_enum)]
+        state: Switch,
+        #[clap(long, help = "Refuse the change instead of removing perks")]
+        keep: bool,
+    },
+    #[clap(about = "Set a stat bonus from gear or chems, which only raises the effective stat")]
+    Buff {
+        stat: SpecialStat,
+        amount: Option<u8>,
+    },
+    #[clap(
+        about = "Set the difficulty (affects damage, carry weight, and descriptions)",
+        alias = "diff"
+    )]
     Difficulty { difficulty: Difficulty },
     #[clap(
         alias = "ll",
         about = "Limit the maximum required level for added perks"
     )]
     LevelLimit { level: Option<u8> },
+    #[clap(
+        alias = "opt",
+        about = "Optimize S.P.E.C.I.A.L. and perks for weighted stats (e.g. \"crit=2 carry\")"
+    )]
+    Optimize {
+        weights: Vec<Weight>,
+        #[clap(
+            long,
+            default_value = "greedy",
+            help = "Search strategy: greedy or deep"
+        )]
+        search: Search,
+        #[clap(
+            long,
+            default_value = "5",
+            help = "Time budget in seconds for deep search"
+        )]
+        time: f32,
+        #[clap(
+            long,
+            help = "Stop deep search after this many moves per chain instead, which gives the same result every run"
+        )]
+        steps: Option<u64>,
+        #[clap(long, default_value_t = DEFAULT_SEED, help = "Seed for deep search")]
+        seed: u64,
+        #[clap(long, help = "Level to optimize for (defaults to the level limit)")]
+        level: Option<u8>,
+        #[clap(long, help = "List up to this many non-dominated builds instead")]
+        pareto: Option<usize>,
+        #[clap(long, help = "Threads to search with (defaults to one per core)")]
+        threads: Option<usize>,
+    },
+    #[clap(about = "Use one of the builds listed by \"optimize --pareto\"")]
+    Accept { index: usize },
+    #[clap(
+        about = "Rank the perk ranks that can be taken next by how much they improve weighted stats per point (e.g. \"value crit=2 carry\")"
+    )]
+    Value {
+        weights: Vec<Weight>,
+        #[clap(long, help = "Level to take ranks by (defaults to the next level)")]
+        level: Option<u8>,
+        #[clap(long, default_value_t = 10, help = "How many ranks to list")]
+        count: usize,
+    },
     #[clap(alias = "map", about = "Toggle the build sheet display")]
     Sheet,
+    #[clap(
+        about = "Show a derived stat after every command with how it changed, like \"watch carry weight\""
+    )]
+    Watch { stat: Vec<String> },
+    #[clap(about = "Stop showing a watched stat, or every one")]
+    Unwatch { stat: Vec<String> },
+    #[clap(
+        about = "Define a stat shown with every build by a formula, like \"define stealth_score agility*2 + sneak_rank*3\", or remove it with no formula"
+    )]
+    Define {
+        name: Option<String>,
+        formula: Vec<String>,
+    },
+    #[clap(
+        about = "Work out a formula over the build's stats, like \"calc base_health + 20*level\""
+    )]
+    Calc {
+        #[clap(allow_hyphen_values = true)]
+        formula: Vec<String>,
+    },
+    #[clap(about = "Explain what sets the build's required level and how to lower it")]
+    Why {
+        #[clap(arg_enum)]
+        topic: WhyTopic,
+    },
+    #[clap(about = "Check that the build's perk points add up level by level")]
+    Verify,
+    #[clap(about = "Copy the S.P.E.C.I.A.L. allocation or perks of a saved build into this one")]
+    Copy {
+        #[clap(subcommand)]
+        what: CopyAction,
+    },
+    #[clap(
+        about = "Merge a saved build's perks into this one, taking the higher of each S.P.E.C.I.A.L. stat"
+    )]
+    Merge { build: Vec<String> },
+    #[clap(
+        about = "Resolve a conflict in a merge by keeping this build's side or taking the other's"
+    )]
+    Resolve {
+        #[clap(arg_enum)]
+        side: Side,
+    },
+    #[clap(about = "Rank wanted perks by priority and see which fit by the level limit")]
+    Wishlist {
+        #[clap(subcommand)]
+        action: Option<WishlistAction>,
+    },
+    #[clap(about = "Plan what each level-up is spent on and check the order works")]
+    Plan {
+        #[clap(subcommand)]
+        action: Option<PlanAction>,
+    },
+    #[clap(about = "List, save, apply, or delete named S.P.E.C.I.A.L. allocations")]
+    Preset {
+        #[clap(subcommand)]
+        action: Option<PresetAction>,
+    },
+    #[clap(about = "List, add, or remove carried gear to see how much carry weight is left")]
+    Gear {
+        #[clap(subcommand)]
+        action: Option<GearAction>,
+    },
     #[clap(display_order = 2, about = "Save the build")]
-    Save { name: Vec<String> },
+    Save {
+        name: Vec<String>,
+        #[clap(
+            short,
+            long,
+            multiple_values = true,
+            help = "Note why the build changed, like -m \"swapped to rifles\""
+        )]
+        message: Vec<String>,
+    },
+    #[clap(
+        display_order = 2,
+        about = "Show what changed each time the build was saved"
+    )]
+    Log,
     #[clap(display_order = 2, about = "Load a build")]
-    Load { path: Vec<PathBuf> },
+    Load {
+        #[clap(
+            long,
+            help = "Load the valid parts of a broken build file, listing what was dropped"
+        )]
+        partial: bool,
+        path: Vec<PathBuf>,
+    },
+    #[clap(
+        display_order = 2,
+        about = "Switch to an open build by number or name, or open a saved build alongside"
+    )]
+    Switch {
+        #[clap(long, help = "Open a new empty build")]
+        new: bool,
+        build: Vec<String>,
+    },
+    #[clap(display_order = 2, about = "Close the current build without saving it")]
+    Close,
+    #[clap(about = "Keep a copy of the build under a name until the tool is closed")]
+    Snapshot { name: Vec<String> },
+    #[clap(about = "Go back to a snapshot of the build")]
+    Rollback { name: Vec<String> },
+    #[clap(about = "List the snapshots taken since the tool was opened")]
+    Snapshots,
+    #[clap(about = "Take back the last change to the build, or the last few")]
+    Undo {
+        #[clap(default_value_t = 1)]
+        steps: usize,
+    },
+    #[clap(about = "Make an undone change again, or the last few")]
+    Redo {
+        #[clap(default_value_t = 1)]
+        steps: usize,
+    },
+    #[clap(
+        display_order = 2,
+        about = "Check off a reached level or acquired perk, or show progress"
+    )]
+    Check { target: Vec<String> },
+    #[clap(
+        display_order = 2,
+        about = "Set the current level and show the planned picks due by it"
+    )]
+    Level {
+        level: u8,
+        #[clap(long, help = "Check off the due picks")]
+        apply: bool,
+    },
+    #[clap(
+        display_order = 2,
+        about = "Mark a magazine issue as collected, or uncollect it if it already is"
+    )]
+    Collect {
+        magazine: String,
+        tail_and_issue: Vec<String>,
+    },
+    #[clap(
+        display_order = 2,
+        about = "Plan the level a stat bobblehead or the book is collected at (1 for the start)"
+    )]
+    Pickup { level: u8, item: Vec<String> },
+    #[clap(
+        display_order = 2,
+        about = "Export the build (formats: checklist), or every build (archive)"
+    )]
+    Export {
+        format: ExportFormat,
+        path: Vec<PathBuf>,
+    },
+    #[clap(
+        display_order = 2,
+        about = "Show a code others can import the build from"
+    )]
+    Share {
+        #[clap(
+            long,
+            help = "Sign the code with this machine's key so changes to it are caught"
+        )]
+        sign: bool,
+    },
+    #[clap(
+        about = "Trust the key a signed share code was signed with under a name, or list the trusted keys"
+    )]
+    Trust {
+        name: Option<String>,
+        code: Option<String>,
+    },
+    #[clap(about = "Stop trusting a key")]
+    Untrust { name: String },
+    #[clap(
+        display_order = 2,
+        about = "Import a build from a file, link, or share code, or every build with \"import archive <FILE>\""
+    )]
+    Import {
+        #[clap(
+            long,
+            short,
+            help = "Skip detection (formats: text, json, falloutbuilds, code, schedule)"
+        )]
+        format: Option<ImportFormat>,
+        #[clap(long, help = "Replace builds that differ from the ones in an archive")]
+        overwrite: bool,
+        source: Vec<String>,
+    },
+    #[clap(alias = "catalog", about = "Show or reload the perk data in use")]
+    Perks {
+        #[clap(subcommand)]
+        action: Option<PerksAction>,
+    },
+    #[clap(
+        about = "Show or change settings (keys: builds-dir, sync-dir, backup-url, backup-token, match-threshold, match-initials, fold-diacritics, fold-case, exact-match)"
+    )]
+    Config {
+        key: Option<ConfigKey>,
+        value: Vec<String>,
+    },
+    #[clap(about = "Upload or download the builds folder to the backup server")]
+    Backup {
+        #[clap(subcommand)]
+        action: BackupAction,
+    },
+    #[clap(about = "Mirror the builds folder with another folder")]
+    Sync { dir: Vec<PathBuf> },
     #[clap(about = "Open the folder where builds are saved")]
     Builds,
+    #[clap(
+        about = "Find saved builds with a perk or matching stats, like \"find rifleman 3\" or \"find luck>=8 level<=40\""
+    )]
+    Find { query: Vec<String> },
+    #[clap(about = "Show statistics about saved builds")]
+    Stats {
+        #[clap(subcommand)]
+        action: StatsAction,
+    },
+    #[clap(about = "Reload scripts from the scripts folder and list their hooks and commands")]
+    Scripts,
     #[clap(display_order = 2, about = "Exit this tool")]
     Exit,
+    /// A command added by a script
+    #[clap(external_subcommand)]
+    Script(Vec<String>),
 }
 
-fn join_perk_def(parts: &[String]) -> anyhow::Result<PerkDef> {
-    if parts.is_empty() {
-        bail!("You must specify a perk")
-    } else {
-        parts.iter().map(String::as_str).collect::<String>().parse()
+impl Command {
+    /// Commands that only change or describe the open build, the ones HTTP clients
+    /// may run. Anything that reads or writes files, the config, or the network is left out.
+    fn in_memory(&self) -> bool {
+        matches!(
+            self,
+            Command::Set { .. }
+                | Command::Add { .. }
+                | Command::Remove { .. }
+                | Command::Perk { .. }
+                | Command::Path { .. }
+                | Command::Special { .. }
+                | Command::Tree { .. }
+                | Command::Bobbleheads { .. }
+                | Command::Magazines { .. }
+                | Command::Companions { .. }
+                | Command::Factions
+                | Command::OtherPerks
+                | Command::Synergies
+                | Command::Matrix
+                | Command::Reset { .. }
+                | Command::Loadout { .. }
+                | Command::Tag { .. }
+                | Command::Skill { .. }
+                | Command::Trait { .. }
+                | Command::Name { .. }
+                | Command::Gender { .. }
+                | Command::Book { .. }
+                | Command::Bobblehead { .. }
+                | Command::Buff { .. }
+                | Command::Difficulty { .. }
+                | Command::LevelLimit { .. }
+                | Command::Optimize { .. }
+                | Command::Accept { .. }
+                | Command::Value { .. }
+                | Command::Sheet
+                | Command::Watch { .. }
+                | Command::Unwatch { .. }
+                | Command::Calc { .. }
+                | Command::Why { .. }
+                | Command::Verify
+                | Command::Wishlist { .. }
+                | Command::Plan { .. }
+                | Command::Gear { .. }
+                | Command::Log
+                | Command::Snapshot { .. }
+                | Command::Rollback { .. }
+                | Command::Snapshots
+                | Command::Undo { .. }
+                | Command::Redo { .. }
+                | Command::Check { .. }
+                | Command::Level { .. }
+                | Command::Collect { .. }
+                | Command::Pickup { .. }
+                | Command::Share { sign: false }
+        )
+    }
+    /// Commands that only make sense with Fallout 4's perk chart
+    fn fo4_only(&self) -> bool {
+        matches!(
+            self,
+            Command::Path { .. }
+                | Command::Tree { .. }
+                | Command::Bobbleheads { .. }
+                | Command::Magazines { .. }
+                | Command::Companions { .. }
+                | Command::Factions
+                | Command::OtherPerks
+                | Command::Synergies
+                | Command::Matrix
+                | Command::Book { .. }
+                | Command::Bobblehead { .. }
+                | Command::Buff { .. }
+                | Command::Difficulty { .. }
+                | Command::Optimize { .. }
+                | Command::Accept { .. }
+                | Command::Value { .. }
+                | Command::Sheet
+                | Command::Watch { .. }
+                | Command::Unwatch { .. }
+                | Command::Define { .. }
+                | Command::Verify
+                | Command::Why { .. }
+                | Command::Copy { .. }
+                | Command::Merge { .. }
+                | Command::Resolve { .. }
+                | Command::Preset { .. }
+                | Command::Wishlist { .. }
+                | Command::Plan { .. }
+                | Command::Gear { .. }
+                | Command::Check { .. }
+                | Command::Level { .. }
+                | Command::Collect { .. }
+                | Command::Pickup { .. }
+                | Command::Export { .. }
+                | Command::Share { .. }
+        )
     }
 }
 
-fn join_perk_def_and_rank(parts: &[String]) -> anyhow::Result<(PerkDef, Option<u8>)> {
-    if parts.is_empty() {
-        bail!("You must specify a perk")
-    } else if parts.len() == 1 {
-        parts[0].parse::<PerkDef>().map(|def| (def, None))
-    } else if let Ok(last) = parts.last().unwrap().parse::<u8>() {
-        let sub = &parts[..(parts.len() - 1)];
-        if sub
-            .last()
-            .and_then(|part| part.parse::<u8>().ok())
-            .is_some()
-        {
-            join_perk_def(sub).map(|def| (def, Some(last)))
-        } else if let Ok(def) = join_perk_def(sub) {
-            Ok((def, Some(last)))
-        } else {
-            join_perk_def(parts).map(|def| (def, None))
-        }
-    } else {
-        join_perk_def(parts).map(|def| (def, None))
-    }
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+enum Switch {
+    On,
+    Off,
+}
+
+#[derive(Debug, Clone, Copy, clap::Subcommand)]
+enum BackupAction {
+    #[clap(about = "Upload all builds")]
+    Push,
+    #[clap(about = "Download all builds, replacing local copies")]
+    Pull,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum LoadoutAction {
+    #[clap(about = "Save the equipped cards as a loadout")]
+    Save { name: Vec<String> },
+    #[clap(about = "Swap in a saved loadout")]
+    Load { name: Vec<String> },
+    #[clap(about = "Delete a saved loadout")]
+    Delete { name: Vec<String> },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum GearAction {
+    #[clap(about = "Add an item by its weight and name, replacing one with the same name")]
+    Add {
+        weight: f32,
+        #[clap(required = true)]
+        name: Vec<String>,
+        #[clap(long, default_value = "1", help = "How many of the item are carried")]
+        count: u32,
+        #[clap(long, help = "The item only weighs anything on Survival, like ammo")]
+        survival: bool,
+        #[clap(
+            long,
+            help = "Where the armor or clothing is worn, like chest or left-arm"
+        )]
+        slot: Option<GearSlot>,
+        #[clap(long, help = "The worn item's legendary effect, like powered")]
+        legendary: Option<String>,
+    },
+    #[clap(about = "Remove an item")]
+    Remove { name: Vec<String> },
+    #[clap(about = "Remove every item")]
+    Clear,
+    #[clap(about = "List the legendary effects armor can have")]
+    Legendaries,
+}
+
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+enum WhyTopic {
+    Level,
+}
+
+#[derive(Debug, Clone, Copy, clap::ArgEnum)]
+enum Side {
+    Mine,
+    Theirs,
+    Cancel,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum CopyAction {
+    #[clap(about = "Take on a saved build's S.P.E.C.I.A.L. allocation")]
+    Special {
+        build: Vec<String>,
+        #[clap(long, help = "Refuse the change instead of removing perks")]
+        keep: bool,
+    },
+    #[clap(
+        about = "Merge in a saved build's perks, optionally only one category like \"copy perks Sniper magazine\""
+    )]
+    Perks { build: Vec<String> },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum WishlistAction {
+    #[clap(
+        about = "Add a perk at a rank, or its highest, last or at a position like \"wishlist add --at 1 rifleman 3\""
+    )]
+    Add {
+        #[clap(
+            long,
+            help = "The position on the wishlist, 1 for the highest priority"
+        )]
+        at: Option<usize>,
+        #[clap(required = true)]
+        perk: Vec<String>,
+    },
+    #[clap(about = "Take a perk off the wishlist")]
+    Remove {
+        #[clap(required = true)]
+        perk: Vec<String>,
+    },
+    #[clap(about = "Empty the wishlist")]
+    Clear,
+    #[clap(about = "Take every wish that fits by the level limit")]
+    Apply,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum PlanAction {
+    #[clap(
+        about = "Plan a level-up for a perk rank or a S.P.E.C.I.A.L. point, like \"plan set 5 rifleman 2\" or \"plan set 3 agility\""
+    )]
+    Set {
+        level: u8,
+        #[clap(required = true)]
+        what: Vec<String>,
+    },
+    #[clap(about = "Clear one level of the plan, or all of it")]
+    Clear { level: Option<u8> },
+    #[clap(about = "Replace the plan with one that takes each perk rank as early as it can")]
+    Auto,
+    #[clap(about = "Show the plan level by level with its milestones")]
+    Show,
+    #[clap(
+        about = "Add a reminder to a level, like \"plan milestone 15 get the Perception bobblehead before Rifleman 3\", or remove a level's reminders with no text"
+    )]
+    Milestone { level: u8, text: Vec<String> },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum PresetAction {
+    #[clap(about = "Save the build's S.P.E.C.I.A.L. allocation as a preset")]
+    Save { name: Vec<String> },
+    #[clap(about = "Set the build's S.P.E.C.I.A.L. allocation to a preset")]
+    Apply {
+        name: Vec<String>,
+        #[clap(long, help = "Refuse the change instead of removing perks")]
+        keep: bool,
+    },
+    #[clap(about = "Delete a preset")]
+    Delete { name: Vec<String> },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum StatsAction {
+    #[clap(about = "Show which perks, S.P.E.C.I.A.L. spreads, and archetypes saved builds favor")]
+    Builds,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum PerksAction {
+    #[clap(about = "Reload the perk data file")]
+    Reload,
+    #[clap(about = "List the language-independent keys used by language files")]
+    Keys,
+    #[clap(
+        about = "Summarize the perk data: perks per category, effect coverage, and what each DLC adds"
+    )]
+    Stats {
+        #[clap(long, help = "List the perks that have no effects, only a description")]
+        list: bool,
+    },
 }
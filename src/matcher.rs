@@ -0,0 +1,225 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use crate::special::{similarity, PerkDef, PerkId};
+
+/// How similar a name must be to a query to count as a match, unless configured otherwise
+pub const MATCH_THRESHOLD: f64 = 0.6;
+
+/// How close a name's initials matching a query scores, just below an exact match
+const INITIALS_SCORE: f64 = 0.95;
+
+/// How perk names are matched against what the user typed
+#[derive(Debug, Clone, Copy)]
+pub struct MatchOptions {
+    /// How similar a name must be to a query to count as a match
+    pub threshold: f64,
+    /// Whether a name's initials match it, like "gs" for Gun Slinger
+    pub initials: bool,
+    /// Whether accented letters match their plain forms
+    pub fold_diacritics: bool,
+    /// Whether upper and lower case letters match each other
+    pub fold_case: bool,
+    /// Only accept names that match exactly, after folding, for scripting
+    pub exact: bool,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        MatchOptions {
+            threshold: MATCH_THRESHOLD,
+            initials: true,
+            fold_diacritics: true,
+            fold_case: true,
+            exact: false,
+        }
+    }
+}
+
+impl MatchOptions {
+    /// The options from the config file, if there is one
+    pub fn current() -> Self {
+        #[cfg(feature = "native")]
+        {
+            crate::config::config().match_options()
+        }
+        #[cfg(not(feature = "native"))]
+        {
+            MatchOptions::default()
+        }
+    }
+    fn fold(&self, s: &str) -> String {
+        let s = s.trim();
+        let s = if self.fold_case {
+            s.to_lowercase()
+        } else {
+            s.to_string()
+        };
+        if self.fold_diacritics {
+            s.chars().map(fold_diacritic).collect()
+        } else {
+            s
+        }
+    }
+}
+
+/// The plain letter an accented Latin letter is a form of
+fn fold_diacritic(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' => 'a',
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' => 'A',
+        'ç' | 'ć' | 'č' => 'c',
+        'Ç' | 'Ć' | 'Č' => 'C',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => 'e',
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ę' | 'Ě' => 'E',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => 'i',
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' => 'I',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'Ñ' | 'Ń' | 'Ň' => 'N',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' => 'o',
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' => 'O',
+        'ś' | 'š' => 's',
+        'Ś' | 'Š' => 'S',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' => 'u',
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' => 'U',
+        'ý' | 'ÿ' => 'y',
+        'Ý' | 'Ÿ' => 'Y',
+        'ź' | 'ż' | 'ž' => 'z',
+        'Ź' | 'Ż' | 'Ž' => 'Z',
+        c => c,
+    }
+}
+
+/// The first letter of each word of a name, like "gs" for "gun slinger",
+/// if it has more than one word
+fn initials(name: &str) -> Option<String> {
+    let initials: String = name
+        .split_whitespace()
+        .filter_map(|word| word.chars().next())
+        .collect();
+    (initials.chars().count() > 1).then_some(initials)
+}
+
+fn compact(s: &str) -> String {
+    s.split_whitespace().collect()
+}
+
+fn trigrams(s: &str) -> Vec<[char; 3]> {
+    let chars: Vec<char> = s.chars().filter(|c| c.is_alphanumeric()).collect();
+    let mut grams: Vec<[char; 3]> = chars.windows(3).map(|w| [w[0], w[1], w[2]]).collect();
+    grams.sort();
+    grams.dedup();
+    grams
+}
+
+/// Perk names and aliases, lowercased once and indexed by word and trigram
+/// so a query is only scored against names it has something in common with
+#[derive(Debug, Clone, Default)]
+pub struct NameIndex {
+    names: Vec<(String, PerkId)>,
+    tokens: BTreeMap<String, Vec<usize>>,
+    trigrams: BTreeMap<[char; 3], Vec<usize>>,
+    initials: BTreeMap<String, Vec<usize>>,
+}
+
+impl NameIndex {
+    pub fn new(perks: &BTreeMap<PerkId, Arc<PerkDef>>) -> Self {
+        let mut index = NameIndex::default();
+        let mut seen = BTreeSet::new();
+        // Lookups are always folded so they find candidates however the scoring is configured
+        let folded = MatchOptions::default();
+        for (id, def) in perks {
+            for name in def.name.iter().chain(&def.aliases) {
+                let name = name.trim().to_string();
+                if !seen.insert((name.clone(), *id)) {
+                    continue;
+                }
+                let i = index.names.len();
+                let lookup = folded.fold(&name);
+                for token in lookup.split_whitespace() {
+                    index.tokens.entry(token.into()).or_default().push(i);
+                }
+                for gram in trigrams(&lookup) {
+                    index.trigrams.entry(gram).or_default().push(i);
+                }
+                if let Some(initials) = initials(&lookup) {
+                    index.initials.entry(initials).or_default().push(i);
+                }
+                index.names.push((name, *id));
+            }
+        }
+        index
+    }
+    fn score(
+        &self,
+        query: &str,
+        entries: impl Iterator<Item = usize>,
+        options: &MatchOptions,
+    ) -> Vec<(PerkId, f64)> {
+        let query = options.fold(query);
+        let mut best: BTreeMap<PerkId, f64> = BTreeMap::new();
+        for i in entries {
+            let (name, id) = &self.names[i];
+            let name = options.fold(name);
+            let sim = if name == query {
+                1.0
+            } else if options.exact {
+                // Words of a query are often run together, so only spacing may differ
+                if compact(&name) != compact(&query) {
+                    continue;
+                }
+                1.0
+            } else if options.initials
+                && !query.contains(' ')
+                && initials(&name).as_ref() == Some(&query)
+            {
+                INITIALS_SCORE
+            } else {
+                similarity(&query, &name)
+            };
+            let entry = best.entry(*id).or_default();
+            if sim > *entry {
+                *entry = sim;
+            }
+        }
+        let mut ranked: Vec<(PerkId, f64)> = best.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+    /// Every perk sharing a trigram, word prefix, or initials with the query, best match first
+    ///
+    /// If none of those clear the threshold, every name is scored instead,
+    /// so heavily misspelled queries still find their best match.
+    pub fn search(&self, query: &str, options: &MatchOptions) -> Vec<(PerkId, f64)> {
+        let lookup = MatchOptions::default().fold(query);
+        let mut candidates = BTreeSet::new();
+        if let Some(entries) = self.initials.get(&lookup) {
+            candidates.extend(entries.iter().copied());
+        }
+        for gram in trigrams(&lookup) {
+            if let Some(entries) = self.trigrams.get(&gram) {
+                candidates.extend(entries.iter().copied());
+            }
+        }
+        for token in lookup.split_whitespace() {
+            for (_, entries) in self
+                .tokens
+                .range(token.to_string()..)
+                .take_while(|(name, _)| name.starts_with(token))
+            {
+                candidates.extend(entries.iter().copied());
+            }
+        }
+        let ranked = self.score(query, candidates.into_iter(), options);
+        if ranked
+            .first()
+            .is_some_and(|(_, sim)| *sim >= options.threshold)
+        {
+            ranked
+        } else {
+            self.score(query, 0..self.names.len(), options)
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+
+use colored::Colorize;
+
+use crate::{
+    build::Build,
+    expr::format_value,
+    special::{catalog, Combine, EffectValue},
+};
+
+/// A row of the effect matrix: where the effects come from and each one's value from it
+struct Row {
+    source: String,
+    values: Vec<Option<f32>>,
+}
+
+fn combined(values: impl Iterator<Item = EffectValue>, combine: Combine) -> Option<f32> {
+    values
+        .filter_map(EffectValue::number)
+        .reduce(|acc, value| combine.apply(acc, value))
+}
+
+impl Build {
+    /// Write a table with a row for each perk and worn legendary, a column for each
+    /// kind of numeric effect, and what each source adds to each effect
+    pub fn write_effect_matrix(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        let catalog = catalog();
+        let effects: Vec<(&str, Combine)> = catalog
+            .effects
+            .iter()
+            .map(|(name, def)| (name.as_str(), def.combine))
+            .collect();
+        let mut rows = Vec::new();
+        for (id, rank) in &self.perks {
+            let def = catalog.perks.get(id).expect("Unknown perk");
+            rows.push(Row {
+                source: format!("{} {}", self.perk_name(id), rank),
+                values: effects
+                    .iter()
+                    .map(|(name, combine)| combined(def.effect(name, *rank), *combine))
+                    .collect(),
+            });
+        }
+        for (name, item) in &self.gear {
+            if item.slot.is_none() {
+                continue;
+            }
+            if let Some(def) = item.legendary_def() {
+                rows.push(Row {
+                    source: name.clone(),
+                    values: effects
+                        .iter()
+                        .map(|(effect, combine)| {
+                            combined(def.effects.get(effect).into_iter(), *combine)
+                        })
+                        .collect(),
+                });
+            }
+        }
+        rows.retain(|row| row.values.iter().any(Option::is_some));
+        if rows.is_empty() {
+            return writeln!(f, "None of the build's perks or gear have numeric effects");
+        }
+        let columns: Vec<usize> = (0..effects.len())
+            .filter(|&i| rows.iter().any(|row| row.values[i].is_some()))
+            .collect();
+        let headers: Vec<String> = columns
+            .iter()
+            .map(|&i| {
+                let (name, _) = effects[i];
+                catalog.effects[name]
+                    .label
+                    .clone()
+                    .unwrap_or_else(|| name.into())
+            })
+            .collect();
+        let totals: Vec<f32> = columns
+            .iter()
+            .map(|&i| self.effect_total(effects[i].0))
+            .collect();
+        let cell = |value: Option<f32>| value.map_or_else(|| "·".into(), format_value);
+        let widths: Vec<usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(c, &i)| {
+                rows.iter()
+                    .map(|row| cell(row.values[i]).chars().count())
+                    .chain([headers[c].chars().count(), format_value(totals[c]).len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let source_width = rows
+            .iter()
+            .map(|row| row.source.chars().count())
+            .chain([5])
+            .max()
+            .unwrap_or(0);
+        write!(f, "{:source_width$}", "", source_width = source_width)?;
+        for (header, width) in headers.iter().zip(&widths) {
+            write!(
+                f,
+                "  {}",
+                format!("{:>width$}", header, width = width).bright_cyan()
+            )?;
+        }
+        writeln!(f)?;
+        for row in &rows {
+            write!(
+                f,
+                "{:source_width$}",
+                row.source,
+                source_width = source_width
+            )?;
+            for (&i, width) in columns.iter().zip(&widths) {
+                let text = format!("{:>width$}", cell(row.values[i]), width = width);
+                match row.values[i] {
+                    Some(_) => write!(f, "  {}", text.bright_white())?,
+                    None => write!(f, "  {}", text.bright_black())?,
+                }
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "{}",
+            format!("{:source_width$}", "Total", source_width = source_width).bright_yellow()
+        )?;
+        for (total, width) in totals.iter().zip(&widths) {
+            let text = format!("{:>width$}", format_value(*total), width = width);
+            write!(f, "  {}", text.bright_yellow())?;
+        }
+        writeln!(f)
+    }
+}
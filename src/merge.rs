@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+
+use anyhow::bail;
+
+use crate::{
+    build::Build,
+    copy::check_same_game,
+    special::{catalog, PerkId, SpecialStat},
+};
+
+/// Where a build and one being merged into it disagree
+#[derive(Debug, Clone, Copy)]
+pub enum MergeConflict {
+    /// The same perk at different ranks
+    Rank { id: PerkId, mine: u8, theirs: u8 },
+    /// Perks that one character cannot both earn
+    Exclusive { mine: PerkId, theirs: PerkId },
+}
+
+/// Another build being merged into this one, waiting on its conflicts to be resolved
+pub struct Merge {
+    /// The name of the build being merged in
+    pub from: String,
+    other: Build,
+    conflicts: Vec<MergeConflict>,
+    /// For each resolved conflict, whether the other build's side was taken
+    choices: Vec<bool>,
+}
+
+impl Merge {
+    /// The next conflict to resolve
+    pub fn conflict(&self) -> Option<MergeConflict> {
+        self.conflicts.get(self.choices.len()).copied()
+    }
+    /// Resolve the next conflict, keeping this build's side or taking the other build's
+    pub fn resolve(&mut self, theirs: bool) {
+        if self.conflict().is_some() {
+            self.choices.push(theirs);
+        }
+    }
+    /// Ask about the next conflict, like "Rifleman is rank 3 here but rank 5 in Sniper"
+    pub fn describe_conflict(&self, build: &Build) -> Option<String> {
+        let left = self.conflicts.len() - self.choices.len();
+        let question = match self.conflict()? {
+            MergeConflict::Rank { id, mine, theirs } => format!(
+                "{} is rank {} here but rank {} in {}",
+                build.perk_name(&id),
+                mine,
+                theirs,
+                self.from
+            ),
+            MergeConflict::Exclusive { mine, theirs } => format!(
+                "{} here cannot be earned along with {} from {}",
+                build.perk_name(&mine),
+                build.perk_name(&theirs),
+                self.from
+            ),
+        };
+        Some(if left > 1 {
+            format!("{} ({} conflicts left)", question, left)
+        } else {
+            question
+        })
+    }
+}
+
+impl Build {
+    /// Start merging another build's perks into this one, taking the higher of each
+    /// S.P.E.C.I.A.L. stat once every conflict is resolved
+    pub fn start_merge(&self, other: Build) -> anyhow::Result<Merge> {
+        check_same_game(&other)?;
+        let mut conflicts = Vec::new();
+        for (&id, &theirs) in &other.perks {
+            match self.perks.get(&id) {
+                Some(&mine) if mine != theirs => {
+                    conflicts.push(MergeConflict::Rank { id, mine, theirs })
+                }
+                _ => {}
+            }
+        }
+        for group in &catalog().exclusive {
+            for mine in group.iter().filter(|id| self.perks.contains_key(id)) {
+                for theirs in group.iter().filter(|id| {
+                    id != &mine && other.perks.contains_key(id) && !self.perks.contains_key(id)
+                }) {
+                    conflicts.push(MergeConflict::Exclusive {
+                        mine: *mine,
+                        theirs: *theirs,
+                    });
+                }
+            }
+        }
+        Ok(Merge {
+            from: other
+                .name
+                .clone()
+                .unwrap_or_else(|| "the other build".into()),
+            other,
+            conflicts,
+            choices: Vec::new(),
+        })
+    }
+    /// Finish a merge whose conflicts are all resolved, describing anything that
+    /// could not be taken
+    pub fn finish_merge(&mut self, merge: Merge) -> anyhow::Result<Vec<String>> {
+        if merge.conflict().is_some() {
+            bail!("Resolve every conflict before finishing the merge")
+        }
+        let mut merged = self.clone();
+        let mut notes = Vec::new();
+        let mut declined = BTreeSet::new();
+        for (conflict, &theirs) in merge.conflicts.iter().zip(&merge.choices) {
+            match *conflict {
+                MergeConflict::Rank { id, .. } if !theirs => {
+                    declined.insert(id);
+                }
+                MergeConflict::Rank { .. } => {}
+                MergeConflict::Exclusive { mine, .. } if theirs => {
+                    let removed = merged.remove_perk(mine)?;
+                    notes.push(format!("Removed {}", merged.perk_name(&mine)));
+                    if !removed.is_empty() {
+                        notes.push(format!("Removed {}", merged.describe_perks(&removed)));
+                    }
+                }
+                MergeConflict::Exclusive { theirs, .. } => {
+                    declined.insert(theirs);
+                }
+            }
+        }
+        for &stat in SpecialStat::ALL {
+            let highest = merged.special[&stat].max(merge.other.special[&stat]);
+            if merged.special[&stat] < highest {
+                merged.set(stat, highest)?;
+            }
+        }
+        for (&id, &rank) in &merge.other.perks {
+            if declined.contains(&id) || merged.perks.get(&id) == Some(&rank) {
+                continue;
+            }
+            if let Err(e) = merged.add_perk(id, rank) {
+                notes.push(format!("Skipped {}: {}", merged.perk_name(&id), e));
+            }
+        }
+        *self = merged;
+        Ok(notes)
+    }
+}
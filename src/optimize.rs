@@ -0,0 +1,680 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    iter::once,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+
+use crate::{
+    build::Build,
+    special::{catalog, PerkId, SpecialStat},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Objective {
+    Health,
+    Ap,
+    Xp,
+    Melee,
+    Crit,
+    Carry,
+    Prices,
+    Sprint,
+}
+
+impl Objective {
+    pub const ALL: &'static [Self] = &[
+        Objective::Health,
+        Objective::Ap,
+        Objective::Xp,
+        Objective::Melee,
+        Objective::Crit,
+        Objective::Carry,
+        Objective::Prices,
+        Objective::Sprint,
+    ];
+    /// The objective's raw value, with health taken at a level if one is given
+    /// instead of at the build's required level
+    fn raw_value(&self, build: &Build, level: Option<u8>) -> f32 {
+        match self {
+            Objective::Health => {
+                level.map_or_else(|| build.health(), |level| build.health_at(level))
+            }
+            Objective::Ap => build.base_ap(),
+            Objective::Xp => build.experience_mul() as f32,
+            Objective::Melee => build.melee_damage_mul(),
+            Objective::Crit => 1.0 / build.hits_per_crit() as f32,
+            Objective::Carry => build.carry_weight() as f32,
+            Objective::Prices => 1.0 / build.buying_price_mul(),
+            Objective::Sprint => build.sprint_time(),
+        }
+    }
+    pub fn describe(&self, build: &Build) -> String {
+        self.describe_at(build, None)
+    }
+    pub fn describe_at(&self, build: &Build, level: Option<u8>) -> String {
+        match self {
+            Objective::Health => format!("{:.0} health", self.raw_value(build, level)),
+            Objective::Ap => format!("{:.0} AP", build.base_ap()),
+            Objective::Xp => format!("{:.0}% XP", build.experience_mul() * 100.0),
+            Objective::Melee => format!("{:.0}% melee", build.melee_damage_mul() * 100.0),
+            Objective::Crit => format!("{} hits/crit", build.hits_per_crit()),
+            Objective::Carry => format!("{} carry weight", build.carry_weight()),
+            Objective::Prices => format!("{:.0}% buy prices", build.buying_price_mul() * 100.0),
+            Objective::Sprint => format!("{:.1} s sprint", build.sprint_time()),
+        }
+    }
+    pub fn value(&self, build: &Build) -> f32 {
+        self.value_at(build, None)
+    }
+    pub fn value_at(&self, build: &Build, level: Option<u8>) -> f32 {
+        static BASELINE: Lazy<BTreeMap<Objective, f32>> = Lazy::new(|| {
+            let build = Build::default();
+            Objective::ALL
+                .iter()
+                .map(|obj| (*obj, obj.raw_value(&build, None)))
+                .collect()
+        });
+        self.raw_value(build, level) / BASELINE[self]
+    }
+}
+
+impl FromStr for Objective {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase().replace([' ', '_', '-'], "");
+        Ok(match lower.as_str() {
+            "hp" => Objective::Health,
+            "carryweight" | "weight" => Objective::Carry,
+            "barter" | "buy" | "buying" => Objective::Prices,
+            "crits" | "critical" | "luck" => Objective::Crit,
+            _ => {
+                for obj in Objective::ALL {
+                    if format!("{:?}", obj).to_lowercase().starts_with(&lower) {
+                        return Ok(*obj);
+                    }
+                }
+                bail!("Unknown stat to optimize: {}", s)
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Weight {
+    pub objective: Objective,
+    pub weight: f32,
+}
+
+impl FromStr for Weight {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (objective, weight) = match s.split_once('=') {
+            Some((objective, weight)) => match weight.parse::<f32>() {
+                Ok(weight) if weight.is_finite() => (objective, weight),
+                _ => bail!("Invalid weight: {}", weight),
+            },
+            None => (s, 1.0),
+        };
+        Ok(Weight {
+            objective: objective.parse()?,
+            weight,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Search {
+    Greedy,
+    Deep,
+}
+
+impl FromStr for Search {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_lowercase().as_str() {
+            "greedy" | "fast" => Search::Greedy,
+            "deep" | "anneal" => Search::Deep,
+            _ => bail!("Invalid search: {}", s),
+        })
+    }
+}
+
+pub trait Scorer: Send + Sync {
+    fn score(&self, build: &Build) -> f32;
+    fn objectives(&self, build: &Build) -> Vec<f32> {
+        vec![self.score(build)]
+    }
+    fn describe(&self, build: &Build) -> String {
+        format!("score {:.3}", self.score(build))
+    }
+}
+
+pub struct WeightedScorer {
+    weights: Vec<Weight>,
+}
+
+impl WeightedScorer {
+    pub fn new(weights: Vec<Weight>) -> anyhow::Result<Self> {
+        if weights.is_empty() {
+            bail!("You must specify at least one stat to optimize")
+        }
+        Ok(WeightedScorer { weights })
+    }
+}
+
+impl Scorer for WeightedScorer {
+    fn score(&self, build: &Build) -> f32 {
+        self.weights
+            .iter()
+            .map(|w| w.weight * w.objective.value(build))
+            .sum()
+    }
+    fn objectives(&self, build: &Build) -> Vec<f32> {
+        self.weights
+            .iter()
+            .map(|w| w.objective.value(build))
+            .collect()
+    }
+    fn describe(&self, build: &Build) -> String {
+        let values: Vec<String> = self
+            .weights
+            .iter()
+            .map(|w| w.objective.describe(build))
+            .collect();
+        values.join(", ")
+    }
+}
+
+struct ObjectiveScorer {
+    inner: Arc<dyn Scorer>,
+    index: usize,
+}
+
+impl Scorer for ObjectiveScorer {
+    fn score(&self, build: &Build) -> f32 {
+        self.inner.objectives(build)[self.index]
+    }
+}
+
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+/// Deep searches run this many annealing chains on any number of threads, so that
+/// a seed always gives the same chains
+const CHAINS: u64 = 8;
+/// The seed deep searches use unless given one
+pub const DEFAULT_SEED: u64 = 0x5eed;
+
+/// When a deep search stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// After a time, in which a search may make more or fewer moves from run to run
+    Time(Duration),
+    /// After a number of moves per chain, which gives the same result every run
+    Steps(u64),
+}
+
+impl Budget {
+    /// A time budget in seconds, which must be finite and not negative
+    pub fn seconds(seconds: f32) -> anyhow::Result<Budget> {
+        Duration::try_from_secs_f32(seconds)
+            .map(Budget::Time)
+            .map_err(|_| anyhow!("Invalid search time: {}", seconds))
+    }
+    /// How far through the budget a chain is, or `None` once it is spent
+    fn progress(self, elapsed: Duration, step: u64) -> Option<f32> {
+        let progress = match self {
+            Budget::Time(time) => elapsed.as_secs_f32() / time.as_secs_f32(),
+            Budget::Steps(steps) => step as f32 / steps as f32,
+        };
+        (progress < 1.0).then_some(progress)
+    }
+    /// The budget of each of several runs sharing the threads
+    fn per_run(self, runs: usize) -> Budget {
+        match self {
+            Budget::Time(time) => {
+                let threads = rayon::current_num_threads() as u32;
+                Budget::Time((time * threads / runs.max(1) as u32).min(time))
+            }
+            steps => steps,
+        }
+    }
+}
+
+pub struct Optimizer {
+    scorer: Arc<dyn Scorer>,
+    level: u8,
+    locked: BTreeMap<PerkId, u8>,
+    candidates: Vec<PerkId>,
+}
+
+impl Optimizer {
+    pub fn new(build: &Build, scorer: impl Scorer + 'static, level: u8) -> anyhow::Result<Self> {
+        if level < build.required_level() {
+            bail!(
+                "The build already requires level {}, which is above {}",
+                build.required_level(),
+                level
+            )
+        }
+        let candidates = catalog()
+            .perks
+            .iter()
+            .filter(|(id, def)| matches!(id, PerkId::Special { .. }) && def.has_effects())
+            .map(|(id, _)| *id)
+            .collect();
+        Ok(Optimizer {
+            scorer: Arc::new(scorer),
+            level,
+            locked: build.perks.clone(),
+            candidates,
+        })
+    }
+    pub fn score(&self, build: &Build) -> f32 {
+        self.scorer.score(build)
+    }
+    fn is_valid(&self, build: &Build) -> bool {
+        build
+            .special
+            .values()
+            .all(|points| (1..=10).contains(points))
+            && build.perks.keys().all(|id| match id {
+                PerkId::Special { stat, points } => build.total_base_points(*stat) >= *points,
+                _ => true,
+            })
+            && build.check_book_placement().is_ok()
+            && (self.locked.iter()).all(|(id, rank)| build.perks.get(id) >= Some(rank))
+            && build.required_level() <= self.level
+    }
+    fn neighbors(&self, build: &Build) -> Vec<Build> {
+        let mut neighbors = Vec::new();
+        for &stat in SpecialStat::ALL {
+            let mut next = build.clone();
+            *next.special.get_mut(&stat).unwrap() += 1;
+            neighbors.push(next);
+        }
+        for &id in &self.candidates {
+            if let Some(next) = self.with_rank_delta(build, id, 1) {
+                neighbors.push(next);
+            }
+        }
+        neighbors
+            .into_par_iter()
+            .filter(|next| self.is_valid(next))
+            .collect()
+    }
+    fn with_rank_delta(&self, build: &Build, id: PerkId, delta: i8) -> Option<Build> {
+        let def = catalog().perks.get(&id).cloned()?;
+        let rank = build.perks.get(&id).copied().unwrap_or(0) as i8 + delta;
+        if rank < 0 || rank > def.max_rank() as i8 {
+            return None;
+        }
+        let mut next = build.clone();
+        if rank == 0 {
+            next.perks.remove(&id);
+        } else {
+            next.add_perk_impl(id, rank as u8);
+        }
+        Some(next)
+    }
+    pub fn greedy(&self, build: &Build) -> Build {
+        let mut current = build.clone();
+        let mut score = self.score(&current);
+        loop {
+            let neighbors = self.neighbors(&current);
+            // Score in parallel but pick sequentially so ties resolve the same on any thread count
+            let scores: Vec<f32> = neighbors.par_iter().map(|next| self.score(next)).collect();
+            let best = scores
+                .into_iter()
+                .zip(neighbors)
+                .max_by(|(a, _), (b, _)| a.total_cmp(b));
+            match best {
+                Some((next_score, next)) if next_score > score => {
+                    score = next_score;
+                    current = next;
+                }
+                _ => break current,
+            }
+        }
+    }
+    fn random_move(&self, build: &Build, rng: &mut Rng) -> Option<Build> {
+        let stats = SpecialStat::ALL;
+        let moves = if self.candidates.is_empty() { 3 } else { 5 };
+        match rng.below(moves) {
+            0 => {
+                let from = stats[rng.below(stats.len())];
+                let to = stats[rng.below(stats.len())];
+                let mut next = build.clone();
+                *next.special.get_mut(&from)? -= 1;
+                *next.special.get_mut(&to)? += 1;
+                Some(next)
+            }
+            1 => {
+                let mut next = build.clone();
+                *next.special.get_mut(&stats[rng.below(stats.len())])? += 1;
+                Some(next)
+            }
+            2 => {
+                let mut next = build.clone();
+                *next.special.get_mut(&stats[rng.below(stats.len())])? -= 1;
+                Some(next)
+            }
+            3 => {
+                let id = self.candidates[rng.below(self.candidates.len())];
+                self.with_rank_delta(build, id, if rng.below(2) == 0 { 1 } else { -1 })
+            }
+            _ => {
+                let from = self.candidates[rng.below(self.candidates.len())];
+                let to = self.candidates[rng.below(self.candidates.len())];
+                let next = self.with_rank_delta(build, from, -1)?;
+                self.with_rank_delta(&next, to, 1)
+            }
+        }
+    }
+    /// Anneal a fixed number of chains from the greedy result, each seeded from `seed`,
+    /// and keep the best, preferring earlier chains on ties. The first chain reports
+    /// its progress to `progress`.
+    pub fn anneal(
+        &self,
+        build: &Build,
+        budget: Budget,
+        seed: u64,
+        progress: &mut (dyn Write + Send),
+    ) -> Build {
+        let start = self.greedy(build);
+        let best_score = Mutex::new(self.score(&start));
+        let progress = Mutex::new(progress);
+        let budget = budget.per_run(CHAINS as usize);
+        let chains: Vec<Build> = (0..CHAINS)
+            .into_par_iter()
+            .map(|chain| {
+                let rng = Rng::seeded(seed, chain);
+                let report = (chain == 0).then_some(&progress);
+                self.anneal_chain(&start, budget, rng, &best_score, report)
+            })
+            .collect();
+        let _ = writeln!(progress.into_inner().unwrap());
+        self.best_of(chains)
+    }
+    fn anneal_chain(
+        &self,
+        start: &Build,
+        budget: Budget,
+        mut rng: Rng,
+        shared_best: &Mutex<f32>,
+        report: Option<&Mutex<&mut (dyn Write + Send)>>,
+    ) -> Build {
+        let mut best = start.clone();
+        let mut best_score = self.score(&best);
+        let mut current = best.clone();
+        let mut current_score = best_score;
+        let start = Instant::now();
+        let mut last_report = start;
+        let mut step = 0;
+        while let Some(progress) = budget.progress(start.elapsed(), step) {
+            step += 1;
+            let temperature = 0.1 * (1.0 - progress) + 0.001;
+            let next = match self.random_move(&current, &mut rng) {
+                Some(next) if self.is_valid(&next) => next,
+                _ => continue,
+            };
+            let next_score = self.score(&next);
+            let delta = next_score - current_score;
+            if delta > 0.0 || rng.unit() < (delta / temperature).exp() {
+                current = next;
+                current_score = next_score;
+                if current_score > best_score {
+                    best = current.clone();
+                    best_score = current_score;
+                    let mut shared = shared_best.lock().unwrap();
+                    *shared = shared.max(best_score);
+                }
+            }
+            if let Some(out) = report.filter(|_| last_report.elapsed() >= REPORT_INTERVAL) {
+                last_report = Instant::now();
+                let mut out = out.lock().unwrap();
+                let _ = write!(
+                    out,
+                    "\rSearching... {:>3.0}% (best score {:.3})",
+                    progress * 100.0,
+                    *shared_best.lock().unwrap()
+                );
+                let _ = out.flush();
+            }
+        }
+        best
+    }
+    fn best_of(&self, builds: Vec<Build>) -> Build {
+        let mut builds = builds.into_iter();
+        let mut best = builds.next().expect("No builds to choose from");
+        let mut best_score = self.score(&best);
+        for build in builds {
+            let score = self.score(&build);
+            if score > best_score {
+                best = build;
+                best_score = score;
+            }
+        }
+        best
+    }
+    fn objectives(&self, build: &Build) -> Vec<f32> {
+        let mut objectives = self.scorer.objectives(build);
+        objectives.push(-(build.required_level() as f32));
+        objectives
+    }
+    pub fn pareto(
+        &self,
+        build: &Build,
+        count: usize,
+        search: Search,
+        budget: Budget,
+        seed: u64,
+    ) -> Vec<Build> {
+        let start_level = build.required_level();
+        let step = ((self.level - start_level) as usize / 8).max(1);
+        let levels: Vec<u8> = (start_level..self.level)
+            .step_by(step)
+            .chain(once(self.level))
+            .collect();
+        let mut variants = vec![self.scorer.clone()];
+        let objective_count = self.scorer.objectives(build).len();
+        if objective_count > 1 {
+            for index in 0..objective_count {
+                variants.push(Arc::new(ObjectiveScorer {
+                    inner: self.scorer.clone(),
+                    index,
+                }));
+            }
+        }
+        let runs: Vec<(u8, &Arc<dyn Scorer>)> = levels
+            .iter()
+            .flat_map(|&level| variants.iter().map(move |scorer| (level, scorer)))
+            .collect();
+        // Runs share the threads, so each gets a slice of the budget
+        let run_budget = budget.per_run(runs.len());
+        let results: Vec<Build> = runs
+            .par_iter()
+            .enumerate()
+            .map(|(i, (level, scorer))| {
+                let optimizer = Optimizer {
+                    scorer: Arc::clone(scorer),
+                    level: *level,
+                    locked: self.locked.clone(),
+                    candidates: self.candidates.clone(),
+                };
+                let start = optimizer.greedy(build);
+                match search {
+                    Search::Greedy => start,
+                    Search::Deep => {
+                        let rng = Rng::seeded(seed, i as u64);
+                        let best = Mutex::new(optimizer.score(&start));
+                        optimizer.anneal_chain(&start, run_budget, rng, &best, None)
+                    }
+                }
+            })
+            .collect();
+        let mut pool: Vec<Build> = Vec::new();
+        for result in results {
+            if !pool.iter().any(|b| same_build(b, &result)) {
+                pool.push(result);
+            }
+        }
+        let objectives: Vec<Vec<f32>> = pool.iter().map(|b| self.objectives(b)).collect();
+        let mut front: Vec<Build> = pool
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !objectives.iter().any(|o| dominates(o, &objectives[*i])))
+            .map(|(_, b)| b.clone())
+            .collect();
+        front.sort_by(|a, b| self.score(b).total_cmp(&self.score(a)));
+        front.truncate(count);
+        front
+    }
+    pub fn summary(&self, build: &Build) -> String {
+        let stats: Vec<String> = SpecialStat::ALL
+            .iter()
+            .map(|stat| format!("{}{}", &stat.to_string()[..1], build.special[stat]))
+            .collect();
+        format!(
+            "score {:.3}, level {}: {} ({})",
+            self.score(build),
+            build.required_level(),
+            self.scorer.describe(build),
+            stats.join(" ")
+        )
+    }
+}
+
+fn dominates(a: &[f32], b: &[f32]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a >= b) && a.iter().zip(b).any(|(a, b)| a > b)
+}
+
+fn same_build(a: &Build, b: &Build) -> bool {
+    a.special == b.special && a.perks == b.perks
+}
+
+struct Rng(u64);
+
+impl Rng {
+    /// One of several independent streams from the same seed
+    fn seeded(seed: u64, stream: u64) -> Self {
+        Rng((seed ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15)) | 1)
+    }
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+    fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    fn optimizer(build: &Build) -> Optimizer {
+        let weights = ["carry=2", "health", "crit"]
+            .iter()
+            .map(|weight| weight.parse().unwrap())
+            .collect();
+        Optimizer::new(build, WeightedScorer::new(weights).unwrap(), 25).unwrap()
+    }
+
+    #[test]
+    fn deep_search_is_the_same_for_a_seed_on_any_thread_count() {
+        let build = Build::default();
+        let optimizer = optimizer(&build);
+        let run = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| optimizer.anneal(&build, Budget::Steps(200), 7, &mut io::sink()))
+        };
+        let first = run(1);
+        assert!(same_build(&first, &run(1)));
+        assert!(same_build(&first, &run(4)));
+        assert!(optimizer.is_valid(&first));
+        assert!(optimizer.score(&first) >= optimizer.score(&optimizer.greedy(&build)));
+    }
+
+    #[test]
+    fn weights_must_be_finite() {
+        let weight: Weight = "Carry Weight=2.5".parse().unwrap();
+        assert_eq!(weight.objective, Objective::Carry);
+        assert_eq!(weight.weight, 2.5);
+        assert_eq!("hp".parse::<Weight>().unwrap().weight, 1.0);
+        for weight in [
+            "health=inf",
+            "health=NaN",
+            "health=",
+            "health=lots",
+            "charm=1",
+        ] {
+            assert!(weight.parse::<Weight>().is_err(), "{}", weight);
+        }
+        assert!(WeightedScorer::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn searches_keep_locked_perks_and_the_level_cap() {
+        let mut build = Build::default();
+        let locked = PerkId::Special {
+            stat: SpecialStat::Agility,
+            points: 1,
+        };
+        build.add_perk(locked, 1).unwrap();
+        let optimizer = optimizer(&build);
+        let greedy = optimizer.greedy(&build);
+        assert!(optimizer.is_valid(&greedy));
+        assert!(greedy.perks.get(&locked) >= Some(&1));
+        assert!(greedy.required_level() <= 25);
+        build.add_perk(locked, 2).unwrap();
+        let scorer = WeightedScorer::new(vec!["health".parse().unwrap()]).unwrap();
+        assert!(Optimizer::new(&build, scorer, 5).is_err());
+    }
+
+    #[test]
+    fn pareto_fronts_only_hold_undominated_builds() {
+        let build = Build::default();
+        let optimizer = optimizer(&build);
+        let front = optimizer.pareto(&build, 3, Search::Greedy, Budget::Steps(0), DEFAULT_SEED);
+        assert!(!front.is_empty() && front.len() <= 3);
+        for a in &front {
+            assert!(optimizer.is_valid(a));
+            for b in &front {
+                assert!(!dominates(
+                    &optimizer.objectives(a),
+                    &optimizer.objectives(b)
+                ));
+            }
+        }
+        assert!(dominates(&[2.0, 1.0], &[1.0, 1.0]));
+        assert!(!dominates(&[1.0, 1.0], &[1.0, 1.0]));
+        assert!(!dominates(&[2.0, 0.0], &[1.0, 1.0]));
+    }
+
+    #[test]
+    fn search_times_must_fit_a_duration() {
+        assert_eq!(
+            Budget::seconds(1.5).unwrap(),
+            Budget::Time(Duration::from_millis(1500))
+        );
+        for seconds in [f32::INFINITY, f32::NAN, 1e30, -1.0] {
+            assert!(Budget::seconds(seconds).is_err(), "{}", seconds);
+        }
+    }
+}
@@ -0,0 +1,475 @@
+//! The shape of the perk data files. The build script includes this module to
+//! check the built-in data, so it only depends on std, serde, and rmp-serde.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SpecialStat {
+    Strength,
+    Perception,
+    Endurance,
+    Charisma,
+    Intelligence,
+    Agility,
+    Luck,
+}
+
+impl SpecialStat {
+    pub const ALL: &'static [Self] = &[
+        SpecialStat::Strength,
+        SpecialStat::Perception,
+        SpecialStat::Endurance,
+        SpecialStat::Charisma,
+        SpecialStat::Intelligence,
+        SpecialStat::Agility,
+        SpecialStat::Luck,
+    ];
+}
+
+/// A perk name, shared between every definition and alias that uses it
+pub type Name = Arc<str>;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PerkDef {
+    pub name: MaybeGendered<Name>,
+    pub ranks: Ranks,
+    #[serde(default)]
+    pub gender: GenderCondition,
+    /// Names from other loaded languages, which match the perk but are never displayed
+    #[serde(skip)]
+    pub aliases: Vec<Name>,
+}
+
+/// How a perk depends on the player's gender beyond its name
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct GenderCondition {
+    /// The only gender of character that can take the perk
+    #[serde(default)]
+    pub only: Option<Gender>,
+    /// Whose characters the perk's `damage_against_add` effect applies to
+    #[serde(default)]
+    pub against: Option<GenderTarget>,
+}
+
+/// A gender relative to the player's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum GenderTarget {
+    Same,
+    Opposite,
+}
+
+pub type FullyVariable<T> = MaybeDifficultied<MaybeGendered<T>>;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rank {
+    #[serde(default = "default_required_level", alias = "level")]
+    pub required_level: u8,
+    #[serde(alias = "desc")]
+    pub description: FullyVariable<String>,
+    #[serde(default, skip_serializing_if = "Effects::is_empty", flatten)]
+    pub effects: Effects,
+}
+
+fn default_required_level() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Ranks {
+    UniformCumulative {
+        count: u8,
+        #[serde(alias = "desc")]
+        description: FullyVariable<String>,
+        #[serde(default, skip_serializing_if = "Effects::is_empty", flatten)]
+        effects: Effects,
+    },
+    Single {
+        #[serde(alias = "desc")]
+        description: FullyVariable<String>,
+        #[serde(default, skip_serializing_if = "Effects::is_empty", flatten)]
+        effects: Effects,
+    },
+    VaryingCumulative(Vec<Rank>),
+}
+
+impl Ranks {
+    pub fn max_rank(&self) -> u8 {
+        match self {
+            Ranks::Single { .. } => 1,
+            Ranks::UniformCumulative { count, .. } => *count,
+            Ranks::VaryingCumulative(ranks) => ranks.len() as u8,
+        }
+    }
+    pub fn required_level(&self, rank: u8) -> u8 {
+        match self {
+            Ranks::VaryingCumulative(ranks) => ranks[rank as usize - 1].required_level,
+            _ => 1,
+        }
+    }
+    pub fn descriptions_mut(&mut self) -> Vec<&mut FullyVariable<String>> {
+        match self {
+            Ranks::Single { description, .. } | Ranks::UniformCumulative { description, .. } => {
+                vec![description]
+            }
+            Ranks::VaryingCumulative(ranks) => {
+                ranks.iter_mut().map(|rank| &mut rank.description).collect()
+            }
+        }
+    }
+    pub fn effects(&self) -> Vec<&Effects> {
+        match self {
+            Ranks::Single { effects, .. } | Ranks::UniformCumulative { effects, .. } => {
+                vec![effects]
+            }
+            Ranks::VaryingCumulative(ranks) => ranks.iter().map(|rank| &rank.effects).collect(),
+        }
+    }
+    pub fn highest_rank_within_level(&self, level: u8) -> u8 {
+        match self {
+            Ranks::Single { .. } => 1,
+            Ranks::UniformCumulative { count, .. } => *count,
+            Ranks::VaryingCumulative(ranks) => ranks
+                .iter()
+                .filter(|rank| rank.required_level <= level)
+                .count() as u8,
+        }
+    }
+}
+
+/// How the values of an effect from several perks combine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Combine {
+    #[default]
+    Add,
+    Mul,
+}
+
+/// A kind of effect, declared in the `effects` section of the perk data
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EffectDef {
+    #[serde(default)]
+    pub combine: Combine,
+    /// The range every value of the effect is expected to be in, checked by the linter
+    #[serde(default)]
+    pub range: Option<[f32; 2]>,
+    /// If set, the effect's total is listed in the build summary under this label
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum EffectValue {
+    Number(f32),
+    Stat(StatIncrease),
+}
+
+/// The effects of a perk rank by the name of their kind
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Effects(pub(crate) BTreeMap<String, EffectValue>);
+
+impl Effects {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+    pub fn get(&self, name: &str) -> Option<EffectValue> {
+        self.0.get(name).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct StatIncrease {
+    pub stat: SpecialStat,
+    #[serde(default = "default_stat_increase")]
+    pub increase: u8,
+}
+
+fn default_stat_increase() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MaybeVaried<T, M> {
+    One(T),
+    Multi(M),
+}
+
+pub type MaybeGendered<T> = MaybeVaried<T, Gendered<T>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct Gendered<T> {
+    pub male: T,
+    pub female: T,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum Gender {
+    #[default]
+    Male,
+    Female,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    VeryEasy,
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    VeryHard,
+    Survival,
+}
+
+/// A value for each difficulty. Only the normal value is required, and any
+/// difficulty without its own value uses it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct Difficultied<T> {
+    pub very_easy: Option<T>,
+    pub easy: Option<T>,
+    pub normal: T,
+    pub hard: Option<T>,
+    pub very_hard: Option<T>,
+    pub survival: Option<T>,
+}
+
+pub type MaybeDifficultied<T> = MaybeVaried<T, Difficultied<T>>;
+
+/// A legendary effect a piece of armor can have, declared in the `legendaries` section of the perk data
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LegendaryDef {
+    pub desc: String,
+    /// The same kinds of effects perks have, which combine with theirs
+    #[serde(default, flatten)]
+    pub effects: Effects,
+}
+
+/// How a difficulty changes combat, declared in the `difficulties` section of the perk data
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DifficultyDef {
+    /// The multiplier on damage the player deals
+    #[serde(default = "one")]
+    pub damage_dealt: f32,
+    /// The multiplier on damage the player takes
+    #[serde(default = "one")]
+    pub damage_taken: f32,
+    /// Anything else the difficulty changes, like how often legendary enemies spawn
+    #[serde(default)]
+    pub notes: Vec<String>,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct AllPerksRep {
+    #[serde(default)]
+    pub(crate) version: Option<String>,
+    #[serde(default)]
+    pub(crate) effects: BTreeMap<String, EffectDef>,
+    #[serde(default)]
+    pub(crate) difficulties: BTreeMap<Difficulty, DifficultyDef>,
+    #[serde(default)]
+    pub(crate) legendaries: BTreeMap<String, LegendaryDef>,
+    #[serde(default)]
+    pub(crate) special: BTreeMap<SpecialStat, Vec<PerkDef>>,
+    #[serde(default)]
+    pub(crate) bobbleheads: BTreeMap<MaybeGendered<String>, Rank>,
+    #[serde(default)]
+    pub(crate) magazines: BTreeMap<String, Ranks>,
+    #[serde(default)]
+    pub(crate) companions: BTreeMap<String, Ranks>,
+    #[serde(default)]
+    pub(crate) factions: BTreeMap<String, Ranks>,
+    #[serde(default)]
+    pub(crate) other: BTreeMap<String, Ranks>,
+    #[serde(default)]
+    pub(crate) synergies: Vec<SynergyRep>,
+    #[serde(default)]
+    pub(crate) archetypes: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub(crate) exclusive: Vec<Vec<String>>,
+    #[serde(default)]
+    pub(crate) dlc: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub(crate) struct SynergyRep {
+    pub(crate) perks: [String; 2],
+    pub(crate) note: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Location {
+    pub location: String,
+    #[serde(default)]
+    pub level: Option<u8>,
+    #[serde(default)]
+    pub quest: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LocationsRep {
+    #[serde(default)]
+    pub(crate) bobbleheads: BTreeMap<String, Location>,
+    #[serde(default)]
+    pub(crate) magazines: BTreeMap<String, Issues>,
+    #[serde(default)]
+    pub(crate) companions: BTreeMap<String, Location>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Issues {
+    One(Location),
+    Many(Vec<Location>),
+}
+
+/// Every name a perk goes by
+fn names<S: AsRef<str>>(name: &MaybeGendered<S>) -> Vec<&str> {
+    match name {
+        MaybeVaried::One(name) => vec![name.as_ref()],
+        MaybeVaried::Multi(Gendered { male, female }) => vec![male.as_ref(), female.as_ref()],
+    }
+}
+
+pub(crate) fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// The start of perk data serialized by [`AllPerksRep::to_blob`]
+const BLOB_MAGIC: &[u8] = b"FO4PERKS";
+/// Changed whenever the serialized form of the perk data changes, so that data
+/// serialized by another version is rejected instead of misread
+const BLOB_VERSION: u32 = 1;
+
+impl AllPerksRep {
+    /// Serialize the perk data along with the hash of the source it was parsed from,
+    /// in a form that is much faster to load than YAML
+    #[cfg_attr(not(feature = "native"), allow(dead_code))]
+    pub(crate) fn to_blob(&self, hash: u64) -> Result<Vec<u8>, String> {
+        let mut blob = BLOB_MAGIC.to_vec();
+        blob.extend(BLOB_VERSION.to_le_bytes());
+        blob.extend(hash.to_le_bytes());
+        rmp_serde::encode::write_named(&mut blob, self).map_err(|e| e.to_string())?;
+        Ok(blob)
+    }
+    /// Read perk data serialized by [`AllPerksRep::to_blob`], and the hash of its source
+    pub(crate) fn from_blob(blob: &[u8]) -> Result<(u64, Self), String> {
+        let rest = blob
+            .strip_prefix(BLOB_MAGIC)
+            .ok_or("Not serialized perk data")?;
+        let (version, rest) = rest.split_at_checked(4).ok_or("Perk data is truncated")?;
+        let version = u32::from_le_bytes(version.try_into().unwrap());
+        if version != BLOB_VERSION {
+            return Err(format!(
+                "Perk data is serialized in version {} of the format, not {}",
+                version, BLOB_VERSION
+            ));
+        }
+        let (hash, rest) = rest.split_at_checked(8).ok_or("Perk data is truncated")?;
+        let hash = u64::from_le_bytes(hash.try_into().unwrap());
+        let rep = rmp_serde::from_slice(rest).map_err(|e| e.to_string())?;
+        Ok((hash, rep))
+    }
+    /// The names of every perk, normalized for lookup
+    pub(crate) fn perk_names(&self) -> BTreeSet<String> {
+        let mut perk_names: BTreeSet<String> = SpecialStat::ALL
+            .iter()
+            .map(|stat| normalize(&format!("{:?}", stat)))
+            .collect();
+        for def in self.special.values().flatten() {
+            perk_names.extend(names(&def.name).into_iter().map(normalize));
+        }
+        for name in self.bobbleheads.keys() {
+            perk_names.extend(names(name).into_iter().map(normalize));
+        }
+        for section in [
+            &self.magazines,
+            &self.companions,
+            &self.factions,
+            &self.other,
+        ] {
+            perk_names.extend(section.keys().map(|name| normalize(name)));
+        }
+        perk_names
+    }
+    /// Check that every perk named outside its own definition exists, and that
+    /// every effect is declared
+    pub(crate) fn check(&self) -> Result<(), String> {
+        let perk_names = self.perk_names();
+        let check = |perks: &[String], context: &str| match perks
+            .iter()
+            .find(|perk| !perk_names.contains(&normalize(perk)))
+        {
+            Some(perk) => Err(format!("Unknown perk in {}: {}", context, perk)),
+            None => Ok(()),
+        };
+        for synergy in &self.synergies {
+            check(&synergy.perks, "synergies")?;
+        }
+        for (name, perks) in &self.archetypes {
+            check(perks, &format!("archetype {}", name))?;
+        }
+        for perks in &self.exclusive {
+            check(perks, "exclusive")?;
+        }
+        for (name, perks) in &self.dlc {
+            check(perks, &format!("DLC {}", name))?;
+        }
+        let mut ranks: Vec<(&str, Vec<&Effects>)> = Vec::new();
+        for def in self.special.values().flatten() {
+            ranks.push((names(&def.name)[0], def.ranks.effects()));
+        }
+        for (name, rank) in &self.bobbleheads {
+            ranks.push((names(name)[0], vec![&rank.effects]));
+        }
+        for section in [
+            &self.magazines,
+            &self.companions,
+            &self.factions,
+            &self.other,
+        ] {
+            for (name, def) in section {
+                ranks.push((name, def.effects()));
+            }
+        }
+        for (perk, effects) in ranks {
+            if let Some(name) = effects
+                .into_iter()
+                .flat_map(Effects::names)
+                .find(|name| !self.effects.contains_key(*name))
+            {
+                return Err(format!(
+                    "{} has the effect `{}`, which is not declared under `effects`",
+                    perk, name
+                ));
+            }
+        }
+        for (legendary, def) in &self.legendaries {
+            if let Some(name) = def
+                .effects
+                .names()
+                .find(|name| !self.effects.contains_key(*name))
+            {
+                return Err(format!(
+                    "The legendary effect {} has the effect `{}`, which is not declared under `effects`",
+                    legendary, name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::io::{self, stdout, Write};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+
+/// An entry of a listing that can be filtered and picked
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub label: String,
+    /// The command that shows the entry
+    pub show: String,
+    /// The command that adds the entry to the build, if it can be added
+    pub add: Option<String>,
+}
+
+/// What came of filtering a listing
+pub enum Picked {
+    /// A command to run for the picked entry
+    Run(String),
+    /// The entries that match, when there is no terminal to pick from
+    Matches(String),
+    Cancelled,
+}
+
+/// How well a query matches a label, if every character of the query appears in it
+/// in order. Runs of characters and matches at the start of words score higher.
+fn score(query: &str, label: &str) -> Option<i32> {
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut from = 0;
+    let mut last = None;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let at = from + label[from..].iter().position(|&l| l == c)?;
+        score += 1;
+        if last == Some(at.wrapping_sub(1)) {
+            score += 4;
+        }
+        if at == 0 || !label[at - 1].is_alphanumeric() {
+            score += 3;
+        }
+        last = Some(at);
+        from = at + 1;
+    }
+    Some(score)
+}
+
+/// The entries that match a query, best first, keeping the listing's order among equals
+fn matches<'a>(entries: &'a [Entry], query: &str) -> Vec<&'a Entry> {
+    let mut scored: Vec<(i32, &Entry)> = entries
+        .iter()
+        .filter_map(|entry| Some((score(query, &entry.label)?, entry)))
+        .collect();
+    scored.sort_by_key(|(score, _)| -score);
+    scored.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Filter a listing, narrowing it as the query is typed and picking an entry to show
+/// or add. When not `interactive`, the entries matching the query are listed instead.
+pub fn pick(entries: &[Entry], query: &str, interactive: bool) -> io::Result<Picked> {
+    if !interactive {
+        let found = matches(entries, query);
+        return Ok(Picked::Matches(if found.is_empty() {
+            format!("Nothing listed matches \"{}\"", query)
+        } else {
+            found
+                .iter()
+                .map(|entry| format!("  {}", entry.label))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }));
+    }
+    let mut out = stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+    let res = Picker {
+        entries,
+        query: query.into(),
+        selected: 0,
+    }
+    .run(&mut out);
+    execute!(out, Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    res
+}
+
+struct Picker<'a> {
+    entries: &'a [Entry],
+    query: String,
+    selected: usize,
+}
+
+impl Picker<'_> {
+    fn run(&mut self, out: &mut impl Write) -> io::Result<Picked> {
+        loop {
+            let found = matches(self.entries, &self.query);
+            self.selected = self.selected.min(found.len().saturating_sub(1));
+            self.draw(out, &found)?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let control = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => return Ok(Picked::Cancelled),
+                KeyCode::Char('c') if control => return Ok(Picked::Cancelled),
+                KeyCode::Char('n') if control => self.selected += 1,
+                KeyCode::Char('p') if control => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Char(c) => {
+                    self.query.push(c);
+                    self.selected = 0;
+                }
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.selected = 0;
+                }
+                KeyCode::Down => self.selected += 1,
+                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Enter => {
+                    if let Some(entry) = found.get(self.selected) {
+                        return Ok(Picked::Run(entry.show.clone()));
+                    }
+                }
+                KeyCode::Tab => {
+                    if let Some(add) = found.get(self.selected).and_then(|e| e.add.clone()) {
+                        return Ok(Picked::Run(add));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    fn draw(&self, out: &mut impl Write, found: &[&Entry]) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let rows = (height as usize).saturating_sub(3);
+        let first = self.selected.saturating_sub(rows.saturating_sub(1));
+        queue!(
+            out,
+            Clear(ClearType::All),
+            MoveTo(0, 0),
+            Print(format!(
+                "/{}  ({}/{})",
+                self.query,
+                found.len(),
+                self.entries.len()
+            ))
+        )?;
+        for (row, entry) in found.iter().skip(first).take(rows).enumerate() {
+            let label: String = entry
+                .label
+                .chars()
+                .take((width as usize).saturating_sub(2))
+                .collect();
+            queue!(out, MoveTo(0, row as u16 + 1))?;
+            if first + row == self.selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(format!("> {}", label)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(format!("  {}", label)))?;
+            }
+        }
+        let adds = found.get(self.selected).is_some_and(|e| e.add.is_some());
+        queue!(
+            out,
+            MoveTo(0, height.saturating_sub(1)),
+            Print(if adds {
+                "Enter: show   Tab: add   Esc: cancel"
+            } else {
+                "Enter: show   Esc: cancel"
+            })
+        )?;
+        out.flush()
+    }
+}
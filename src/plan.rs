@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::bail;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build::Build,
+    special::{catalog, join_perk_id_and_rank, PerkId, SpecialStat},
+};
+
+/// What the point from a level-up is spent on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanStep {
+    Perk { perk: PerkId, rank: u8 },
+    Special(SpecialStat),
+}
+
+impl Build {
+    fn describe_step(&self, step: &PlanStep) -> String {
+        match step {
+            PlanStep::Perk { perk, rank } => format!("{} {}", self.perk_name(perk), rank),
+            PlanStep::Special(stat) => format!("+1 {}", stat),
+        }
+    }
+    /// Plan what a level-up is spent on: a S.P.E.C.I.A.L. stat by its full name, or a
+    /// perk with an optional rank, which defaults to the one after the highest planned
+    pub fn set_plan_step(&mut self, level: u8, words: &[String]) -> anyhow::Result<PlanStep> {
+        if level < 2 {
+            bail!("The first level-up is at level 2")
+        }
+        let text = words.join(" ");
+        let stat = SpecialStat::ALL
+            .iter()
+            .find(|stat| stat.to_string().eq_ignore_ascii_case(&text));
+        let step = match stat {
+            Some(stat) => PlanStep::Special(*stat),
+            None => {
+                let (perk, rank) = join_perk_id_and_rank(words)?;
+                if !matches!(perk, PerkId::Special { .. }) {
+                    bail!("{} is not picked with a level-up", self.perk_name(&perk))
+                }
+                let rank = rank.unwrap_or_else(|| {
+                    self.plan
+                        .iter()
+                        .filter(|(other, _)| **other != level)
+                        .filter_map(|(_, step)| match step {
+                            PlanStep::Perk { perk: p, rank } if *p == perk => Some(*rank),
+                            _ => None,
+                        })
+                        .max()
+                        .unwrap_or(0)
+                        + 1
+                });
+                let max = catalog().perks.get(&perk).expect("Unknown perk").max_rank();
+                if rank == 0 || rank > max {
+                    bail!("{} has ranks 1 to {}", self.perk_name(&perk), max)
+                }
+                PlanStep::Perk { perk, rank }
+            }
+        };
+        self.plan.insert(level, step);
+        Ok(step)
+    }
+    /// Plan the build's level-ups from scratch, taking each perk rank at the first level
+    /// it allows and spending level-up S.P.E.C.I.A.L. points, taken from the highest
+    /// stats, on the levels in between or when a perk needs them
+    pub fn auto_plan(&mut self) {
+        self.plan.clear();
+        let mut special = self.special.clone();
+        let mut extra: BTreeMap<SpecialStat, u8> = BTreeMap::new();
+        for _ in 0..self.level_up_assigned_special_points() {
+            let Some((&stat, _)) = special
+                .iter()
+                .filter(|(_, points)| **points > 1)
+                .max_by_key(|(stat, points)| (**points, std::cmp::Reverse(**stat)))
+            else {
+                break;
+            };
+            *special.get_mut(&stat).unwrap() -= 1;
+            *extra.entry(stat).or_default() += 1;
+        }
+        let mut picks = self.planned_picks();
+        let mut level = 2;
+        while (!picks.is_empty() || !extra.is_empty()) && level < u8::MAX {
+            let ready = picks.iter().position(|&(earliest, perk, rank)| {
+                let points_met = match perk {
+                    PerkId::Special { stat, points } => {
+                        self.stat_at_with(stat, level, special[&stat]) >= points
+                    }
+                    _ => true,
+                };
+                earliest <= level
+                    && points_met
+                    && (rank == 1
+                        || self.plan.values().any(|step| {
+                            *step
+                                == PlanStep::Perk {
+                                    perk,
+                                    rank: rank - 1,
+                                }
+                        }))
+            });
+            if let Some(i) = ready {
+                let (_, perk, rank) = picks.remove(i);
+                self.plan.insert(level, PlanStep::Perk { perk, rank });
+            } else if let Some(&first) = extra.keys().next() {
+                let stat = picks
+                    .iter()
+                    .find_map(|(_, perk, _)| match perk {
+                        PerkId::Special { stat, points }
+                            if extra.contains_key(stat)
+                                && self.stat_at_with(*stat, level, special[stat]) < *points =>
+                        {
+                            Some(*stat)
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(first);
+                *special.get_mut(&stat).unwrap() += 1;
+                let left = extra.get_mut(&stat).unwrap();
+                *left -= 1;
+                if *left == 0 {
+                    extra.remove(&stat);
+                }
+                self.plan.insert(level, PlanStep::Special(stat));
+            }
+            level += 1;
+        }
+    }
+    /// The points of a stat the build has at a level, given which of its level-up
+    /// points are planned for later and when its bobblehead and the book are collected
+    fn stat_at(&self, stat: SpecialStat, level: u8) -> u8 {
+        let later = self
+            .plan
+            .range(level + 1..)
+            .filter(|(_, step)| **step == PlanStep::Special(stat))
+            .count() as u8;
+        self.stat_at_with(stat, level, self.special[&stat].saturating_sub(later))
+    }
+    /// A stat's base points plus its bobblehead and the book if collected by a level
+    fn stat_at_with(&self, stat: SpecialStat, level: u8, mut points: u8) -> u8 {
+        if self.bobblehead_for(stat)
+            && self.bobblehead_levels.get(&stat).copied().unwrap_or(1) <= level
+        {
+            points += 1;
+        }
+        if self.special_book == Some(stat) && self.book_level.unwrap_or(1) <= level {
+            points += 1;
+        }
+        points
+    }
+    /// Check each planned level-up against the build, describing each problem
+    pub fn check_plan(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (&level, step) in &self.plan {
+            let mut problem = |text: String| {
+                problems.push(format!("Level {}: {}", level, text));
+            };
+            match *step {
+                PlanStep::Perk { perk, rank } => {
+                    let name = self.perk_name(&perk);
+                    let def = catalog().perks.get(&perk).cloned().expect("Unknown perk");
+                    if self.perks.get(&perk).is_none_or(|taken| *taken < rank) {
+                        problem(format!("the build does not take {} {}", name, rank));
+                    }
+                    let required = def.ranks.required_level(rank);
+                    if required > level {
+                        problem(format!("{} {} needs level {}", name, rank, required));
+                    }
+                    let earlier = |r: u8| {
+                        self.plan
+                            .range(..level)
+                            .any(|(_, step)| *step == PlanStep::Perk { perk, rank: r })
+                    };
+                    if rank > 1 && !earlier(rank - 1) {
+                        problem(format!("{} {} is not planned before it", name, rank - 1));
+                    }
+                    if self.plan.range(..level).any(|(_, other)| other == step) {
+                        problem(format!("{} {} is planned twice", name, rank));
+                    }
+                    if let PerkId::Special { stat, points } = perk {
+                        let have = self.stat_at(stat, level);
+                        if have < points {
+                            problem(format!(
+                                "{} needs {} {}, but the build only has {} by then",
+                                name, stat, points, have
+                            ));
+                        }
+                    }
+                }
+                PlanStep::Special(stat) => {
+                    let planned = self
+                        .plan
+                        .values()
+                        .filter(|step| **step == PlanStep::Special(stat))
+                        .count() as u8;
+                    if planned >= self.special[&stat] {
+                        problem(format!(
+                            "more {} points are planned than the build has past 1",
+                            stat
+                        ));
+                    }
+                }
+            }
+        }
+        let special_steps = self
+            .plan
+            .values()
+            .filter(|step| matches!(step, PlanStep::Special(_)))
+            .count() as u8;
+        if special_steps > self.level_up_assigned_special_points() {
+            problems.push(format!(
+                "The plan has {} S.P.E.C.I.A.L. point{}, but the build only spends {} past its initial points",
+                special_steps,
+                if special_steps == 1 { "" } else { "s" },
+                self.level_up_assigned_special_points()
+            ));
+        }
+        problems.dedup();
+        problems
+    }
+    /// The perk ranks the build takes that are not planned for any level
+    fn unplanned_ranks(&self) -> Vec<(PerkId, u8)> {
+        self.planned_picks()
+            .into_iter()
+            .map(|(_, perk, rank)| (perk, rank))
+            .filter(|&(perk, rank)| {
+                !self
+                    .plan
+                    .values()
+                    .any(|step| *step == PlanStep::Perk { perk, rank })
+            })
+            .collect()
+    }
+    /// Add a milestone to a level, or remove the level's milestones if there is no text
+    pub fn set_milestone(&mut self, level: u8, words: &[String]) -> anyhow::Result<String> {
+        if level == 0 {
+            bail!("There is no level 0")
+        }
+        if words.is_empty() {
+            return match self.milestones.remove(&level) {
+                Some(removed) => Ok(format!(
+                    "Removed {} milestone{} from level {}",
+                    removed.len(),
+                    if removed.len() == 1 { "" } else { "s" },
+                    level
+                )),
+                None => bail!("There are no milestones at level {}", level),
+            };
+        }
+        let text = words.join(" ");
+        self.milestones.entry(level).or_default().push(text.clone());
+        Ok(format!("Milestone at level {}: {}", level, text))
+    }
+    /// The milestones due after one level up to and including another
+    pub fn milestones_between(&self, after: u8, up_to: u8) -> Vec<(u8, &str)> {
+        if after >= up_to {
+            return Vec::new();
+        }
+        self.milestones
+            .range(after + 1..=up_to)
+            .flat_map(|(level, texts)| texts.iter().map(|text| (*level, text.as_str())))
+            .collect()
+    }
+    /// Describe the plan level by level, with what is left to plan and any problems
+    pub fn describe_plan(&self) -> String {
+        if self.plan.is_empty() && self.milestones.is_empty() {
+            return "Nothing is planned yet, try \"plan auto\" or \"plan set <LEVEL> <PERK>\""
+                .into();
+        }
+        let mut lines = vec!["Level plan".bright_yellow().to_string()];
+        let levels: BTreeSet<u8> = self
+            .plan
+            .keys()
+            .chain(self.milestones.keys())
+            .copied()
+            .collect();
+        for level in levels {
+            let mut label = level.to_string();
+            if let Some(step) = self.plan.get(&level) {
+                let text = self.describe_step(step);
+                lines.push(match step {
+                    PlanStep::Special(_) => format!("  {:>3}  {}", label, text.bright_cyan()),
+                    PlanStep::Perk { .. } => format!("  {:>3}  {}", label, text),
+                });
+                label.clear();
+            }
+            for text in self.milestones.get(&level).into_iter().flatten() {
+                let text = format!("Milestone: {}", text);
+                lines.push(format!("  {:>3}  {}", label, text.bright_magenta()));
+                label.clear();
+            }
+        }
+        let mut left: Vec<String> = self
+            .unplanned_ranks()
+            .iter()
+            .map(|(perk, rank)| format!("{} {}", self.perk_name(perk), rank))
+            .collect();
+        let special_steps = self
+            .plan
+            .values()
+            .filter(|step| matches!(step, PlanStep::Special(_)))
+            .count() as u8;
+        let special_left = self
+            .level_up_assigned_special_points()
+            .saturating_sub(special_steps);
+        if special_left > 0 {
+            left.push(format!(
+                "{} S.P.E.C.I.A.L. point{}",
+                special_left,
+                if special_left == 1 { "" } else { "s" }
+            ));
+        }
+        if !left.is_empty() {
+            lines.push(format!("Not planned yet: {}", left.join(", ")));
+        }
+        for problem in self.check_plan() {
+            lines.push(problem.bright_red().to_string());
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(Into::into).collect()
+    }
+
+    fn special(stat: SpecialStat, points: u8) -> PerkId {
+        PerkId::Special { stat, points }
+    }
+
+    #[test]
+    fn plan_steps_default_to_the_next_rank() {
+        let mut build = Build::default();
+        let gunslinger = special(SpecialStat::Agility, 1);
+        assert!(build.set_plan_step(1, &words("gunslinger")).is_err());
+        assert_eq!(
+            build.set_plan_step(2, &words("gunslinger")).unwrap(),
+            PlanStep::Perk {
+                perk: gunslinger,
+                rank: 1
+            }
+        );
+        assert_eq!(
+            build.set_plan_step(7, &words("gunslinger")).unwrap(),
+            PlanStep::Perk {
+                perk: gunslinger,
+                rank: 2
+            }
+        );
+        assert_eq!(
+            build.set_plan_step(8, &words("Agility")).unwrap(),
+            PlanStep::Special(SpecialStat::Agility)
+        );
+        assert!(build.set_plan_step(9, &words("gunslinger 6")).is_err());
+        assert_eq!(build.plan.len(), 3);
+    }
+
+    #[test]
+    fn plans_are_checked_against_the_build() {
+        let mut build = Build::default();
+        build.set_plan_step(3, &words("gunslinger 2")).unwrap();
+        build.set_plan_step(4, &words("strength")).unwrap();
+        let problems = build.check_plan();
+        assert!(problems.contains(&"Level 3: the build does not take Gunslinger 2".to_string()));
+        assert!(problems.contains(&"Level 3: Gunslinger 2 needs level 7".to_string()));
+        assert!(problems.contains(&"Level 3: Gunslinger 1 is not planned before it".to_string()));
+        assert!(problems.contains(
+            &"Level 4: more Strength points are planned than the build has past 1".into()
+        ));
+    }
+
+    #[test]
+    fn automatic_plans_have_no_problems() {
+        let mut build = Build::default();
+        for stat in SpecialStat::ALL {
+            build.set(*stat, 5).unwrap();
+        }
+        build.add_perk(special(SpecialStat::Agility, 1), 3).unwrap();
+        build
+            .add_perk(special(SpecialStat::Strength, 5), 1)
+            .unwrap();
+        build.auto_plan();
+        assert_eq!(build.check_plan(), Vec::<String>::new());
+        let special_steps = build
+            .plan
+            .values()
+            .filter(|step| matches!(step, PlanStep::Special(_)))
+            .count() as u8;
+        assert_eq!(special_steps, build.level_up_assigned_special_points());
+        assert_eq!(build.unplanned_ranks(), Vec::new());
+    }
+
+    #[test]
+    fn milestones_are_listed_by_level() {
+        let mut build = Build::default();
+        assert!(build.set_milestone(0, &words("start")).is_err());
+        build
+            .set_milestone(5, &words("Reach Diamond City"))
+            .unwrap();
+        build
+            .set_milestone(10, &words("Join the Brotherhood"))
+            .unwrap();
+        assert_eq!(
+            build.milestones_between(4, 10),
+            [(5, "Reach Diamond City"), (10, "Join the Brotherhood")]
+        );
+        assert!(build.milestones_between(10, 5).is_empty());
+        build.set_milestone(5, &[]).unwrap();
+        assert!(build.set_milestone(5, &[]).is_err());
+    }
+}
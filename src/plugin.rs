@@ -0,0 +1,218 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::bail;
+use serde::Serialize;
+
+const COMPRESSED: u32 = 0x0004_0000;
+const LOCALIZED: u32 = 0x0000_0080;
+
+#[derive(Debug, Default)]
+pub struct PerkRecord {
+    pub form_id: u32,
+    pub editor_id: String,
+    pub name: String,
+    pub description: String,
+    pub level: u8,
+    pub playable: bool,
+    pub next: Option<u32>,
+}
+
+pub struct Plugin {
+    pub perks: Vec<PerkRecord>,
+    pub warnings: Vec<String>,
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        if self.pos + n > self.bytes.len() {
+            bail!("Unexpected end of plugin file at byte {}", self.pos)
+        }
+        let slice = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+    fn done(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+}
+
+fn zstring(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn subrecords(data: &[u8]) -> anyhow::Result<Vec<([u8; 4], &[u8])>> {
+    let mut cursor = Cursor {
+        bytes: data,
+        pos: 0,
+    };
+    let mut subrecords = Vec::new();
+    let mut extended_size = None;
+    while !cursor.done() {
+        let kind: [u8; 4] = cursor.take(4)?.try_into()?;
+        let size = cursor.u16()? as usize;
+        let size = extended_size.take().unwrap_or(size);
+        let data = cursor.take(size)?;
+        if &kind == b"XXXX" {
+            extended_size = Some(u32::from_le_bytes(data.try_into()?) as usize);
+        } else {
+            subrecords.push((kind, data));
+        }
+    }
+    Ok(subrecords)
+}
+
+fn read_perk(form_id: u32, data: &[u8], localized: bool) -> anyhow::Result<PerkRecord> {
+    let mut perk = PerkRecord {
+        form_id,
+        ..Default::default()
+    };
+    for (kind, data) in subrecords(data)? {
+        let text = || {
+            if localized && data.len() == 4 {
+                format!(
+                    "[string {:08X}]",
+                    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+                )
+            } else {
+                zstring(data)
+            }
+        };
+        match &kind {
+            b"EDID" => perk.editor_id = zstring(data),
+            b"FULL" => perk.name = text(),
+            b"DESC" => perk.description = text(),
+            b"DATA" if data.len() >= 4 => {
+                perk.level = data[1];
+                perk.playable = data[3] != 0;
+            }
+            b"NNAM" if data.len() == 4 => {
+                let next = u32::from_le_bytes(data.try_into()?);
+                perk.next = (next != 0).then_some(next);
+            }
+            _ => {}
+        }
+    }
+    Ok(perk)
+}
+
+pub fn read_plugin(path: &Path) -> anyhow::Result<Plugin> {
+    let bytes = fs::read(path)?;
+    if !bytes.starts_with(b"TES4") {
+        bail!("Not a Bethesda plugin file")
+    }
+    let mut cursor = Cursor {
+        bytes: &bytes,
+        pos: 0,
+    };
+    let mut plugin = Plugin {
+        perks: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let mut localized = false;
+    let mut skipped = 0;
+    while !cursor.done() {
+        let kind: [u8; 4] = cursor.take(4)?.try_into()?;
+        let size = cursor.u32()? as usize;
+        if &kind == b"GRUP" {
+            // Group contents follow the rest of the 24 byte header
+            cursor.take(16)?;
+            continue;
+        }
+        let flags = cursor.u32()?;
+        let form_id = cursor.u32()?;
+        cursor.take(8)?;
+        let data = cursor.take(size)?;
+        match &kind {
+            b"TES4" if flags & LOCALIZED != 0 => {
+                localized = true;
+                plugin.warnings.push(
+                    "Plugin is localized, names and descriptions are string table ids".into(),
+                );
+            }
+            b"PERK" if flags & COMPRESSED != 0 => skipped += 1,
+            b"PERK" => plugin.perks.push(read_perk(form_id, data, localized)?),
+            _ => {}
+        }
+    }
+    if skipped > 0 {
+        plugin
+            .warnings
+            .push(format!("Skipped {} compressed perk records", skipped));
+    }
+    Ok(plugin)
+}
+
+#[derive(Serialize)]
+struct RankRep {
+    level: u8,
+    desc: String,
+}
+
+#[derive(Serialize)]
+struct PerkPackRep {
+    other: BTreeMap<String, Vec<RankRep>>,
+}
+
+impl Plugin {
+    /// Every playable perk as the chain of its rank records, following each rank's next perk
+    pub fn chains(&self) -> Vec<Vec<&PerkRecord>> {
+        let by_id: BTreeMap<u32, &PerkRecord> = self.perks.iter().map(|p| (p.form_id, p)).collect();
+        let targets: HashSet<u32> = self.perks.iter().filter_map(|p| p.next).collect();
+        let mut chains = Vec::new();
+        for first in self
+            .perks
+            .iter()
+            .filter(|p| p.playable && !p.name.is_empty() && !targets.contains(&p.form_id))
+        {
+            let mut chain = Vec::new();
+            let mut seen = HashSet::new();
+            let mut current = Some(first);
+            while let Some(perk) = current {
+                if !seen.insert(perk.form_id) {
+                    break;
+                }
+                chain.push(perk);
+                current = perk.next.and_then(|next| by_id.get(&next).copied());
+            }
+            chains.push(chain);
+        }
+        chains
+    }
+}
+
+pub fn perk_pack(plugin: &Plugin) -> anyhow::Result<String> {
+    let mut other = BTreeMap::new();
+    for chain in plugin.chains() {
+        let first = chain[0];
+        let ranks = chain
+            .iter()
+            .map(|perk| RankRep {
+                level: perk.level.max(1),
+                desc: perk.description.clone(),
+            })
+            .collect();
+        let name = if other.contains_key(&first.name) {
+            format!("{} ({})", first.name, first.editor_id)
+        } else {
+            first.name.clone()
+        };
+        other.insert(name, ranks);
+    }
+    Ok(serde_yaml::to_string(&PerkPackRep { other })?)
+}
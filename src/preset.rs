@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+
+use crate::{
+    build::Build,
+    special::{PerkId, SpecialStat},
+    storage::Storage,
+};
+
+/// The file presets are kept in, next to builds
+pub const PRESETS_FILE: &str = "presets.yaml";
+
+/// A S.P.E.C.I.A.L. allocation by stat
+pub type Preset = BTreeMap<SpecialStat, u8>;
+
+/// Every saved preset, by name
+pub fn presets(storage: &dyn Storage) -> anyhow::Result<BTreeMap<String, Preset>> {
+    match storage.read(PRESETS_FILE)? {
+        Some(bytes) => Ok(serde_yaml::from_slice(&bytes)?),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+fn write_presets(storage: &dyn Storage, presets: &BTreeMap<String, Preset>) -> anyhow::Result<()> {
+    storage.write(PRESETS_FILE, serde_yaml::to_string(presets)?.as_bytes())
+}
+
+/// Save a build's S.P.E.C.I.A.L. allocation as a preset, returning whether one was replaced
+pub fn save_preset(storage: &dyn Storage, name: &str, build: &Build) -> anyhow::Result<bool> {
+    let mut presets = presets(storage)?;
+    let replaced = presets.insert(name.into(), build.special.clone()).is_some();
+    write_presets(storage, &presets)?;
+    Ok(replaced)
+}
+
+/// Find a saved preset by name
+pub fn find_preset(storage: &dyn Storage, name: &str) -> anyhow::Result<Preset> {
+    let presets = presets(storage)?;
+    match presets.get(name) {
+        Some(preset) => Ok(preset.clone()),
+        None if presets.is_empty() => {
+            bail!("No presets saved, try \"preset save <NAME>\"")
+        }
+        None => bail!(
+            "No preset named {:?}. The saved presets are {}",
+            name,
+            presets.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+pub fn delete_preset(storage: &dyn Storage, name: &str) -> anyhow::Result<()> {
+    let mut presets = presets(storage)?;
+    if presets.remove(name).is_none() {
+        bail!("No preset named {:?}", name)
+    }
+    write_presets(storage, &presets)
+}
+
+/// Describe a preset's allocation, like "S 1, P 8, E 1, ..."
+pub fn describe_preset(preset: &Preset) -> String {
+    SpecialStat::ALL
+        .iter()
+        .map(|stat| {
+            let initial = stat.to_string().chars().next().unwrap_or_default();
+            format!("{} {}", initial, preset.get(stat).copied().unwrap_or(1))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Build {
+    /// Set every stat to a preset's allocation, returning the perks and ranks
+    /// that no longer have enough points and were removed
+    pub fn apply_preset(&mut self, preset: &Preset) -> anyhow::Result<Vec<(PerkId, u8)>> {
+        let mut removed = Vec::new();
+        for &stat in SpecialStat::ALL {
+            removed.extend(self.set(stat, preset.get(&stat).copied().unwrap_or(1))?);
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,376 @@
+use std::fmt;
+
+use anyhow::bail;
+
+use crate::{
+    build::{Build, Game},
+    classic::{ClassicGame, SPECIAL_POINTS},
+    fo76::{FO76, MAX_STAT, SPECIAL_LEVEL_CAP},
+    special::{catalog, join_perk_id, join_perk_id_and_rank, PerkId, SpecialStat},
+};
+
+/// The rules a build is planned under: how points are budgeted, how levels pay for them,
+/// which stats are derived from them, and which perk data they draw from
+pub trait GameProfile: Sync {
+    fn game(&self) -> Game;
+    /// The highest value a S.P.E.C.I.A.L. stat can be assigned
+    fn max_stat(&self) -> u8;
+    /// The most S.P.E.C.I.A.L. points that can be assigned on top of the 1 every stat starts with
+    fn special_budget(&self) -> Option<u8>;
+    fn level_cap(&self) -> Option<u16>;
+    fn required_level(&self, build: &Build) -> u16;
+    fn derived_stats(&self, build: &Build) -> Vec<(&'static str, String)>;
+    /// The names of every perk, card, or trait in the game's data
+    fn perk_names(&self, build: &Build) -> Vec<String>;
+    /// Set a stat, returning what was done. With `keep`, the change is refused
+    /// instead of removing any perks that would lose their points.
+    fn set_stat(
+        &self,
+        build: &mut Build,
+        stat: SpecialStat,
+        value: u8,
+        keep: bool,
+    ) -> anyhow::Result<String>;
+    /// Add a perk from the words of an "add" command, returning what was done
+    fn add_perk(&self, build: &mut Build, words: &[String]) -> anyhow::Result<String>;
+    /// Remove a perk from the words of a "remove" command, returning what was done.
+    /// With `keep`, the change is refused instead of removing any other perks.
+    fn remove_perk(
+        &self,
+        build: &mut Build,
+        words: &[String],
+        keep: bool,
+    ) -> anyhow::Result<String>;
+    fn fmt_build(&self, build: &Build, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+/// Split a trailing rank off of a perk name
+fn name_and_rank(words: &[String]) -> (String, Option<u8>) {
+    match words {
+        [name @ .., last] if !name.is_empty() => match last.parse::<u8>() {
+            Ok(rank) => (name.join(" "), Some(rank)),
+            Err(_) => (words.join(" "), None),
+        },
+        _ => (words.join(" "), None),
+    }
+}
+
+fn added(name: &str, rank: u8, verb: &str) -> String {
+    if rank == 0 {
+        format!("Removed {}", name)
+    } else {
+        format!("{} {} rank {}", verb, name, rank)
+    }
+}
+
+/// Describe a change along with the perks it removed, or refuse it with `keep`
+pub fn with_removed(
+    build: &mut Build,
+    keep: bool,
+    doing: String,
+    done: String,
+    change: impl FnOnce(&mut Build) -> anyhow::Result<Vec<(PerkId, u8)>>,
+) -> anyhow::Result<String> {
+    let mut changed = build.clone();
+    let removed = change(&mut changed)?;
+    if removed.is_empty() {
+        *build = changed;
+        return Ok(done);
+    }
+    let perks = build.describe_perks(&removed);
+    if keep {
+        bail!("{} would remove {}, so nothing was changed", doing, perks);
+    }
+    *build = changed;
+    Ok(format!("{} also removed {}", doing, perks))
+}
+
+pub struct Fallout4;
+pub struct Fallout76;
+pub struct Classic(pub ClassicGame);
+
+impl Game {
+    pub fn profile(self) -> &'static dyn GameProfile {
+        match self {
+            Game::Fallout4 => &Fallout4,
+            Game::Fallout76 => &Fallout76,
+            Game::Fallout3 => &Classic(ClassicGame::Fallout3),
+            Game::NewVegas => &Classic(ClassicGame::NewVegas),
+        }
+    }
+    /// Whether the game's perk data is still incomplete, which builds for it are warned about
+    pub fn is_experimental(self) -> bool {
+        matches!(self, Game::Fallout3 | Game::NewVegas)
+    }
+    /// A warning for builds of an experimental game
+    pub fn experimental_note(self) -> Option<String> {
+        self.is_experimental().then(|| {
+            format!(
+                "{} support is experimental, its perk list is incomplete",
+                self
+            )
+        })
+    }
+}
+
+impl Build {
+    pub fn profile(&self) -> &'static dyn GameProfile {
+        self.game().profile()
+    }
+}
+
+impl GameProfile for Fallout4 {
+    fn game(&self) -> Game {
+        Game::Fallout4
+    }
+    fn max_stat(&self) -> u8 {
+        10
+    }
+    fn special_budget(&self) -> Option<u8> {
+        None
+    }
+    fn level_cap(&self) -> Option<u16> {
+        None
+    }
+    fn required_level(&self, build: &Build) -> u16 {
+        build.stats().required_level as u16
+    }
+    fn derived_stats(&self, build: &Build) -> Vec<(&'static str, String)> {
+        let stats = build.stats();
+        vec![
+            ("Health", format!("{:.0}", stats.health)),
+            ("AP", format!("{:.0}", stats.base_ap)),
+            ("XP", format!("{:.0}%", stats.experience_mul * 100.0)),
+            ("Hits per Crit", stats.hits_per_crit.to_string()),
+            ("Carry Weight", stats.carry_weight.to_string()),
+        ]
+    }
+    fn perk_names(&self, build: &Build) -> Vec<String> {
+        let gender = build.gender.unwrap_or_default();
+        catalog()
+            .perks
+            .values()
+            .map(|def| def.name[gender].to_string())
+            .collect()
+    }
+    fn set_stat(
+        &self,
+        build: &mut Build,
+        stat: SpecialStat,
+        value: u8,
+        keep: bool,
+    ) -> anyhow::Result<String> {
+        with_removed(
+            build,
+            keep,
+            format!("Setting {} to {}", stat, value),
+            format!("Set {:?} to {}", stat, value),
+            |build| build.set(stat, value),
+        )
+    }
+    fn add_perk(&self, build: &mut Build, words: &[String]) -> anyhow::Result<String> {
+        let (id, rank) = join_perk_id_and_rank(words)?;
+        let perk = catalog().perks.get(&id).cloned().expect("Unknown perk");
+        let name = &perk.name[build.gender.unwrap_or_default()];
+        let wanted = rank.unwrap_or_else(|| perk.max_rank()).min(perk.max_rank());
+        let rank = match build.rank_cap(&id) {
+            Some(cap) if cap < wanted => {
+                if cap == 0 {
+                    bail!(
+                        "{} needs level {}, past the level limit of {}",
+                        name,
+                        perk.ranks.required_level(1),
+                        build.level_limit.unwrap_or_default()
+                    )
+                }
+                cap
+            }
+            _ => wanted,
+        };
+        build.add_perk(id, rank)?;
+        let mut message = added(name, rank, "Added");
+        if rank < wanted {
+            message.push_str(&format!(
+                ", since rank {} needs level {}, past the level limit of {}",
+                rank + 1,
+                perk.ranks.required_level(rank + 1),
+                build.level_limit.unwrap_or_default()
+            ));
+        }
+        Ok(message)
+    }
+    fn remove_perk(
+        &self,
+        build: &mut Build,
+        words: &[String],
+        keep: bool,
+    ) -> anyhow::Result<String> {
+        let id = join_perk_id(words)?;
+        let name = build.perk_name(&id);
+        with_removed(
+            build,
+            keep,
+            format!("Removing {}", name),
+            format!("Removed {}", name),
+            |build| build.remove_perk(id),
+        )
+    }
+    fn fmt_build(&self, build: &Build, f: &mut fmt::Formatter) -> fmt::Result {
+        build.fmt_fo4(f)
+    }
+}
+
+impl GameProfile for Fallout76 {
+    fn game(&self) -> Game {
+        Game::Fallout76
+    }
+    fn max_stat(&self) -> u8 {
+        MAX_STAT
+    }
+    fn special_budget(&self) -> Option<u8> {
+        Some(SPECIAL_LEVEL_CAP as u8 - 1)
+    }
+    fn level_cap(&self) -> Option<u16> {
+        None
+    }
+    fn required_level(&self, build: &Build) -> u16 {
+        build.fo76_required_level()
+    }
+    fn derived_stats(&self, build: &Build) -> Vec<(&'static str, String)> {
+        let points = |stat| build.fo76_card_points(stat) as u16;
+        vec![
+            (
+                "Health",
+                (245 + 5 * points(SpecialStat::Endurance)).to_string(),
+            ),
+            ("AP", (60 + 5 * points(SpecialStat::Agility)).to_string()),
+            (
+                "Carry Weight",
+                (150 + 5 * points(SpecialStat::Strength)).to_string(),
+            ),
+        ]
+    }
+    fn perk_names(&self, build: &Build) -> Vec<String> {
+        let gender = build.gender.unwrap_or_default();
+        FO76.iter()
+            .map(|(_, def)| def.name[gender].clone())
+            .collect()
+    }
+    fn set_stat(
+        &self,
+        build: &mut Build,
+        stat: SpecialStat,
+        value: u8,
+        _keep: bool,
+    ) -> anyhow::Result<String> {
+        build.set_fo76(stat, value)?;
+        Ok(format!("Set {:?} to {}", stat, value))
+    }
+    fn add_perk(&self, build: &mut Build, words: &[String]) -> anyhow::Result<String> {
+        let (name, rank) = name_and_rank(words);
+        let (name, rank) = build.equip_card(&name, rank)?;
+        Ok(added(&name, rank, "Equipped"))
+    }
+    fn remove_perk(
+        &self,
+        build: &mut Build,
+        words: &[String],
+        _keep: bool,
+    ) -> anyhow::Result<String> {
+        let (_, def) = FO76.find(&words.join(" "))?;
+        build.unequip_card(def.key())?;
+        Ok(format!(
+            "Removed {}",
+            def.name[build.gender.unwrap_or_default()]
+        ))
+    }
+    fn fmt_build(&self, build: &Build, f: &mut fmt::Formatter) -> fmt::Result {
+        match &build.fo76 {
+            Some(fo76) => build.fmt_fo76(fo76, f),
+            None => Ok(()),
+        }
+    }
+}
+
+impl GameProfile for Classic {
+    fn game(&self) -> Game {
+        match self.0 {
+            ClassicGame::Fallout3 => Game::Fallout3,
+            ClassicGame::NewVegas => Game::NewVegas,
+        }
+    }
+    fn max_stat(&self) -> u8 {
+        10
+    }
+    fn special_budget(&self) -> Option<u8> {
+        Some(SPECIAL_POINTS - SpecialStat::ALL.len() as u8)
+    }
+    fn level_cap(&self) -> Option<u16> {
+        Some(self.0.rules().level_cap as u16)
+    }
+    fn required_level(&self, build: &Build) -> u16 {
+        build.classic_required_level() as u16
+    }
+    fn derived_stats(&self, build: &Build) -> Vec<(&'static str, String)> {
+        let stat = |stat| build.special[&stat] as u16;
+        let level = build.classic_required_level() as u16;
+        let (health, ap) = match self.0 {
+            ClassicGame::Fallout3 => (
+                90 + 20 * stat(SpecialStat::Endurance) + 10 * level,
+                65 + 2 * stat(SpecialStat::Agility),
+            ),
+            ClassicGame::NewVegas => (
+                100 + 20 * stat(SpecialStat::Endurance) + 5 * (level - 1),
+                65 + 3 * stat(SpecialStat::Agility),
+            ),
+        };
+        vec![
+            ("Health", health.to_string()),
+            ("AP", ap.to_string()),
+            (
+                "Carry Weight",
+                (150 + 10 * stat(SpecialStat::Strength)).to_string(),
+            ),
+        ]
+    }
+    fn perk_names(&self, build: &Build) -> Vec<String> {
+        let gender = build.gender.unwrap_or_default();
+        let rules = self.0.rules();
+        rules
+            .perks
+            .iter()
+            .map(|def| def.name[gender].clone())
+            .chain(rules.traits.iter().map(|def| def.name.clone()))
+            .collect()
+    }
+    fn set_stat(
+        &self,
+        build: &mut Build,
+        stat: SpecialStat,
+        value: u8,
+        _keep: bool,
+    ) -> anyhow::Result<String> {
+        build.set_classic(stat, value)?;
+        Ok(format!("Set {:?} to {}", stat, value))
+    }
+    fn add_perk(&self, build: &mut Build, words: &[String]) -> anyhow::Result<String> {
+        let (name, rank) = name_and_rank(words);
+        let (name, rank) = build.add_classic_perk(&name, rank)?;
+        Ok(added(&name, rank, "Added"))
+    }
+    fn remove_perk(
+        &self,
+        build: &mut Build,
+        words: &[String],
+        _keep: bool,
+    ) -> anyhow::Result<String> {
+        let name = build.remove_classic_perk(&words.join(" "))?;
+        Ok(format!("Removed {}", name))
+    }
+    fn fmt_build(&self, build: &Build, f: &mut fmt::Formatter) -> fmt::Result {
+        match &build.classic {
+            Some(classic) => build.fmt_classic(classic, f),
+            None => Ok(()),
+        }
+    }
+}
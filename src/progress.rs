@@ -0,0 +1,364 @@
+use std::fmt;
+
+use anyhow::bail;
+use colored::Colorize;
+
+use crate::{
+    build::Build,
+    special::{catalog, BobbleheadId, PerkId, PerkKind, SpecialStat},
+};
+
+impl Build {
+    pub fn planned_picks(&self) -> Vec<(u8, PerkId, u8)> {
+        let mut picks: Vec<(u8, PerkId, u8)> = self
+            .perks
+            .iter()
+            .filter(|(id, _)| matches!(id, PerkId::Special { .. }))
+            .flat_map(|(id, rank)| (1..=*rank).map(move |r| (self.pick_level(*id, r), *id, r)))
+            .collect();
+        picks.sort();
+        picks
+    }
+    /// The lowest level a perk rank can be taken at, counting both the rank's
+    /// own requirement and when the stat points it needs are collected
+    pub fn pick_level(&self, id: PerkId, rank: u8) -> u8 {
+        let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+        let for_points = match id {
+            PerkId::Special { stat, points } => self.points_level(stat, points),
+            _ => 1,
+        };
+        def.ranks.required_level(rank).max(for_points)
+    }
+    /// The lowest level a stat reaches some number of base points, given when
+    /// its bobblehead and the book are collected
+    pub fn points_level(&self, stat: SpecialStat, points: u8) -> u8 {
+        let mut levels = Vec::new();
+        if self.bobblehead_for(stat) {
+            levels.push(self.bobblehead_levels.get(&stat).copied().unwrap_or(1));
+        }
+        if self.special_book == Some(stat) {
+            levels.push(self.book_level.unwrap_or(1));
+        }
+        levels.sort();
+        let missing = points.saturating_sub(self.special[&stat]) as usize;
+        match missing {
+            0 => 1,
+            n => levels.get(n - 1).copied().unwrap_or(1),
+        }
+    }
+    /// Plan the level a stat's bobblehead is collected at
+    pub fn set_bobblehead_level(&mut self, stat: SpecialStat, level: u8) -> anyhow::Result<()> {
+        self.invalidate_stats();
+        if !self.bobblehead_for(stat) {
+            bail!("The {} bobblehead is not part of the build", stat);
+        }
+        let before = self.bobblehead_levels.clone();
+        if level <= 1 {
+            self.bobblehead_levels.remove(&stat);
+        } else {
+            self.bobblehead_levels.insert(stat, level);
+        }
+        self.sync_book_order()
+            .inspect_err(|_| self.bobblehead_levels = before)
+    }
+    /// Plan the level the book is read at
+    pub fn set_book_level(&mut self, level: u8) -> anyhow::Result<()> {
+        self.invalidate_stats();
+        if self.special_book.is_none() {
+            bail!("The S.P.E.C.I.A.L. book is not part of the build");
+        }
+        let before = self.book_level;
+        self.book_level = (level > 1).then_some(level);
+        self.sync_book_order()
+            .inspect_err(|_| self.book_level = before)
+    }
+    /// Work out whether the book's stat's bobblehead comes first from their planned levels
+    fn sync_book_order(&mut self) -> anyhow::Result<()> {
+        let Some(stat) = self.special_book else {
+            return Ok(());
+        };
+        if let (Some(bobblehead), Some(book)) = (self.bobblehead_levels.get(&stat), self.book_level)
+        {
+            self.set_book(Some(stat), *bobblehead < book)?;
+        }
+        Ok(())
+    }
+    /// Find where picks that unlock at the given levels can't all be taken by level `by`,
+    /// along with the level-up S.P.E.C.I.A.L. points that share the same perk points.
+    /// Returns the earliest level from which the picks left need more points than are
+    /// earned from then until `by`, along with how many are needed and earned.
+    fn overspent_level(mut levels: Vec<u8>, special: u8, by: u8) -> Option<(u8, u16, u16)> {
+        levels.iter_mut().for_each(|level| *level = (*level).max(2));
+        (2..=by.max(2)).find_map(|from| {
+            let mut needed = levels.iter().filter(|&&level| level >= from).count() as u16;
+            if from == 2 {
+                needed += special as u16;
+            }
+            let earned = (by as u16 + 1).saturating_sub(from as u16);
+            (needed > earned).then_some((from, needed, earned))
+        })
+    }
+    /// Check that the build's perk points add up level by level, describing each problem
+    pub fn verify_points(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let special = self.level_up_assigned_special_points();
+        let planned: Vec<u8> = self
+            .planned_picks()
+            .iter()
+            .map(|(level, ..)| *level)
+            .collect();
+        let mut check = |levels: Vec<u8>, by: u8, what: &str| {
+            if let Some((from, needed, earned)) = Self::overspent_level(levels, special, by) {
+                problems.push(format!(
+                    "Over-spends from level {}: {} needs {} perk points there, \
+                    but only {} are earned by level {}",
+                    from, what, needed, earned, by
+                ));
+            }
+        };
+        check(planned.clone(), self.required_level(), "the build");
+        if let Some(limit) = self.level_limit {
+            check(planned, limit, "the build");
+        }
+        if let Some(level) = self.current_level {
+            let completed = self
+                .planned_picks()
+                .into_iter()
+                .filter(|(_, id, rank)| self.is_completed(id, *rank))
+                .map(|(level, ..)| level)
+                .collect();
+            check(completed, level, "what is checked off");
+        }
+        problems.dedup();
+        problems
+    }
+    pub fn is_completed(&self, id: &PerkId, rank: u8) -> bool {
+        self.completed.get(id).is_some_and(|r| *r >= rank)
+    }
+    /// The planned picks not checked off yet that can be taken by a level,
+    /// given the perk points that have been earned by then
+    pub fn due_picks(&self, level: u8) -> Vec<(PerkId, u8)> {
+        let available = level.saturating_sub(1 + self.level_up_assigned_special_points());
+        let mut spent = self.completed_perk_points();
+        let mut due = Vec::new();
+        for (required, id, rank) in self.planned_picks() {
+            if self.is_completed(&id, rank) {
+                continue;
+            }
+            if required > level || spent >= available {
+                break;
+            }
+            due.push((id, rank));
+            spent += 1;
+        }
+        due
+    }
+    pub fn check_level(&mut self, level: u8) -> usize {
+        self.current_level = Some(level);
+        let due = self.due_picks(level);
+        for &(id, rank) in &due {
+            self.completed.insert(id, rank);
+        }
+        due.len()
+    }
+    /// The rank of a planned perk that has been checked off so far
+    pub fn reached_rank(&self, id: &PerkId) -> u8 {
+        let planned = self.perks.get(id).copied().unwrap_or(0);
+        self.completed
+            .get(id)
+            .map_or(0, |rank| (*rank).min(planned))
+    }
+    pub fn check_perk(&mut self, id: PerkId, rank: Option<u8>) -> anyhow::Result<u8> {
+        let def = catalog()
+            .perks
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown perk"))?;
+        let current = self.completed.get(&id).copied().unwrap_or(0);
+        let rank = rank.unwrap_or(current + 1).min(def.max_rank());
+        if rank == 0 {
+            self.completed.remove(&id);
+        } else {
+            self.completed.insert(id, rank);
+        }
+        Ok(rank)
+    }
+    fn completed_perk_points(&self) -> u8 {
+        self.completed
+            .iter()
+            .filter(|(id, _)| matches!(id, PerkId::Special { .. }))
+            .map(|(_, rank)| rank)
+            .sum()
+    }
+    pub fn off_plan(&self) -> Vec<(PerkId, u8)> {
+        self.completed
+            .iter()
+            .filter(|(id, rank)| self.perks.get(*id).is_none_or(|r| r < *rank))
+            .map(|(id, rank)| (*id, *rank))
+            .collect()
+    }
+    pub fn progress_summary(&self) -> Option<String> {
+        if self.completed.is_empty() && self.current_level.is_none() {
+            return None;
+        }
+        let picks = self.planned_picks();
+        let done = picks
+            .iter()
+            .filter(|(_, id, rank)| self.is_completed(id, *rank))
+            .count();
+        let mut summary = format!("Progress: {}/{} picks", done, picks.len());
+        if let Some(level) = self.current_level {
+            summary.push_str(&format!(" at level {}", level));
+        }
+        let off_plan = self.off_plan().len();
+        if off_plan > 0 {
+            summary.push_str(&format!(", {} off plan", off_plan));
+        }
+        Some(summary)
+    }
+    pub fn write_progress(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        let gender = self.gender.unwrap_or_default();
+        let name = |id: &PerkId| catalog().perks[id].name[gender].clone();
+        writeln!(f, "{}", "Progress".bright_yellow())?;
+        if let Some(summary) = self.progress_summary() {
+            writeln!(f, "  {}", summary)?;
+        } else {
+            writeln!(f, "  {}", "Nothing checked off yet".bright_black())?;
+        }
+        let off_plan = self.off_plan();
+        if !off_plan.is_empty() {
+            writeln!(f, "{}", "Off Plan".bright_yellow())?;
+            for (id, rank) in off_plan {
+                writeln!(f, "  {} {}", name(&id), rank.to_string().bright_red())?;
+            }
+        }
+        let level = self.current_level.unwrap_or(1);
+        let mut spent = self.level_up_assigned_special_points() + self.completed_perk_points();
+        let catalog = catalog();
+        let mut bobbleheads: Vec<(u8, PerkId, &str)> = self
+            .perks
+            .keys()
+            .filter(|id| id.kind() == PerkKind::Bobblehead && !self.completed.contains_key(id))
+            .filter_map(|id| {
+                let location = catalog.locations.get(id)?;
+                let planned = match id {
+                    PerkId::Bobblehead(BobbleheadId::Special(stat)) => {
+                        self.bobblehead_levels.get(stat).copied()
+                    }
+                    _ => None,
+                };
+                let level = planned.or(location.level).unwrap_or(1);
+                Some((level, *id, location.location.as_str()))
+            })
+            .collect();
+        bobbleheads.sort();
+        if !self.milestones.is_empty() {
+            writeln!(f, "{}", "Milestones".bright_yellow())?;
+            for (at, text) in self.milestones_between(0, u8::MAX) {
+                if at <= level {
+                    writeln!(f, "  {:>2} {}", at, text.bright_white())?;
+                } else {
+                    writeln!(f, "  {:>2} {}", at, text)?;
+                }
+            }
+        }
+        if !bobbleheads.is_empty() {
+            writeln!(f, "{}", "Bobbleheads".bright_yellow())?;
+            for (recommended, id, location) in bobbleheads {
+                let text = format!("{} {}", name(&id), format!("({})", location).bright_black());
+                if recommended <= level {
+                    writeln!(f, "  {:>2} {}", recommended, text.bright_white())?;
+                } else {
+                    writeln!(f, "  {:>2} {}", recommended, text)?;
+                }
+            }
+        }
+        let remaining: Vec<(u8, PerkId, u8)> = self
+            .planned_picks()
+            .into_iter()
+            .filter(|(_, id, rank)| !self.is_completed(id, *rank))
+            .collect();
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "{}", "Remaining".bright_yellow())?;
+        for (required, id, rank) in remaining {
+            spent += 1;
+            let projected = required.max(spent + 1);
+            let text = format!("{} {}", name(&id), rank);
+            let text = if projected <= level {
+                text.bright_white()
+            } else {
+                text.white()
+            };
+            writeln!(
+                f,
+                "  {:>2} {} {}",
+                projected,
+                text,
+                if projected > required {
+                    format!("(planned for {})", required).bright_black()
+                } else {
+                    "".normal()
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn special(stat: SpecialStat, points: u8) -> PerkId {
+        PerkId::Special { stat, points }
+    }
+
+    #[test]
+    fn picks_wait_for_the_bobblehead_that_unlocks_them() {
+        let mut build = Build::default();
+        let bobblehead = PerkId::Bobblehead(BobbleheadId::Special(SpecialStat::Agility));
+        build.add_perk(bobblehead, 1).unwrap();
+        build.add_perk(special(SpecialStat::Agility, 2), 1).unwrap();
+        assert_eq!(build.special[&SpecialStat::Agility], 1);
+        assert!(build
+            .set_bobblehead_level(SpecialStat::Strength, 5)
+            .is_err());
+        build
+            .set_bobblehead_level(SpecialStat::Agility, 12)
+            .unwrap();
+        assert_eq!(build.points_level(SpecialStat::Agility, 2), 12);
+        assert_eq!(build.pick_level(special(SpecialStat::Agility, 2), 1), 12);
+        assert!(build.set_book_level(3).is_err());
+    }
+
+    #[test]
+    fn levels_check_off_the_picks_they_can_pay_for() {
+        let mut build = Build::default();
+        build.set(SpecialStat::Agility, 2).unwrap();
+        build.add_perk(special(SpecialStat::Agility, 1), 2).unwrap();
+        build.add_perk(special(SpecialStat::Agility, 2), 1).unwrap();
+        assert_eq!(build.progress_summary(), None);
+        assert_eq!(build.check_level(2), 1);
+        assert_eq!(build.check_level(3), 1);
+        assert_eq!(build.check_level(6), 0);
+        assert_eq!(build.check_level(7), 1);
+        assert_eq!(build.reached_rank(&special(SpecialStat::Agility, 1)), 2);
+        assert_eq!(
+            build.progress_summary().as_deref(),
+            Some("Progress: 3/3 picks at level 7")
+        );
+        assert!(build.verify_points().is_empty());
+    }
+
+    #[test]
+    fn checked_perks_outside_the_plan_are_off_plan() {
+        let mut build = Build::default();
+        let id = special(SpecialStat::Strength, 1);
+        assert_eq!(build.check_perk(id, Some(99)).unwrap(), 5);
+        assert_eq!(build.off_plan(), [(id, 5)]);
+        assert_eq!(build.check_perk(id, Some(0)).unwrap(), 0);
+        assert!(build.off_plan().is_empty());
+    }
+}
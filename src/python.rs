@@ -0,0 +1,199 @@
+// The wrappers pyo3 generates convert errors into their own type
+#![allow(clippy::useless_conversion)]
+
+use std::io;
+
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyDict, PyList},
+};
+use serde_json::Value;
+
+use crate::{
+    build::Build,
+    optimize::{Budget, Objective, Optimizer, Search, Weight, WeightedScorer, DEFAULT_SEED},
+    special::{catalog, PerkId, SpecialStat},
+};
+
+fn py_error(e: impl ToString) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+fn to_py(py: Python, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => b.into_py(py),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into_py(py),
+            None => n.as_f64().unwrap_or_default().into_py(py),
+        },
+        Value::String(s) => s.into_py(py),
+        Value::Array(values) => {
+            let values = values
+                .iter()
+                .map(|value| to_py(py, value))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new_bound(py, values).into_py(py)
+        }
+        Value::Object(object) => {
+            let dict = PyDict::new_bound(py);
+            for (key, value) in object {
+                dict.set_item(key, to_py(py, value)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+#[pyclass(name = "Build")]
+#[derive(Clone)]
+pub struct PyBuild(Build);
+
+#[pymethods]
+impl PyBuild {
+    #[new]
+    fn new() -> Self {
+        PyBuild(Build::default())
+    }
+    #[staticmethod]
+    fn from_json(json: &str) -> PyResult<Self> {
+        let value = serde_json::from_str(json).map_err(py_error)?;
+        Build::from_json(value).map(PyBuild).map_err(py_error)
+    }
+    fn to_json(&self) -> PyResult<String> {
+        Ok(self.0.to_json().map_err(py_error)?.to_string())
+    }
+    #[staticmethod]
+    fn from_share_code(code: &str) -> PyResult<Self> {
+        Build::from_share_code(code).map(PyBuild).map_err(py_error)
+    }
+    fn share_code(&self) -> PyResult<String> {
+        self.0.share_code().map_err(py_error)
+    }
+    fn set(&mut self, stat: &str, value: u8) -> PyResult<()> {
+        let stat: SpecialStat = stat.parse().map_err(py_error)?;
+        self.0.set(stat, value).map(|_| ()).map_err(py_error)
+    }
+    fn set_bobblehead(&mut self, stat: &str, on: bool) -> PyResult<()> {
+        let stat: SpecialStat = stat.parse().map_err(py_error)?;
+        self.0
+            .set_bobblehead(stat, on)
+            .map(|_| ())
+            .map_err(py_error)
+    }
+    /// Add a perk at the given rank, or its highest rank within the level limit,
+    /// returning the rank that was taken
+    #[pyo3(signature = (perk, rank = None))]
+    fn add_perk(&mut self, perk: &str, rank: Option<u8>) -> PyResult<u8> {
+        let id: PerkId = perk.parse().map_err(py_error)?;
+        let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+        let rank = rank.unwrap_or_else(|| def.max_rank()).min(
+            def.ranks
+                .highest_rank_within_level(self.0.level_limit.unwrap_or(u8::MAX)),
+        );
+        self.0.add_perk(id, rank).map_err(py_error)?;
+        Ok(rank)
+    }
+    fn remove_perk(&mut self, perk: &str) -> PyResult<()> {
+        let id: PerkId = perk.parse().map_err(py_error)?;
+        self.0.remove_perk(id).map(|_| ()).map_err(py_error)
+    }
+    /// Assigned points by stat name
+    #[getter]
+    fn special<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        for stat in SpecialStat::ALL {
+            dict.set_item(stat.to_string(), self.0.special[stat])?;
+        }
+        Ok(dict)
+    }
+    /// Perk ranks by perk name
+    #[getter]
+    fn perks<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        for (id, rank) in &self.0.perks {
+            dict.set_item(self.0.perk_name(id), rank)?;
+        }
+        Ok(dict)
+    }
+    #[getter]
+    fn required_level(&self) -> u8 {
+        self.0.required_level()
+    }
+    fn stats(&self, py: Python) -> PyResult<PyObject> {
+        to_py(py, &serde_json::to_value(self.0.stats()).map_err(py_error)?)
+    }
+    fn __str__(&self) -> String {
+        colored::control::set_override(false);
+        self.0.to_string()
+    }
+}
+
+/// Every perk in the catalog, named and described for the default gender and difficulty
+#[pyfunction]
+fn perks(py: Python) -> PyResult<PyObject> {
+    let perks: Vec<_> = catalog()
+        .perks
+        .iter()
+        .map(|(id, def)| def.info(*id, Default::default(), Default::default()))
+        .collect();
+    to_py(py, &serde_json::to_value(perks).map_err(py_error)?)
+}
+
+#[pyfunction]
+fn perk(py: Python, name: &str) -> PyResult<PyObject> {
+    let id: PerkId = name.parse().map_err(py_error)?;
+    let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+    let info = def.info(id, Default::default(), Default::default());
+    to_py(py, &serde_json::to_value(info).map_err(py_error)?)
+}
+
+/// The names of the stats the optimizer can weigh
+#[pyfunction]
+fn objectives() -> Vec<String> {
+    Objective::ALL
+        .iter()
+        .map(|obj| format!("{:?}", obj).to_lowercase())
+        .collect()
+}
+
+/// Optimize a build for weights like `"health=2"` at a level, returning the new build
+#[pyfunction]
+#[pyo3(signature = (build, weights, level = None, search = "greedy", time = 2.0, seed = DEFAULT_SEED))]
+fn optimize(
+    build: &PyBuild,
+    weights: Vec<String>,
+    level: Option<u8>,
+    search: &str,
+    time: f32,
+    seed: u64,
+) -> PyResult<PyBuild> {
+    let build = &build.0;
+    let weights = weights
+        .iter()
+        .map(|weight| weight.parse::<Weight>())
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(py_error)?;
+    let search: Search = search.parse().map_err(py_error)?;
+    let level = level
+        .or(build.level_limit)
+        .unwrap_or_else(|| build.required_level());
+    let scorer = WeightedScorer::new(weights).map_err(py_error)?;
+    let optimizer = Optimizer::new(build, scorer, level).map_err(py_error)?;
+    let budget = Budget::seconds(time).map_err(py_error)?;
+    Ok(PyBuild(match search {
+        Search::Greedy => optimizer.greedy(build),
+        Search::Deep => optimizer.anneal(build, budget, seed, &mut io::sink()),
+    }))
+}
+
+#[pymodule]
+fn fo4builder(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBuild>()?;
+    m.add_function(wrap_pyfunction!(perks, m)?)?;
+    m.add_function(wrap_pyfunction!(perk, m)?)?;
+    m.add_function(wrap_pyfunction!(objectives, m)?)?;
+    m.add_function(wrap_pyfunction!(optimize, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use std::{
+    io::{self, stdin, stdout, BufRead, Write},
+    iter::once,
+};
+
+use anyhow::anyhow;
+use clap::{CommandFactory, Parser};
+use serde_json::{json, Value};
+
+use fo4builder::{
+    build::Build,
+    special::{catalog, PerkId},
+};
+
+use crate::{reload_perks, Command, Outcome, Session};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for RpcError {
+    fn from(e: serde_json::Error) -> Self {
+        RpcError::new(SERVER_ERROR, e)
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        RpcError::new(SERVER_ERROR, e)
+    }
+}
+
+pub fn run() -> anyhow::Result<()> {
+    serve(stdin().lock(), stdout())
+}
+
+pub fn serve(input: impl BufRead, mut output: impl Write) -> anyhow::Result<()> {
+    let mut session = Session {
+        build: Build::default(),
+        pareto_builds: Vec::new(),
+        progress: Box::new(io::sink()),
+        tabs: Default::default(),
+        merge: None,
+        scripts: Default::default(),
+        snapshots: Default::default(),
+        history: Default::default(),
+        listing: Vec::new(),
+    };
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if catalog().changed() {
+            if let Err(e) = reload_perks(&mut session.build) {
+                eprintln!("Perk data not reloaded: {}", e);
+            }
+        }
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                respond(&mut output, Value::Null, Err(RpcError::new(PARSE_ERROR, e)))?;
+                continue;
+            }
+        };
+        let id = request.get("id").cloned();
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some(method) => {
+                let params = request.get("params").cloned().unwrap_or(Value::Null);
+                handle(&mut session, method, params)
+            }
+            None => Err(RpcError::new(INVALID_REQUEST, "Missing method")),
+        };
+        // Requests without an id are notifications and get no response
+        if let Some(id) = id {
+            respond(&mut output, id, result)?;
+        }
+    }
+    Ok(())
+}
+
+fn respond(out: &mut impl Write, id: Value, result: Result<Value, RpcError>) -> anyhow::Result<()> {
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    };
+    writeln!(out, "{}", response)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn handle(session: &mut Session, method: &str, params: Value) -> Result<Value, RpcError> {
+    let build = &mut session.build;
+    Ok(match method {
+        "build.get" => build.to_json()?,
+        "build.set" => {
+            *build = Build::from_json(params)?;
+            build.to_json()?
+        }
+        "build.stats" => serde_json::to_value(build.stats())?,
+        "catalog.perks" => {
+            let gender = build.gender.unwrap_or_default();
+            let difficulty = build.difficulty.unwrap_or_default();
+            serde_json::to_value(
+                catalog()
+                    .perks
+                    .iter()
+                    .map(|(id, def)| def.info(*id, gender, difficulty))
+                    .collect::<Vec<_>>(),
+            )?
+        }
+        "catalog.perk" => {
+            let name = string_params(params)?.join(" ");
+            let id: PerkId = name.parse()?;
+            let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+            let gender = build.gender.unwrap_or_default();
+            let difficulty = build.difficulty.unwrap_or_default();
+            serde_json::to_value(def.info(id, gender, difficulty))?
+        }
+        _ => {
+            if Command::command().find_subcommand(method).is_none() {
+                return Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("Unknown method: {}", method),
+                ));
+            }
+            let args = string_params(params)?;
+            let args = once("fo4")
+                .chain(once(method))
+                .chain(args.iter().map(String::as_str));
+            let command = Command::try_parse_from(args).map_err(|e| {
+                RpcError::new(
+                    INVALID_PARAMS,
+                    e.to_string()
+                        .trim_end_matches("\n\nFor more information try --help\n"),
+                )
+            })?;
+            match session.apply(command) {
+                Outcome::Message(message) => json!({
+                    "message": message?,
+                    "build": session.build.to_json()?,
+                }),
+                Outcome::View(_) | Outcome::Exit => {
+                    return Err(RpcError::new(
+                        METHOD_NOT_FOUND,
+                        format!("\"{}\" is not available over RPC", method),
+                    ))
+                }
+            }
+        }
+    })
+}
+
+fn string_params(params: Value) -> Result<Vec<String>, RpcError> {
+    match params {
+        Value::Null => Ok(Vec::new()),
+        Value::String(s) => Ok(s.split_whitespace().map(Into::into).collect()),
+        Value::Array(values) => values
+            .into_iter()
+            .map(|value| match value {
+                Value::String(s) => Ok(s),
+                Value::Number(n) => Ok(n.to_string()),
+                value => Err(anyhow!("Expected a string, found {}", value)),
+            })
+            .collect::<anyhow::Result<_>>()
+            .map_err(|e| RpcError::new(INVALID_PARAMS, e)),
+        value => Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("Expected a string or array of strings, found {}", value),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run requests, one per line, returning the responses
+    fn exchange(requests: &str) -> Vec<Value> {
+        let mut output = Vec::new();
+        serve(requests.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn error_code(response: &Value) -> i64 {
+        response["error"]["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn malformed_requests_get_errors() {
+        let responses = exchange(concat!(
+            "{not json\n",
+            "\n",
+            "{\"id\": 1}\n",
+            "{\"id\": 2, \"method\": \"fly\"}\n",
+            "{\"id\": 3, \"method\": \"set\", \"params\": {\"stat\": \"strength\"}}\n",
+            "{\"id\": 4, \"method\": \"set\", \"params\": [\"strength\", true]}\n",
+            "{\"id\": 5, \"method\": \"set\", \"params\": \"strength\"}\n",
+        ));
+        let codes: Vec<(Value, i64)> = responses
+            .iter()
+            .map(|response| (response["id"].clone(), error_code(response)))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (Value::Null, PARSE_ERROR),
+                (json!(1), INVALID_REQUEST),
+                (json!(2), METHOD_NOT_FOUND),
+                (json!(3), INVALID_PARAMS),
+                (json!(4), INVALID_PARAMS),
+                (json!(5), INVALID_PARAMS),
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_change_the_session_build() {
+        let responses = exchange(concat!(
+            "{\"method\": \"set\", \"params\": [\"strength\", 3]}\n",
+            "{\"id\": \"a\", \"method\": \"set\", \"params\": \"agility 4\"}\n",
+            "{\"id\": \"b\", \"method\": \"build.get\"}\n",
+        ));
+        // The first request has no id, so it gets no response
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], "a");
+        assert!(responses[0]["result"]["message"].is_string());
+        let special = &responses[1]["result"]["special"];
+        assert_eq!(special["Strength"], 3, "{}", responses[1]);
+        assert_eq!(special["Agility"], 4, "{}", responses[1]);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    fmt,
+    io::{self, stdout, IsTerminal, Write},
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    queue,
+    style::Print,
+    terminal::{self, Clear, ClearType},
+};
+
+/// Rows kept free below a frame for the next command and its progress output
+const RESERVED: usize = 2;
+
+/// Text for the next frame, drawn off screen before being presented
+#[derive(Default)]
+pub struct Frame(String);
+
+impl Frame {
+    pub fn line(&mut self, text: impl fmt::Display) {
+        self.0.push_str(&text.to_string());
+        self.0.push('\n');
+    }
+    #[cfg(feature = "tui")]
+    pub fn text(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Write for Frame {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.push_str(s);
+        Ok(())
+    }
+}
+
+/// Presents frames at the top of the terminal, diffing each against the one before it
+/// so only the lines that changed are rewritten
+#[derive(Default)]
+pub struct Screen {
+    /// The lines on screen from the top row, or `None` if the screen has
+    /// scrolled since and must be redrawn in full
+    lines: Option<Vec<String>>,
+}
+
+impl Screen {
+    pub fn present(&mut self, frame: Frame) {
+        let mut stdout = stdout().lock();
+        if !stdout.is_terminal() {
+            print!("{}[2J{}", 27 as char, frame.0);
+            return;
+        }
+        let lines: Vec<String> = frame.0.lines().map(Into::into).collect();
+        let res = match &self.lines {
+            Some(old) => Screen::diff(&mut stdout, old, &lines),
+            None => Screen::redraw(&mut stdout, &lines),
+        };
+        if res.and_then(|_| stdout.flush()).is_err() {
+            self.lines = None;
+            return;
+        }
+        let (width, height) = terminal::size().unwrap_or((u16::MAX, u16::MAX));
+        let fits = lines.len() + RESERVED < height as usize
+            && lines.iter().all(|line| visible_len(line) <= width as usize);
+        self.lines = fits.then_some(lines);
+    }
+    fn diff(out: &mut impl Write, old: &[String], new: &[String]) -> io::Result<()> {
+        for (i, line) in new.iter().enumerate() {
+            if old.get(i) != Some(line) {
+                queue!(
+                    out,
+                    MoveTo(0, i as u16),
+                    Print(line),
+                    Clear(ClearType::UntilNewLine)
+                )?;
+            }
+        }
+        queue!(
+            out,
+            MoveTo(0, new.len() as u16),
+            Clear(ClearType::FromCursorDown)
+        )
+    }
+    fn redraw(out: &mut impl Write, lines: &[String]) -> io::Result<()> {
+        queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        for line in lines {
+            queue!(out, Print(line), Print('\n'))?;
+        }
+        queue!(out, Clear(ClearType::FromCursorDown))
+    }
+}
+
+/// The number of characters in a line, not counting color codes
+fn visible_len(line: &str) -> usize {
+    let mut len = 0;
+    let mut escape = false;
+    for c in line.chars() {
+        match c {
+            '\x1b' => escape = true,
+            c if escape => escape = !c.is_ascii_alphabetic(),
+            _ => len += 1,
+        }
+    }
+    len
+}
@@ -0,0 +1,326 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::anyhow;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST};
+
+use crate::{
+    build::{Build, Game},
+    config::data_dir,
+    expr::MAX_DEPTH,
+    special::SpecialStat,
+};
+
+/// The hooks a script can define, each called with the build as `this`
+pub const HOOKS: &[&str] = &["after_add", "after_remove", "after_set", "before_save"];
+
+/// Scripts from the data folder that hook into changes to the build and add commands
+#[derive(Default)]
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<(String, AST)>,
+    /// Scripts that could not be loaded, with why
+    pub errors: Vec<String>,
+    /// What the scripts printed since it was last taken
+    output: Arc<Mutex<Vec<String>>>,
+}
+
+fn script_error(e: impl ToString) -> Box<EvalAltResult> {
+    e.to_string().into()
+}
+
+/// How many operations one call into a script may run, so a runaway loop errors
+/// instead of hanging
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+fn engine(output: Arc<Mutex<Vec<String>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_expr_depths(MAX_DEPTH, MAX_DEPTH)
+        .set_max_call_levels(MAX_DEPTH)
+        .set_max_string_size(1 << 20)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000);
+    engine.on_print(move |text| output.lock().unwrap().push(text.into()));
+    engine
+        .register_type_with_name::<Build>("Build")
+        .register_get("name", |build: &mut Build| {
+            build.name.clone().map_or(Dynamic::UNIT, Dynamic::from)
+        })
+        .register_get("game", |build: &mut Build| build.game().to_string())
+        .register_get("level", |build: &mut Build| {
+            build.profile().required_level(build) as i64
+        })
+        .register_get("level_limit", |build: &mut Build| {
+            build
+                .level_limit
+                .map_or(Dynamic::UNIT, |limit| Dynamic::from(limit as i64))
+        })
+        .register_get("perks", |build: &mut Build| {
+            let mut perks = Map::new();
+            if build.game() == Game::Fallout4 {
+                for (id, rank) in &build.perks {
+                    perks.insert(build.perk_name(id).into(), Dynamic::from(*rank as i64));
+                }
+            }
+            perks
+        })
+        .register_fn("stat", |build: &mut Build, name: &str| {
+            build
+                .variable(&name.to_lowercase())
+                .map(|value| value as f64)
+                .ok_or_else(|| script_error(format!("Unknown stat {}", name)))
+        })
+        .register_fn("add", |build: &mut Build, perk: &str| {
+            let words: Vec<String> = perk.split_whitespace().map(Into::into).collect();
+            build
+                .profile()
+                .add_perk(build, &words)
+                .map_err(script_error)
+        })
+        .register_fn("remove", |build: &mut Build, perk: &str| {
+            let words: Vec<String> = perk.split_whitespace().map(Into::into).collect();
+            build
+                .profile()
+                .remove_perk(build, &words, false)
+                .map_err(script_error)
+        })
+        .register_fn("set", |build: &mut Build, stat: &str, value: i64| {
+            let stat: SpecialStat = stat.parse().map_err(script_error)?;
+            let value = u8::try_from(value).map_err(script_error)?;
+            build
+                .profile()
+                .set_stat(build, stat, value, false)
+                .map_err(script_error)
+        });
+    engine
+}
+
+impl Scripts {
+    /// The folder scripts are loaded from
+    pub fn dir() -> anyhow::Result<PathBuf> {
+        Ok(data_dir()?.join("scripts"))
+    }
+    /// Load every .rhai file in the scripts folder
+    pub fn load() -> Scripts {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut scripts = Scripts {
+            engine: engine(output.clone()),
+            output,
+            ..Default::default()
+        };
+        let dir = match Scripts::dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                scripts.errors.push(e.to_string());
+                return scripts;
+            }
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            return scripts;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            match scripts.engine.compile_file(path) {
+                Ok(ast) => scripts.scripts.push((name, ast)),
+                Err(e) => scripts.errors.push(format!("{}: {}", name, e)),
+            }
+        }
+        scripts
+    }
+    /// Call a function in every script that defines it with the build as `this`,
+    /// returning what they printed or returned. The build is only changed if every
+    /// call succeeds.
+    fn call(
+        &self,
+        build: &mut Build,
+        function: &str,
+        args: impl FuncArgs + Clone,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut this = Dynamic::from(build.clone());
+        let mut lines = Vec::new();
+        for (name, ast) in &self.scripts {
+            if !ast.iter_functions().any(|f| f.name == function) {
+                continue;
+            }
+            let options = CallFnOptions::new()
+                .eval_ast(false)
+                .bind_this_ptr(&mut this);
+            let result = self.engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut Scope::new(),
+                ast,
+                function,
+                args.clone(),
+            );
+            lines.append(&mut self.output.lock().unwrap());
+            let value = result.map_err(|e| anyhow!("Script {} failed: {}", name, e))?;
+            if let Some(text) = value.try_cast::<String>() {
+                lines.push(text);
+            }
+        }
+        *build = this
+            .try_cast()
+            .ok_or_else(|| anyhow!("A script replaced the build with something else"))?;
+        Ok(lines)
+    }
+    /// Run a hook after a change to the build, adding what the scripts say to the
+    /// message of the change
+    pub fn after(
+        &self,
+        build: &mut Build,
+        change: anyhow::Result<String>,
+        hook: &str,
+        args: impl FuncArgs + Clone,
+    ) -> anyhow::Result<String> {
+        let message = change?;
+        let lines = self
+            .call(build, hook, args)
+            .map_err(|e| anyhow!("{}\n{}", message, e))?;
+        Ok(std::iter::once(message)
+            .chain(lines)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+    /// Run the hook before a build is saved. A script can stop the save by throwing.
+    pub fn before_save(&self, build: &mut Build) -> anyhow::Result<Vec<String>> {
+        let name = build.name.clone().unwrap_or_default();
+        self.call(build, "before_save", (name,))
+    }
+    /// The commands the scripts add, which are functions named like `command_<NAME>`
+    pub fn commands(&self) -> Vec<String> {
+        let mut commands: Vec<String> = self
+            .scripts
+            .iter()
+            .flat_map(|(_, ast)| ast.iter_functions())
+            .filter_map(|f| f.name.strip_prefix("command_").map(String::from))
+            .collect();
+        commands.sort();
+        commands.dedup();
+        commands
+    }
+    /// Run a command added by a script, given the words typed
+    pub fn run_command(&self, build: &mut Build, words: &[String]) -> anyhow::Result<String> {
+        let (name, args) = words
+            .split_first()
+            .ok_or_else(|| anyhow!("No command given"))?;
+        let function = format!("command_{}", name.to_lowercase().replace('-', "_"));
+        if !self
+            .scripts
+            .iter()
+            .any(|(_, ast)| ast.iter_functions().any(|f| f.name == function))
+        {
+            anyhow::bail!(
+                "Unknown command: {}\nType \"help\" for usage information",
+                name
+            )
+        }
+        let args: rhai::Array = args.iter().cloned().map(Dynamic::from).collect();
+        Ok(self.call(build, &function, (args,))?.join("\n"))
+    }
+    /// List the loaded scripts with their hooks and commands
+    pub fn describe(&self) -> String {
+        let mut lines = Vec::new();
+        if self.scripts.is_empty() && self.errors.is_empty() {
+            if let Ok(dir) = Scripts::dir() {
+                lines.push(format!(
+                    "No scripts, put .rhai files in {}",
+                    dir.to_string_lossy()
+                ));
+            }
+        }
+        for (name, ast) in &self.scripts {
+            let functions: Vec<&str> = ast
+                .iter_functions()
+                .map(|f| f.name)
+                .filter(|f| HOOKS.contains(f) || f.starts_with("command_"))
+                .collect();
+            lines.push(if functions.is_empty() {
+                format!("  {}: no hooks or commands", name)
+            } else {
+                format!("  {}: {}", name, functions.join(", "))
+            });
+        }
+        for error in &self.errors {
+            lines.push(format!("  {}", error));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scripts(sources: &[(&str, &str)]) -> Scripts {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let engine = engine(output.clone());
+        let scripts = sources
+            .iter()
+            .map(|(name, source)| (name.to_string(), engine.compile(source).unwrap()))
+            .collect();
+        Scripts {
+            engine,
+            scripts,
+            output,
+            ..Default::default()
+        }
+    }
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(Into::into).collect()
+    }
+
+    #[test]
+    fn commands_change_the_build_and_report_back() {
+        let scripts = scripts(&[(
+            "tank",
+            r#"fn command_tank(args) { this.set("Endurance", parse_int(args[0])); print("Tanky"); "Done" }"#,
+        )]);
+        assert_eq!(scripts.commands(), ["tank"]);
+        let mut build = Build::default();
+        let message = scripts.run_command(&mut build, &words("tank 7")).unwrap();
+        assert_eq!(message, "Tanky\nDone");
+        assert_eq!(build.special[&SpecialStat::Endurance], 7);
+        assert!(scripts.run_command(&mut build, &words("nope")).is_err());
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        let scripts = scripts(&[("spin", "fn command_spin(args) { loop {} }")]);
+        let mut build = Build::default();
+        let e = scripts.run_command(&mut build, &words("spin")).unwrap_err();
+        assert!(e.to_string().starts_with("Script spin failed"), "{}", e);
+    }
+
+    #[test]
+    fn the_build_is_only_changed_if_every_hook_succeeds() {
+        let scripts = scripts(&[
+            ("a", r#"fn before_save(name) { this.set("Strength", 9); }"#),
+            (
+                "b",
+                r#"fn before_save(name) { throw "Not saving " + name; }"#,
+            ),
+        ]);
+        let mut build = Build {
+            name: Some("sniper".into()),
+            ..Default::default()
+        };
+        let e = scripts.before_save(&mut build).unwrap_err();
+        assert!(e.to_string().contains("Not saving sniper"), "{}", e);
+        assert_eq!(build.special[&SpecialStat::Strength], 1);
+    }
+}
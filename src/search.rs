@@ -0,0 +1,125 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use crate::{
+    build::{Build, Game},
+    special::{join_perk_id_and_rank, PerkId, SpecialStat},
+    storage::Storage,
+    usage::{saved_builds, SavedBuilds},
+};
+
+/// How a number is compared in a search, like the ">=" in "luck>=8"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    AtMost,
+    Equal,
+    AtLeast,
+    Greater,
+}
+
+impl Comparison {
+    /// The operators in the order they are tried, so "<=" is found before "<"
+    const OPERATORS: &'static [(&'static str, Comparison)] = &[
+        ("<=", Comparison::AtMost),
+        (">=", Comparison::AtLeast),
+        ("==", Comparison::Equal),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+        ("=", Comparison::Equal),
+    ];
+    pub fn test(self, value: u16, against: u16) -> bool {
+        match self {
+            Comparison::Less => value < against,
+            Comparison::AtMost => value <= against,
+            Comparison::Equal => value == against,
+            Comparison::AtLeast => value >= against,
+            Comparison::Greater => value > against,
+        }
+    }
+}
+
+/// Something a saved build must have to be found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Takes a Fallout 4 perk at the given rank or higher
+    Perk(PerkId, u8),
+    /// Allocates a number of points to a stat, not counting bobbleheads or the book
+    Stat(SpecialStat, Comparison, u16),
+    /// Needs a level to be complete
+    Level(Comparison, u16),
+}
+
+impl Condition {
+    pub fn matches(&self, build: &Build) -> bool {
+        match *self {
+            Condition::Perk(id, rank) => {
+                build.game() == Game::Fallout4 && build.perks.get(&id).is_some_and(|r| *r >= rank)
+            }
+            Condition::Stat(stat, comparison, value) => build
+                .special
+                .get(&stat)
+                .is_some_and(|points| comparison.test(*points as u16, value)),
+            Condition::Level(comparison, value) => {
+                comparison.test(build.profile().required_level(build), value)
+            }
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((op, comparison, at)) = Comparison::OPERATORS
+            .iter()
+            .find_map(|(op, comparison)| s.find(op).map(|at| (op, *comparison, at)))
+        else {
+            bail!("{:?} is not a comparison like \"luck>=8\"", s)
+        };
+        let (subject, value) = (&s[..at], &s[at + op.len()..]);
+        let Ok(value) = value.parse::<u16>() else {
+            bail!("{:?} is not a number in {:?}", value, s)
+        };
+        if subject.eq_ignore_ascii_case("level") {
+            return Ok(Condition::Level(comparison, value));
+        }
+        match subject.parse::<SpecialStat>() {
+            Ok(stat) if !subject.is_empty() => Ok(Condition::Stat(stat, comparison, value)),
+            _ => bail!(
+                "Unknown stat {:?}, expected a S.P.E.C.I.A.L. stat or \"level\"",
+                subject
+            ),
+        }
+    }
+}
+
+/// Parse search words like "luck>=8 mister sandman 2" into conditions that must all hold.
+/// Words with a comparison are stat conditions and the rest name a perk and its least rank.
+pub fn parse_conditions(words: &[String]) -> anyhow::Result<Vec<Condition>> {
+    let (comparisons, perk): (Vec<String>, Vec<String>) = words
+        .iter()
+        .cloned()
+        .partition(|word| word.contains(['<', '>', '=']));
+    let mut conditions = comparisons
+        .into_iter()
+        .map(|word| word.parse())
+        .collect::<anyhow::Result<Vec<Condition>>>()?;
+    if !perk.is_empty() {
+        let (id, rank) = join_perk_id_and_rank(&perk)?;
+        conditions.push(Condition::Perk(id, rank.unwrap_or(1)));
+    }
+    if conditions.is_empty() {
+        bail!("Search for a perk or a stat, like \"find rifleman\" or \"find luck>=8\"")
+    }
+    Ok(conditions)
+}
+
+/// The saved builds that match every condition
+pub fn find(storage: &dyn Storage, conditions: &[Condition]) -> anyhow::Result<SavedBuilds> {
+    let mut saved = saved_builds(storage)?;
+    saved
+        .builds
+        .retain(|(_, build)| conditions.iter().all(|condition| condition.matches(build)));
+    Ok(saved)
+}
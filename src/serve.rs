@@ -0,0 +1,343 @@
+use std::{io, iter::once};
+
+use anyhow::{anyhow, bail};
+use clap::Parser;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use fo4builder::{
+    build::Build,
+    special::{catalog, PerkId},
+    storage::{Folder, Storage},
+};
+
+use crate::{reload_perks, Command, Outcome, Session};
+
+pub fn serve(host: &str, port: u16, token: Option<String>) -> anyhow::Result<()> {
+    let rng = SystemRandom::new();
+    let token = match token {
+        Some(token) => token,
+        None => {
+            let mut bytes = [0; 16];
+            rng.fill(&mut bytes)
+                .map_err(|_| anyhow!("Unable to generate a token"))?;
+            let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            println!("Token: {}", token);
+            token
+        }
+    };
+    // Requests are checked against a MAC of the expected header rather than the
+    // header itself, so the check takes as long wherever a wrong token differs
+    let key = hmac::Key::generate(hmac::HMAC_SHA256, &rng)
+        .map_err(|_| anyhow!("Unable to generate a key"))?;
+    let authorization = hmac::sign(&key, format!("Bearer {}", token).as_bytes());
+    let server = Server::http((host, port)).map_err(|e| anyhow!("{}", e))?;
+    println!("Listening on http://{}:{}", host, port);
+    println!("Send \"Authorization: Bearer <TOKEN>\" with every request");
+    let mut session = Session {
+        build: Build::default(),
+        pareto_builds: Vec::new(),
+        progress: Box::new(io::sink()),
+        tabs: Default::default(),
+        merge: None,
+        scripts: Default::default(),
+        snapshots: Default::default(),
+        history: Default::default(),
+        listing: Vec::new(),
+    };
+    for mut request in server.incoming_requests() {
+        if catalog().changed() {
+            if let Err(e) = reload_perks(&mut session.build) {
+                eprintln!("Perk data not reloaded: {}", e);
+            }
+        }
+        let (status, body) = respond(&mut session, &mut request, &key, &authorization);
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(
+                Header::from_bytes("Content-Type", "application/json").expect("Invalid header"),
+            );
+        if let Err(e) = request.respond(response) {
+            eprintln!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Check a request's origin and token before handling it, returning the status and body
+fn respond(
+    session: &mut Session,
+    request: &mut Request,
+    key: &hmac::Key,
+    authorization: &hmac::Tag,
+) -> (u16, Value) {
+    let authorized = header(request, "Authorization")
+        .is_some_and(|value| hmac::verify(key, value.as_bytes(), authorization.as_ref()).is_ok());
+    if !same_origin(request) {
+        (
+            403,
+            json!({ "error": "Cross-origin requests are not allowed" }),
+        )
+    } else if !authorized {
+        (401, json!({ "error": "Missing or wrong token" }))
+    } else {
+        match handle(session, request) {
+            Ok(Some(body)) => (200, body),
+            Ok(None) => (404, json!({ "error": "Not found" })),
+            Err(e) => (400, json!({ "error": e.to_string() })),
+        }
+    }
+}
+
+fn handle(session: &mut Session, request: &mut Request) -> anyhow::Result<Option<Value>> {
+    let mut body = String::new();
+    request.as_reader().read_to_string(&mut body)?;
+    let url = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_owned();
+    let path: Vec<String> = url
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .map(decode)
+        .collect();
+    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+    let build = &mut session.build;
+    Ok(Some(match (request.method(), path.as_slice()) {
+        (Method::Get, ["build"]) => build.to_json()?,
+        (Method::Put, ["build"]) => {
+            *build = Build::from_json(serde_json::from_str(&body)?)?;
+            build.to_json()?
+        }
+        (Method::Get, ["stats"]) => to_value(build.stats())?,
+        (Method::Get, ["perks"]) => {
+            let gender = build.gender.unwrap_or_default();
+            let difficulty = build.difficulty.unwrap_or_default();
+            to_value(
+                catalog()
+                    .perks
+                    .iter()
+                    .map(|(id, def)| def.info(*id, gender, difficulty))
+                    .collect::<Vec<_>>(),
+            )?
+        }
+        (Method::Get, ["perks", name]) => {
+            let id: PerkId = name.parse()?;
+            let def = match catalog().perks.get(&id).cloned() {
+                Some(def) => def,
+                None => return Ok(None),
+            };
+            let gender = build.gender.unwrap_or_default();
+            let difficulty = build.difficulty.unwrap_or_default();
+            to_value(def.info(id, gender, difficulty))?
+        }
+        (Method::Get, ["builds"]) => to_value(
+            Folder(Build::dir())
+                .list()?
+                .into_iter()
+                .filter(|name| name != "perks.yaml")
+                .filter_map(|name| name.strip_suffix(".yaml").map(String::from))
+                .collect::<Vec<_>>(),
+        )?,
+        (Method::Get, ["builds", name]) => {
+            Build::check_name(name)?;
+            Build::load(Build::dir().join(format!("{}.yaml", name)))?.to_json()?
+        }
+        (Method::Put, ["builds", name]) => {
+            Build::check_name(name)?;
+            build.name = Some(name.to_string());
+            build.save(None)?;
+            build.to_json()?
+        }
+        (Method::Post, ["command"]) => {
+            let args = once("fo4").chain(body.split_whitespace());
+            let command = Command::try_parse_from(args).map_err(|e| {
+                anyhow!(
+                    "{}",
+                    e.to_string()
+                        .trim_end_matches("\n\nFor more information try --help\n")
+                )
+            })?;
+            if !command.in_memory() {
+                bail!(
+                    "\"{}\" is not available over HTTP",
+                    body.split_whitespace().next().unwrap_or_default()
+                )
+            }
+            match session.apply(command) {
+                Outcome::Message(message) => json!({
+                    "message": message?,
+                    "build": session.build.to_json()?,
+                }),
+                Outcome::View(_) | Outcome::Exit => bail!(
+                    "\"{}\" is not available over HTTP",
+                    body.split_whitespace().next().unwrap_or_default()
+                ),
+            }
+        }
+        _ => return Ok(None),
+    }))
+}
+
+fn header(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.to_string())
+}
+
+/// Browsers send an `Origin` with cross-origin requests, which must not reach the planner
+fn same_origin(request: &Request) -> bool {
+    match header(request, "Origin") {
+        Some(origin) => {
+            header(request, "Host").map(|host| format!("http://{}", host)) == Some(origin)
+        }
+        None => true,
+    }
+}
+
+fn to_value(value: impl Serialize) -> anyhow::Result<Value> {
+    Ok(serde_json::to_value(value)?)
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (b, _) => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::TcpStream, thread};
+
+    use super::*;
+
+    const TOKEN: &str = "Bearer secret";
+
+    fn session() -> Session {
+        Session {
+            build: Build::default(),
+            pareto_builds: Vec::new(),
+            progress: Box::new(io::sink()),
+            tabs: Default::default(),
+            merge: None,
+            scripts: Default::default(),
+            snapshots: Default::default(),
+            history: Default::default(),
+            listing: Vec::new(),
+        }
+    }
+
+    /// Send a request through a local server and respond to it as [`serve`] would
+    fn exchange(
+        session: &mut Session,
+        method: &str,
+        path: &str,
+        headers: &[&str],
+        body: &str,
+    ) -> (u16, Value) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        let mut raw = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, path, addr);
+        for header in headers {
+            raw.push_str(&format!("{}\r\n", header));
+        }
+        raw.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(raw.as_bytes()).unwrap();
+        });
+        let mut request = server.recv().unwrap();
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"test key");
+        let authorization = hmac::sign(&key, TOKEN.as_bytes());
+        let response = respond(session, &mut request, &key, &authorization);
+        client.join().unwrap();
+        response
+    }
+
+    fn command(session: &mut Session, text: &str) -> (u16, Value) {
+        let auth = format!("Authorization: {}", TOKEN);
+        exchange(session, "POST", "/command", &[&auth], text)
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let mut session = session();
+        assert_eq!(exchange(&mut session, "GET", "/build", &[], "").0, 401);
+        let wrong = ["Authorization: Bearer secreT"];
+        assert_eq!(exchange(&mut session, "GET", "/build", &wrong, "").0, 401);
+        let right = ["Authorization: Bearer secret"];
+        assert_eq!(exchange(&mut session, "GET", "/build", &right, "").0, 200);
+        assert_eq!(exchange(&mut session, "GET", "/nowhere", &right, "").0, 404);
+    }
+
+    #[test]
+    fn cross_origin_requests_are_refused() {
+        let mut session = session();
+        let headers = [
+            "Authorization: Bearer secret",
+            "Origin: http://evil.example",
+        ];
+        let (status, body) = exchange(&mut session, "GET", "/build", &headers, "");
+        assert_eq!(status, 403, "{}", body);
+    }
+
+    #[test]
+    fn only_commands_that_stay_in_memory_run() {
+        let mut session = session();
+        let (status, body) = command(&mut session, "set strength 6");
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(
+            session.build.special[&fo4builder::special::SpecialStat::Strength],
+            6
+        );
+        assert_eq!(command(&mut session, "share").0, 200);
+        for text in ["save", "share --sign", "export csv", "scripts", "exit"] {
+            let (status, body) = command(&mut session, text);
+            assert_eq!(status, 400, "{}: {}", text, body);
+        }
+    }
+
+    #[test]
+    fn build_names_are_checked() {
+        let mut session = session();
+        let auth = format!("Authorization: {}", TOKEN);
+        for path in ["/builds/..%2Fescaped", "/builds/perks", "/builds/.hidden"] {
+            let (status, body) = exchange(&mut session, "PUT", path, &[&auth], "");
+            assert_eq!(status, 400, "{}: {}", path, body);
+        }
+    }
+
+    #[test]
+    fn paths_are_percent_decoded() {
+        assert_eq!(
+            decode("magazine%3A%20grognak+the%zz"),
+            "magazine: grognak the%zz"
+        );
+    }
+}
@@ -0,0 +1,442 @@
+#[cfg(feature = "native")]
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use anyhow::{anyhow, bail};
+#[cfg(feature = "native")]
+use ring::{
+    rand::SystemRandom,
+    signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519},
+};
+
+use crate::{
+    build::Build,
+    special::{catalog, BobbleheadId, PerkId, SpecialStat},
+};
+
+const PREFIX: &str = "FO4-";
+/// Separates a share code from its signature, which is the signing public key followed by the signature
+const SIGNATURE_SEPARATOR: char = '.';
+#[cfg(feature = "native")]
+const PUBLIC_KEY_LEN: usize = 32;
+#[cfg(feature = "native")]
+const SIGNATURE_LEN: usize = 64;
+/// Version 1 codes wrote every perk by its position in the perk data, and version 2
+/// writes the perks whose ids are positions by key
+const VERSION: u8 = 2;
+/// Marks a perk written as the length of its key followed by the key
+const KEY_TAG: u8 = 13;
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn stat_index(stat: SpecialStat) -> u8 {
+    SpecialStat::ALL.iter().position(|s| *s == stat).unwrap() as u8
+}
+
+fn stat_at(index: u8) -> anyhow::Result<SpecialStat> {
+    match SpecialStat::ALL.get(index as usize) {
+        Some(stat) => Ok(*stat),
+        None => bail!("Share code contains an invalid stat"),
+    }
+}
+
+fn encode_id(id: PerkId, bytes: &mut Vec<u8>) -> anyhow::Result<()> {
+    match id {
+        PerkId::Special { stat, points } => bytes.extend([stat_index(stat), points]),
+        PerkId::Bobblehead(BobbleheadId::Special(stat)) => bytes.extend([7, stat_index(stat)]),
+        _ => {
+            let catalog = catalog();
+            let key = catalog.key(&id).ok_or_else(|| anyhow!("Unknown perk"))?;
+            let len = u8::try_from(key.len())
+                .map_err(|_| anyhow!("The key {} is too long for a share code", key))?;
+            bytes.extend([KEY_TAG, len]);
+            bytes.extend(key.as_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Read a perk from the start of a code's bytes, returning the bytes after it
+fn decode_id(version: u8, bytes: &[u8]) -> anyhow::Result<(PerkId, &[u8])> {
+    let [tag, value, rest @ ..] = bytes else {
+        bail!("Share code is truncated")
+    };
+    let i = *value as usize;
+    let legacy = |id| {
+        PerkId::from_legacy(id)
+            .ok_or_else(|| anyhow!("Share code refers to a perk that is not in the perk data"))
+    };
+    let id = match (tag, version) {
+        (0..=6, _) => PerkId::Special {
+            stat: stat_at(*tag)?,
+            points: *value,
+        },
+        (7, _) => PerkId::Bobblehead(BobbleheadId::Special(stat_at(*value)?)),
+        (8, 1) => legacy(PerkId::Bobblehead(BobbleheadId::Other(i)))?,
+        (9, 1) => legacy(PerkId::Magazine(i))?,
+        (10, 1) => legacy(PerkId::Companion(i))?,
+        (11, 1) => legacy(PerkId::Faction(i))?,
+        (12, 1) => legacy(PerkId::Other(i))?,
+        (&KEY_TAG, 2..) => {
+            if rest.len() < i {
+                bail!("Share code is truncated")
+            }
+            let (key, rest) = rest.split_at(i);
+            let key = std::str::from_utf8(key)
+                .map_err(|_| anyhow!("Share code contains an invalid perk"))?;
+            let id = catalog().id_by_key(key).ok_or_else(|| {
+                anyhow!(
+                    "Share code refers to {}, which is not in the perk data",
+                    key
+                )
+            })?;
+            return Ok((id, rest));
+        }
+        _ => bail!("Share code contains an invalid perk"),
+    };
+    Ok((id, rest))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            s.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    s
+}
+
+fn unbase64(s: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for chunk in s.as_bytes().chunks(4) {
+        if chunk.len() < 2 {
+            bail!("Share code is truncated")
+        }
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            match ALPHABET.iter().position(|a| a == c) {
+                Some(v) => n |= (v as u32) << (18 - 6 * i),
+                None => bail!("Share code contains an invalid character: {}", *c as char),
+            }
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Split a share code into its code without the prefix and its signature, if it has one
+fn split_code(code: &str) -> (&str, Option<&str>) {
+    let code = code.trim();
+    let code = code.strip_prefix(PREFIX).unwrap_or(code);
+    match code.split_once(SIGNATURE_SEPARATOR) {
+        Some((code, signature)) => (code, Some(signature)),
+        None => (code, None),
+    }
+}
+
+#[cfg(feature = "native")]
+fn key_path() -> PathBuf {
+    Build::dir().join(".share-key")
+}
+
+/// The key this machine signs share codes with, created the first time it is needed
+#[cfg(feature = "native")]
+fn local_key() -> anyhow::Result<Ed25519KeyPair> {
+    let path = key_path();
+    let pkcs8 = if path.exists() {
+        fs::read(&path)?
+    } else {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| anyhow!("Unable to generate a signing key"))?;
+        fs::create_dir_all(Build::dir())?;
+        fs::write(&path, pkcs8.as_ref())?;
+        pkcs8.as_ref().to_vec()
+    };
+    Ed25519KeyPair::from_pkcs8(&pkcs8)
+        .map_err(|_| anyhow!("Signing key at {} is invalid", path.to_string_lossy()))
+}
+
+#[cfg(feature = "native")]
+fn fingerprint(public_key: &[u8]) -> String {
+    public_key[..4]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(feature = "native")]
+fn trusted_path() -> PathBuf {
+    Build::dir().join(".trusted-keys")
+}
+
+/// The public keys of people whose signed share codes are trusted, by the name they were
+/// trusted under
+#[cfg(feature = "native")]
+fn trusted_keys() -> anyhow::Result<BTreeMap<String, String>> {
+    let path = trusted_path();
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    serde_yaml::from_slice(&fs::read(&path)?)
+        .map_err(|e| anyhow!("{} is invalid: {}", path.to_string_lossy(), e))
+}
+
+#[cfg(feature = "native")]
+fn write_trusted_keys(keys: &BTreeMap<String, String>) -> anyhow::Result<()> {
+    fs::create_dir_all(Build::dir())?;
+    fs::write(trusted_path(), serde_yaml::to_vec(keys)?)?;
+    Ok(())
+}
+
+/// Who signed a share code
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signer {
+    /// The code has no signature, so anyone could have changed it
+    Unsigned,
+    /// This machine's key
+    Own,
+    /// A key trusted with [`trust_share_key`], by the name it was trusted under
+    Trusted(String),
+    /// A key that is not trusted, by its fingerprint. Anyone can sign a code with a key
+    /// of their own, so this does not say who made it.
+    Unknown(String),
+}
+
+#[cfg(feature = "native")]
+impl Signer {
+    pub fn describe(&self) -> String {
+        match self {
+            Signer::Unsigned => "Share code is not signed, so it may have been altered".into(),
+            Signer::Own => "Signature verified, signed by your key".into(),
+            Signer::Trusted(name) => format!("Signature verified, signed by {}", name),
+            Signer::Unknown(fingerprint) => format!(
+                "Signed by key {}, which is not trusted, so anyone could have signed it. \
+                If you know whose key it is, trust it with \"trust <NAME> <CODE>\"",
+                fingerprint
+            ),
+        }
+    }
+}
+
+/// Check a share code's signature, returning the public key that signed it
+#[cfg(feature = "native")]
+fn signing_key(code: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let (code, signature) = match split_code(code) {
+        (_, None) => return Ok(None),
+        (code, Some(signature)) => (code, signature),
+    };
+    let bytes = unbase64(signature)?;
+    if bytes.len() != PUBLIC_KEY_LEN + SIGNATURE_LEN {
+        bail!("Share code signature is truncated")
+    }
+    let (public_key, signature) = bytes.split_at(PUBLIC_KEY_LEN);
+    UnparsedPublicKey::new(&ED25519, public_key)
+        .verify(format!("{}{}", PREFIX, code).as_bytes(), signature)
+        .map_err(|_| {
+            anyhow!("Share code does not match its signature, it may have been altered")
+        })?;
+    Ok(Some(public_key.to_vec()))
+}
+
+/// Check a share code's signature against this machine's key and the trusted keys
+///
+/// Codes that are unsigned or signed by an unknown key are accepted, but the
+/// [`Signer`] says so.
+#[cfg(feature = "native")]
+pub fn verify_share_code(code: &str) -> anyhow::Result<Signer> {
+    let Some(public_key) = signing_key(code)? else {
+        return Ok(Signer::Unsigned);
+    };
+    if key_path().exists() && local_key()?.public_key().as_ref() == public_key {
+        return Ok(Signer::Own);
+    }
+    let encoded = base64(&public_key);
+    Ok(trusted_keys()?
+        .into_iter()
+        .find(|(_, key)| *key == encoded)
+        .map_or_else(
+            || Signer::Unknown(fingerprint(&public_key)),
+            |(name, _)| Signer::Trusted(name),
+        ))
+}
+
+/// Trust the key a share code was signed with under a name, returning its fingerprint
+#[cfg(feature = "native")]
+pub fn trust_share_key(name: &str, code: &str) -> anyhow::Result<String> {
+    let Some(public_key) = signing_key(code)? else {
+        bail!("That share code is not signed")
+    };
+    let mut keys = trusted_keys()?;
+    keys.insert(name.into(), base64(&public_key));
+    write_trusted_keys(&keys)?;
+    Ok(fingerprint(&public_key))
+}
+
+/// Stop trusting a key by the name it was trusted under
+#[cfg(feature = "native")]
+pub fn untrust_share_key(name: &str) -> anyhow::Result<()> {
+    let mut keys = trusted_keys()?;
+    if keys.remove(name).is_none() {
+        bail!("No key is trusted as {}", name)
+    }
+    write_trusted_keys(&keys)
+}
+
+/// The trusted keys' names and fingerprints
+#[cfg(feature = "native")]
+pub fn trusted_share_keys() -> anyhow::Result<Vec<(String, String)>> {
+    trusted_keys()?
+        .into_iter()
+        .map(|(name, key)| {
+            let key = unbase64(&key)?;
+            if key.len() != PUBLIC_KEY_LEN {
+                bail!("The key trusted as {} is invalid", name)
+            }
+            Ok((name, fingerprint(&key)))
+        })
+        .collect()
+}
+
+impl Build {
+    /// A share code signed with this machine's key, so others can tell it was not altered
+    #[cfg(feature = "native")]
+    pub fn signed_share_code(&self) -> anyhow::Result<String> {
+        let code = self.share_code()?;
+        let key = local_key()?;
+        let mut bytes = key.public_key().as_ref().to_vec();
+        bytes.extend(key.sign(code.as_bytes()).as_ref());
+        Ok(format!("{}{}{}", code, SIGNATURE_SEPARATOR, base64(&bytes)))
+    }
+    /// The fingerprint of the key [`Build::signed_share_code`] signs with
+    #[cfg(feature = "native")]
+    pub fn share_key_fingerprint() -> anyhow::Result<String> {
+        Ok(fingerprint(local_key()?.public_key().as_ref()))
+    }
+    /// A short code that carries the build's S.P.E.C.I.A.L., book, and perks
+    pub fn share_code(&self) -> anyhow::Result<String> {
+        let mut bytes = vec![VERSION];
+        bytes.extend(SpecialStat::ALL.iter().map(|stat| self.special[stat]));
+        bytes.push(self.special_book.map_or(0, |stat| stat_index(stat) + 1));
+        for (id, rank) in &self.perks {
+            encode_id(*id, &mut bytes)?;
+            bytes.push(*rank);
+        }
+        Ok(format!("{}{}", PREFIX, base64(&bytes)))
+    }
+    /// Decode a share code, rejecting it if it is signed and the signature does not match
+    pub fn from_share_code(code: &str) -> anyhow::Result<Build> {
+        #[cfg(feature = "native")]
+        verify_share_code(code)?;
+        let (code, _) = split_code(code);
+        let bytes = unbase64(code)?;
+        let (version, rest) = match bytes.split_first() {
+            Some(split) => split,
+            None => bail!("Share code is empty"),
+        };
+        if !(1..=VERSION).contains(version) {
+            bail!("Share code version {} is not supported", version)
+        }
+        if rest.len() < 8 {
+            bail!("Share code is truncated")
+        }
+        let mut build = Build::default();
+        for (stat, points) in SpecialStat::ALL.iter().zip(rest) {
+            if !(1..=10).contains(points) {
+                bail!("Share code contains an invalid {} value", stat)
+            }
+            build.special.insert(*stat, *points);
+        }
+        build.special_book = match rest[7] {
+            0 => None,
+            i => Some(stat_at(i - 1)?),
+        };
+        let mut perks = &rest[8..];
+        while !perks.is_empty() {
+            let (id, after) = decode_id(*version, perks)?;
+            let [rank, after @ ..] = after else {
+                bail!("Share code is truncated")
+            };
+            let def = match catalog().perks.get(&id).cloned() {
+                Some(def) => def,
+                None => bail!("Share code refers to a perk that is not in the perk data"),
+            };
+            if *rank == 0 || *rank > def.max_rank() {
+                bail!(
+                    "Share code has an invalid rank for {}",
+                    build.perk_name(&id)
+                )
+            }
+            build.perks.insert(id, *rank);
+            perks = after;
+        }
+        Ok(build)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A code made before perks were written by key, with Iron Fist 2, the Sneak
+    /// bobblehead, two issues of Grognak the Barbarian, and Ace Operator
+    const VERSION_1: &str = "FO4-AQMBAQEBAQEAAAECCAoBCQ8CCwAB";
+
+    fn keys(build: &Build) -> Vec<String> {
+        let catalog = catalog();
+        build
+            .perks
+            .iter()
+            .map(|(id, rank)| format!("{} {}", catalog.key(id).unwrap(), rank))
+            .collect()
+    }
+
+    #[test]
+    fn version_1_codes_decode_by_position() {
+        let build = Build::from_share_code(VERSION_1).unwrap();
+        assert_eq!(build.special[&SpecialStat::Strength], 3);
+        assert_eq!(
+            keys(&build),
+            [
+                "strength-1 2",
+                "bobblehead-sneak-bobblehead 1",
+                "magazine-grognak-the-barbarian 2",
+                "faction-ace-operator 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn codes_round_trip_by_key() {
+        let build = Build::from_share_code(VERSION_1).unwrap();
+        let code = build.share_code().unwrap();
+        assert_ne!(code, VERSION_1);
+        let decoded = Build::from_share_code(&code).unwrap();
+        assert_eq!(decoded.special, build.special);
+        assert_eq!(decoded.special_book, build.special_book);
+        assert_eq!(keys(&decoded), keys(&build));
+    }
+
+    #[test]
+    fn perks_without_a_key_are_not_shared() {
+        let mut build = Build::default();
+        build.perks.insert(PerkId::Other(usize::MAX), 1);
+        assert!(build.share_code().is_err());
+    }
+
+    #[test]
+    fn truncated_codes_are_rejected() {
+        let code = Build::from_share_code(VERSION_1)
+            .unwrap()
+            .share_code()
+            .unwrap();
+        let bytes = unbase64(code.strip_prefix(PREFIX).unwrap()).unwrap();
+        let truncated = format!("{}{}", PREFIX, base64(&bytes[..bytes.len() - 4]));
+        assert!(Build::from_share_code(&truncated).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::bail;
+
+use crate::Session;
+
+impl Session {
+    /// Keep a copy of the current build under a name for this session, replacing any
+    /// snapshot already by that name
+    pub fn snapshot(&mut self, name: &str) -> anyhow::Result<String> {
+        if name.is_empty() {
+            bail!("Name the snapshot, like \"snapshot before rifles\"")
+        }
+        let replaced = self
+            .snapshots
+            .insert(name.into(), self.build.clone())
+            .is_some();
+        Ok(if replaced {
+            format!("Replaced snapshot {}", name)
+        } else {
+            format!("Took snapshot {}", name)
+        })
+    }
+    /// Replace the current build with a snapshot, which is kept to roll back to again
+    pub fn rollback(&mut self, name: &str) -> anyhow::Result<String> {
+        let Some(snapshot) = self.snapshots.get(name) else {
+            if self.snapshots.is_empty() {
+                bail!("There are no snapshots, take one with \"snapshot <NAME>\"")
+            }
+            bail!("There is no snapshot named {}", name)
+        };
+        self.build = snapshot.clone();
+        self.merge = None;
+        Ok(format!("Rolled back to snapshot {}", name))
+    }
+    /// List the snapshots taken this session
+    pub fn describe_snapshots(&self) -> String {
+        if self.snapshots.is_empty() {
+            return "No snapshots yet, take one with \"snapshot <NAME>\"".into();
+        }
+        let width = self.snapshots.keys().map(String::len).max().unwrap_or(0);
+        self.snapshots
+            .iter()
+            .map(|(name, build)| {
+                format!(
+                    "  {:width$}  {}, level {}",
+                    name,
+                    build.name.as_deref().unwrap_or("untitled"),
+                    build.profile().required_level(build),
+                    width = width
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
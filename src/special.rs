@@ -1,40 +1,33 @@
 use std::{
-    cmp::Ordering,
-    collections::BTreeMap,
-    fmt,
-    iter::{empty, once},
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    iter::once,
     ops::Index,
-    process::exit,
+    path::{Path, PathBuf},
     str::FromStr,
+    sync::{Arc, RwLock},
+    time::SystemTime,
 };
 
-use anyhow::bail;
-use bimap::BiBTreeMap;
+use anyhow::{anyhow, bail};
 use once_cell::sync::Lazy;
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
-pub enum SpecialStat {
-    Strength,
-    Perception,
-    Endurance,
-    Charisma,
-    Intelligence,
-    Agility,
-    Luck,
-}
+use serde::{
+    de::{self, value::MapAccessDeserializer, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 
-impl SpecialStat {
-    pub const ALL: &'static [Self] = &[
-        SpecialStat::Strength,
-        SpecialStat::Perception,
-        SpecialStat::Endurance,
-        SpecialStat::Charisma,
-        SpecialStat::Intelligence,
-        SpecialStat::Agility,
-        SpecialStat::Luck,
-    ];
-}
+pub use crate::perk_data::{
+    Combine, Difficultied, Difficulty, DifficultyDef, EffectDef, EffectValue, Effects,
+    FullyVariable, Gender, GenderCondition, GenderTarget, Gendered, LegendaryDef, Location,
+    MaybeDifficultied, MaybeGendered, MaybeVaried, Name, PerkDef, Rank, Ranks, SpecialStat,
+    StatIncrease,
+};
+#[cfg(feature = "native")]
+use crate::{build::Build, config::config};
+use crate::{
+    matcher::{MatchOptions, NameIndex},
+    perk_data::{AllPerksRep, Issues, LocationsRep},
+};
 
 impl FromStr for SpecialStat {
     type Err = String;
@@ -55,7 +48,7 @@ impl fmt::Display for SpecialStat {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum PerkId {
     Special { stat: SpecialStat, points: u8 },
     Bobblehead(BobbleheadId),
@@ -65,6 +58,107 @@ pub enum PerkId {
     Other(usize),
 }
 
+/// How perk ids were saved before they were saved by key. The indices are positions
+/// in the perk data, so they only mean the same perk while the data keeps its order.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "PerkId")]
+enum IndexPerkId {
+    Special { stat: SpecialStat, points: u8 },
+    Bobblehead(BobbleheadId),
+    Magazine(usize),
+    Companion(usize),
+    Faction(usize),
+    Other(usize),
+}
+
+/// The keys of the perks older files saved by index, by kind and index
+#[derive(Deserialize)]
+struct LegacyIds {
+    bobbleheads: Vec<String>,
+    magazines: Vec<String>,
+    companions: Vec<String>,
+    factions: Vec<String>,
+    other: Vec<String>,
+}
+
+static LEGACY_IDS: Lazy<LegacyIds> = Lazy::new(|| {
+    serde_yaml::from_str(include_str!("legacy_ids.yaml")).expect("Invalid legacy perk ids")
+});
+
+impl PerkId {
+    /// The perk an id saved in the index form refers to in the current perk data. S.P.E.C.I.A.L.
+    /// perks and bobbleheads never moved, and the rest are looked up by the key their
+    /// index had when ids stopped being saved by index.
+    pub fn from_legacy(id: PerkId) -> Option<PerkId> {
+        let legacy = &*LEGACY_IDS;
+        let key = match id {
+            PerkId::Special { .. } | PerkId::Bobblehead(BobbleheadId::Special(_)) => {
+                return Some(id)
+            }
+            PerkId::Bobblehead(BobbleheadId::Other(i)) => legacy.bobbleheads.get(i),
+            PerkId::Magazine(i) => legacy.magazines.get(i),
+            PerkId::Companion(i) => legacy.companions.get(i),
+            PerkId::Faction(i) => legacy.factions.get(i),
+            PerkId::Other(i) => legacy.other.get(i),
+        }?;
+        catalog().id_by_key(key)
+    }
+}
+
+/// Perk ids are saved by their key from [`Catalog::key`], like `strength-6` or
+/// `magazine-scav-1-the-terrible-truce`, which stays the same when the perk data is
+/// reordered. Formats that are not human-readable, and perks missing from the data,
+/// keep the index form.
+impl Serialize for PerkId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match catalog().key(self) {
+            Some(key) if serializer.is_human_readable() => serializer.serialize_str(key),
+            _ => IndexPerkId::serialize(self, serializer),
+        }
+    }
+}
+
+/// Perk ids are read from either a key or the index form older files were saved with,
+/// which is moved to where the perk is now
+impl<'de> Deserialize<'de> for PerkId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return IndexPerkId::deserialize(deserializer);
+        }
+        struct PerkIdVisitor;
+        impl<'de> Visitor<'de> for PerkIdVisitor {
+            type Value = PerkId;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a perk key like `strength-6`")
+            }
+            fn visit_str<E: de::Error>(self, key: &str) -> Result<PerkId, E> {
+                let catalog = catalog();
+                catalog.id_by_key(key).ok_or_else(|| {
+                    let closest = catalog
+                        .perk_keys
+                        .values()
+                        .map(|other| (other, similarity(key, other)))
+                        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                        .filter(|(_, sim)| *sim >= 0.7);
+                    match closest {
+                        Some((other, _)) => E::custom(format!(
+                            "unknown perk `{}`, the closest is `{}`",
+                            key, other
+                        )),
+                        None => E::custom(format!("unknown perk `{}`", key)),
+                    }
+                })
+            }
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<PerkId, A::Error> {
+                let id = IndexPerkId::deserialize(MapAccessDeserializer::new(map))?;
+                PerkId::from_legacy(id)
+                    .ok_or_else(|| de::Error::custom(format!("unknown perk {:?}", id)))
+            }
+        }
+        deserializer.deserialize_any(PerkIdVisitor)
+    }
+}
+
 impl PerkId {
     pub fn kind(&self) -> PerkKind {
         match self {
@@ -107,7 +201,42 @@ impl fmt::Display for PerkKind {
     }
 }
 
-fn similarity(a: impl AsRef<str>, b: impl AsRef<str>) -> f64 {
+impl FromStr for PerkKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        let singular = lower.strip_suffix('s').unwrap_or(&lower);
+        Ok(match singular {
+            "bobblehead" | "bobble" => PerkKind::Bobblehead,
+            "magazine" | "mag" => PerkKind::Magazine,
+            "companion" => PerkKind::Companion,
+            "faction" => PerkKind::Faction,
+            "other" => PerkKind::Other,
+            "" => bail!("Missing perk category"),
+            _ => PerkKind::Special(
+                lower
+                    .parse()
+                    .map_err(|_| anyhow!("Unknown perk category: {}", s.trim()))?,
+            ),
+        })
+    }
+}
+
+impl PerkKind {
+    /// The prefix that limits a perk name to this kind, as in "magazine: grognak"
+    pub fn qualifier(self) -> String {
+        match self {
+            PerkKind::Special(stat) => stat.to_string().to_lowercase(),
+            PerkKind::Bobblehead => "bobblehead".into(),
+            PerkKind::Magazine => "magazine".into(),
+            PerkKind::Companion => "companion".into(),
+            PerkKind::Faction => "faction".into(),
+            PerkKind::Other => "other".into(),
+        }
+    }
+}
+
+pub fn similarity(a: impl AsRef<str>, b: impl AsRef<str>) -> f64 {
     fn sim(a: &str, b: &str) -> f64 {
         (strsim::jaro_winkler(a, b) * 2.0 + strsim::normalized_levenshtein(a, b)) / 3.0
     }
@@ -121,178 +250,224 @@ fn similarity(a: impl AsRef<str>, b: impl AsRef<str>) -> f64 {
     (base + parts) / 2.0
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct PerkDef {
-    pub name: MaybeGendered<String>,
-    pub ranks: Ranks,
+impl GenderTarget {
+    pub fn resolve(self, gender: Gender) -> Gender {
+        match self {
+            GenderTarget::Same => gender,
+            GenderTarget::Opposite => gender.opposite(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PerkInfo {
+    pub id: PerkId,
+    pub kind: String,
+    pub name: String,
+    pub ranks: Vec<RankInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RankInfo {
+    pub required_level: u8,
+    pub description: String,
 }
 
 impl PerkDef {
+    pub fn info(&self, id: PerkId, gender: Gender, difficulty: Difficulty) -> PerkInfo {
+        let rank = |required_level, description: &FullyVariable<String>| RankInfo {
+            required_level,
+            description: description[difficulty][gender].clone(),
+        };
+        PerkInfo {
+            id,
+            kind: id.kind().to_string(),
+            name: self.name[gender].to_string(),
+            ranks: match &self.ranks {
+                Ranks::Single { description, .. } => vec![rank(1, description)],
+                Ranks::UniformCumulative {
+                    count, description, ..
+                } => (0..*count).map(|_| rank(1, description)).collect(),
+                Ranks::VaryingCumulative(ranks) => ranks
+                    .iter()
+                    .map(|r| rank(r.required_level, &r.description))
+                    .collect(),
+            },
+        }
+    }
     pub fn max_rank(&self) -> u8 {
         self.ranks.max_rank()
     }
 }
 
-impl FromStr for PerkDef {
+/// How close two matches of different kinds must score for a name to be ambiguous
+const AMBIGUITY: f64 = 0.01;
+
+impl FromStr for PerkId {
     type Err = anyhow::Error;
+    /// Find a perk by name, optionally qualified by its kind as in "magazine: grognak"
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let s = s.to_lowercase();
-        let s = &s;
-        let (def, sim) = PERKS
-            .right_values()
-            .flat_map(|def| {
-                def.name.iter().map(move |name| {
-                    let name = name.to_lowercase();
-                    (def, similarity(s, name))
-                })
+        let (kind, name) = match s.split_once(':') {
+            Some((kind, name)) => (Some(kind.parse::<PerkKind>()?), name),
+            None => (None, s),
+        };
+        let threshold = MatchOptions::current().threshold;
+        let matches: Vec<(PerkId, f64)> = catalog()
+            .search(name)
+            .into_iter()
+            .filter(|(id, sim)| *sim >= threshold && kind.is_none_or(|k| id.kind() == k))
+            .collect();
+        let Some(&(id, best)) = matches.first() else {
+            bail!("Unknown perk: {}", s.to_lowercase())
+        };
+        // An exact name wins outright unless another kind has it too
+        let rivals: Vec<PerkId> = matches
+            .iter()
+            .filter(|(other, sim)| {
+                other.kind() != id.kind() && (best - sim < AMBIGUITY) && (best < 1.0 || *sim >= 1.0)
             })
-            .max_by_key(|(_, sim)| (*sim * 1000000.0) as u32)
-            .unwrap();
-        if sim >= 0.6 {
-            Ok(def.clone())
-        } else {
-            bail!("Unknown perk: {}", s)
+            .map(|(other, _)| *other)
+            .collect();
+        if rivals.is_empty() {
+            return Ok(id);
         }
+        let candidates: Vec<String> = once(id)
+            .chain(rivals)
+            .map(|id| {
+                format!(
+                    "\"{}: {}\"",
+                    id.kind().qualifier(),
+                    catalog().perks[&id].name[Gender::default()].trim()
+                )
+            })
+            .collect();
+        bail!(
+            "\"{}\" could be more than one perk, try {}",
+            name.trim(),
+            candidates.join(" or ")
+        )
     }
 }
 
-impl PartialEq for PerkDef {
-    fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+pub fn join_perk_id(parts: &[String]) -> anyhow::Result<PerkId> {
+    if parts.is_empty() {
+        bail!("You must specify a perk")
+    } else {
+        parts.iter().map(String::as_str).collect::<String>().parse()
     }
 }
 
-impl Eq for PerkDef {}
-
-impl PartialOrd for PerkDef {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+pub fn join_perk_id_and_rank(parts: &[String]) -> anyhow::Result<(PerkId, Option<u8>)> {
+    if parts.is_empty() {
+        bail!("You must specify a perk")
+    } else if parts.len() == 1 {
+        parts[0].parse::<PerkId>().map(|id| (id, None))
+    } else if let Ok(last) = parts.last().unwrap().parse::<u8>() {
+        let sub = &parts[..(parts.len() - 1)];
+        if sub
+            .last()
+            .and_then(|part| part.parse::<u8>().ok())
+            .is_some()
+        {
+            join_perk_id(sub).map(|id| (id, Some(last)))
+        } else if let Ok(id) = join_perk_id(sub) {
+            Ok((id, Some(last)))
+        } else {
+            join_perk_id(parts).map(|id| (id, None))
+        }
+    } else {
+        join_perk_id(parts).map(|id| (id, None))
     }
 }
 
-impl Ord for PerkDef {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.name.cmp(&other.name)
-    }
+/// The values an effect contributes for a perk at some rank
+#[derive(Debug, Clone)]
+pub enum EffectValues<T> {
+    Option(Option<T>),
+    Repeat(T, u8),
 }
 
-pub type FullyVariable<T> = MaybeDifficultied<MaybeGendered<T>>;
-
-#[derive(Debug, Clone, Deserialize)]
-pub struct Rank {
-    #[serde(default = "default_required_level", alias = "level")]
-    pub required_level: u8,
-    #[serde(alias = "desc")]
-    pub description: FullyVariable<String>,
-    #[serde(default, skip_serializing_if = "Vec::is_empty", flatten)]
-    pub effects: Effects,
-}
-
-fn default_required_level() -> u8 {
-    1
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(untagged)]
-pub enum Ranks {
-    UniformCumulative {
-        count: u8,
-        #[serde(alias = "desc")]
-        description: FullyVariable<String>,
-        #[serde(default, skip_serializing_if = "Vec::is_empty", flatten)]
-        effects: Effects,
-    },
-    Single {
-        #[serde(alias = "desc")]
-        description: FullyVariable<String>,
-        #[serde(default, skip_serializing_if = "Vec::is_empty", flatten)]
-        effects: Effects,
-    },
-    VaryingCumulative(Vec<Rank>),
-}
-
-impl Ranks {
-    pub fn max_rank(&self) -> u8 {
+impl<T: Copy> Iterator for EffectValues<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
         match self {
-            Ranks::Single { .. } => 1,
-            Ranks::UniformCumulative { count, .. } => *count,
-            Ranks::VaryingCumulative(ranks) => ranks.len() as u8,
+            EffectValues::Option(val) => val.take(),
+            EffectValues::Repeat(_, 0) => None,
+            EffectValues::Repeat(val, count) => {
+                *count -= 1;
+                Some(*val)
+            }
         }
     }
-    pub fn required_level(&self, rank: u8) -> u8 {
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self {
+            EffectValues::Option(val) => val.is_some() as usize,
+            EffectValues::Repeat(_, count) => *count as usize,
+        };
+        (len, Some(len))
+    }
+}
+
+impl Combine {
+    pub fn identity(self) -> f32 {
         match self {
-            Ranks::VaryingCumulative(ranks) => ranks[rank as usize - 1].required_level,
-            _ => 1,
+            Combine::Add => 0.0,
+            Combine::Mul => 1.0,
         }
     }
-    pub fn highest_rank_within_level(&self, level: u8) -> u8 {
+    pub fn apply(self, a: f32, b: f32) -> f32 {
         match self {
-            Ranks::Single { .. } => 1,
-            Ranks::UniformCumulative { count, .. } => *count,
-            Ranks::VaryingCumulative(ranks) => ranks
-                .iter()
-                .filter(|rank| rank.required_level <= level)
-                .count() as u8,
-        }
-    }
-}
-
-macro_rules! effects {
-    ($(($name:ident, $ty:ty)),* $(,)?) => {
-        #[derive(Debug, Clone, Default, Deserialize)]
-        pub struct Effects {
-            $(
-                #[serde(default, skip_serializing_if = "Option::is_none")]
-                $name: Option<$ty>,
-            )*
-        }
-        impl PerkDef {
-            $(
-                #[allow(dead_code)]
-                pub fn $name(&self, rank: u8) -> Box<dyn Iterator<Item = $ty>> {
-                    match &self.ranks {
-                        Ranks::Single { effects, .. } => if let Some(val) = effects.$name {
-                            Box::new(once(val)) as Box<dyn Iterator<Item = $ty>>
-                        } else {
-                            Box::new(empty())
-                        }.into_iter(),
-                        Ranks::UniformCumulative { count, effects, .. } => if let Some(val) = effects.$name {
-                            Box::new((0..*count).map(move |_| val)) as Box<dyn Iterator<Item = $ty>>
-                        } else {
-                            Box::new(empty())
-                        }
-                        Ranks::VaryingCumulative(ranks) => Box::new(ranks
-                            .iter()
-                            .take(rank as usize)
-                            .rev()
-                            .find_map(|rank| rank.effects.$name)
-                            .into_iter())
-                    }
-                }
-            )*
+            Combine::Add => a + b,
+            Combine::Mul => a * b,
         }
-    };
+    }
 }
 
-effects!(
-    (melee_damage_add, f32),
-    (carry_weight_add, u16),
-    (hp_add, f32),
-    (ap_add, f32),
-    (buy_price_sub, f32),
-    (stat_increase, StatIncrease),
-    (sprint_drain_mul, f32),
-);
-
-#[derive(Debug, Clone, Copy, Deserialize)]
-pub struct StatIncrease {
-    pub stat: SpecialStat,
-    #[serde(default = "default_stat_increase")]
-    pub increase: u8,
+impl EffectValue {
+    pub fn number(self) -> Option<f32> {
+        match self {
+            EffectValue::Number(n) => Some(n),
+            EffectValue::Stat(_) => None,
+        }
+    }
+    pub fn stat(self) -> Option<StatIncrease> {
+        match self {
+            EffectValue::Number(_) => None,
+            EffectValue::Stat(si) => Some(si),
+        }
+    }
 }
 
-fn default_stat_increase() -> u8 {
-    1
+impl PerkDef {
+    /// The values of an effect for the perk at some rank
+    pub fn effect(&self, name: &str, rank: u8) -> EffectValues<EffectValue> {
+        match &self.ranks {
+            Ranks::Single { effects, .. } => EffectValues::Option(effects.get(name)),
+            Ranks::UniformCumulative { count, effects, .. } => match effects.get(name) {
+                Some(val) => EffectValues::Repeat(val, *count),
+                None => EffectValues::Option(None),
+            },
+            Ranks::VaryingCumulative(ranks) => EffectValues::Option(
+                ranks
+                    .iter()
+                    .take(rank as usize)
+                    .rev()
+                    .find_map(|rank| rank.effects.get(name)),
+            ),
+        }
+    }
+    pub fn has_effect(&self, name: &str) -> bool {
+        self.ranks
+            .effects()
+            .iter()
+            .any(|effects| effects.get(name).is_some())
+    }
+    pub fn has_effects(&self) -> bool {
+        self.ranks
+            .effects()
+            .iter()
+            .any(|effects| !effects.is_empty())
+    }
 }
 
 pub trait Selectable<T>: Index<Self::Selector, Output = T> {
@@ -300,13 +475,6 @@ pub trait Selectable<T>: Index<Self::Selector, Output = T> {
     fn selectors() -> &'static [Self::Selector];
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
-#[serde(untagged)]
-pub enum MaybeVaried<T, M> {
-    One(T),
-    Multi(M),
-}
-
 impl<T, M> MaybeVaried<T, M>
 where
     M: Selectable<T>,
@@ -342,14 +510,6 @@ impl<T, M> From<T> for MaybeVaried<T, M> {
     }
 }
 
-pub type MaybeGendered<T> = MaybeVaried<T, Gendered<T>>;
-
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
-pub struct Gendered<T> {
-    pub male: T,
-    pub female: T,
-}
-
 impl<T> Index<Gender> for Gendered<T> {
     type Output = T;
     fn index(&self, gender: Gender) -> &Self::Output {
@@ -367,11 +527,20 @@ impl<T> Selectable<T> for Gendered<T> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
-pub enum Gender {
-    #[default]
-    Male,
-    Female,
+impl Gender {
+    pub fn opposite(self) -> Self {
+        match self {
+            Gender::Male => Gender::Female,
+            Gender::Female => Gender::Male,
+        }
+    }
+    /// How characters of the gender are referred to as a group
+    pub fn plural(self) -> &'static str {
+        match self {
+            Gender::Male => "Men",
+            Gender::Female => "Women",
+        }
+    }
 }
 
 impl FromStr for Gender {
@@ -385,64 +554,102 @@ impl FromStr for Gender {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
-pub enum Difficulty {
-    VeryEasy,
-    Easy,
-    #[default]
-    Normal,
-    Hard,
-    VeryHard,
-    Survival,
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
-pub struct Difficultied<T> {
-    pub normal: T,
-    pub survival: T,
+impl<T> Difficultied<T> {
+    /// The difficulties other than normal that have their own value
+    pub fn overrides(&self) -> impl Iterator<Item = (Difficulty, &T)> {
+        [
+            (Difficulty::VeryEasy, &self.very_easy),
+            (Difficulty::Easy, &self.easy),
+            (Difficulty::Hard, &self.hard),
+            (Difficulty::VeryHard, &self.very_hard),
+            (Difficulty::Survival, &self.survival),
+        ]
+        .into_iter()
+        .filter_map(|(difficulty, val)| Some((difficulty, val.as_ref()?)))
+    }
 }
 
 impl<T> Index<Difficulty> for Difficultied<T> {
     type Output = T;
     fn index(&self, difficulty: Difficulty) -> &Self::Output {
         match difficulty {
-            Difficulty::Survival => &self.survival,
-            _ => &self.normal,
+            Difficulty::VeryEasy => self.very_easy.as_ref(),
+            Difficulty::Easy => self.easy.as_ref(),
+            Difficulty::Normal => None,
+            Difficulty::Hard => self.hard.as_ref(),
+            Difficulty::VeryHard => self.very_hard.as_ref(),
+            Difficulty::Survival => self.survival.as_ref(),
         }
+        .unwrap_or(&self.normal)
     }
 }
 
 impl<T> Selectable<T> for Difficultied<T> {
     type Selector = Difficulty;
     fn selectors() -> &'static [Self::Selector] {
-        &[Difficulty::Normal, Difficulty::Survival]
+        Difficulty::ALL
     }
 }
 
-pub type MaybeDifficultied<T> = MaybeVaried<T, Difficultied<T>>;
+impl Difficulty {
+    pub const ALL: &'static [Self] = &[
+        Difficulty::VeryEasy,
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::VeryHard,
+        Difficulty::Survival,
+    ];
+    /// How the difficulty changes combat, as given in the perk data
+    pub fn effects(self) -> DifficultyDef {
+        catalog()
+            .difficulties
+            .get(&self)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Default for DifficultyDef {
+    fn default() -> Self {
+        DifficultyDef {
+            damage_dealt: 1.0,
+            damage_taken: 1.0,
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for DifficultyDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Damage dealt {:.0}%, taken {:.0}%",
+            self.damage_dealt * 100.0,
+            self.damage_taken * 100.0
+        )?;
+        for note in &self.notes {
+            write!(f, ", {}", note)?;
+        }
+        Ok(())
+    }
+}
 
 impl FromStr for Difficulty {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.to_lowercase();
-        let (difficulty, sim) = [
-            Difficulty::VeryEasy,
-            Difficulty::Easy,
-            Difficulty::Normal,
-            Difficulty::Hard,
-            Difficulty::VeryHard,
-            Difficulty::Survival,
-        ]
-        .into_iter()
-        .map(|difficulty| {
-            (
-                difficulty,
-                similarity(format!("{:?}", difficulty).to_lowercase(), &s),
-            )
-        })
-        .max_by_key(|(_, sim)| (*sim * 1000000.0) as u64)
-        .unwrap();
-        println!("{:?}: {}", difficulty, sim);
+        let (difficulty, sim) = Difficulty::ALL
+            .iter()
+            .copied()
+            .map(|difficulty| {
+                (
+                    difficulty,
+                    similarity(format!("{:?}", difficulty).to_lowercase(), &s),
+                )
+            })
+            .max_by_key(|(_, sim)| (*sim * 1000000.0) as u64)
+            .unwrap();
         if sim >= 0.6 {
             Ok(difficulty)
         } else {
@@ -468,102 +675,662 @@ pub enum SkillBobblehead {
     Unarmed,
 }
 
+#[derive(Debug, Clone)]
+pub struct Synergy {
+    pub perks: [PerkId; 2],
+    pub note: String,
+}
+
+/// A style of build, recognized by the perks it invests in
+#[derive(Debug, Clone)]
+pub struct Archetype {
+    pub name: String,
+    pub perks: Vec<PerkId>,
+}
+
+impl Location {
+    pub fn describe(&self) -> String {
+        match &self.quest {
+            Some(quest) => format!("{} ({})", self.location, quest),
+            None => self.location.clone(),
+        }
+    }
+}
+
+impl Synergy {
+    pub fn other(&self, id: &PerkId) -> Option<PerkId> {
+        match self.perks {
+            [a, b] if a == *id => Some(b),
+            [a, b] if b == *id => Some(a),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Deserialize)]
-struct AllPerksRep {
-    special: BTreeMap<SpecialStat, Vec<PerkDef>>,
-    bobbleheads: BTreeMap<MaybeGendered<String>, Rank>,
-    magazines: BTreeMap<String, Ranks>,
-    companions: BTreeMap<String, Ranks>,
-    factions: BTreeMap<String, Ranks>,
+struct LanguageRep {
+    name: String,
     #[serde(default)]
-    other: BTreeMap<String, Ranks>,
+    perks: BTreeMap<String, LocalizedPerk>,
+}
+
+#[derive(Deserialize)]
+struct LocalizedPerk {
+    name: MaybeGendered<String>,
+    #[serde(default)]
+    ranks: Vec<FullyVariable<String>>,
+}
+
+/// Hands out one shared allocation for each distinct perk name
+#[derive(Default)]
+struct Interner(BTreeSet<Name>);
+
+impl Interner {
+    fn intern(&mut self, s: &str) -> Name {
+        if let Some(name) = self.0.get(s) {
+            return name.clone();
+        }
+        let name = Name::from(s);
+        self.0.insert(name.clone());
+        name
+    }
+    fn gendered<S: AsRef<str>>(&mut self, name: &MaybeGendered<S>) -> MaybeGendered<Name> {
+        match name {
+            MaybeVaried::One(name) => MaybeVaried::One(self.intern(name.as_ref())),
+            MaybeVaried::Multi(Gendered { male, female }) => MaybeVaried::Multi(Gendered {
+                male: self.intern(male.as_ref()),
+                female: self.intern(female.as_ref()),
+            }),
+        }
+    }
+}
+
+pub struct Catalog {
+    pub perks: BTreeMap<PerkId, Arc<PerkDef>>,
+    pub synergies: Vec<Synergy>,
+    pub archetypes: Vec<Archetype>,
+    /// Groups of perks that no one character can earn more than one of
+    pub exclusive: Vec<Vec<PerkId>>,
+    /// The perks each DLC adds, by the DLC's name
+    pub dlc: BTreeMap<String, Vec<PerkId>>,
+    /// The kinds of effects perks can have
+    pub effects: BTreeMap<String, EffectDef>,
+    /// How each difficulty changes combat
+    pub difficulties: BTreeMap<Difficulty, DifficultyDef>,
+    /// The legendary effects armor can have, by name
+    pub legendaries: BTreeMap<String, LegendaryDef>,
+    pub locations: BTreeMap<PerkId, Location>,
+    pub issue_locations: BTreeMap<PerkId, Vec<Location>>,
+    pub source: Option<PathBuf>,
+    pub version: String,
+    pub hash: u64,
+    /// The code and name of every language loaded alongside the base data
+    pub languages: Vec<(String, String)>,
+    /// The code of the language perks are displayed in, if not the base data's
+    pub language: Option<String>,
+    perk_hashes: BTreeMap<PerkId, String>,
+    perk_keys: BTreeMap<PerkId, String>,
+    index: NameIndex,
+    names: Interner,
+    modified: Option<SystemTime>,
+}
+
+fn slug(s: &str) -> String {
+    s.to_lowercase()
+        .replace('\'', "")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn fnv(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogStamp {
+    pub version: String,
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub perks: BTreeMap<PerkId, String>,
 }
 
-pub static PERKS: Lazy<BiBTreeMap<PerkId, PerkDef>> = Lazy::new(|| {
-    let rep: AllPerksRep = match serde_yaml::from_str(include_str!("perks.yaml")) {
-        Ok(rep) => rep,
-        Err(e) => {
-            println!("{}", e);
-            exit(1);
+fn perk_id_by_name_in(
+    perks: &BTreeMap<PerkId, PerkDef>,
+    name: &str,
+    filter: impl Fn(PerkKind) -> bool,
+) -> Option<PerkId> {
+    let name = name.trim().to_lowercase();
+    perks.iter().find_map(|(id, def)| {
+        (filter(id.kind()) && def.name.iter().any(|n| n.trim().to_lowercase() == name))
+            .then_some(*id)
+    })
+}
+
+/// The built-in data, converted to JSON by the build script
+const BUILTIN_PERKS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/perks.bin"));
+const BUILTIN_LOCATIONS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/locations.msgpack"));
+
+impl Catalog {
+    pub fn parse(yaml: &str) -> anyhow::Result<Self> {
+        Catalog::from_rep(serde_yaml::from_str(yaml)?, fnv(yaml))
+    }
+    fn builtin() -> anyhow::Result<Self> {
+        let (hash, rep) = AllPerksRep::from_blob(BUILTIN_PERKS).map_err(anyhow::Error::msg)?;
+        Catalog::from_rep(rep, hash)
+    }
+    fn from_rep(rep: AllPerksRep, hash: u64) -> anyhow::Result<Self> {
+        rep.check().map_err(anyhow::Error::msg)?;
+        let version = rep.version.unwrap_or_default();
+        let effects = rep.effects;
+        let difficulties = rep.difficulties;
+        let legendaries = rep.legendaries;
+        let mut names = Interner::default();
+        let mut perks = BTreeMap::new();
+        for (stat, defs) in rep.special {
+            for (i, mut def) in defs.into_iter().enumerate() {
+                def.name = names.gendered(&def.name);
+                perks.insert(
+                    PerkId::Special {
+                        stat,
+                        points: i as u8 + 1,
+                    },
+                    def,
+                );
+            }
         }
-    };
-    let mut perks = BiBTreeMap::new();
-    for (stat, defs) in rep.special {
-        for (i, def) in defs.into_iter().enumerate() {
+        for &stat in SpecialStat::ALL {
             perks.insert(
-                PerkId::Special {
-                    stat,
-                    points: i as u8 + 1,
+                PerkId::Bobblehead(BobbleheadId::Special(stat)),
+                PerkDef {
+                    name: names.intern(&stat.to_string()).into(),
+                    ranks: Ranks::Single {
+                        description: MaybeVaried::One(MaybeVaried::One(format!(
+                            "Increase {} by 1.",
+                            stat
+                        ))),
+                        effects: Effects(
+                            [(
+                                "stat_increase".into(),
+                                EffectValue::Stat(StatIncrease { stat, increase: 1 }),
+                            )]
+                            .into(),
+                        ),
+                    },
+                    gender: GenderCondition::default(),
+                    aliases: Vec::new(),
                 },
-                def,
             );
         }
-    }
-    for &stat in SpecialStat::ALL {
-        perks.insert(
-            PerkId::Bobblehead(BobbleheadId::Special(stat)),
-            PerkDef {
-                name: stat.to_string().into(),
-                ranks: Ranks::Single {
-                    description: MaybeVaried::One(MaybeVaried::One(format!(
-                        "Increase {} by 1.",
-                        stat
-                    ))),
-                    effects: Effects {
-                        stat_increase: Some(StatIncrease { stat, increase: 1 }),
-                        ..Default::default()
+        for (i, (name, rank)) in rep.bobbleheads.into_iter().enumerate() {
+            perks.insert(
+                PerkId::Bobblehead(BobbleheadId::Other(i)),
+                PerkDef {
+                    name: names.gendered(&name),
+                    ranks: Ranks::Single {
+                        description: rank.description,
+                        effects: rank.effects,
                     },
+                    gender: GenderCondition::default(),
+                    aliases: Vec::new(),
                 },
-            },
-        );
-    }
-    for (i, (name, rank)) in rep.bobbleheads.into_iter().enumerate() {
-        perks.insert(
-            PerkId::Bobblehead(BobbleheadId::Other(i)),
-            PerkDef {
-                name,
-                ranks: Ranks::Single {
-                    description: rank.description,
-                    effects: rank.effects,
+            );
+        }
+        for (i, (name, ranks)) in rep.magazines.into_iter().enumerate() {
+            perks.insert(
+                PerkId::Magazine(i),
+                PerkDef {
+                    name: names.intern(&name).into(),
+                    ranks,
+                    gender: GenderCondition::default(),
+                    aliases: Vec::new(),
                 },
-            },
-        );
+            );
+        }
+        for (i, (name, ranks)) in rep.companions.into_iter().enumerate() {
+            perks.insert(
+                PerkId::Companion(i),
+                PerkDef {
+                    name: names.intern(&name).into(),
+                    ranks,
+                    gender: GenderCondition::default(),
+                    aliases: Vec::new(),
+                },
+            );
+        }
+        for (i, (name, ranks)) in rep.factions.into_iter().enumerate() {
+            perks.insert(
+                PerkId::Faction(i),
+                PerkDef {
+                    name: names.intern(&name).into(),
+                    ranks,
+                    gender: GenderCondition::default(),
+                    aliases: Vec::new(),
+                },
+            );
+        }
+        for (i, (name, ranks)) in rep.other.into_iter().enumerate() {
+            perks.insert(
+                PerkId::Other(i),
+                PerkDef {
+                    name: names.intern(&name).into(),
+                    ranks,
+                    gender: GenderCondition::default(),
+                    aliases: Vec::new(),
+                },
+            );
+        }
+        let mut synergies = Vec::new();
+        for syn in rep.synergies {
+            let mut ids = Vec::new();
+            for name in &syn.perks {
+                match perk_id_by_name_in(&perks, name, |_| true) {
+                    Some(id) => ids.push(id),
+                    None => bail!("Unknown perk in synergies: {}", name),
+                }
+            }
+            synergies.push(Synergy {
+                perks: [ids[0], ids[1]],
+                note: syn.note,
+            });
+        }
+        let mut archetypes = Vec::new();
+        for (name, names) in rep.archetypes {
+            let mut ids = Vec::new();
+            for perk in &names {
+                match perk_id_by_name_in(&perks, perk, |_| true) {
+                    Some(id) => ids.push(id),
+                    None => bail!("Unknown perk in archetype {}: {}", name, perk),
+                }
+            }
+            archetypes.push(Archetype { name, perks: ids });
+        }
+        let mut exclusive = Vec::new();
+        for names in rep.exclusive {
+            let mut ids = Vec::new();
+            for perk in &names {
+                match perk_id_by_name_in(&perks, perk, |_| true) {
+                    Some(id) => ids.push(id),
+                    None => bail!("Unknown perk in exclusive: {}", perk),
+                }
+            }
+            exclusive.push(ids);
+        }
+        let mut dlc = BTreeMap::new();
+        for (name, names) in rep.dlc {
+            let mut ids = Vec::new();
+            for perk in &names {
+                match perk_id_by_name_in(&perks, perk, |_| true) {
+                    Some(id) => ids.push(id),
+                    None => bail!("Unknown perk in DLC {}: {}", name, perk),
+                }
+            }
+            dlc.insert(name, ids);
+        }
+        let rep: LocationsRep = rmp_serde::from_slice(BUILTIN_LOCATIONS)?;
+        let mut locations = BTreeMap::new();
+        let mut issue_locations = BTreeMap::new();
+        for (kind, entries) in [
+            (PerkKind::Bobblehead, rep.bobbleheads),
+            (PerkKind::Companion, rep.companions),
+        ] {
+            for (name, location) in entries {
+                if let Some(id) = perk_id_by_name_in(&perks, &name, |k| k == kind) {
+                    locations.insert(id, location);
+                }
+            }
+        }
+        for (name, issues) in rep.magazines {
+            if let Some(id) = perk_id_by_name_in(&perks, &name, |k| k == PerkKind::Magazine) {
+                match issues {
+                    Issues::One(location) => {
+                        locations.insert(id, location);
+                    }
+                    Issues::Many(issues) => {
+                        issue_locations.insert(id, issues);
+                    }
+                }
+            }
+        }
+        let perks: BTreeMap<PerkId, Arc<PerkDef>> = perks
+            .into_iter()
+            .map(|(id, def)| (id, Arc::new(def)))
+            .collect();
+        // Perks are hashed as JSON, which keeps fields in declaration order and maps
+        // sorted, so a perk's hash only changes when its data does
+        let perk_hashes = perks
+            .iter()
+            .map(|(id, def)| Ok((*id, format!("{:016x}", fnv(&serde_json::to_string(def)?)))))
+            .collect::<anyhow::Result<_>>()?;
+        let perk_keys: BTreeMap<PerkId, String> = perks
+            .iter()
+            .map(|(id, def)| {
+                let name = slug(&def.name[Gender::Male]);
+                let key = match id {
+                    PerkId::Special { stat, points } => {
+                        format!("{}-{}", slug(&stat.to_string()), points)
+                    }
+                    PerkId::Bobblehead(_) => format!("bobblehead-{}", name),
+                    PerkId::Magazine(_) => format!("magazine-{}", name),
+                    PerkId::Companion(_) => format!("companion-{}", name),
+                    PerkId::Faction(_) => format!("faction-{}", name),
+                    PerkId::Other(_) => format!("other-{}", name),
+                };
+                (*id, key)
+            })
+            .collect();
+        // Share codes and plans refer to perks by key, so two perks must never share one
+        let mut keyed = BTreeMap::new();
+        for (id, key) in &perk_keys {
+            if let Some(other) = keyed.insert(key, id) {
+                bail!(
+                    "{} and {} share the key {}",
+                    perks[other].name[Gender::Male],
+                    perks[id].name[Gender::Male],
+                    key
+                )
+            }
+        }
+        let index = NameIndex::new(&perks);
+        Ok(Catalog {
+            perks,
+            synergies,
+            archetypes,
+            exclusive,
+            dlc,
+            effects,
+            difficulties,
+            legendaries,
+            locations,
+            issue_locations,
+            source: None,
+            version,
+            hash,
+            languages: Vec::new(),
+            language: None,
+            perk_hashes,
+            perk_keys,
+            index,
+            names,
+            modified: None,
+        })
     }
-    for (i, (name, ranks)) in rep.magazines.into_iter().enumerate() {
-        perks.insert(
-            PerkId::Magazine(i),
-            PerkDef {
-                name: name.into(),
-                ranks,
-            },
-        );
+    /// Parse perk data through a binary copy kept in the data directory, which is
+    /// much faster to deserialize and is reused until the data's hash changes
+    #[cfg(feature = "native")]
+    fn parse_cached(yaml: &str) -> anyhow::Result<Self> {
+        let hash = fnv(yaml);
+        let path = Build::dir().join("perks.cache");
+        let cached = fs::read(&path)
+            .ok()
+            .and_then(|blob| AllPerksRep::from_blob(&blob).ok());
+        if let Some((cached_hash, rep)) = cached {
+            if cached_hash == hash {
+                return Catalog::from_rep(rep, hash);
+            }
+        }
+        let rep: AllPerksRep = serde_yaml::from_str(yaml)?;
+        let blob = rep.to_blob(hash);
+        let catalog = Catalog::from_rep(rep, hash)?;
+        if let Ok(blob) = blob {
+            fs::create_dir_all(Build::dir())
+                .and_then(|_| fs::write(&path, blob))
+                .ok();
+        }
+        Ok(catalog)
     }
-    for (i, (name, ranks)) in rep.companions.into_iter().enumerate() {
-        perks.insert(
-            PerkId::Companion(i),
-            PerkDef {
-                name: name.into(),
-                ranks,
-            },
-        );
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        if let Some(path) = path {
+            let yaml = fs::read_to_string(path)?;
+            #[cfg(feature = "native")]
+            let catalog = Catalog::parse_cached(&yaml);
+            #[cfg(not(feature = "native"))]
+            let catalog = Catalog::parse(&yaml);
+            let mut catalog =
+                catalog.map_err(|e| anyhow!("Error in {}: {}", path.to_string_lossy(), e))?;
+            catalog.source = Some(path.to_path_buf());
+            catalog.modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+            Ok(catalog)
+        } else {
+            let mut catalog = Catalog::builtin()?;
+            if catalog.version.is_empty() {
+                catalog.version = env!("CARGO_PKG_VERSION").into();
+            }
+            Ok(catalog)
+        }
     }
-    for (i, (name, ranks)) in rep.factions.into_iter().enumerate() {
-        perks.insert(
-            PerkId::Faction(i),
-            PerkDef {
-                name: name.into(),
-                ranks,
-            },
+    pub fn perk_hash(&self, id: &PerkId) -> Option<String> {
+        self.perk_hashes.get(id).cloned()
+    }
+    /// A key for a perk that stays the same across languages and reorderings of the data
+    pub fn key(&self, id: &PerkId) -> Option<&str> {
+        self.perk_keys.get(id).map(String::as_str)
+    }
+    /// Perks whose names or aliases resemble a query, best match first
+    pub fn search(&self, query: &str) -> Vec<(PerkId, f64)> {
+        self.index.search(query, &MatchOptions::current())
+    }
+    pub fn id_by_key(&self, key: &str) -> Option<PerkId> {
+        self.perk_keys
+            .iter()
+            .find_map(|(id, k)| (k == key).then_some(*id))
+    }
+    /// Load a language's names and descriptions, either displaying them
+    /// or only accepting them when matching perk names
+    pub fn localize(&mut self, code: &str, yaml: &str, display: bool) -> anyhow::Result<()> {
+        let rep: LanguageRep = serde_yaml::from_str(yaml)?;
+        let keys: BTreeMap<&str, PerkId> = self
+            .perk_keys
+            .iter()
+            .map(|(id, key)| (key.as_str(), *id))
+            .collect();
+        for (key, localized) in rep.perks {
+            let id = *keys
+                .get(key.as_str())
+                .ok_or_else(|| anyhow!("Unknown perk key: {}", key))?;
+            let def = Arc::make_mut(self.perks.get_mut(&id).unwrap());
+            if display {
+                def.aliases.extend(def.name.iter().cloned());
+                def.name = self.names.gendered(&localized.name);
+                for (description, localized) in def
+                    .ranks
+                    .descriptions_mut()
+                    .into_iter()
+                    .zip(localized.ranks)
+                {
+                    *description = localized;
+                }
+            } else {
+                def.aliases
+                    .extend(localized.name.iter().map(|name| self.names.intern(name)));
+            }
+        }
+        self.index = NameIndex::new(&self.perks);
+        self.languages.push((code.into(), rep.name));
+        if display {
+            self.language = Some(code.into());
+        }
+        Ok(())
+    }
+    #[cfg(feature = "native")]
+    pub fn language_dir() -> PathBuf {
+        Build::dir().join("lang")
+    }
+    /// Load every language file in the language directory side by side
+    #[cfg(feature = "native")]
+    pub fn load_languages(&mut self, display: Option<&str>) -> anyhow::Result<()> {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(Catalog::language_dir()) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+                .collect(),
+            Err(_) => return Ok(()),
+        };
+        paths.sort();
+        for path in paths {
+            let code = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let yaml = fs::read_to_string(&path)?;
+            self.localize(&code, &yaml, display == Some(code.as_str()))
+                .map_err(|e| anyhow!("Error in {}: {}", path.to_string_lossy(), e))?;
+        }
+        Ok(())
+    }
+    pub fn stamp<'a>(&self, ids: impl IntoIterator<Item = &'a PerkId>) -> CatalogStamp {
+        CatalogStamp {
+            version: self.version.clone(),
+            hash: format!("{:016x}", self.hash),
+            perks: ids
+                .into_iter()
+                .filter_map(|id| Some((*id, self.perk_hash(id)?)))
+                .collect(),
+        }
+    }
+    #[cfg(feature = "native")]
+    pub fn changed(&self) -> bool {
+        let path = Catalog::active_path();
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        modified != self.modified
+    }
+    /// The perk data file in use: one chosen with [`use_data_file`], or else the one
+    /// installed in the data folder, which is only used if it exists
+    #[cfg(feature = "native")]
+    pub fn active_path() -> PathBuf {
+        DATA_FILE
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| Build::dir().join("perks.yaml"))
+    }
+}
+
+/// A perk data file chosen to use instead of the built-in or installed data
+#[cfg(feature = "native")]
+static DATA_FILE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Use a perk data file, like one for an overhaul mod, instead of the built-in or
+/// installed data. The file is checked first, and any mistakes `lint-data` finds in it
+/// are returned.
+#[cfg(feature = "native")]
+pub fn use_data_file(path: PathBuf) -> anyhow::Result<Vec<String>> {
+    if !path.exists() {
+        bail!(
+            "The perk data file {} does not exist",
+            path.to_string_lossy()
+        )
+    }
+    let catalog = Catalog::load(Some(&path))?;
+    *DATA_FILE.write().unwrap() = Some(path);
+    Ok(crate::lint::lint(&catalog))
+}
+
+#[cfg(feature = "native")]
+fn load_active() -> anyhow::Result<Catalog> {
+    let path = Catalog::active_path();
+    let chosen = DATA_FILE.read().unwrap().is_some();
+    let mut catalog = Catalog::load((chosen || path.exists()).then_some(path.as_path()))?;
+    catalog.load_languages(config().language.as_deref())?;
+    Ok(catalog)
+}
+
+#[cfg(not(feature = "native"))]
+fn load_active() -> anyhow::Result<Catalog> {
+    Catalog::load(None)
+}
+
+static CATALOG: RwLock<Option<Arc<Catalog>>> = RwLock::new(None);
+
+/// The perk data in use, loading it the first time it is needed
+pub fn try_catalog() -> anyhow::Result<Arc<Catalog>> {
+    if let Some(catalog) = &*CATALOG.read().unwrap() {
+        return Ok(catalog.clone());
+    }
+    let mut slot = CATALOG.write().unwrap();
+    if let Some(catalog) = &*slot {
+        return Ok(catalog.clone());
+    }
+    let catalog = Arc::new(load_active()?);
+    *slot = Some(catalog.clone());
+    Ok(catalog)
+}
+
+/// The perk data in use. Programs should call [`try_catalog`] first to handle data
+/// that does not load, after which this can't fail.
+pub fn catalog() -> Arc<Catalog> {
+    try_catalog().unwrap_or_else(|e| panic!("The perk data could not be loaded: {}", e))
+}
+
+/// Load the perk data again and use it from now on. Anything still holding the old
+/// data keeps it until it lets go.
+pub fn reload_catalog() -> anyhow::Result<Arc<Catalog>> {
+    let catalog = Arc::new(load_active()?);
+    *CATALOG.write().unwrap() = Some(catalog.clone());
+    Ok(catalog)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_ids_are_in_the_perk_data() {
+        let catalog = Catalog::load(None).unwrap();
+        let legacy = &*LEGACY_IDS;
+        let keys = (legacy.bobbleheads.iter())
+            .chain(&legacy.magazines)
+            .chain(&legacy.companions)
+            .chain(&legacy.factions)
+            .chain(&legacy.other);
+        for key in keys {
+            assert!(catalog.id_by_key(key).is_some(), "{} is missing", key);
+        }
+    }
+
+    #[test]
+    fn serialized_perk_data_reads_back_the_same() {
+        let yaml = include_str!("perks.yaml");
+        let rep: AllPerksRep = serde_yaml::from_str(yaml).unwrap();
+        let blob = rep.to_blob(fnv(yaml)).unwrap();
+        let (hash, read) = AllPerksRep::from_blob(&blob).unwrap();
+        assert_eq!(hash, fnv(yaml));
+        assert_eq!(read.to_blob(hash).unwrap(), blob);
+        Catalog::from_rep(read, hash).unwrap();
+
+        let mut newer = blob.clone();
+        newer[b"FO4PERKS".len()] += 1;
+        assert!(AllPerksRep::from_blob(&newer).is_err());
+        assert!(AllPerksRep::from_blob(&blob[..b"FO4PERKS".len() + 6]).is_err());
+        assert!(AllPerksRep::from_blob(b"{}").is_err());
+    }
+
+    #[test]
+    fn difficulties_parse_from_their_names() {
+        for &difficulty in Difficulty::ALL {
+            let name = format!("{:?}", difficulty);
+            assert_eq!(name.parse::<Difficulty>().unwrap(), difficulty);
+            assert_eq!(
+                name.to_lowercase().parse::<Difficulty>().unwrap(),
+                difficulty
+            );
+        }
+        assert_eq!(
+            "survivl".parse::<Difficulty>().unwrap(),
+            Difficulty::Survival
         );
+        assert!("nuclear".parse::<Difficulty>().is_err());
     }
-    for (i, (name, ranks)) in rep.other.into_iter().enumerate() {
-        perks.insert(
-            PerkId::Other(i),
-            PerkDef {
-                name: name.into(),
-                ranks,
-            },
+
+    #[test]
+    fn perks_with_the_same_key_are_rejected() {
+        let yaml = include_str!("perks.yaml").replacen(
+            "factions:\n",
+            "factions:\n  Crusader-of-Atom:\n    desc: A copy.\n",
+            1,
         );
+        let err = Catalog::parse(&yaml).err().unwrap().to_string();
+        assert!(err.contains("faction-crusader-of-atom"), "{}", err);
     }
-    perks
-});
+}
@@ -0,0 +1,86 @@
+#[cfg(feature = "native")]
+use std::{fs, io, path::PathBuf};
+
+/// Somewhere builds can be saved to and loaded from
+pub trait Storage {
+    fn read(&self, name: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    fn write(&self, name: &str, bytes: &[u8]) -> anyhow::Result<()>;
+    fn list(&self) -> anyhow::Result<Vec<String>>;
+}
+
+/// Files in a folder on disk
+#[cfg(feature = "native")]
+pub struct Folder(pub PathBuf);
+
+#[cfg(feature = "native")]
+impl Storage for Folder {
+    fn read(&self, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match fs::read(self.0.join(name)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+    fn write(&self, name: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.0)?;
+        fs::write(self.0.join(name), bytes)?;
+        Ok(())
+    }
+    fn list(&self) -> anyhow::Result<Vec<String>> {
+        let mut names = Vec::new();
+        if !self.0.exists() {
+            return Ok(names);
+        }
+        for entry in fs::read_dir(&self.0)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// The browser's local storage, with every key prefixed so builds don't collide
+/// with anything else the page stores
+#[cfg(feature = "web")]
+pub struct LocalStorage;
+
+#[cfg(feature = "web")]
+impl LocalStorage {
+    const PREFIX: &'static str = "fo4builder/";
+    fn storage() -> anyhow::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| anyhow::anyhow!("Local storage is not available"))
+    }
+}
+
+#[cfg(feature = "web")]
+impl Storage for LocalStorage {
+    fn read(&self, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let item = Self::storage()?
+            .get_item(&format!("{}{}", Self::PREFIX, name))
+            .map_err(|_| anyhow::anyhow!("Unable to read {} from local storage", name))?;
+        Ok(item.map(String::into_bytes))
+    }
+    fn write(&self, name: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        Self::storage()?
+            .set_item(
+                &format!("{}{}", Self::PREFIX, name),
+                std::str::from_utf8(bytes)?,
+            )
+            .map_err(|_| anyhow::anyhow!("Unable to write {} to local storage", name))
+    }
+    fn list(&self) -> anyhow::Result<Vec<String>> {
+        let storage = Self::storage()?;
+        let len = storage.length().unwrap_or(0);
+        let mut names: Vec<String> = (0..len)
+            .filter_map(|i| storage.key(i).ok().flatten())
+            .filter_map(|key| key.strip_prefix(Self::PREFIX).map(Into::into))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use crate::build::Build;
+
+const LAST_SYNC: &str = ".last-sync";
+
+#[derive(Default)]
+pub struct SyncReport {
+    pub pushed: Vec<String>,
+    pub pulled: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+fn files(dir: &Path) -> anyhow::Result<BTreeSet<String>> {
+    let mut files = BTreeSet::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_file() && name != LAST_SYNC {
+            files.insert(name);
+        }
+    }
+    Ok(files)
+}
+
+fn modified(path: &Path) -> anyhow::Result<u64> {
+    Ok(fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)?
+        .as_nanos() as u64)
+}
+
+fn last_sync(dir: &Path) -> BTreeMap<String, [u64; 2]> {
+    fs::read(dir.join(LAST_SYNC))
+        .ok()
+        .and_then(|bytes| serde_yaml::from_slice(&bytes).ok())
+        .unwrap_or_default()
+}
+
+pub fn sync(other: &Path) -> anyhow::Result<SyncReport> {
+    let local = Build::dir();
+    fs::create_dir_all(&local)?;
+    fs::create_dir_all(other)?;
+    let last = last_sync(&local);
+    let mut report = SyncReport::default();
+    let mut synced = BTreeMap::new();
+    let names: BTreeSet<String> = files(&local)?.into_iter().chain(files(other)?).collect();
+    for name in names {
+        let here = local.join(&name);
+        let there = other.join(&name);
+        match (here.exists(), there.exists()) {
+            (true, false) => {
+                fs::copy(&here, &there)?;
+                report.pushed.push(name.clone());
+            }
+            (false, true) => {
+                fs::copy(&there, &here)?;
+                report.pulled.push(name.clone());
+            }
+            _ if fs::read(&here)? == fs::read(&there)? => {}
+            _ => {
+                let times = [modified(&here)?, modified(&there)?];
+                let changed = last.get(&name).map_or([true, true], |last| {
+                    [times[0] != last[0], times[1] != last[1]]
+                });
+                match changed {
+                    [true, false] => {
+                        fs::copy(&here, &there)?;
+                        report.pushed.push(name.clone());
+                    }
+                    [false, true] => {
+                        fs::copy(&there, &here)?;
+                        report.pulled.push(name.clone());
+                    }
+                    _ => {
+                        report.conflicts.push(name.clone());
+                        if let Some(times) = last.get(&name) {
+                            synced.insert(name, *times);
+                        }
+                        continue;
+                    }
+                }
+            }
+        }
+        synced.insert(name.clone(), [modified(&here)?, modified(&there)?]);
+    }
+    fs::write(local.join(LAST_SYNC), serde_yaml::to_vec(&synced)?)?;
+    Ok(report)
+}
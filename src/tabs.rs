@@ -0,0 +1,152 @@
+use std::mem;
+
+use anyhow::bail;
+use colored::Colorize;
+use fo4builder::{build::Build, watch::Watch};
+use itertools::Itertools;
+
+use crate::{history::History, screen::Frame, Session};
+
+/// The builds open alongside the current one
+#[derive(Default)]
+pub struct Tabs {
+    /// The other open builds in order, each with its own optimizer results and history
+    others: Vec<(Build, Vec<Build>, History)>,
+    /// Where the current build sits among the others
+    active: usize,
+}
+
+fn tab_name(build: &Build) -> &str {
+    build.name.as_deref().unwrap_or("untitled")
+}
+
+impl Session {
+    /// The names of the open builds in order
+    fn tab_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .tabs
+            .others
+            .iter()
+            .map(|(build, ..)| tab_name(build))
+            .collect();
+        names.insert(self.tabs.active, tab_name(&self.build));
+        names
+    }
+    /// Write the current build, under a bar of the open builds if there is more than one
+    /// and over its watched stats, which are compared to their values in `watched`
+    pub fn write_header(&self, frame: &mut Frame, watched: &[(Watch, f32)]) {
+        if let Some(bar) = self.tab_bar() {
+            frame.line(bar);
+        }
+        frame.line(&self.build);
+        if let Some(strip) = self.build.watch_strip(watched) {
+            frame.line(format!("{}\n", strip));
+        }
+    }
+    /// A bar of the open builds with the current one marked, if there is more than one
+    pub fn tab_bar(&self) -> Option<String> {
+        if self.tabs.others.is_empty() {
+            return None;
+        }
+        Some(
+            self.tab_names()
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    let label = format!("{} {}", i + 1, name);
+                    if i == self.tabs.active {
+                        format!("[{}]", label).bright_white().bold().to_string()
+                    } else {
+                        format!(" {} ", label).dimmed().to_string()
+                    }
+                })
+                .join(" "),
+        )
+    }
+    /// Switch to an open build by number or name, or open a saved build. With `new`,
+    /// an empty build is opened instead, and with no target the open builds are listed.
+    pub fn switch(&mut self, target: &str, new: bool) -> anyhow::Result<String> {
+        let names = self.tab_names();
+        if new {
+            if names.iter().any(|name| name.eq_ignore_ascii_case(target)) {
+                bail!("{} is already open", target)
+            }
+            let mut build = Build::default();
+            build.name = (!target.is_empty()).then(|| target.into());
+            self.open(build);
+            return Ok(format!("Opened new build {}", tab_name(&self.build)));
+        }
+        if target.is_empty() {
+            return Ok(self.describe_tabs());
+        }
+        let index = match target.parse::<usize>() {
+            Ok(number) if (1..=names.len()).contains(&number) => number - 1,
+            Ok(number) => bail!("There is no open build {}", number),
+            Err(_) => match names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(target))
+            {
+                Some(index) => index,
+                None => {
+                    let build = Build::load(target)?;
+                    self.open(build);
+                    return Ok(format!("Opened {}", tab_name(&self.build)));
+                }
+            },
+        };
+        if index == self.tabs.active {
+            bail!("{} is already the current build", names[index])
+        }
+        self.activate(index);
+        Ok(format!("Switched to {}", tab_name(&self.build)))
+    }
+    /// Open a build in a new tab and switch to it
+    fn open(&mut self, build: Build) {
+        self.tabs
+            .others
+            .push((build, Vec::new(), History::default()));
+        self.activate(self.tabs.others.len());
+    }
+    /// Close the current build, switching to the one after it, or before it if it was the last
+    pub fn close(&mut self) -> anyhow::Result<String> {
+        if self.tabs.others.is_empty() {
+            bail!("This is the only open build")
+        }
+        let closed = tab_name(&self.build).to_string();
+        let index = self.tabs.active.min(self.tabs.others.len() - 1);
+        (self.build, self.pareto_builds, self.history) = self.tabs.others.remove(index);
+        self.tabs.active = index;
+        self.merge = None;
+        Ok(format!(
+            "Closed {}, switched to {}",
+            closed,
+            tab_name(&self.build)
+        ))
+    }
+    /// List the open builds by number
+    fn describe_tabs(&self) -> String {
+        self.tab_names()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let current = if i == self.tabs.active {
+                    " (current)"
+                } else {
+                    ""
+                };
+                format!("  {}: {}{}", i + 1, name, current)
+            })
+            .join("\n")
+    }
+    fn activate(&mut self, index: usize) {
+        let current = (
+            mem::take(&mut self.build),
+            mem::take(&mut self.pareto_builds),
+            mem::take(&mut self.history),
+        );
+        self.tabs.others.insert(self.tabs.active, current);
+        (self.build, self.pareto_builds, self.history) = self.tabs.others.remove(index);
+        self.tabs.active = index;
+        self.merge = None;
+    }
+}
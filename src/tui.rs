@@ -0,0 +1,288 @@
+use std::{io, iter::once, mem};
+
+use ansi_to_tui::IntoText;
+use clap::Parser;
+use colored::Colorize;
+use fo4builder::{
+    build::{Build, Game},
+    script::Scripts,
+    special::catalog,
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Style},
+    text::Text,
+    widgets::{Block, Paragraph, Wrap},
+    DefaultTerminal,
+};
+
+use crate::{
+    parse_error_message, picker::Picked, reload_perks, screen::Frame, type_help, write_reloaded,
+    Command, Outcome, Session,
+};
+
+/// How many lines a page key scrolls
+const PAGE: u16 = 10;
+
+/// The panes that scroll, in the order focus moves through them
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Build,
+    Perks,
+    Output,
+}
+
+struct Tui {
+    session: Session,
+    /// What the last command printed
+    output: String,
+    input: String,
+    /// Where the cursor is in the input, in characters
+    cursor: usize,
+    /// The lines entered so far, oldest first
+    typed: Vec<String>,
+    /// Which entered line is recalled into the input, if one is
+    recalled: Option<usize>,
+    focus: Pane,
+    /// How far each pane is scrolled, by pane
+    scroll: [u16; 3],
+    done: bool,
+}
+
+/// Run the planner full-screen, with panes for the build, its perks, and the last
+/// command's output over a line for typing commands
+pub fn run(build: Build, notes: Vec<String>) -> anyhow::Result<()> {
+    let scripts = Scripts::load();
+    let mut output: Vec<String> = notes
+        .iter()
+        .chain(&scripts.errors)
+        .map(|note| note.bright_yellow().to_string())
+        .collect();
+    output.push(type_help());
+    let mut tui = Tui {
+        session: Session {
+            build,
+            pareto_builds: Vec::new(),
+            progress: Box::new(io::sink()),
+            tabs: Default::default(),
+            merge: None,
+            scripts,
+            snapshots: Default::default(),
+            history: Default::default(),
+            listing: Vec::new(),
+        },
+        output: output.join("\n"),
+        input: String::new(),
+        cursor: 0,
+        typed: Vec::new(),
+        recalled: None,
+        focus: Pane::Output,
+        scroll: [0; 3],
+        done: false,
+    };
+    let mut terminal = ratatui::init();
+    let res = tui.run(&mut terminal);
+    ratatui::restore();
+    res
+}
+
+impl Tui {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+        while !self.done {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.key(key.code, key.modifiers);
+                }
+            }
+        }
+        Ok(())
+    }
+    /// The panes shown for the current build
+    fn panes(&self) -> Vec<Pane> {
+        if self.session.build.game() == Game::Fallout4 {
+            vec![Pane::Build, Pane::Perks, Pane::Output]
+        } else {
+            vec![Pane::Build, Pane::Output]
+        }
+    }
+    fn pane_text(&self, pane: Pane) -> String {
+        let build = &self.session.build;
+        let mut text = String::new();
+        match pane {
+            Pane::Build => {
+                if let Some(bar) = self.session.tab_bar() {
+                    text.push_str(&bar);
+                    text.push('\n');
+                }
+                if build.game() == Game::Fallout4 {
+                    let _ = build.write_summary(&mut text);
+                } else {
+                    text.push_str(&build.to_string());
+                }
+            }
+            Pane::Perks => {
+                let _ = build.write_perk_list(&mut text);
+            }
+            Pane::Output => text.push_str(&self.output),
+        }
+        text
+    }
+    fn draw(&self, frame: &mut ratatui::Frame) {
+        let [top, output, input] = Layout::vertical([
+            Constraint::Percentage(60),
+            Constraint::Min(5),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+        if self.panes().contains(&Pane::Perks) {
+            let [build, perks] = Layout::horizontal([Constraint::Percentage(50); 2]).areas(top);
+            self.draw_pane(frame, build, Pane::Build, "Build");
+            self.draw_pane(frame, perks, Pane::Perks, "Perks");
+        } else {
+            self.draw_pane(frame, top, Pane::Build, "Build");
+        }
+        self.draw_pane(frame, output, Pane::Output, "Output");
+        frame.render_widget(
+            Paragraph::new(self.input.as_str()).block(Block::bordered().title("Command")),
+            input,
+        );
+        let column = (self.cursor as u16).min(input.width.saturating_sub(3));
+        frame.set_cursor_position((input.x + 1 + column, input.y + 1));
+    }
+    fn draw_pane(&self, frame: &mut ratatui::Frame, area: Rect, pane: Pane, title: &str) {
+        let text = self.pane_text(pane);
+        let text = text.into_text().unwrap_or_else(|_| Text::raw(text.clone()));
+        let border = if self.focus == pane {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let mut paragraph = Paragraph::new(text)
+            .block(Block::bordered().title(title).border_style(border))
+            .scroll((self.scroll[pane as usize], 0));
+        if pane == Pane::Output {
+            paragraph = paragraph.wrap(Wrap { trim: false });
+        }
+        frame.render_widget(paragraph, area);
+    }
+    fn key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        match code {
+            KeyCode::Char('c' | 'd') if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.done = true
+            }
+            KeyCode::Char(c) => {
+                let at = self.byte_index();
+                self.input.insert(at, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let at = self.byte_index();
+                self.input.remove(at);
+            }
+            KeyCode::Delete if self.cursor < self.input.chars().count() => {
+                let at = self.byte_index();
+                self.input.remove(at);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.input.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.chars().count(),
+            KeyCode::Esc => {
+                self.input.clear();
+                self.cursor = 0;
+                self.recalled = None;
+            }
+            KeyCode::Up => self.recall(-1),
+            KeyCode::Down => self.recall(1),
+            KeyCode::Tab => {
+                let panes = self.panes();
+                let i = panes.iter().position(|p| *p == self.focus).unwrap_or(0);
+                self.focus = panes[(i + 1) % panes.len()];
+            }
+            KeyCode::PageUp => {
+                let scroll = &mut self.scroll[self.focus as usize];
+                *scroll = scroll.saturating_sub(PAGE);
+            }
+            KeyCode::PageDown => {
+                let lines = self.pane_text(self.focus).lines().count() as u16;
+                let scroll = &mut self.scroll[self.focus as usize];
+                *scroll = (*scroll + PAGE).min(lines.saturating_sub(1));
+            }
+            KeyCode::Enter => self.enter(),
+            _ => {}
+        }
+    }
+    /// Where the cursor is in the input, in bytes
+    fn byte_index(&self) -> usize {
+        self.input
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.input.len(), |(i, _)| i)
+    }
+    /// Put an earlier or later entered line in the input
+    fn recall(&mut self, step: isize) {
+        if self.typed.is_empty() {
+            return;
+        }
+        let last = self.typed.len() - 1;
+        let recalled = match self.recalled {
+            None if step < 0 => Some(last),
+            None => return,
+            Some(i) if step < 0 => Some(i.saturating_sub(1)),
+            Some(i) if i < last => Some(i + 1),
+            Some(_) => None,
+        };
+        self.recalled = recalled;
+        self.input = recalled.map_or_else(String::new, |i| self.typed[i].clone());
+        self.cursor = self.input.chars().count();
+    }
+    /// Run the typed line as a command, the same way the prompt does
+    fn enter(&mut self) {
+        let line = mem::take(&mut self.input);
+        self.cursor = 0;
+        self.recalled = None;
+        if !line.trim().is_empty() {
+            self.typed.push(line.clone());
+        }
+        if let Some(query) = line.trim_start().strip_prefix('/') {
+            self.output = match self.session.filter_listing(query.trim(), false) {
+                Ok(Picked::Matches(text)) => text,
+                Ok(_) => String::new(),
+                Err(e) => e.to_string().bright_red().to_string(),
+            };
+            self.scroll = [0; 3];
+            return;
+        }
+        let session = &mut self.session;
+        let reloaded = catalog()
+            .changed()
+            .then(|| reload_perks(&mut session.build));
+        let args: Vec<&str> = once("fo4").chain(line.split_whitespace()).collect();
+        let watched = session.build.watch_values();
+        let mut frame = Frame::default();
+        write_reloaded(&mut frame, reloaded);
+        match Command::try_parse_from(args) {
+            Ok(command) => match session.apply(command) {
+                Outcome::Message(Ok(message)) => {
+                    if !message.is_empty() {
+                        frame.line(message.bright_green())
+                    }
+                }
+                Outcome::Message(Err(e)) => frame.line(e.to_string().bright_red()),
+                Outcome::View(view) => {
+                    let _ = view.write(&mut frame, &session.build);
+                }
+                Outcome::Exit => self.done = true,
+            },
+            Err(e) => frame.line(parse_error_message(&e, &session.scripts)),
+        }
+        if let Some(strip) = session.build.watch_strip(&watched) {
+            frame.line(strip);
+        }
+        self.output = frame.text().trim_end().into();
+        self.scroll = [0; 3];
+    }
+}
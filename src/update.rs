@@ -0,0 +1,84 @@
+use std::{fs, io::Read};
+
+use anyhow::{anyhow, bail};
+use ring::signature::{UnparsedPublicKey, ED25519};
+
+use crate::{config::config, special::Catalog};
+
+/// Where perk data releases are published unless `data-url` is configured
+pub const DEFAULT_URL: &str = "https://github.com/kaikalii/fo4builder/releases/latest/download";
+/// The Ed25519 key perk data releases are signed with unless `data-key` is configured
+pub const RELEASE_KEY: &str = "9c602006416200353bb95dae5afed5748ccc4e228b1e26fd556d15c9bc50b104";
+
+pub struct Updated {
+    pub old_version: String,
+    pub new_version: String,
+    pub changed: bool,
+}
+
+fn get(url: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    ureq::get(url)
+        .call()
+        .map_err(|e| anyhow!("Unable to download {}: {}", url, e))?
+        .into_reader()
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn hex(s: &str) -> anyhow::Result<Vec<u8>> {
+    let s = s.trim();
+    if !s.len().is_multiple_of(2) {
+        bail!("Invalid hex string")
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| anyhow!("Invalid hex string")))
+        .collect()
+}
+
+/// Check that `sig` is a hex Ed25519 signature of `data` by the hex public `key`
+pub fn verify(data: &[u8], sig: &str, key: &str) -> anyhow::Result<()> {
+    let key = hex(key).map_err(|_| anyhow!("Invalid data key"))?;
+    let sig = hex(sig).map_err(|_| anyhow!("Invalid perk data signature"))?;
+    UnparsedPublicKey::new(&ED25519, key)
+        .verify(data, &sig)
+        .map_err(|_| anyhow!("Perk data signature does not match, not installing it"))
+}
+
+/// Download, verify, and install the latest perk data release
+pub fn update_data(url: Option<String>) -> anyhow::Result<Updated> {
+    let config = config();
+    let url = url
+        .or(config.data_url)
+        .unwrap_or_else(|| DEFAULT_URL.into());
+    let url = url.trim_end_matches('/');
+    let key = config.data_key.unwrap_or_else(|| RELEASE_KEY.into());
+    let data = get(&format!("{}/perks.yaml", url))?;
+    let sig = String::from_utf8(get(&format!("{}/perks.yaml.sig", url))?)?;
+    verify(&data, &sig, &key)?;
+    let yaml = String::from_utf8(data)?;
+    let new =
+        Catalog::parse(&yaml).map_err(|e| anyhow!("Downloaded perk data is invalid: {}", e))?;
+    let path = Catalog::active_path();
+    let old = if path.exists() {
+        Catalog::load(Some(&path)).ok()
+    } else {
+        Catalog::load(None).ok()
+    };
+    let changed = old.as_ref().is_none_or(|old| old.hash != new.hash);
+    if changed {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if path.exists() {
+            fs::copy(&path, path.with_extension("yaml.bak"))?;
+        }
+        fs::write(&path, yaml)?;
+    }
+    Ok(Updated {
+        old_version: old.map(|old| old.version).unwrap_or_default(),
+        new_version: new.version,
+        changed,
+    })
+}
@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, fmt};
+
+use colored::Colorize;
+
+use crate::{
+    build::{Build, Game},
+    load,
+    preset::PRESETS_FILE,
+    special::{catalog, Archetype, Gender, PerkId, SpecialStat},
+    storage::Storage,
+};
+
+/// Files kept next to builds that are not builds themselves
+const NOT_BUILDS: &[&str] = &["perks.yaml", "config.yaml", PRESETS_FILE];
+
+/// How many of the most used perks are listed
+const TOP_PERKS: usize = 10;
+
+impl Build {
+    /// The archetype the build invests the most perk ranks in, if any
+    pub fn archetype(&self) -> Option<Archetype> {
+        let mut best: Option<(&Archetype, u32)> = None;
+        let catalog = catalog();
+        for archetype in &catalog.archetypes {
+            let ranks: u32 = archetype
+                .perks
+                .iter()
+                .filter_map(|id| self.perks.get(id))
+                .map(|&rank| rank as u32)
+                .sum();
+            if ranks > best.map_or(0, |(_, most)| most) {
+                best = Some((archetype, ranks));
+            }
+        }
+        best.map(|(archetype, _)| archetype.clone())
+    }
+}
+
+/// A line about one saved build
+pub struct BuildSummary {
+    pub name: String,
+    pub game: Game,
+    pub archetype: Option<String>,
+    pub required_level: u16,
+    pub special: Vec<u8>,
+    pub perks: usize,
+}
+
+/// What a collection of saved builds has in common
+#[derive(Default)]
+pub struct Usage {
+    pub builds: Vec<BuildSummary>,
+    /// Each perk taken by a Fallout 4 build, with how many builds take it and their total rank
+    pub perks: BTreeMap<PerkId, (usize, u32)>,
+    /// The S.P.E.C.I.A.L. spreads of Fallout 4 builds, with how many builds use each
+    pub spreads: BTreeMap<Vec<u8>, usize>,
+    pub archetypes: BTreeMap<String, usize>,
+    /// The files that could not be read as builds, with why
+    pub unreadable: Vec<(String, String)>,
+}
+
+/// The builds saved in a storage
+pub struct SavedBuilds {
+    /// Each build, by file name
+    pub builds: Vec<(String, Build)>,
+    /// The files that could not be read as builds, with why
+    pub unreadable: Vec<(String, String)>,
+}
+
+/// Read every build in a storage
+pub fn saved_builds(storage: &dyn Storage) -> anyhow::Result<SavedBuilds> {
+    let mut saved = SavedBuilds {
+        builds: Vec::new(),
+        unreadable: Vec::new(),
+    };
+    for name in storage.list()? {
+        if !name.ends_with(".yaml") || NOT_BUILDS.contains(&name.as_str()) {
+            continue;
+        }
+        let Some(bytes) = storage.read(&name)? else {
+            continue;
+        };
+        match load::parse(&bytes, &name) {
+            Ok(build) => saved.builds.push((name, build)),
+            Err(e) => saved.unreadable.push((name, e.to_string())),
+        }
+    }
+    Ok(saved)
+}
+
+/// Read every build in a storage and gather what they have in common
+pub fn scan(storage: &dyn Storage) -> anyhow::Result<Usage> {
+    let saved = saved_builds(storage)?;
+    let mut usage = Usage {
+        unreadable: saved.unreadable,
+        ..Usage::default()
+    };
+    for (name, build) in saved.builds {
+        usage.add(name.trim_end_matches(".yaml"), &build);
+    }
+    Ok(usage)
+}
+
+impl Usage {
+    fn add(&mut self, file: &str, build: &Build) {
+        let game = build.game();
+        let special: Vec<u8> = SpecialStat::ALL
+            .iter()
+            .map(|stat| build.special[stat])
+            .collect();
+        let archetype = (game == Game::Fallout4)
+            .then(|| build.archetype())
+            .flatten()
+            .map(|archetype| archetype.name.clone());
+        if game == Game::Fallout4 {
+            for (id, rank) in &build.perks {
+                let (builds, ranks) = self.perks.entry(*id).or_default();
+                *builds += 1;
+                *ranks += *rank as u32;
+            }
+            *self.spreads.entry(special.clone()).or_default() += 1;
+            if let Some(archetype) = &archetype {
+                *self.archetypes.entry(archetype.clone()).or_default() += 1;
+            }
+        }
+        self.builds.push(BuildSummary {
+            name: build.name.clone().unwrap_or_else(|| file.into()),
+            game,
+            archetype,
+            required_level: build.profile().required_level(build),
+            special,
+            perks: build.perks.len(),
+        });
+    }
+    fn fo4_builds(&self) -> usize {
+        self.builds
+            .iter()
+            .filter(|summary| summary.game == Game::Fallout4)
+            .count()
+    }
+}
+
+fn spread(special: &[u8]) -> String {
+    special
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fo4 = self.fo4_builds();
+        let mut lines = vec![if self.builds.is_empty() {
+            "No saved builds to look at, try \"save <NAME>\"".into()
+        } else {
+            format!("{} saved builds, {} for Fallout 4", self.builds.len(), fo4)
+        }];
+        if fo4 > 0 {
+            lines.push(format!("\n{}", "Most used perks".bright_yellow()));
+            let mut perks: Vec<_> = self.perks.iter().collect();
+            perks.sort_by(|(_, a), (_, b)| b.cmp(a));
+            for (id, (builds, ranks)) in perks.into_iter().take(TOP_PERKS) {
+                let Some(def) = catalog().perks.get(id).cloned() else {
+                    continue;
+                };
+                lines.push(format!(
+                    "  {:24} {} of {} builds, rank {:.1} on average",
+                    def.name[Gender::default()].to_string(),
+                    builds,
+                    fo4,
+                    *ranks as f32 / *builds as f32
+                ));
+            }
+            lines.push(format!("\n{}", "Average S.P.E.C.I.A.L.".bright_yellow()));
+            for (i, stat) in SpecialStat::ALL.iter().enumerate() {
+                let total: usize = self
+                    .spreads
+                    .iter()
+                    .map(|(special, count)| special[i] as usize * count)
+                    .sum();
+                lines.push(format!(
+                    "  {:12} {:.1}",
+                    stat.to_string(),
+                    total as f32 / fo4 as f32
+                ));
+            }
+            let repeated: Vec<_> = self
+                .spreads
+                .iter()
+                .filter(|(_, count)| **count > 1)
+                .collect();
+            if !repeated.is_empty() {
+                lines.push(format!("\n{}", "Repeated spreads".bright_yellow()));
+                for (special, count) in repeated {
+                    lines.push(format!("  {} in {} builds", spread(special), count));
+                }
+            }
+            if !self.archetypes.is_empty() {
+                lines.push(format!("\n{}", "Archetypes".bright_yellow()));
+                let mut archetypes: Vec<_> = self.archetypes.iter().collect();
+                archetypes.sort_by(|a, b| b.1.cmp(a.1));
+                for (archetype, count) in archetypes {
+                    let builds = if *count == 1 { "build" } else { "builds" };
+                    lines.push(format!("  {:16} {} {}", archetype, count, builds));
+                }
+            }
+        }
+        if !self.builds.is_empty() {
+            lines.push(format!("\n{}", "Builds".bright_yellow()));
+            for summary in &self.builds {
+                lines.push(format!(
+                    "  {}: {}, level {}, {}, {} perks",
+                    summary.name.bright_white(),
+                    summary
+                        .archetype
+                        .clone()
+                        .unwrap_or_else(|| summary.game.to_string()),
+                    summary.required_level,
+                    spread(&summary.special),
+                    summary.perks
+                ));
+            }
+        }
+        for (name, e) in &self.unreadable {
+            let reason = e.lines().next().unwrap_or_default();
+            lines.push(format!("Skipped {}: {}", name, reason));
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
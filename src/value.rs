@@ -0,0 +1,165 @@
+use colored::Colorize;
+
+use crate::{
+    build::Build,
+    optimize::{Objective, Weight},
+    special::{catalog, PerkId, SpecialStat},
+};
+
+/// What taking one more rank of a perk gets a build
+pub struct RankValue {
+    pub perk: PerkId,
+    pub rank: u8,
+    /// The perk point plus any S.P.E.C.I.A.L. points the rank needs first
+    pub points: u8,
+    /// How much the rank raises the weighted score
+    pub benefit: f32,
+    /// The build with the rank taken
+    pub taken: Build,
+}
+
+impl RankValue {
+    pub fn per_point(&self) -> f32 {
+        self.benefit / self.points as f32
+    }
+}
+
+/// The weights to value perks by, which default to every stat counting the same
+fn weights_or_all(weights: &[Weight]) -> Vec<Weight> {
+    if weights.is_empty() {
+        Objective::ALL
+            .iter()
+            .map(|&objective| Weight {
+                objective,
+                weight: 1.0,
+            })
+            .collect()
+    } else {
+        weights.to_vec()
+    }
+}
+
+/// A build's weighted score with every build at the same level, so that the levels
+/// spent on perk ranks don't count as health gained
+fn score_at(weights: &[Weight], build: &Build, level: u8) -> f32 {
+    weights
+        .iter()
+        .map(|w| w.weight * w.objective.value_at(build, Some(level)))
+        .sum()
+}
+
+impl Build {
+    /// The next rank of each level-up perk the build could take by a level, valued by
+    /// how much it raises the weighted stats for each point it costs, best first
+    pub fn rank_values(&self, weights: &[Weight], level: u8) -> Vec<RankValue> {
+        let weights = weights_or_all(weights);
+        let before = score_at(&weights, self, level);
+        let mut values: Vec<RankValue> = catalog()
+            .perks
+            .iter()
+            .filter(|(id, def)| {
+                matches!(id, PerkId::Special { .. }) && def.has_effects() && self.can_take(def)
+            })
+            .filter_map(|(&perk, def)| {
+                let rank = self.perks.get(&perk).copied().unwrap_or(0) + 1;
+                if rank > def.max_rank() {
+                    return None;
+                }
+                let mut taken = self.clone();
+                taken.add_perk_impl(perk, rank);
+                if taken.special.values().any(|points| *points > 10)
+                    || taken.required_level() > level
+                {
+                    return None;
+                }
+                let special: u8 = SpecialStat::ALL
+                    .iter()
+                    .map(|stat| taken.special[stat] - self.special[stat])
+                    .sum();
+                Some(RankValue {
+                    perk,
+                    rank,
+                    points: 1 + special,
+                    benefit: score_at(&weights, &taken, level) - before,
+                    taken,
+                })
+            })
+            .collect();
+        values.sort_by(|a, b| b.per_point().total_cmp(&a.per_point()));
+        values
+    }
+    /// Describe the best perk ranks to take by a level for the weighted stats, with
+    /// what each changes and its value per point spent
+    pub fn describe_values(&self, weights: &[Weight], level: u8, count: usize) -> String {
+        let values = self.rank_values(weights, level);
+        let goals = if weights.is_empty() {
+            "every stat".to_string()
+        } else {
+            weights
+                .iter()
+                .map(|w| format!("{:?}={}", w.objective, w.weight).to_lowercase())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let (useful, useless): (Vec<&RankValue>, Vec<&RankValue>) =
+            values.iter().partition(|value| value.benefit > 0.0);
+        if useful.is_empty() {
+            return format!(
+                "No perk rank that can be taken by level {} improves {}",
+                level, goals
+            );
+        }
+        let mut lines = vec![format!("Best value by level {} for {}", level, goals)
+            .bright_yellow()
+            .to_string()];
+        let weights = weights_or_all(weights);
+        for (i, value) in useful.iter().take(count).enumerate() {
+            let changes: Vec<String> = weights
+                .iter()
+                .map(|w| w.objective)
+                .filter(|obj| {
+                    obj.describe_at(self, Some(level)) != obj.describe_at(&value.taken, Some(level))
+                })
+                .map(|obj| obj.describe_at(&value.taken, Some(level)))
+                .collect();
+            let cost = if value.points == 1 {
+                String::new()
+            } else {
+                format!(", {} points", value.points)
+            };
+            lines.push(format!(
+                "  {:>2}. {} {}{}: {} ({:.3} per point)",
+                i + 1,
+                self.perk_name(&value.perk),
+                value.rank,
+                cost,
+                if changes.is_empty() {
+                    "slightly better".into()
+                } else {
+                    changes.join(", ")
+                },
+                value.per_point()
+            ));
+        }
+        if !useless.is_empty() {
+            lines.push(
+                format!(
+                    "{} can be taken but {} none of these stats",
+                    if useless.len() == 1 {
+                        "1 other rank".to_string()
+                    } else {
+                        format!("{} other ranks", useless.len())
+                    },
+                    if useless.len() == 1 {
+                        "changes"
+                    } else {
+                        "change"
+                    }
+                )
+                .bright_black()
+                .to_string(),
+            );
+        }
+        lines.join("\n")
+    }
+}
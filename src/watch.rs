@@ -0,0 +1,179 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::bail;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build::{Build, Game},
+    gear::Pounds,
+    special::SpecialStat,
+};
+
+/// A derived stat shown after every command, with how it changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Watch {
+    RequiredLevel,
+    Health,
+    Ap,
+    Xp,
+    MeleeDamage,
+    HitsPerCrit,
+    CarryWeight,
+    FreeCarryWeight,
+    BuyPrices,
+    SellPrices,
+    SprintTime,
+    /// A stat's effective points, counting bobbleheads, the book, and buffs
+    Special(SpecialStat),
+}
+
+impl Watch {
+    pub const ALL: &'static [Self] = &[
+        Watch::RequiredLevel,
+        Watch::Health,
+        Watch::Ap,
+        Watch::Xp,
+        Watch::MeleeDamage,
+        Watch::HitsPerCrit,
+        Watch::CarryWeight,
+        Watch::FreeCarryWeight,
+        Watch::BuyPrices,
+        Watch::SellPrices,
+        Watch::SprintTime,
+    ];
+    pub fn value(&self, build: &Build) -> f32 {
+        let stats = build.stats();
+        match self {
+            Watch::RequiredLevel => stats.required_level as f32,
+            Watch::Health => stats.health,
+            Watch::Ap => stats.base_ap,
+            Watch::Xp => stats.experience_mul as f32,
+            Watch::MeleeDamage => stats.melee_damage_mul,
+            Watch::HitsPerCrit => stats.hits_per_crit as f32,
+            Watch::CarryWeight => stats.carry_weight as f32,
+            Watch::FreeCarryWeight => build.free_carry_weight(),
+            Watch::BuyPrices => stats.buying_price_mul,
+            Watch::SellPrices => stats.selling_price_mul,
+            Watch::SprintTime => stats.sprint_time,
+            Watch::Special(stat) => stats.special.get(stat).copied().unwrap_or(0) as f32,
+        }
+    }
+    /// Format a value of the stat, or a change in it
+    pub fn format(&self, value: f32) -> String {
+        match self {
+            Watch::Xp | Watch::MeleeDamage | Watch::BuyPrices | Watch::SellPrices => {
+                format!("{:.0}%", value * 100.0)
+            }
+            Watch::SprintTime => format!("{:.1} s", value),
+            Watch::FreeCarryWeight => Pounds(value).to_string(),
+            _ => format!("{:.0}", value),
+        }
+    }
+    /// Whether the build is better off when the stat goes up
+    fn higher_is_better(&self) -> bool {
+        !matches!(
+            self,
+            Watch::RequiredLevel | Watch::HitsPerCrit | Watch::BuyPrices
+        )
+    }
+    fn key(&self) -> String {
+        self.to_string().to_lowercase().replace([' ', '.'], "")
+    }
+}
+
+impl FromStr for Watch {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase().replace([' ', '_', '-', '.'], "");
+        Ok(match lower.as_str() {
+            "level" => Watch::RequiredLevel,
+            "hp" => Watch::Health,
+            "crit" | "crits" => Watch::HitsPerCrit,
+            "carry" | "weight" => Watch::CarryWeight,
+            "free" | "freecarry" | "freeweight" => Watch::FreeCarryWeight,
+            "buy" | "barter" => Watch::BuyPrices,
+            "sell" => Watch::SellPrices,
+            "sprint" => Watch::SprintTime,
+            "melee" => Watch::MeleeDamage,
+            _ => {
+                if let Some(watch) = Watch::ALL.iter().find(|watch| watch.key() == lower) {
+                    return Ok(*watch);
+                }
+                match lower.parse::<SpecialStat>() {
+                    Ok(stat) if !lower.is_empty() => Watch::Special(stat),
+                    _ => bail!(
+                        "Unknown stat to watch: {}. Try one of {}, or a S.P.E.C.I.A.L. stat",
+                        s,
+                        Watch::ALL
+                            .iter()
+                            .map(Watch::key)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Watch::RequiredLevel => write!(f, "Level"),
+            Watch::Health => write!(f, "Health"),
+            Watch::Ap => write!(f, "AP"),
+            Watch::Xp => write!(f, "XP"),
+            Watch::MeleeDamage => write!(f, "Melee Damage"),
+            Watch::HitsPerCrit => write!(f, "Hits per Crit"),
+            Watch::CarryWeight => write!(f, "Carry Weight"),
+            Watch::FreeCarryWeight => write!(f, "Free Carry Weight"),
+            Watch::BuyPrices => write!(f, "Buy Prices"),
+            Watch::SellPrices => write!(f, "Sell Prices"),
+            Watch::SprintTime => write!(f, "Sprint Time"),
+            Watch::Special(stat) => write!(f, "{}", stat),
+        }
+    }
+}
+
+impl Build {
+    /// The current value of each watched stat
+    pub fn watch_values(&self) -> Vec<(Watch, f32)> {
+        if self.game() != Game::Fallout4 {
+            return Vec::new();
+        }
+        self.watches
+            .iter()
+            .map(|watch| (*watch, watch.value(self)))
+            .collect()
+    }
+    /// A line of the watched stats, with arrows for how each changed since `before`
+    pub fn watch_strip(&self, before: &[(Watch, f32)]) -> Option<String> {
+        let values = self.watch_values();
+        if values.is_empty() {
+            return None;
+        }
+        let items: Vec<String> = values
+            .into_iter()
+            .map(|(watch, value)| {
+                let mut item = format!("{} {}", watch, watch.format(value).bright_white());
+                let previous = before.iter().find(|(other, _)| *other == watch);
+                if let Some(&(_, previous)) = previous {
+                    let delta = value - previous;
+                    if delta.abs() > 1e-4 {
+                        let arrow = if delta > 0.0 { "▲" } else { "▼" };
+                        let change = format!("{}{}", arrow, watch.format(delta.abs()));
+                        item.push(' ');
+                        item.push_str(&if (delta > 0.0) == watch.higher_is_better() {
+                            change.bright_green().to_string()
+                        } else {
+                            change.bright_red().to_string()
+                        });
+                    }
+                }
+                item
+            })
+            .collect();
+        Some(items.join(" │ "))
+    }
+}
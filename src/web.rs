@@ -0,0 +1,120 @@
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    build::Build,
+    special::{catalog, PerkId, SpecialStat},
+    storage::{LocalStorage, Storage},
+};
+
+fn js_error(e: impl ToString) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+fn to_js(value: Value) -> Result<JsValue, JsError> {
+    js_sys::JSON::parse(&value.to_string()).map_err(|_| JsError::new("Invalid JSON"))
+}
+
+fn from_js(value: &JsValue) -> Result<Value, JsError> {
+    let json: String = js_sys::JSON::stringify(value)
+        .map_err(|_| JsError::new("Value cannot be converted to JSON"))?
+        .into();
+    serde_json::from_str(&json).map_err(js_error)
+}
+
+#[wasm_bindgen(js_name = Build)]
+pub struct WebBuild(Build);
+
+#[wasm_bindgen(js_class = Build)]
+impl WebBuild {
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> WebBuild {
+        WebBuild(Build::default())
+    }
+    #[wasm_bindgen(js_name = fromJson)]
+    pub fn from_json(value: &JsValue) -> Result<WebBuild, JsError> {
+        Build::from_json(from_js(value)?)
+            .map(WebBuild)
+            .map_err(js_error)
+    }
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<JsValue, JsError> {
+        to_js(self.0.to_json().map_err(js_error)?)
+    }
+    pub fn load(name: &str) -> Result<WebBuild, JsError> {
+        Build::load_from(&LocalStorage, name)
+            .map(WebBuild)
+            .map_err(js_error)
+    }
+    pub fn save(&mut self) -> Result<(), JsError> {
+        self.0.save_to(&LocalStorage, None).map_err(js_error)
+    }
+    pub fn set(&mut self, stat: &str, value: u8) -> Result<(), JsError> {
+        let stat: SpecialStat = stat.parse().map_err(js_error)?;
+        self.0.set(stat, value).map(|_| ()).map_err(js_error)
+    }
+    #[wasm_bindgen(js_name = setBobblehead)]
+    pub fn set_bobblehead(&mut self, stat: &str, on: bool) -> Result<(), JsError> {
+        let stat: SpecialStat = stat.parse().map_err(js_error)?;
+        self.0
+            .set_bobblehead(stat, on)
+            .map(|_| ())
+            .map_err(js_error)
+    }
+    /// Add a perk at the given rank, or its highest rank within the level limit.
+    /// Returns the rank that was taken.
+    #[wasm_bindgen(js_name = addPerk)]
+    pub fn add_perk(&mut self, perk: &str, rank: Option<u8>) -> Result<u8, JsError> {
+        let id: PerkId = perk.parse().map_err(js_error)?;
+        let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+        let rank = rank.unwrap_or_else(|| def.max_rank()).min(
+            def.ranks
+                .highest_rank_within_level(self.0.level_limit.unwrap_or(u8::MAX)),
+        );
+        self.0.add_perk(id, rank).map_err(js_error)?;
+        Ok(rank)
+    }
+    #[wasm_bindgen(js_name = removePerk)]
+    pub fn remove_perk(&mut self, perk: &str) -> Result<(), JsError> {
+        let id: PerkId = perk.parse().map_err(js_error)?;
+        self.0.remove_perk(id).map(|_| ()).map_err(js_error)
+    }
+    pub fn stats(&self) -> Result<JsValue, JsError> {
+        to_js(serde_json::to_value(self.0.stats()).map_err(js_error)?)
+    }
+    /// The same summary the terminal shows, without colors
+    pub fn summary(&self) -> String {
+        colored::control::set_override(false);
+        self.0.to_string()
+    }
+    /// Every perk in the catalog, named and described for this build's gender and difficulty
+    pub fn perks(&self) -> Result<JsValue, JsError> {
+        let gender = self.0.gender.unwrap_or_default();
+        let difficulty = self.0.difficulty.unwrap_or_default();
+        let perks: Vec<_> = catalog()
+            .perks
+            .iter()
+            .map(|(id, def)| def.info(*id, gender, difficulty))
+            .collect();
+        to_js(serde_json::to_value(perks).map_err(js_error)?)
+    }
+    pub fn perk(&self, name: &str) -> Result<JsValue, JsError> {
+        let id: PerkId = name.parse().map_err(js_error)?;
+        let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+        let gender = self.0.gender.unwrap_or_default();
+        let difficulty = self.0.difficulty.unwrap_or_default();
+        to_js(serde_json::to_value(def.info(id, gender, difficulty)).map_err(js_error)?)
+    }
+}
+
+#[wasm_bindgen(js_name = savedBuilds)]
+pub fn saved_builds() -> Result<JsValue, JsError> {
+    let names: Vec<String> = LocalStorage
+        .list()
+        .map_err(js_error)?
+        .into_iter()
+        .filter_map(|name| name.strip_suffix(".yaml").map(Into::into))
+        .collect();
+    to_js(json!(names))
+}
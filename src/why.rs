@@ -0,0 +1,100 @@
+use colored::Colorize;
+
+use crate::{
+    build::Build,
+    special::{catalog, PerkId},
+};
+
+impl Build {
+    /// The required level with one rank taken off of a perk
+    fn level_without_rank(&self, id: PerkId) -> u8 {
+        let mut build = self.clone();
+        match build.perks.get(&id).copied() {
+            Some(rank) if rank > 1 => {
+                build.perks.insert(id, rank - 1);
+            }
+            _ => {
+                build.perks.remove(&id);
+            }
+        }
+        build.required_level()
+    }
+    /// The required level without a perk at all
+    fn level_without_perk(&self, id: PerkId) -> u8 {
+        let mut build = self.clone();
+        build.perks.remove(&id);
+        build.required_level()
+    }
+    /// Explain which perk ranks and spent points set the required level, and what
+    /// taking them back would bring it down to
+    pub fn describe_level_drivers(&self) -> String {
+        let level = self.required_level();
+        if level <= 1 {
+            return "The build can be made at level 1".into();
+        }
+        let mut lines = vec![format!(
+            "{} comes from:",
+            format!("Required level {}", level).bright_white()
+        )];
+        let mut by_level: Vec<(u8, PerkId, u8)> = self
+            .perks
+            .iter()
+            .map(|(id, rank)| (self.pick_level(*id, *rank), *id, *rank))
+            .collect();
+        by_level.sort_by_key(|(pick, ..)| std::cmp::Reverse(*pick));
+        for &(pick, id, rank) in by_level.iter().take_while(|(pick, ..)| *pick == level) {
+            let def = catalog().perks.get(&id).cloned().expect("Unknown perk");
+            let name = self.perk_name(&id);
+            let reason = if def.ranks.required_level(rank) == pick {
+                format!("{} rank {} needs level {}", name, rank, pick)
+            } else {
+                format!(
+                    "{} needs level {} to collect the points it takes",
+                    name, pick
+                )
+            };
+            let lowered = if rank > 1 {
+                format!(
+                    "down to rank {}: level {}, without it: level {}",
+                    rank - 1,
+                    self.level_without_rank(id),
+                    self.level_without_perk(id)
+                )
+            } else {
+                format!("without it: level {}", self.level_without_perk(id))
+            };
+            lines.push(format!("  {}", reason));
+            lines.push(format!("    {}", lowered.bright_black()));
+        }
+        let spent = self.level_up_assigned_points();
+        if spent + 1 == level {
+            let for_ranks = by_level.first().map_or(1, |(pick, ..)| *pick);
+            lines.push(format!(
+                "  {} points spent after the initial ones, one per level",
+                spent
+            ));
+            lines.push(format!(
+                "    {}",
+                format!(
+                    "each point taken back lowers it by 1, down to level {} where rank requirements take over",
+                    for_ranks.max(1)
+                )
+                .bright_black()
+            ));
+        } else {
+            lines.push(format!(
+                "  Spent points alone would need level {}",
+                spent + 1
+            ));
+        }
+        if let Some(&(next, id, rank)) = by_level.iter().find(|(pick, ..)| *pick < level) {
+            lines.push(format!(
+                "The next highest rank requirement is level {} for {} rank {}",
+                next,
+                self.perk_name(&id),
+                rank
+            ));
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,148 @@
+use anyhow::bail;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build::Build,
+    special::{catalog, PerkId},
+};
+
+/// A perk rank the build should take if it fits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Wish {
+    pub perk: PerkId,
+    pub rank: u8,
+}
+
+/// How a wish fares when the wishlist is planned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WishStatus {
+    /// The build already takes the perk at the rank or higher
+    Taken,
+    /// The wish fits, and the plan needs this level once it is taken
+    Fits(u8),
+    /// Taking the wish would need this level, past the level limit
+    Misses(u8),
+    /// The wish cannot be taken at all
+    Unavailable(String),
+}
+
+impl Build {
+    /// Add a perk to the wishlist, at its highest rank if none is given and
+    /// at the lowest priority unless a position is given. A perk already on
+    /// the wishlist is replaced.
+    pub fn wish(
+        &mut self,
+        id: PerkId,
+        rank: Option<u8>,
+        at: Option<usize>,
+    ) -> anyhow::Result<Wish> {
+        let def = catalog()
+            .perks
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown perk"))?;
+        let rank = rank.unwrap_or_else(|| def.max_rank());
+        if rank == 0 {
+            bail!("A wish must be for rank 1 or higher")
+        }
+        if rank > def.max_rank() {
+            bail!("{} only has {} ranks", self.perk_name(&id), def.max_rank())
+        }
+        self.wishlist.retain(|wish| wish.perk != id);
+        let wish = Wish { perk: id, rank };
+        let index = match at {
+            Some(0) => bail!("Positions on the wishlist start at 1"),
+            Some(position) => (position - 1).min(self.wishlist.len()),
+            None => self.wishlist.len(),
+        };
+        self.wishlist.insert(index, wish);
+        Ok(wish)
+    }
+    /// Take a perk off the wishlist
+    pub fn unwish(&mut self, id: PerkId) -> anyhow::Result<()> {
+        let before = self.wishlist.len();
+        self.wishlist.retain(|wish| wish.perk != id);
+        if self.wishlist.len() == before {
+            bail!("{} is not on the wishlist", self.perk_name(&id))
+        }
+        Ok(())
+    }
+    /// Take each wish in priority order if the build can, without needing a level past
+    /// the level limit, returning the planned build and how each wish fared
+    pub fn plan_wishlist(&self) -> (Build, Vec<WishStatus>) {
+        let mut plan = self.clone();
+        let mut statuses = Vec::new();
+        for wish in &self.wishlist {
+            if plan
+                .perks
+                .get(&wish.perk)
+                .is_some_and(|rank| *rank >= wish.rank)
+            {
+                statuses.push(WishStatus::Taken);
+                continue;
+            }
+            let mut trial = plan.clone();
+            if let Err(e) = trial.add_perk(wish.perk, wish.rank) {
+                statuses.push(WishStatus::Unavailable(e.to_string()));
+                continue;
+            }
+            let level = trial.required_level();
+            if self.level_limit.is_some_and(|limit| level > limit) {
+                statuses.push(WishStatus::Misses(level));
+            } else {
+                statuses.push(WishStatus::Fits(level));
+                plan = trial;
+            }
+        }
+        (plan, statuses)
+    }
+    /// Take every wish that fits, returning how many were taken
+    pub fn apply_wishlist(&mut self) -> usize {
+        let (plan, statuses) = self.plan_wishlist();
+        *self = plan;
+        statuses
+            .iter()
+            .filter(|status| matches!(status, WishStatus::Fits(_)))
+            .count()
+    }
+    /// Describe each wish in priority order and how it fares
+    pub fn describe_wishlist(&self) -> String {
+        if self.wishlist.is_empty() {
+            return "The wishlist is empty, try \"wishlist add <PERK> [RANK]\"".into();
+        }
+        let (_, statuses) = self.plan_wishlist();
+        let mut lines = vec![match self.level_limit {
+            Some(limit) => format!("Wishlist by level {}", limit),
+            None => "Wishlist, with no level limit".into(),
+        }];
+        let names: Vec<String> = self
+            .wishlist
+            .iter()
+            .map(|wish| format!("{} {}", self.perk_name(&wish.perk), wish.rank))
+            .collect();
+        let width = names.iter().map(String::len).max().unwrap_or(0);
+        for (i, (name, status)) in names.iter().zip(&statuses).enumerate() {
+            let status = match status {
+                WishStatus::Taken => "taken".bright_green(),
+                WishStatus::Fits(level) => format!("fits, plan needs level {}", level).normal(),
+                WishStatus::Misses(level) => format!("misses, needs level {}", level).bright_red(),
+                WishStatus::Unavailable(e) => e.bright_red(),
+            };
+            lines.push(format!(
+                "  {}. {:width$}  {}",
+                i + 1,
+                name,
+                status,
+                width = width
+            ));
+        }
+        if statuses
+            .iter()
+            .any(|status| matches!(status, WishStatus::Fits(_)))
+        {
+            lines.push("Type \"wishlist apply\" to take the wishes that fit".into());
+        }
+        lines.join("\n")
+    }
+}
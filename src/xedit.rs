@@ -0,0 +1,223 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+use crate::{
+    csv,
+    plugin::{PerkRecord, Plugin},
+    special::*,
+};
+
+#[derive(Clone, Copy)]
+enum Column {
+    FormId,
+    EditorId,
+    Name,
+    Description,
+    Level,
+    Playable,
+    Next,
+}
+
+fn column(header: &str) -> Option<Column> {
+    let header: String = header
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    Some(match header.as_str() {
+        "formid" | "fid" | "record" => Column::FormId,
+        "editorid" | "edid" => Column::EditorId,
+        "name" | "full" => Column::Name,
+        "description" | "desc" => Column::Description,
+        "level" | "data" | "datalevel" => Column::Level,
+        "playable" => Column::Playable,
+        "next" | "nextperk" | "nnam" => Column::Next,
+        _ => return None,
+    })
+}
+
+/// Parse a form ID as xEdit writes it, either bare hex or inside a record reference
+/// like `Fallout4.esm [PERK:0004A0B4]` or `IronFist02 "Iron Fist" [PERK:000CB4E4]`
+fn form_id(s: &str) -> Option<u32> {
+    let s = s.trim();
+    let hex = match s.rfind(':') {
+        Some(i) if s.ends_with(']') => &s[i + 1..s.len() - 1],
+        _ => s.trim_start_matches("0x"),
+    };
+    u32::from_str_radix(hex, 16).ok().filter(|&id| id != 0)
+}
+
+fn playable(s: &str) -> bool {
+    !matches!(
+        s.trim().to_lowercase().as_str(),
+        "0" | "false" | "no" | "non-playable"
+    )
+}
+
+fn set_field(perk: &mut PerkRecord, column: Column, value: &str) -> anyhow::Result<()> {
+    match column {
+        Column::FormId => {
+            perk.form_id = form_id(value).ok_or_else(|| anyhow!("Invalid form ID: {}", value))?
+        }
+        Column::EditorId => perk.editor_id = value.trim().into(),
+        Column::Name => perk.name = value.trim().into(),
+        Column::Description => perk.description = value.trim().into(),
+        Column::Level => perk.level = value.trim().parse().unwrap_or(0),
+        Column::Playable => perk.playable = playable(value),
+        Column::Next => perk.next = form_id(value),
+    }
+    Ok(())
+}
+
+fn read_csv(text: &str) -> anyhow::Result<Vec<PerkRecord>> {
+    let mut rows = csv::rows(text).into_iter();
+    let columns: Vec<Option<Column>> = match rows.next() {
+        Some(header) => header.iter().map(|h| column(h)).collect(),
+        None => bail!("Dump is empty"),
+    };
+    if !columns.iter().any(|c| matches!(c, Some(Column::FormId))) {
+        bail!("Dump has no form ID column")
+    }
+    let mut perks = Vec::new();
+    for (i, row) in rows.enumerate() {
+        let mut perk = PerkRecord {
+            playable: true,
+            ..Default::default()
+        };
+        for (column, value) in columns.iter().zip(&row) {
+            if let Some(column) = column {
+                set_field(&mut perk, *column, value)
+                    .map_err(|e| anyhow!("Row {}: {}", i + 2, e))?;
+            }
+        }
+        perks.push(perk);
+    }
+    Ok(perks)
+}
+
+fn read_json(text: &str) -> anyhow::Result<Vec<PerkRecord>> {
+    let value: Value = serde_json::from_str(text)?;
+    let records = match value {
+        Value::Array(records) => records,
+        Value::Object(mut object) => match object.remove("perks").or_else(|| object.remove("PERK"))
+        {
+            Some(Value::Array(records)) => records,
+            _ => bail!("Expected a list of perk records"),
+        },
+        _ => bail!("Expected a list of perk records"),
+    };
+    let mut perks = Vec::new();
+    for (i, record) in records.into_iter().enumerate() {
+        let Value::Object(record) = record else {
+            bail!("Record {} is not an object", i + 1)
+        };
+        let mut perk = PerkRecord {
+            playable: true,
+            ..Default::default()
+        };
+        for (key, value) in &record {
+            if let Some(column) = column(key) {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Null => continue,
+                    value => value.to_string(),
+                };
+                set_field(&mut perk, column, &value)
+                    .map_err(|e| anyhow!("Record {}: {}", i + 1, e))?;
+            }
+        }
+        if perk.form_id == 0 {
+            bail!("Record {} has no form ID", i + 1)
+        }
+        perks.push(perk);
+    }
+    Ok(perks)
+}
+
+/// Read perk records exported from xEdit as CSV or JSON
+pub fn read_dump(path: &Path) -> anyhow::Result<Plugin> {
+    let text = fs::read_to_string(path)?;
+    let text = text.trim_start_matches('\u{feff}');
+    let is_json = path.extension().is_some_and(|ext| ext == "json")
+        || text.trim_start().starts_with(['[', '{']);
+    let perks = if is_json {
+        read_json(text)?
+    } else {
+        read_csv(text)?
+    };
+    let mut warnings = Vec::new();
+    if perks.iter().any(|perk| perk.name.starts_with("[string ")) {
+        warnings.push("Dump is localized, names and descriptions are string table ids".into());
+    }
+    Ok(Plugin { perks, warnings })
+}
+
+pub fn is_dump(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "csv" || ext == "json")
+}
+
+fn normalize(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Check a catalog's S.P.E.C.I.A.L. perks against the records in a dump
+pub fn verify(catalog: &Catalog, dump: &Plugin) -> Vec<String> {
+    let chains = dump.chains();
+    let by_name: BTreeMap<String, &Vec<&PerkRecord>> = chains
+        .iter()
+        .map(|chain| (chain[0].name.trim().to_lowercase(), chain))
+        .collect();
+    let mut problems = Vec::new();
+    for (id, def) in catalog.perks.iter() {
+        let name = &def.name[Gender::Male];
+        let chain = def
+            .name
+            .iter()
+            .find_map(|name| by_name.get(&name.trim().to_lowercase()));
+        let chain = match chain {
+            Some(chain) => chain,
+            None if matches!(id, PerkId::Special { .. }) => {
+                problems.push(format!("{}: not found in dump", name));
+                continue;
+            }
+            None => continue,
+        };
+        let ranks = def.max_rank() as usize;
+        if ranks != chain.len() {
+            problems.push(format!(
+                "{}: {} rank(s) in data, {} in dump",
+                name,
+                ranks,
+                chain.len()
+            ));
+        }
+        for (i, record) in chain.iter().enumerate().take(ranks) {
+            let rank = i as u8 + 1;
+            let level = def.ranks.required_level(rank);
+            if level != record.level.max(1) {
+                problems.push(format!(
+                    "{} rank {}: level {} in data, {} in dump",
+                    name, rank, level, record.level
+                ));
+            }
+            let description = match &def.ranks {
+                Ranks::VaryingCumulative(ranks) => &ranks[i].description,
+                Ranks::Single { description, .. }
+                | Ranks::UniformCumulative { description, .. } => description,
+            };
+            let description = &description[Difficulty::Normal][Gender::Male];
+            if !record.description.starts_with("[string ")
+                && normalize(description) != normalize(&record.description)
+            {
+                problems.push(format!(
+                    "{} rank {}: description differs, dump has \"{}\"",
+                    name, rank, record.description
+                ));
+            }
+        }
+    }
+    problems
+}